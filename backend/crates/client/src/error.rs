@@ -7,12 +7,24 @@ pub enum Error {
     /// Non-2xx response
     #[error("The server returned non-2xx response: ({0}) {1}")]
     Non2xxResponse(reqwest::StatusCode, String),
+    /// Response the client cannot use
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
     /// Failed to parse URL
     #[error("Failed to parse URL: {0}")]
     UrlParse(#[from] url::ParseError),
     /// Reqwest client error
     #[error("Reqwest client error: {0}")]
     Reqwest(#[from] reqwest::Error),
+    /// IO error
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Failed to decrypt
+    #[error("Decryption error: {0}")]
+    Crypto(#[from] music3_common::crypto::Error),
+    /// Failed to (de)serialize the upload state
+    #[error("Invalid upload state: {0}")]
+    UploadState(#[from] serde_json::Error),
 }
 
 /// Result type
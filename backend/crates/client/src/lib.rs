@@ -2,9 +2,16 @@
 #![deny(unsafe_code, missing_docs, clippy::unwrap_used)]
 
 pub mod error;
+pub mod playback;
+pub mod profile;
 pub mod solana;
+pub mod track;
+pub mod upload;
 use error::Result;
-use music3_common::param::auth::{AuthRequest, AuthResponse, ChallengeRequest, ChallengeResponse};
+use music3_common::param::auth::{
+    AuthRequest, AuthResponse, ChallengeRequest, ChallengeResponse, RefreshRequest,
+};
+use music3_common::param::upload::UploadResponse;
 use reqwest::{multipart, Url};
 use solana_sdk::pubkey::Pubkey;
 
@@ -60,22 +67,63 @@ impl Client {
         Ok(response.json().await?)
     }
 
+    /// Exchange a refresh token for a new access token and refresh token
+    pub async fn refresh(&self, refresh_token: &str) -> Result<AuthResponse> {
+        let url = self.base_url.join("/auth/refresh")?;
+        let response = self
+            .client
+            .post(url)
+            .json(&RefreshRequest {
+                refresh_token: refresh_token.to_string(),
+            })
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(error::Error::Non2xxResponse(status, response.text().await?));
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Logout, revoking the session of the access token
+    pub async fn logout(&self, jwt: &str) -> Result<()> {
+        let url = self.base_url.join("/auth/logout")?;
+        let response = self.client.post(url).bearer_auth(jwt).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(error::Error::Non2xxResponse(status, response.text().await?));
+        }
+        Ok(())
+    }
+
     /// Upload a music file
-    pub async fn upload_music(&self, file: Vec<u8>) -> Result<()> {
+    pub async fn upload_music(
+        &self,
+        jwt: &str,
+        file: Vec<u8>,
+        file_name: &str,
+    ) -> Result<UploadResponse> {
         // 创建多部分表单
         let form = multipart::Form::new().part(
             "file",
-            multipart::Part::bytes(file).file_name("default.mp3"),
+            multipart::Part::bytes(file).file_name(file_name.to_string()),
         );
 
         // 发送请求
-        let url = self.base_url.join("/upload")?;
-        let response = self.client.post(url).multipart(form).send().await?;
+        let url = self.base_url.join("/file/upload")?;
+        let response = self
+            .client
+            .post(url)
+            .bearer_auth(jwt)
+            .multipart(form)
+            .send()
+            .await?;
         let status = response.status();
         if !status.is_success() {
             return Err(error::Error::Non2xxResponse(status, response.text().await?));
         }
-        Ok(())
+        Ok(response.json().await?)
     }
 }
 
@@ -88,16 +136,24 @@ mod tests {
     async fn get_challenge_and_authorize() {
         let client = Client::default();
         let keypair = Keypair::new();
-        let challenge = client.get_challenge(keypair.pubkey()).await.unwrap();
+        let challenge = client
+            .get_challenge(keypair.pubkey())
+            .await
+            .expect("Failed to get challenge");
         let signature = keypair.sign_message(&challenge.build_message());
         let request = AuthRequest {
             pub_key: keypair.pubkey(),
             signature,
             hmac: challenge.hmac,
             timestamp: challenge.timestamp,
+            nonce: challenge.nonce,
+            message: challenge.message,
             duration: 3600,
         };
-        let response = client.authorize(&request).await.unwrap();
+        let response = client
+            .authorize(&request)
+            .await
+            .expect("Failed to authorize");
         assert_eq!(response.pub_key, keypair.pubkey());
     }
 }
@@ -0,0 +1,316 @@
+//! # Playback
+//!
+//! 播放前向服务端获取加密给钱包的内容密钥，用钱包私钥解密后即可解密音频。
+//! 服务端同时返回播放授权的原因和过期时间。
+//!
+//! [`TrackReader`] 通过 `Range` 请求下载密文，边下载边解密，拖动进度时从所在分块重新请求。
+//! 也可以先用 [`Client::playback_url`] 换取短期有效的签名地址，之后的请求不再携带 JWT。
+//!
+
+use crate::error::{Error, Result};
+use crate::upload::json;
+use crate::Client;
+use futures::Stream;
+use music3_common::crypto::{open_key, ContentKey, Decryptor, Header, HEADER_LEN};
+use music3_common::param::hls::HlsJob;
+use music3_common::param::playback::{PlaybackUrlResponse, Subscription, TrackKeyResponse};
+use reqwest::{header, StatusCode, Url};
+use solana_sdk::signature::Keypair;
+
+impl Client {
+    /// Get the decryption key of a track, sealed to the wallet of the JWT
+    pub async fn track_key(&self, jwt: &str, track_id: &str) -> Result<TrackKeyResponse> {
+        let url = self.base_url.join(&format!("/track/{track_id}/key"))?;
+        let response = self.client.get(url).bearer_auth(jwt).send().await?;
+        json(response).await
+    }
+
+    /// Get the decryption key of a track and open it with the wallet `keypair`
+    pub async fn open_track_key(
+        &self,
+        jwt: &str,
+        track_id: &str,
+        keypair: &Keypair,
+    ) -> Result<ContentKey> {
+        let response = self.track_key(jwt, track_id).await?;
+        let bytes = keypair.to_bytes();
+        let mut secret = [0; 32];
+        secret.copy_from_slice(&bytes[..32]);
+        Ok(open_key(&response.key, &secret)?)
+    }
+
+    /// Get the paid subscription of the wallet of the JWT
+    pub async fn subscription(&self, jwt: &str) -> Result<Option<Subscription>> {
+        let url = self.base_url.join("/subscription")?;
+        let response = self.client.get(url).bearer_auth(jwt).send().await?;
+        json(response).await
+    }
+
+    /// Start packaging a track created by the wallet of the JWT into HLS renditions
+    pub async fn package_hls(&self, jwt: &str, track_id: &str) -> Result<HlsJob> {
+        let url = self.base_url.join(&format!("/track/{track_id}/hls"))?;
+        let response = self.client.post(url).bearer_auth(jwt).send().await?;
+        json(response).await
+    }
+
+    /// Get the progress of an HLS packaging job
+    pub async fn hls_job(&self, jwt: &str, job_id: &str) -> Result<HlsJob> {
+        let url = self.base_url.join(&format!("/hls/job/{job_id}"))?;
+        let response = self.client.get(url).bearer_auth(jwt).send().await?;
+        json(response).await
+    }
+
+    /// Get a short-lived playback URL of a track, which needs no bearer token
+    pub async fn playback_url(&self, jwt: &str, track_id: &str) -> Result<PlaybackUrlResponse> {
+        let url = self.base_url.join(&format!("/track/{track_id}/url"))?;
+        let response = self.client.post(url).bearer_auth(jwt).send().await?;
+        let mut response: PlaybackUrlResponse = json(response).await?;
+        response.url = self.base_url.join(&response.url)?.to_string();
+        Ok(response)
+    }
+
+    /// Open a track for streaming, `key` is the content key of [`Client::open_track_key`]
+    pub async fn stream_track(
+        &self,
+        jwt: &str,
+        track_id: &str,
+        key: &ContentKey,
+    ) -> Result<TrackReader> {
+        let url = self.base_url.join(&format!("/track/{track_id}/stream"))?;
+        self.open_stream(url, Some(jwt.to_string()), key).await
+    }
+
+    /// Open a track for streaming from a URL of [`Client::playback_url`]
+    pub async fn stream_track_url(&self, url: &str, key: &ContentKey) -> Result<TrackReader> {
+        self.open_stream(Url::parse(url)?, None, key).await
+    }
+
+    async fn open_stream(
+        &self,
+        url: Url,
+        jwt: Option<String>,
+        key: &ContentKey,
+    ) -> Result<TrackReader> {
+        let range = (0, Some(HEADER_LEN as u64 - 1));
+        let mut response = request_range(&self.client, &url, jwt.as_deref(), range).await?;
+        let ciphertext_len = complete_length(&response)?;
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        while let Some(chunk) = response.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+        let header = Header::parse(&bytes)?;
+        Ok(TrackReader {
+            client: self.client.clone(),
+            url,
+            jwt,
+            decryptor: Decryptor::new(key, header),
+            ciphertext_len,
+            len: header.plaintext_len(ciphertext_len)?,
+            position: 0,
+            response: None,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+/// Reads a track from the server, decrypting the chunks as they arrive
+pub struct TrackReader {
+    client: reqwest::Client,
+    url: Url,
+    /// Bearer token, unless the URL is signed
+    jwt: Option<String>,
+    decryptor: Decryptor,
+    ciphertext_len: u64,
+    /// Plaintext size
+    len: u64,
+    /// Plaintext offset of the next read
+    position: u64,
+    /// Response streaming the chunks from the one at `position`
+    response: Option<reqwest::Response>,
+    /// Received bytes of the next chunk
+    buffer: Vec<u8>,
+}
+
+impl TrackReader {
+    /// Size of the decrypted track
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the decrypted track is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Plaintext offset of the next read
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Move to a plaintext offset, the next read requests the chunk containing it
+    pub fn seek(&mut self, position: u64) {
+        self.position = position.min(self.len);
+        self.response = None;
+        self.buffer.clear();
+    }
+
+    /// Decrypt from the current position to the end of its chunk, `None` at the end of the track
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        let header = *self.decryptor.header();
+        let chunk_size = u64::from(header.chunk_size);
+        if self.position >= self.len {
+            return Ok(None);
+        }
+        let index = self.position / chunk_size;
+        let start = header.chunk_offset(index);
+        let end = header.chunk_offset(index + 1).min(self.ciphertext_len);
+        let response = match &mut self.response {
+            Some(response) => response,
+            None => {
+                // stream every following chunk, a seek cancels the rest
+                let range = (start, None);
+                let response =
+                    request_range(&self.client, &self.url, self.jwt.as_deref(), range).await?;
+                self.response.insert(response)
+            }
+        };
+        let sealed_len = (end - start) as usize;
+        while self.buffer.len() < sealed_len {
+            let chunk = response.chunk().await?.ok_or_else(|| {
+                Error::InvalidResponse(format!("Track truncated in chunk {index}"))
+            })?;
+            self.buffer.extend_from_slice(&chunk);
+        }
+        let sealed: Vec<u8> = self.buffer.drain(..sealed_len).collect();
+        let chunk_index = u32::try_from(index)
+            .map_err(|_| Error::InvalidResponse(format!("Too many chunks: {index}")))?;
+        let last = end == self.ciphertext_len;
+        let mut plaintext = self.decryptor.decrypt_chunk(chunk_index, last, &sealed)?;
+        // a seek may land in the middle of the chunk
+        plaintext.drain(..(self.position - index * chunk_size) as usize);
+        self.position += plaintext.len() as u64;
+        Ok(Some(plaintext))
+    }
+
+    /// Decrypted chunks from the current position to the end of the track
+    pub fn into_stream(self) -> impl Stream<Item = Result<Vec<u8>>> {
+        futures::stream::try_unfold(self, |mut reader| async move {
+            Ok(reader.next_chunk().await?.map(|chunk| (chunk, reader)))
+        })
+    }
+}
+
+/// Request the ciphertext from `start` to `end` inclusive, or to the end of the file
+async fn request_range(
+    client: &reqwest::Client,
+    url: &Url,
+    jwt: Option<&str>,
+    (start, end): (u64, Option<u64>),
+) -> Result<reqwest::Response> {
+    let range = match end {
+        Some(end) => format!("bytes={start}-{end}"),
+        None => format!("bytes={start}-"),
+    };
+    let mut request = client.get(url.clone()).header(header::RANGE, range);
+    if let Some(jwt) = jwt {
+        request = request.bearer_auth(jwt);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Non2xxResponse(status, response.text().await?));
+    }
+    if status != StatusCode::PARTIAL_CONTENT {
+        return Err(Error::InvalidResponse(format!(
+            "Expected a partial response, got {status}"
+        )));
+    }
+    Ok(response)
+}
+
+/// Size of the whole file, from the `Content-Range` of a partial response
+fn complete_length(response: &reqwest::Response) -> Result<u64> {
+    response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit_once('/'))
+        .and_then(|(_, len)| len.parse().ok())
+        .ok_or_else(|| Error::InvalidResponse("Missing Content-Range".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::upload::tests::{app, options, write_wav};
+    use futures::TryStreamExt;
+
+    #[tokio::test]
+    async fn stream_and_seek() {
+        let app = app().await;
+        let (path, state_path) = (app.dir.join("song.wav"), app.dir.join("song.state"));
+        let mut data = write_wav(&path, 4000);
+        for (i, byte) in data.iter_mut().enumerate().skip(44) {
+            *byte = i as u8;
+        }
+        std::fs::write(&path, &data).expect("Failed to write file");
+        let upload = app
+            .client
+            .upload_resumable(&app.jwt, &path, &state_path, &options())
+            .await
+            .expect("Failed to upload");
+        let key = app
+            .client
+            .open_track_key(&app.jwt, &upload.id, &app.keypair)
+            .await
+            .expect("Failed to get the key");
+
+        let reader = app
+            .client
+            .stream_track(&app.jwt, &upload.id, &key)
+            .await
+            .expect("Failed to open the track");
+        assert_eq!(reader.len(), data.len() as u64);
+        let chunks: Vec<Vec<u8>> = reader
+            .into_stream()
+            .try_collect()
+            .await
+            .expect("Failed to stream");
+        assert_eq!(chunks.len(), 9);
+        assert_eq!(chunks.concat(), data);
+
+        let mut reader = app
+            .client
+            .stream_track(&app.jwt, &upload.id, &key)
+            .await
+            .expect("Failed to open the track");
+        reader.seek(2500);
+        let chunk = reader.next_chunk().await.expect("Failed to read");
+        assert_eq!(chunk.as_deref(), Some(&data[2500..3000]));
+        let chunk = reader.next_chunk().await.expect("Failed to read");
+        assert_eq!(chunk.as_deref(), Some(&data[3000..4000]));
+        // back to an earlier chunk
+        reader.seek(10);
+        let chunk = reader.next_chunk().await.expect("Failed to read");
+        assert_eq!(chunk.as_deref(), Some(&data[10..1000]));
+        reader.seek(u64::MAX);
+        assert_eq!(reader.position(), data.len() as u64);
+        assert_eq!(reader.next_chunk().await.expect("Failed to read"), None);
+
+        let signed = app
+            .client
+            .playback_url(&app.jwt, &upload.id)
+            .await
+            .expect("Failed to get a playback URL");
+        let reader = app
+            .client
+            .stream_track_url(&signed.url, &key)
+            .await
+            .expect("Failed to open the signed URL");
+        let chunks: Vec<Vec<u8>> = reader
+            .into_stream()
+            .try_collect()
+            .await
+            .expect("Failed to stream");
+        assert_eq!(chunks.concat(), data);
+    }
+}
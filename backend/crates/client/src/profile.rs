@@ -0,0 +1,107 @@
+//! # Profiles
+//!
+//! 读取、修改自己的资料，上传头像，读取任意钱包的公开主页。
+//!
+
+use crate::error::Result;
+use crate::upload::json;
+use crate::Client;
+use music3_common::param::profile::{Profile, PublicProfile, UpdateProfileRequest};
+
+impl Client {
+    /// Get the profile of the wallet of the JWT
+    pub async fn profile(&self, jwt: &str) -> Result<Profile> {
+        let url = self.base_url.join("/profile")?;
+        json(self.client.get(url).bearer_auth(jwt).send().await?).await
+    }
+
+    /// Replace the profile of the wallet of the JWT
+    pub async fn update_profile(
+        &self,
+        jwt: &str,
+        request: &UpdateProfileRequest,
+    ) -> Result<Profile> {
+        let url = self.base_url.join("/profile")?;
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(jwt)
+            .json(request)
+            .send()
+            .await?;
+        json(response).await
+    }
+
+    /// Upload a PNG, JPEG, GIF or WebP image as the avatar of the wallet of the JWT
+    pub async fn upload_avatar(&self, jwt: &str, image: Vec<u8>) -> Result<Profile> {
+        let url = self.base_url.join("/profile/avatar")?;
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(jwt)
+            .body(image)
+            .send()
+            .await?;
+        json(response).await
+    }
+
+    /// Get the public page of a wallet
+    pub async fn public_profile(&self, pubkey: &str) -> Result<PublicProfile> {
+        let url = self.base_url.join(&format!("/profiles/{pubkey}"))?;
+        json(self.client.get(url).send().await?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::upload::tests::app;
+    use music3_common::param::profile::SocialLink;
+    use reqwest::StatusCode;
+    use solana_sdk::signer::Signer;
+
+    #[tokio::test]
+    async fn edit_profile() {
+        let app = app().await;
+        let pubkey = app.keypair.pubkey().to_string();
+        let profile = app.client.profile(&app.jwt).await.expect("Failed to get");
+        assert_eq!(profile.pubkey, pubkey);
+
+        let request = UpdateProfileRequest {
+            display_name: Some("DJ".to_string()),
+            bio: None,
+            links: vec![SocialLink {
+                platform: "github".to_string(),
+                url: "https://github.com/dj".to_string(),
+            }],
+        };
+        let profile = app
+            .client
+            .update_profile(&app.jwt, &request)
+            .await
+            .expect("Failed to update");
+        assert_eq!(profile.display_name, request.display_name);
+
+        assert!(matches!(
+            app.client
+                .upload_avatar(&app.jwt, b"not an image".to_vec())
+                .await,
+            Err(Error::Non2xxResponse(StatusCode::UNSUPPORTED_MEDIA_TYPE, _))
+        ));
+        let profile = app
+            .client
+            .upload_avatar(&app.jwt, b"GIF89a\x01\x00\x01\x00".to_vec())
+            .await
+            .expect("Failed to upload");
+        assert!(profile.avatar_url.is_some());
+
+        let page = app
+            .client
+            .public_profile(&pubkey)
+            .await
+            .expect("Failed to get");
+        assert_eq!(page.profile, profile);
+        assert!(page.tracks.is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+//! # Track catalog
+//!
+//! 把上传完成的文件登记为音频，读取、修改、移除、分页列出和搜索目录中的音频，关联为音频铸造的 NFT。
+//!
+
+use crate::error::Result;
+use crate::upload::json;
+use crate::Client;
+use music3_common::param::profile::NftResponse;
+use music3_common::param::search::{SearchQuery, SearchResults};
+use music3_common::param::track::{
+    CreateTrackRequest, ListTracksQuery, TrackInfo, TrackNftRequest, TrackPage, TrackResponse,
+};
+
+impl Client {
+    /// Register an upload of the wallet of the JWT as a track
+    pub async fn create_track(
+        &self,
+        jwt: &str,
+        request: &CreateTrackRequest,
+    ) -> Result<TrackResponse> {
+        let url = self.base_url.join("/tracks")?;
+        let response = self
+            .client
+            .post(url)
+            .bearer_auth(jwt)
+            .json(request)
+            .send()
+            .await?;
+        json(response).await
+    }
+
+    /// Get a track of the catalog
+    pub async fn track(&self, track_id: &str) -> Result<TrackResponse> {
+        let url = self.base_url.join(&format!("/tracks/{track_id}"))?;
+        json(self.client.get(url).send().await?).await
+    }
+
+    /// Replace the catalog information of a track created by the wallet of the JWT
+    pub async fn update_track(
+        &self,
+        jwt: &str,
+        track_id: &str,
+        info: &TrackInfo,
+    ) -> Result<TrackResponse> {
+        let url = self.base_url.join(&format!("/tracks/{track_id}"))?;
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(jwt)
+            .json(info)
+            .send()
+            .await?;
+        json(response).await
+    }
+
+    /// Remove a track created by the wallet of the JWT from the catalog, the upload is kept
+    pub async fn delete_track(&self, jwt: &str, track_id: &str) -> Result<()> {
+        let url = self.base_url.join(&format!("/tracks/{track_id}"))?;
+        let response = self.client.delete(url).bearer_auth(jwt).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(crate::error::Error::Non2xxResponse(
+                status,
+                response.text().await?,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Link a track created by the wallet of the JWT to the NFT minted for it
+    pub async fn link_track_nft(
+        &self,
+        jwt: &str,
+        track_id: &str,
+        request: &TrackNftRequest,
+    ) -> Result<NftResponse> {
+        let url = self.base_url.join(&format!("/tracks/{track_id}/nft"))?;
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(jwt)
+            .json(request)
+            .send()
+            .await?;
+        json(response).await
+    }
+
+    /// List a page of the catalog, newest first
+    pub async fn tracks(&self, query: &ListTracksQuery) -> Result<TrackPage> {
+        let url = self.base_url.join("/tracks")?;
+        json(self.client.get(url).query(query).send().await?).await
+    }
+
+    /// Search the catalog, best match first
+    pub async fn search(&self, query: &SearchQuery) -> Result<SearchResults> {
+        let url = self.base_url.join("/search")?;
+        json(self.client.get(url).query(query).send().await?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::upload::tests::{app, options, write_wav};
+    use reqwest::StatusCode;
+    use solana_sdk::signer::Signer;
+
+    #[tokio::test]
+    async fn register_and_list() {
+        let app = app().await;
+        let (path, state_path) = (app.dir.join("song.wav"), app.dir.join("song.state"));
+        write_wav(&path, 4000);
+        let upload = app
+            .client
+            .upload_resumable(&app.jwt, &path, &state_path, &options())
+            .await
+            .expect("Failed to upload");
+
+        let info = TrackInfo {
+            title: "Song".to_string(),
+            genre: Some("Jazz".to_string()),
+            ..TrackInfo::default()
+        };
+        let request = CreateTrackRequest {
+            upload_id: upload.id.clone(),
+            info: info.clone(),
+        };
+        let track = app
+            .client
+            .create_track(&app.jwt, &request)
+            .await
+            .expect("Failed to create");
+        assert_eq!(track.info, info);
+        assert_eq!(track.audio, upload.audio);
+        assert_eq!(track.owner, app.keypair.pubkey().to_string());
+
+        let info = TrackInfo {
+            artist: Some("Artist".to_string()),
+            ..info
+        };
+        app.client
+            .update_track(&app.jwt, &upload.id, &info)
+            .await
+            .expect("Failed to update");
+        let track = app.client.track(&upload.id).await.expect("Failed to get");
+        assert_eq!(track.info, info);
+
+        let query = ListTracksQuery {
+            owner: Some(app.keypair.pubkey().to_string()),
+            genre: Some("jazz".to_string()),
+            ..ListTracksQuery::default()
+        };
+        let page = app.client.tracks(&query).await.expect("Failed to list");
+        assert_eq!(page.total, 1);
+        assert_eq!(page.tracks, vec![track.clone()]);
+        let query = SearchQuery {
+            q: Some("sogn".to_string()),
+            genre: Some("Jazz".to_string()),
+            ..SearchQuery::default()
+        };
+        let results = app.client.search(&query).await.expect("Failed to search");
+        assert_eq!(results.tracks, vec![track]);
+
+        app.client
+            .delete_track(&app.jwt, &upload.id)
+            .await
+            .expect("Failed to delete");
+        assert!(matches!(
+            app.client.track(&upload.id).await,
+            Err(Error::Non2xxResponse(StatusCode::NOT_FOUND, _))
+        ));
+    }
+}
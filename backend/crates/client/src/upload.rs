@@ -0,0 +1,529 @@
+//! # Resumable upload
+//!
+//! 大文件分块上传：每个分块确认后把进度写入状态文件，失败的分块按指数退避重试；
+//! 进程重启后读取状态文件，向服务端查询已接收的字节数并从该位置继续上传。
+//! 状态文件记录了文件的大小、修改时间和开头部分的哈希，文件被修改或替换后不再续传，而是创建新的会话。
+//!
+
+use crate::error::{Error, Result};
+use crate::Client;
+use music3_common::param::upload::{
+    CreateUploadSessionRequest, UploadChunkQuery, UploadResponse, UploadSessionResponse,
+};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::SeekFrom;
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// Bytes at the start of the file hashed to tell if it changed
+const HEAD_LEN: u64 = 64 * 1024;
+
+/// Options of a resumable upload
+#[derive(Debug, Clone)]
+pub struct UploadOptions {
+    /// Max bytes per chunk, capped by the chunk size of the server
+    pub chunk_size: u64,
+    /// Attempts per chunk before giving up
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after each failure
+    pub retry_delay: Duration,
+}
+
+impl Default for UploadOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 4 * 1024 * 1024,
+            max_attempts: 5,
+            retry_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Progress saved in the state file
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UploadState {
+    /// Session ID
+    pub session_id: String,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// Last modification of the file, in nanoseconds since the unix epoch
+    #[serde(default)]
+    pub modified: u64,
+    /// SHA-256 of the first [`HEAD_LEN`] bytes of the file, in hex
+    #[serde(default)]
+    pub head_sha256: String,
+    /// Bytes confirmed by the server
+    pub offset: u64,
+}
+
+impl UploadState {
+    fn matches(&self, fingerprint: &Fingerprint) -> bool {
+        self.size == fingerprint.size
+            && self.modified == fingerprint.modified
+            && self.head_sha256 == fingerprint.head_sha256
+    }
+}
+
+/// What changes when the file is modified or replaced
+struct Fingerprint {
+    size: u64,
+    modified: u64,
+    head_sha256: String,
+}
+
+impl Fingerprint {
+    async fn of(path: &Path) -> Result<Self> {
+        let metadata = tokio::fs::metadata(path).await?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| {
+                u64::try_from(since.as_nanos()).unwrap_or(u64::MAX)
+            });
+        let mut head = Vec::new();
+        tokio::fs::File::open(path)
+            .await?
+            .take(HEAD_LEN)
+            .read_to_end(&mut head)
+            .await?;
+        Ok(Self {
+            size: metadata.len(),
+            modified,
+            head_sha256: format!("{:x}", Sha256::digest(&head)),
+        })
+    }
+}
+
+impl Client {
+    /// Create a resumable upload session
+    pub async fn create_upload_session(
+        &self,
+        jwt: &str,
+        size: u64,
+    ) -> Result<UploadSessionResponse> {
+        let url = self.base_url.join("/file/upload/session")?;
+        let response = self
+            .client
+            .post(url)
+            .bearer_auth(jwt)
+            .json(&CreateUploadSessionRequest { size })
+            .send()
+            .await?;
+        json(response).await
+    }
+
+    /// Get the progress of a resumable upload session
+    pub async fn upload_session_status(
+        &self,
+        jwt: &str,
+        session_id: &str,
+    ) -> Result<UploadSessionResponse> {
+        let url = self
+            .base_url
+            .join(&format!("/file/upload/session/{session_id}"))?;
+        let response = self.client.get(url).bearer_auth(jwt).send().await?;
+        json(response).await
+    }
+
+    /// Upload a chunk starting at `offset`
+    pub async fn upload_chunk(
+        &self,
+        jwt: &str,
+        session_id: &str,
+        offset: u64,
+        chunk: Vec<u8>,
+    ) -> Result<UploadSessionResponse> {
+        let url = self
+            .base_url
+            .join(&format!("/file/upload/session/{session_id}"))?;
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(jwt)
+            .query(&UploadChunkQuery { offset })
+            .body(chunk)
+            .send()
+            .await?;
+        json(response).await
+    }
+
+    /// Finalize a resumable upload session once every byte is uploaded
+    pub async fn finalize_upload(&self, jwt: &str, session_id: &str) -> Result<UploadResponse> {
+        let url = self
+            .base_url
+            .join(&format!("/file/upload/session/{session_id}/finalize"))?;
+        let response = self.client.post(url).bearer_auth(jwt).send().await?;
+        json(response).await
+    }
+
+    /// Upload a file in chunks, resuming the session recorded in `state_path` if any
+    ///
+    /// The state file is updated after every chunk and removed once the upload is finalized,
+    /// so calling this again after a failure or a restart continues where it stopped.
+    /// A new session is created instead if the file was modified since.
+    pub async fn upload_resumable(
+        &self,
+        jwt: &str,
+        path: impl AsRef<Path>,
+        state_path: impl AsRef<Path>,
+        options: &UploadOptions,
+    ) -> Result<UploadResponse> {
+        let (path, state_path) = (path.as_ref(), state_path.as_ref());
+        let fingerprint = Fingerprint::of(path).await?;
+
+        let resumed = match load_state(state_path).await? {
+            Some(state) if state.matches(&fingerprint) => {
+                match self.upload_session_status(jwt, &state.session_id).await {
+                    Ok(session) => Some(session),
+                    // expired, start over
+                    Err(Error::Non2xxResponse(StatusCode::NOT_FOUND, _)) => None,
+                    Err(e) => return Err(e),
+                }
+            }
+            _ => None,
+        };
+        let mut session = match resumed {
+            Some(session) => session,
+            None => {
+                let session = self.create_upload_session(jwt, fingerprint.size).await?;
+                save_state(state_path, &session, &fingerprint).await?;
+                session
+            }
+        };
+
+        let chunk_size = options.chunk_size.min(session.chunk_size).max(1);
+        let mut file = tokio::fs::File::open(path).await?;
+        while session.offset < session.size {
+            let len = chunk_size.min(session.size - session.offset);
+            let mut chunk = vec![0; len as usize];
+            file.seek(SeekFrom::Start(session.offset)).await?;
+            file.read_exact(&mut chunk).await?;
+            session = self.retry_chunk(jwt, &session, chunk, options).await?;
+            save_state(state_path, &session, &fingerprint).await?;
+        }
+
+        let upload = self.finalize_upload(jwt, &session.id).await?;
+        tokio::fs::remove_file(state_path).await?;
+        Ok(upload)
+    }
+
+    async fn retry_chunk(
+        &self,
+        jwt: &str,
+        session: &UploadSessionResponse,
+        chunk: Vec<u8>,
+        options: &UploadOptions,
+    ) -> Result<UploadSessionResponse> {
+        let mut delay = options.retry_delay;
+        let mut attempt = 1;
+        loop {
+            match self
+                .upload_chunk(jwt, &session.id, session.offset, chunk.clone())
+                .await
+            {
+                Ok(progress) => return Ok(progress),
+                Err(e) if attempt < options.max_attempts && retryable(&e) => {}
+                Err(e) => return Err(e),
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            attempt += 1;
+            // the chunk may have been received before the connection dropped
+            if let Ok(progress) = self.upload_session_status(jwt, &session.id).await {
+                if progress.offset != session.offset {
+                    return Ok(progress);
+                }
+            }
+        }
+    }
+}
+
+/// Network errors, server errors and conflicts with a request still in progress
+fn retryable(e: &Error) -> bool {
+    match e {
+        Error::Reqwest(_) => true,
+        Error::Non2xxResponse(status, _) => {
+            status.is_server_error()
+                || matches!(
+                    *status,
+                    StatusCode::CONFLICT
+                        | StatusCode::REQUEST_TIMEOUT
+                        | StatusCode::TOO_MANY_REQUESTS
+                )
+        }
+        _ => false,
+    }
+}
+
+pub(crate) async fn json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T> {
+    let status = response.status();
+    if !status.is_success() {
+        return Err(Error::Non2xxResponse(status, response.text().await?));
+    }
+    Ok(response.json().await?)
+}
+
+async fn load_state(path: &Path) -> Result<Option<UploadState>> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn save_state(
+    path: &Path,
+    session: &UploadSessionResponse,
+    fingerprint: &Fingerprint,
+) -> Result<()> {
+    let state = UploadState {
+        session_id: session.id.clone(),
+        size: session.size,
+        modified: fingerprint.modified,
+        head_sha256: fingerprint.head_sha256.clone(),
+        offset: session.offset,
+    };
+    // write then rename, so a crash never leaves a truncated state file
+    let tmp = path.with_extension("tmp");
+    tokio::fs::write(&tmp, serde_json::to_vec(&state)?).await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use axum::extract::Request;
+    use axum::http::Method;
+    use axum::middleware::Next;
+    use axum::response::{IntoResponse, Response};
+    use music3_common::param::audio::AudioFormat;
+    use music3_common::param::auth::{AuthRequest, Scope};
+    use music3_server::conf::Config;
+    use music3_server::storage::conf::StorageConfig;
+    use music3_test_support::{TempDir, MASTER_KEY, PLAYBACK_SECRET};
+    use solana_sdk::signature::{Keypair, Signer};
+    use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
+    use std::sync::Arc;
+    use url::Url;
+
+    const CHUNK_SIZE: u64 = 1000;
+
+    /// Faults injected into chunk uploads
+    #[derive(Default)]
+    struct Faults {
+        /// Fail this many chunks, then recover
+        fail_next: AtomicU32,
+        /// Chunks accepted before an outage, unlimited if negative
+        budget: AtomicI64,
+        /// Chunks which reached the server
+        received: AtomicU32,
+    }
+
+    pub(crate) struct TestApp {
+        pub(crate) client: Client,
+        pub(crate) jwt: String,
+        pub(crate) keypair: Keypair,
+        faults: Arc<Faults>,
+        pub(crate) dir: TempDir,
+    }
+
+    async fn inject(faults: Arc<Faults>, request: Request, next: Next) -> Response {
+        if request.method() == Method::PUT {
+            let failing = faults
+                .fail_next
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if failing {
+                return StatusCode::SERVICE_UNAVAILABLE.into_response();
+            }
+            if faults.budget.fetch_sub(1, Ordering::SeqCst) == 0 {
+                faults.budget.store(0, Ordering::SeqCst);
+                return StatusCode::SERVICE_UNAVAILABLE.into_response();
+            }
+            faults.received.fetch_add(1, Ordering::SeqCst);
+        }
+        next.run(request).await
+    }
+
+    pub(crate) async fn app() -> TestApp {
+        let keypair = Keypair::new();
+        let dir = TempDir::new("client");
+        let mut config = Config::default();
+        config.auth.policy.grants.insert(
+            keypair.pubkey().to_string(),
+            vec![Scope::Creator, Scope::Upload],
+        );
+        config.upload.dir = dir.join("server");
+        config.storage = StorageConfig::Local {
+            dir: dir.join("storage"),
+        };
+        config.upload.chunk_size = CHUNK_SIZE;
+        config.crypto.master_key = Some(MASTER_KEY.to_string());
+        config.crypto.chunk_size = 1000;
+        config.playback.secret = Some(PLAYBACK_SECRET.to_string());
+
+        let faults = Arc::new(Faults {
+            budget: AtomicI64::new(-1),
+            ..Faults::default()
+        });
+        let router = music3_server::route::router(config)
+            .await
+            .expect("Invalid config")
+            .layer(axum::middleware::from_fn({
+                let faults = faults.clone();
+                move |request, next| inject(faults.clone(), request, next)
+            }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let addr = listener.local_addr().expect("No local address");
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let client = Client::from(Url::parse(&format!("http://{addr}")).expect("Invalid URL"));
+        let challenge = client
+            .get_challenge(keypair.pubkey())
+            .await
+            .expect("Failed to get challenge");
+        let request = AuthRequest {
+            pub_key: keypair.pubkey(),
+            signature: keypair.sign_message(&challenge.build_message()),
+            hmac: challenge.hmac,
+            timestamp: challenge.timestamp,
+            nonce: challenge.nonce,
+            message: challenge.message,
+            duration: 3600,
+        };
+        let jwt = client
+            .authorize(&request)
+            .await
+            .expect("Failed to authorize")
+            .jwt;
+        std::fs::create_dir_all(&*dir).expect("Failed to create dir");
+        TestApp {
+            client,
+            jwt,
+            keypair,
+            faults,
+            dir,
+        }
+    }
+
+    /// Write a silent 16-bit mono WAV file of `frames` samples at 8 kHz
+    pub(crate) fn write_wav(path: &Path, frames: u32) -> Vec<u8> {
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(36 + frames * 2).to_le_bytes());
+        data.extend_from_slice(b"WAVEfmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        for field in [1u16, 1] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&8000u32.to_le_bytes());
+        data.extend_from_slice(&16000u32.to_le_bytes());
+        for field in [2u16, 16] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&(frames * 2).to_le_bytes());
+        data.resize(44 + frames as usize * 2, 0);
+        std::fs::write(path, &data).expect("Failed to write file");
+        data
+    }
+
+    pub(crate) fn options() -> UploadOptions {
+        UploadOptions {
+            chunk_size: 4096,
+            max_attempts: 3,
+            retry_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_failed_chunks() {
+        let app = app().await;
+        let (path, state_path) = (app.dir.join("song.wav"), app.dir.join("song.state"));
+        let data = write_wav(&path, 4000);
+        app.faults.fail_next.store(2, Ordering::SeqCst);
+
+        let upload = app
+            .client
+            .upload_resumable(&app.jwt, &path, &state_path, &options())
+            .await
+            .expect("Failed to upload");
+        assert_eq!(upload.size, data.len() as u64);
+        assert_eq!(upload.audio.format, AudioFormat::Wav);
+        assert_eq!(upload.audio.duration_ms, 500);
+        // chunks are capped by the server
+        assert_eq!(app.faults.received.load(Ordering::SeqCst), 9);
+        assert!(!state_path.exists());
+    }
+
+    #[tokio::test]
+    async fn resume_after_restart() {
+        let app = app().await;
+        let (path, state_path) = (app.dir.join("song.wav"), app.dir.join("song.state"));
+        let data = write_wav(&path, 4000);
+
+        // the connection goes down after 3 chunks
+        app.faults.budget.store(3, Ordering::SeqCst);
+        let result = app
+            .client
+            .upload_resumable(&app.jwt, &path, &state_path, &options())
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::Non2xxResponse(StatusCode::SERVICE_UNAVAILABLE, _))
+        ));
+        let state: UploadState =
+            serde_json::from_slice(&std::fs::read(&state_path).expect("No state file"))
+                .expect("Invalid state file");
+        assert_eq!(state.offset, 3 * CHUNK_SIZE);
+
+        // a new process picks up the state file
+        app.faults.budget.store(-1, Ordering::SeqCst);
+        let client = Client::from(app.client.base_url.clone());
+        let upload = client
+            .upload_resumable(&app.jwt, &path, &state_path, &options())
+            .await
+            .expect("Failed to resume");
+        assert_eq!(upload.id, state.session_id);
+        assert_eq!(upload.size, data.len() as u64);
+        assert_eq!(app.faults.received.load(Ordering::SeqCst), 9);
+        assert!(!state_path.exists());
+    }
+
+    #[tokio::test]
+    async fn restart_modified_file() {
+        let app = app().await;
+        let (path, state_path) = (app.dir.join("song.wav"), app.dir.join("song.state"));
+        let mut data = write_wav(&path, 4000);
+
+        app.faults.budget.store(3, Ordering::SeqCst);
+        let result = app
+            .client
+            .upload_resumable(&app.jwt, &path, &state_path, &options())
+            .await;
+        assert!(result.is_err());
+        let state: UploadState =
+            serde_json::from_slice(&std::fs::read(&state_path).expect("No state file"))
+                .expect("Invalid state file");
+
+        // same size, different content
+        data[100] = 1;
+        std::fs::write(&path, &data).expect("Failed to write file");
+        app.faults.budget.store(-1, Ordering::SeqCst);
+        let upload = app
+            .client
+            .upload_resumable(&app.jwt, &path, &state_path, &options())
+            .await
+            .expect("Failed to upload");
+        assert_ne!(upload.id, state.session_id);
+        assert_eq!(upload.content_hash, format!("{:x}", Sha256::digest(&data)));
+        assert_eq!(app.faults.received.load(Ordering::SeqCst), 3 + 9);
+        assert!(!state_path.exists());
+    }
+}
@@ -0,0 +1,489 @@
+//! # 音频加密
+//!
+//! 每个音频使用随机生成的内容密钥加密，算法为 ChaCha20-Poly1305。
+//! 明文按固定大小切分为分块，每个分块单独加密并带有认证标签，
+//! 因此播放器可以只下载并解密需要的分块，边下载边播放，也可以拖动进度。
+//!
+//! 密文格式：
+//!
+//! ```text
+//! header  = MAGIC (4) || chunk_size (u32 BE) || nonce_prefix (7)
+//! nonce_i = nonce_prefix || i (u32 BE) || last (u8)
+//! chunk_i = ChaCha20-Poly1305(key, nonce_i, aad = header, plaintext_i)
+//! ```
+//!
+//! 与 STREAM 结构相同，分块序号和是否为最后一块都在 nonce 中，
+//! 调换、截断或拼接分块都会导致解密失败。
+//!
+//! 内容密钥发给听众前用 [`seal_key`] 加密给听众的钱包：钱包的 Ed25519 公钥转换为 X25519 公钥，
+//! 与临时密钥协商出共享密钥，再经 HKDF-SHA256 派生出加密内容密钥的密钥。
+//! 钱包用 [`open_key`] 以同样的方式从私钥派生 X25519 私钥并解密。
+//!
+
+use crate::utils::Base64;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt::{Debug, Formatter};
+
+/// Length of a content key
+pub const KEY_LEN: usize = 32;
+
+/// Length of the authentication tag of a chunk
+pub const TAG_LEN: usize = 16;
+
+/// Length of the header before the first chunk
+pub const HEADER_LEN: usize = 15;
+
+/// Magic bytes and version of the format
+pub const MAGIC: [u8; 4] = *b"M3E1";
+
+/// Default plaintext size of a chunk
+pub const DEFAULT_CHUNK_SIZE: u32 = 64 * 1024;
+
+const NONCE_PREFIX_LEN: usize = 7;
+
+/// Encryption errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    /// Not an encrypted file, or an unknown version
+    #[error("Invalid encryption header")]
+    InvalidHeader,
+    /// Key of the wrong length
+    #[error("Invalid key")]
+    InvalidKey,
+    /// Wrong key, or a tampered or truncated chunk
+    #[error("Failed to decrypt chunk {0}")]
+    Decrypt(u32),
+    /// More chunks than the nonce can count
+    #[error("Plaintext too large")]
+    TooLarge,
+}
+
+/// Result type of encryption
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Random key of a single track
+#[derive(Clone, PartialEq, Eq)]
+pub struct ContentKey([u8; KEY_LEN]);
+
+impl ContentKey {
+    /// Generate a random key
+    pub fn generate() -> Self {
+        let mut key = [0; KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    /// Raw bytes of the key
+    pub fn as_bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+}
+
+impl TryFrom<&[u8]> for ContentKey {
+    type Error = Error;
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Ok(Self(bytes.try_into().map_err(|_| Error::InvalidKey)?))
+    }
+}
+
+impl Debug for ContentKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // keep keys out of the logs
+        f.write_str("ContentKey(..)")
+    }
+}
+
+/// Header of an encrypted file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Plaintext size of every chunk but the last one
+    pub chunk_size: u32,
+    /// Random prefix of the nonces
+    pub nonce_prefix: [u8; NONCE_PREFIX_LEN],
+}
+
+impl Header {
+    /// Header with a random nonce prefix
+    pub fn new(chunk_size: u32) -> Self {
+        let mut nonce_prefix = [0; NONCE_PREFIX_LEN];
+        OsRng.fill_bytes(&mut nonce_prefix);
+        Self {
+            chunk_size,
+            nonce_prefix,
+        }
+    }
+
+    /// Parse the first [`HEADER_LEN`] bytes of an encrypted file
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let bytes = bytes.get(..HEADER_LEN).ok_or(Error::InvalidHeader)?;
+        if bytes[..4] != MAGIC {
+            return Err(Error::InvalidHeader);
+        }
+        let chunk_size = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if chunk_size == 0 {
+            return Err(Error::InvalidHeader);
+        }
+        let mut nonce_prefix = [0; NONCE_PREFIX_LEN];
+        nonce_prefix.copy_from_slice(&bytes[8..]);
+        Ok(Self {
+            chunk_size,
+            nonce_prefix,
+        })
+    }
+
+    /// Binary encoding
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4..8].copy_from_slice(&self.chunk_size.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.nonce_prefix);
+        bytes
+    }
+
+    /// Number of chunks of a plaintext, an empty one still has a final chunk
+    pub fn chunk_count(&self, plaintext_len: u64) -> u64 {
+        plaintext_len.div_ceil(u64::from(self.chunk_size)).max(1)
+    }
+
+    /// Size of the encrypted file, header included
+    pub fn ciphertext_len(&self, plaintext_len: u64) -> u64 {
+        HEADER_LEN as u64 + plaintext_len + self.chunk_count(plaintext_len) * TAG_LEN as u64
+    }
+
+    /// Size of the plaintext of an encrypted file of `ciphertext_len` bytes
+    pub fn plaintext_len(&self, ciphertext_len: u64) -> Result<u64> {
+        let body = ciphertext_len
+            .checked_sub(HEADER_LEN as u64)
+            .ok_or(Error::InvalidHeader)?;
+        let sealed = u64::from(self.chunk_size) + TAG_LEN as u64;
+        let (full, rest) = (body / sealed, body % sealed);
+        match rest {
+            0 if full > 0 => Ok(full * u64::from(self.chunk_size)),
+            rest if rest >= TAG_LEN as u64 => {
+                Ok(full * u64::from(self.chunk_size) + rest - TAG_LEN as u64)
+            }
+            _ => Err(Error::InvalidHeader),
+        }
+    }
+
+    /// Offset of the chunk at `index` in the encrypted file
+    pub fn chunk_offset(&self, index: u64) -> u64 {
+        HEADER_LEN as u64 + index * (u64::from(self.chunk_size) + TAG_LEN as u64)
+    }
+
+    fn nonce(&self, index: u32, last: bool) -> Nonce {
+        let mut nonce = Nonce::default();
+        nonce[..NONCE_PREFIX_LEN].copy_from_slice(&self.nonce_prefix);
+        nonce[NONCE_PREFIX_LEN..NONCE_PREFIX_LEN + 4].copy_from_slice(&index.to_be_bytes());
+        nonce[NONCE_PREFIX_LEN + 4] = u8::from(last);
+        nonce
+    }
+}
+
+/// Encrypt `plaintext` in chunks of `chunk_size` bytes
+pub fn encrypt(key: &ContentKey, chunk_size: u32, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let encryptor = Encryptor::new(key, chunk_size)?;
+    let count = encryptor.chunk_count(plaintext.len() as u64)?;
+    let len = usize::try_from(encryptor.header.ciphertext_len(plaintext.len() as u64))
+        .map_err(|_| Error::TooLarge)?;
+    let mut ciphertext = Vec::with_capacity(len);
+    ciphertext.extend_from_slice(&encryptor.aad);
+    let chunks = plaintext.chunks(chunk_size as usize);
+    // `chunks` yields nothing for an empty plaintext
+    let chunks = chunks.chain(plaintext.is_empty().then_some(&[][..]));
+    for (index, chunk) in chunks.enumerate() {
+        let last = index as u64 == count - 1;
+        ciphertext.extend(encryptor.encrypt_chunk(index as u32, last, chunk)?);
+    }
+    Ok(ciphertext)
+}
+
+/// Encrypts the chunks of a file in order, without holding the whole file
+#[derive(Clone)]
+pub struct Encryptor {
+    cipher: ChaCha20Poly1305,
+    header: Header,
+    aad: [u8; HEADER_LEN],
+}
+
+impl Encryptor {
+    /// Encryptor of a new file with a random nonce prefix
+    pub fn new(key: &ContentKey, chunk_size: u32) -> Result<Self> {
+        if chunk_size == 0 {
+            return Err(Error::InvalidHeader);
+        }
+        let header = Header::new(chunk_size);
+        Ok(Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key.as_bytes())),
+            aad: header.to_bytes(),
+            header,
+        })
+    }
+
+    /// Header of the file, written before the first chunk
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Number of chunks of a plaintext, failing when the nonce cannot count them
+    pub fn chunk_count(&self, plaintext_len: u64) -> Result<u64> {
+        let count = self.header.chunk_count(plaintext_len);
+        if count > u64::from(u32::MAX) + 1 {
+            return Err(Error::TooLarge);
+        }
+        Ok(count)
+    }
+
+    /// Encrypt the chunk at `index`, `last` must tell whether it ends the file
+    pub fn encrypt_chunk(&self, index: u32, last: bool, chunk: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload {
+            msg: chunk,
+            aad: &self.aad,
+        };
+        self.cipher
+            .encrypt(&self.header.nonce(index, last), payload)
+            .map_err(|_| Error::TooLarge)
+    }
+}
+
+/// Decrypt a whole file
+pub fn decrypt(key: &ContentKey, ciphertext: &[u8]) -> Result<Vec<u8>> {
+    let decryptor = Decryptor::new(key, Header::parse(ciphertext)?);
+    let sealed_size = decryptor.sealed_chunk_size();
+    let body = &ciphertext[HEADER_LEN..];
+    let count = body.len().div_ceil(sealed_size).max(1);
+    let mut plaintext = Vec::with_capacity(body.len());
+    for index in 0..count {
+        let start = index * sealed_size;
+        let chunk = &body[start..(start + sealed_size).min(body.len())];
+        let index = u32::try_from(index).map_err(|_| Error::TooLarge)?;
+        plaintext.extend(decryptor.decrypt_chunk(index, index as usize == count - 1, chunk)?);
+    }
+    Ok(plaintext)
+}
+
+/// Decrypts the chunks of a file in any order
+#[derive(Clone)]
+pub struct Decryptor {
+    cipher: ChaCha20Poly1305,
+    header: Header,
+    aad: [u8; HEADER_LEN],
+}
+
+impl Decryptor {
+    /// Decryptor of a file with `header`
+    pub fn new(key: &ContentKey, header: Header) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key.as_bytes())),
+            aad: header.to_bytes(),
+            header,
+        }
+    }
+
+    /// Header of the file
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Size of a full chunk with its tag
+    pub fn sealed_chunk_size(&self) -> usize {
+        self.header.chunk_size as usize + TAG_LEN
+    }
+
+    /// Decrypt the chunk at `index`, `last` must tell whether it ends the file
+    pub fn decrypt_chunk(&self, index: u32, last: bool, chunk: &[u8]) -> Result<Vec<u8>> {
+        let payload = Payload {
+            msg: chunk,
+            aad: &self.aad,
+        };
+        self.cipher
+            .decrypt(&self.header.nonce(index, last), payload)
+            .map_err(|_| Error::Decrypt(index))
+    }
+}
+
+/// Content key encrypted to a wallet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SealedKey {
+    /// Ephemeral X25519 public key
+    pub ephemeral_key: Base64,
+    /// Encrypted content key
+    pub ciphertext: Base64,
+}
+
+/// Encrypt a content key to the wallet of the Ed25519 public key `recipient`
+pub fn seal_key(key: &ContentKey, recipient: &[u8; 32]) -> Result<SealedKey> {
+    let recipient = CompressedEdwardsY(*recipient)
+        .decompress()
+        .ok_or(Error::InvalidKey)?
+        .to_montgomery();
+    let mut secret = [0; 32];
+    OsRng.fill_bytes(&mut secret);
+    let ephemeral_key = MontgomeryPoint::mul_base_clamped(secret);
+    let cipher = key_cipher(&recipient.mul_clamped(secret), &ephemeral_key, &recipient)?;
+    let ciphertext = cipher
+        .encrypt(&Nonce::default(), key.as_bytes().as_slice())
+        .map_err(|_| Error::InvalidKey)?;
+    Ok(SealedKey {
+        ephemeral_key: Base64(ephemeral_key.to_bytes().to_vec()),
+        ciphertext: Base64(ciphertext),
+    })
+}
+
+/// Decrypt a sealed content key with the 32-byte Ed25519 secret key of the wallet
+pub fn open_key(sealed: &SealedKey, secret: &[u8; 32]) -> Result<ContentKey> {
+    // the X25519 secret of an Ed25519 key is the first half of the hashed seed
+    let mut scalar = [0; 32];
+    scalar.copy_from_slice(&Sha512::digest(secret)[..32]);
+    let public = EdwardsPoint::mul_base_clamped(scalar).to_montgomery();
+    let ephemeral_key = MontgomeryPoint(
+        sealed
+            .ephemeral_key
+            .as_slice()
+            .try_into()
+            .map_err(|_| Error::InvalidKey)?,
+    );
+    let cipher = key_cipher(&ephemeral_key.mul_clamped(scalar), &ephemeral_key, &public)?;
+    let key = cipher
+        .decrypt(&Nonce::default(), sealed.ciphertext.as_slice())
+        .map_err(|_| Error::InvalidKey)?;
+    ContentKey::try_from(key.as_slice())
+}
+
+/// Cipher of a sealed key, only ever used once so the nonce can be fixed
+fn key_cipher(
+    shared: &MontgomeryPoint,
+    ephemeral_key: &MontgomeryPoint,
+    recipient: &MontgomeryPoint,
+) -> Result<ChaCha20Poly1305> {
+    // a low-order point gives away the shared secret
+    if shared.as_bytes() == &[0; 32] {
+        return Err(Error::InvalidKey);
+    }
+    let salt = Sha256::new()
+        .chain_update(ephemeral_key.as_bytes())
+        .chain_update(recipient.as_bytes())
+        .finalize();
+    let mut key = [0; KEY_LEN];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(b"music3 sealed key", &mut key)
+        .map_err(|_| Error::InvalidKey)?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let key = ContentKey::generate();
+        for len in [0, 1, 99, 100, 101, 1000] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let ciphertext = encrypt(&key, 100, &plaintext).expect("Failed to encrypt");
+            let header = Header::parse(&ciphertext).expect("Invalid header");
+            assert_eq!(header.chunk_size, 100);
+            assert_eq!(
+                ciphertext.len() as u64,
+                header.ciphertext_len(plaintext.len() as u64)
+            );
+            assert_eq!(decrypt(&key, &ciphertext), Ok(plaintext));
+        }
+    }
+
+    #[test]
+    fn random_access() {
+        let key = ContentKey::generate();
+        let plaintext: Vec<u8> = (0..250).map(|i| i as u8).collect();
+        let ciphertext = encrypt(&key, 100, &plaintext).expect("Failed to encrypt");
+        let decryptor = Decryptor::new(&key, Header::parse(&ciphertext).expect("Invalid header"));
+        let chunk = |index: usize| {
+            let start = HEADER_LEN + index * decryptor.sealed_chunk_size();
+            &ciphertext[start..(start + decryptor.sealed_chunk_size()).min(ciphertext.len())]
+        };
+        assert_eq!(
+            decryptor.decrypt_chunk(1, false, chunk(1)),
+            Ok(plaintext[100..200].to_vec())
+        );
+        assert_eq!(
+            decryptor.decrypt_chunk(2, true, chunk(2)),
+            Ok(plaintext[200..].to_vec())
+        );
+        // positions are authenticated
+        assert_eq!(
+            decryptor.decrypt_chunk(0, false, chunk(1)),
+            Err(Error::Decrypt(0))
+        );
+        assert_eq!(
+            decryptor.decrypt_chunk(1, true, chunk(1)),
+            Err(Error::Decrypt(1))
+        );
+    }
+
+    #[test]
+    fn lengths() {
+        let header = Header::new(100);
+        for plaintext_len in [0, 1, 99, 100, 101, 250, 300] {
+            let ciphertext_len = header.ciphertext_len(plaintext_len);
+            assert_eq!(header.plaintext_len(ciphertext_len), Ok(plaintext_len));
+        }
+        assert_eq!(header.chunk_offset(2), HEADER_LEN as u64 + 232);
+        for ciphertext_len in [0, HEADER_LEN as u64, HEADER_LEN as u64 + 15] {
+            assert_eq!(
+                header.plaintext_len(ciphertext_len),
+                Err(Error::InvalidHeader)
+            );
+        }
+    }
+
+    #[test]
+    fn reject_tampering() {
+        let key = ContentKey::generate();
+        let ciphertext = encrypt(&key, 100, &[7; 250]).expect("Failed to encrypt");
+
+        // truncated at a chunk boundary
+        let truncated = &ciphertext[..HEADER_LEN + 2 * (100 + TAG_LEN)];
+        assert_eq!(decrypt(&key, truncated), Err(Error::Decrypt(1)));
+
+        let mut flipped = ciphertext.clone();
+        flipped[HEADER_LEN + 5] ^= 1;
+        assert_eq!(decrypt(&key, &flipped), Err(Error::Decrypt(0)));
+
+        // the header is authenticated too
+        let mut resized = ciphertext.clone();
+        resized[7] = 99;
+        assert_eq!(decrypt(&key, &resized), Err(Error::Decrypt(0)));
+
+        assert_eq!(
+            decrypt(&ContentKey::generate(), &ciphertext),
+            Err(Error::Decrypt(0))
+        );
+        assert_eq!(decrypt(&key, b"M3E0"), Err(Error::InvalidHeader));
+    }
+
+    #[test]
+    fn sealed_key() {
+        let wallet = solana_sdk::signature::Keypair::new();
+        let bytes = wallet.to_bytes();
+        let (secret, public) = bytes.split_at(32);
+        let secret: &[u8; 32] = secret.try_into().expect("32 bytes");
+        let public: &[u8; 32] = public.try_into().expect("32 bytes");
+
+        let key = ContentKey::generate();
+        let sealed = seal_key(&key, public).expect("Failed to seal");
+        assert_eq!(open_key(&sealed, secret), Ok(key.clone()));
+        // a new ephemeral key every time
+        assert_ne!(seal_key(&key, public), Ok(sealed.clone()));
+
+        let other = solana_sdk::signature::Keypair::new().to_bytes();
+        let other: &[u8; 32] = other[..32].try_into().expect("32 bytes");
+        assert_eq!(open_key(&sealed, other), Err(Error::InvalidKey));
+    }
+}
@@ -1,5 +1,7 @@
 #![doc = include_str!("../README.md")]
 #![deny(unsafe_code, missing_docs, clippy::unwrap_used)]
 
+pub mod crypto;
 pub mod param;
+pub mod siws;
 pub mod utils;
@@ -1,2 +1,9 @@
 //! # Parameters module
+pub mod audio;
 pub mod auth;
+pub mod hls;
+pub mod playback;
+pub mod profile;
+pub mod search;
+pub mod track;
+pub mod upload;
@@ -0,0 +1,84 @@
+//! # Audio parameters
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// Audio format detected from the content
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// MPEG-1/2 Layer III
+    Mp3,
+    /// Free Lossless Audio Codec
+    Flac,
+    /// RIFF WAVE
+    Wav,
+    /// Opus in an Ogg container
+    Opus,
+    /// AAC, either raw ADTS or in an MP4/M4A container
+    Aac,
+}
+
+impl AudioFormat {
+    /// MIME type
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "audio/mpeg",
+            AudioFormat::Flac => "audio/flac",
+            AudioFormat::Wav => "audio/wav",
+            AudioFormat::Opus => "audio/ogg",
+            AudioFormat::Aac => "audio/mp4",
+        }
+    }
+
+    /// File extension without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Opus => "opus",
+            AudioFormat::Aac => "m4a",
+        }
+    }
+}
+
+/// Tags embedded in the file, e.g. ID3 or Vorbis comments
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AudioTags {
+    /// Title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Artist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    /// Album
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    /// Release date or year
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Genre
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// Track number
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track: Option<u32>,
+}
+
+/// Audio metadata
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct AudioMetadata {
+    /// Format
+    pub format: AudioFormat,
+    /// Duration in milliseconds
+    pub duration_ms: u64,
+    /// Sample rate in Hz
+    pub sample_rate: u32,
+    /// Number of channels
+    pub channels: u16,
+    /// Average bitrate in bits per second
+    pub bitrate: u32,
+    /// Tags
+    pub tags: AudioTags,
+}
@@ -6,6 +6,20 @@ use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
 
+/// Scope of an access token
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Hash, Ord, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Play tracks
+    Listener,
+    /// Publish and manage own tracks
+    Creator,
+    /// Upload files
+    Upload,
+    /// Administrate the platform, implies every other scope
+    Admin,
+}
+
 /// Hmac request
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChallengeRequest {
@@ -21,12 +35,17 @@ pub struct ChallengeResponse {
     pub hmac: Base64,
     /// Timestamp
     pub timestamp: u64,
+    /// Nonce, each challenge can only be used once
+    pub nonce: Base64,
+    /// Human-readable sign-in message, present in the Sign-In-With-Solana mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
 }
 
 impl ChallengeResponse {
     /// Build the message to sign
     pub fn build_message(&self) -> Vec<u8> {
-        build_message(&self.hmac, self.timestamp)
+        build_message(&self.hmac, self.timestamp, self.message.as_deref())
     }
 }
 
@@ -43,6 +62,11 @@ pub struct AuthRequest {
     pub hmac: Base64,
     /// Timestamp
     pub timestamp: u64,
+    /// Nonce of the challenge
+    pub nonce: Base64,
+    /// Signed sign-in message, required in the Sign-In-With-Solana mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
     /// Duration
     pub duration: u64,
 }
@@ -50,7 +74,7 @@ pub struct AuthRequest {
 impl AuthRequest {
     /// Build the message to verify
     pub fn build_message(&self) -> Vec<u8> {
-        build_message(&self.hmac, self.timestamp)
+        build_message(&self.hmac, self.timestamp, self.message.as_deref())
     }
 }
 
@@ -60,13 +84,29 @@ pub struct AuthResponse {
     /// Public key
     #[serde(with = "crate::utils::serde_str")]
     pub pub_key: Pubkey,
-    /// JWT
+    /// Access token (JWT)
     pub jwt: String,
-    /// Expiration
+    /// Expiration of the access token
     pub exp: u64,
+    /// Refresh token, can be used only once
+    pub refresh_token: String,
+    /// Expiration of the refresh token, i.e. of the session
+    pub refresh_exp: u64,
+    /// Scopes granted to the access token
+    pub scopes: Vec<Scope>,
+}
+
+/// Refresh request
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    /// Refresh token
+    pub refresh_token: String,
 }
 
-fn build_message(hmac: &Base64, timestamp: u64) -> Vec<u8> {
+fn build_message(hmac: &Base64, timestamp: u64, message: Option<&str>) -> Vec<u8> {
+    if let Some(message) = message {
+        return message.as_bytes().to_vec();
+    }
     let mut vec = Vec::with_capacity(hmac.len() + size_of::<u64>());
     vec.extend_from_slice(hmac.as_ref());
     vec.extend_from_slice(&timestamp.to_be_bytes());
@@ -0,0 +1,38 @@
+//! # HLS parameters
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// State of a packaging job
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    /// Waiting to start
+    Queued,
+    /// Transcoding, encrypting or storing the segments
+    Running,
+    /// Playlists ready
+    Completed,
+    /// Stopped by an error
+    Failed,
+}
+
+/// Progress of an HLS packaging job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HlsJob {
+    /// Job ID
+    pub id: String,
+    /// Track being packaged
+    pub track_id: String,
+    /// State of the job
+    pub state: JobState,
+    /// Percentage of the work done
+    pub progress: u8,
+    /// Cause of the failure
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Creation time in seconds since the epoch
+    pub created_at: u64,
+    /// Time of the last change in seconds since the epoch
+    pub updated_at: u64,
+}
@@ -0,0 +1,96 @@
+//! # Playback parameters
+//!
+
+use crate::crypto::SealedKey;
+use serde::{Deserialize, Serialize};
+
+/// Why a wallet may play a track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum GrantReason {
+    /// The wallet uploaded the track
+    Creator,
+    /// The wallet holds an NFT of the track
+    Nft {
+        /// Mint of the NFT
+        mint: String,
+    },
+    /// The wallet has a paid subscription
+    Subscription {
+        /// Plan of the subscription
+        plan: SubscriptionPlan,
+    },
+    /// The wallet stakes SOL with the platform
+    Stake {
+        /// Stake account
+        stake_account: String,
+        /// Delegated lamports
+        lamports: u64,
+    },
+}
+
+/// Permission to play a track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Grant {
+    /// Reason of the grant
+    pub reason: GrantReason,
+    /// Expiration in seconds since the epoch, `None` while the reason holds
+    pub expires_at: Option<u64>,
+}
+
+/// Decryption key of a track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrackKeyResponse {
+    /// Track ID
+    pub id: String,
+    /// Content key encrypted to the wallet of the caller
+    pub key: SealedKey,
+    /// Why the key was handed out
+    pub grant: Grant,
+}
+
+/// Plan of a paid subscription
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SubscriptionPlan {
+    /// 30 days
+    Monthly,
+    /// 365 days
+    Yearly,
+}
+
+impl SubscriptionPlan {
+    /// Duration of a period in seconds
+    pub fn duration_sec(&self) -> u64 {
+        const DAY: u64 = 24 * 60 * 60;
+        match self {
+            Self::Monthly => 30 * DAY,
+            Self::Yearly => 365 * DAY,
+        }
+    }
+}
+
+/// Request recording a paid subscription period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubscribeRequest {
+    /// Plan paid for
+    pub plan: SubscriptionPlan,
+}
+
+/// Paid subscription of a wallet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Subscription {
+    /// Plan of the last period paid for
+    pub plan: SubscriptionPlan,
+    /// Expiration in seconds since the epoch
+    pub expires_at: u64,
+}
+
+/// Signed playback URL
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlaybackUrlResponse {
+    /// Path and query of the stream, usable without a bearer token
+    pub url: String,
+    /// Expiration in seconds since the epoch
+    pub expires_at: u64,
+}
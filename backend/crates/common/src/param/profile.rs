@@ -0,0 +1,168 @@
+//! # Profile parameters
+//!
+
+use crate::param::track::TrackResponse;
+use serde::{Deserialize, Serialize};
+
+/// Max characters of the display name
+pub const MAX_DISPLAY_NAME_LEN: usize = 50;
+
+/// Max characters of the bio
+pub const MAX_BIO_LEN: usize = 1000;
+
+/// Max social links of a profile
+pub const MAX_LINKS: usize = 10;
+
+/// Max characters of the platform and URL of a social link
+pub const MAX_LINK_LEN: usize = 200;
+
+/// Link to an account elsewhere
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SocialLink {
+    /// Platform, e.g. `twitter`
+    pub platform: String,
+    /// URL of the account, `https` only
+    pub url: String,
+}
+
+/// Profile of a wallet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Profile {
+    /// Pubkey of the wallet
+    pub pubkey: String,
+    /// Name shown instead of the pubkey
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Short self-description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    /// Path of the avatar, e.g. `/profiles/{pubkey}/avatar`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_url: Option<String>,
+    /// Social links
+    #[serde(default)]
+    pub links: Vec<SocialLink>,
+    /// Creator verified by the platform
+    pub verified: bool,
+    /// First sign-in in seconds since the epoch
+    pub created_at: u64,
+    /// Last profile change in seconds since the epoch
+    pub updated_at: u64,
+}
+
+/// Request to change the profile of the caller, replacing every field
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UpdateProfileRequest {
+    /// Name shown instead of the pubkey
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    /// Short self-description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bio: Option<String>,
+    /// Social links
+    #[serde(default)]
+    pub links: Vec<SocialLink>,
+}
+
+impl UpdateProfileRequest {
+    /// Check the lengths and links, returning what is wrong
+    pub fn validate(&self) -> Result<(), String> {
+        let too_long = |value: &Option<String>, max: usize| {
+            value
+                .as_ref()
+                .is_some_and(|value| value.chars().count() > max)
+        };
+        if too_long(&self.display_name, MAX_DISPLAY_NAME_LEN) {
+            return Err(format!(
+                "display_name must not exceed {MAX_DISPLAY_NAME_LEN} characters"
+            ));
+        }
+        if too_long(&self.bio, MAX_BIO_LEN) {
+            return Err(format!("bio must not exceed {MAX_BIO_LEN} characters"));
+        }
+        if self.links.len() > MAX_LINKS {
+            return Err(format!("no more than {MAX_LINKS} links"));
+        }
+        for link in &self.links {
+            if link.platform.trim().is_empty()
+                || link.platform.chars().count() > MAX_LINK_LEN
+                || link.url.chars().count() > MAX_LINK_LEN
+            {
+                return Err(format!(
+                    "link platform must not be empty, nor a link exceed {MAX_LINK_LEN} characters"
+                ));
+            }
+            if !link.url.starts_with("https://") {
+                return Err(format!("link must be an https URL: {}", link.url));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Request to verify a creator or take it back
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VerifyRequest {
+    /// Verified or not
+    pub verified: bool,
+}
+
+/// NFT minted for a track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NftResponse {
+    /// Mint address
+    pub mint: String,
+    /// Track the NFT unlocks
+    pub track_id: String,
+    /// URI of the Metaplex metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_uri: Option<String>,
+    /// Mint time in seconds since the epoch
+    pub minted_at: u64,
+}
+
+/// Public page of a wallet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PublicProfile {
+    /// Profile
+    #[serde(flatten)]
+    pub profile: Profile,
+    /// Latest tracks in the catalog created by the wallet, newest first
+    pub tracks: Vec<TrackResponse>,
+    /// NFTs minted for the tracks of the wallet, oldest first
+    pub nfts: Vec<NftResponse>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let link = |url: &str| SocialLink {
+            platform: "twitter".to_string(),
+            url: url.to_string(),
+        };
+        let request = UpdateProfileRequest {
+            display_name: Some("DJ".to_string()),
+            bio: None,
+            links: vec![link("https://x.com/dj")],
+        };
+        assert!(request.validate().is_ok());
+        let insecure = UpdateProfileRequest {
+            links: vec![link("javascript:alert(1)")],
+            ..request.clone()
+        };
+        assert!(insecure.validate().is_err());
+        let many = UpdateProfileRequest {
+            links: vec![link("https://x.com/dj"); MAX_LINKS + 1],
+            ..request.clone()
+        };
+        assert!(many.validate().is_err());
+        let long = UpdateProfileRequest {
+            display_name: Some("x".repeat(MAX_DISPLAY_NAME_LEN + 1)),
+            ..request
+        };
+        assert!(long.validate().is_err());
+    }
+}
@@ -0,0 +1,93 @@
+//! # Search parameters
+//!
+
+use crate::param::track::TrackResponse;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Lower bounds of the price ranges counted in [`SearchFacets::price`], in lamports
+pub const PRICE_BUCKETS: [u64; 4] = [0, 100_000_000, 1_000_000_000, 10_000_000_000];
+
+/// Max tracks skipped by a search
+pub const MAX_OFFSET: u64 = 10_000;
+
+/// Query of a search, every filter must match, the bounds are inclusive
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SearchQuery {
+    /// Words in the title, artist, lyrics or tags, tolerating typos; newest first if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// Genre, regardless of case
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// Min price in lamports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_price: Option<u64>,
+    /// Max price in lamports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_price: Option<u64>,
+    /// Released on or after
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released_from: Option<NaiveDate>,
+    /// Released on or before
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub released_until: Option<NaiveDate>,
+    /// With an asking price or not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priced: Option<bool>,
+    /// Tracks to skip, up to [`MAX_OFFSET`]
+    #[serde(default)]
+    pub offset: u64,
+    /// Max tracks to return, capped like a track listing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
+/// Tracks matching a value of a facet
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FacetCount {
+    /// Value, e.g. `jazz` or `2024`
+    pub value: String,
+    /// Matching tracks
+    pub count: u64,
+}
+
+/// Tracks with an asking price in a price range
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PriceCount {
+    /// Min price in lamports, inclusive
+    pub min: u64,
+    /// Max price in lamports, exclusive, unbounded if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+    /// Matching tracks
+    pub count: u64,
+}
+
+/// Counts of every track matching a search, not only the returned page
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SearchFacets {
+    /// Lowercase genres, most tracks first
+    pub genre: Vec<FacetCount>,
+    /// Release years, latest first
+    pub release_year: Vec<FacetCount>,
+    /// Price ranges of [`PRICE_BUCKETS`], cheapest first
+    pub price: Vec<PriceCount>,
+    /// Tracks with an asking price
+    pub priced: u64,
+}
+
+/// Page of search results, best match first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SearchResults {
+    /// Tracks of the page
+    pub tracks: Vec<TrackResponse>,
+    /// Tracks matching the search
+    pub total: u64,
+    /// Tracks skipped
+    pub offset: u64,
+    /// Page size
+    pub limit: u64,
+    /// Counts of the matching tracks
+    pub facets: SearchFacets,
+}
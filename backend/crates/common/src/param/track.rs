@@ -0,0 +1,202 @@
+//! # Track parameters
+//!
+
+use crate::param::audio::AudioMetadata;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Max characters of the title, artist and genre
+pub const MAX_NAME_LEN: usize = 200;
+
+/// Max characters of the lyrics
+pub const MAX_LYRICS_LEN: usize = 20_000;
+
+/// Max characters of the cover art URL
+pub const MAX_URL_LEN: usize = 2048;
+
+/// Max tags of a track
+pub const MAX_TAGS: usize = 20;
+
+/// Max characters of a tag
+pub const MAX_TAG_LEN: usize = 50;
+
+/// Default page size of a listing
+pub const DEFAULT_PAGE_SIZE: u64 = 20;
+
+/// Max page size of a listing
+pub const MAX_PAGE_SIZE: u64 = 100;
+
+/// Catalog information of a track, set by its creator
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrackInfo {
+    /// Title
+    pub title: String,
+    /// Artist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    /// Genre
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// URL of the cover art, e.g. `ipfs://...` or `https://...`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_url: Option<String>,
+    /// Lyrics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lyrics: Option<String>,
+    /// Release date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<NaiveDate>,
+    /// Free-form tags, e.g. `chill` or `live`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Asking price of the NFT in lamports set by the creator, independent of the on-chain listings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price: Option<u64>,
+}
+
+impl TrackInfo {
+    /// Check the lengths of the fields, returning what is wrong
+    pub fn validate(&self) -> Result<(), String> {
+        if self.title.trim().is_empty() {
+            return Err("title must not be empty".to_string());
+        }
+        let fields = [
+            ("title", Some(&self.title), MAX_NAME_LEN),
+            ("artist", self.artist.as_ref(), MAX_NAME_LEN),
+            ("genre", self.genre.as_ref(), MAX_NAME_LEN),
+            ("cover_url", self.cover_url.as_ref(), MAX_URL_LEN),
+            ("lyrics", self.lyrics.as_ref(), MAX_LYRICS_LEN),
+        ];
+        for (name, value, max) in fields {
+            if value.is_some_and(|value| value.chars().count() > max) {
+                return Err(format!("{name} must not exceed {max} characters"));
+            }
+        }
+        if self.tags.len() > MAX_TAGS {
+            return Err(format!("no more than {MAX_TAGS} tags"));
+        }
+        if self
+            .tags
+            .iter()
+            .any(|tag| tag.trim().is_empty() || tag.chars().count() > MAX_TAG_LEN)
+        {
+            return Err(format!(
+                "tags must not be empty nor exceed {MAX_TAG_LEN} characters"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Request to register an uploaded file as a track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CreateTrackRequest {
+    /// ID returned by the upload
+    pub upload_id: String,
+    /// Catalog information
+    #[serde(flatten)]
+    pub info: TrackInfo,
+}
+
+/// Request to link a track to the NFT minted for it, the holders of the NFT may play the track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrackNftRequest {
+    /// Mint address
+    pub mint: String,
+    /// Verified collection of the NFT, whose other members unlock the track too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collection: Option<String>,
+    /// URI of the Metaplex metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_uri: Option<String>,
+}
+
+/// Track in the catalog
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrackResponse {
+    /// Track ID, the same as the upload ID
+    pub id: String,
+    /// Pubkey of the creator
+    pub owner: String,
+    /// Catalog information
+    #[serde(flatten)]
+    pub info: TrackInfo,
+    /// Metadata parsed from the upload
+    pub audio: AudioMetadata,
+    /// Upload time in seconds since the epoch
+    pub created_at: u64,
+    /// Last change in seconds since the epoch
+    pub updated_at: u64,
+}
+
+/// Query of a track listing, the artist and genre match regardless of case
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ListTracksQuery {
+    /// Tracks of a creator
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Tracks of an artist
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    /// Tracks of a genre
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genre: Option<String>,
+    /// Tracks to skip
+    #[serde(default)]
+    pub offset: u64,
+    /// Max tracks to return, [`DEFAULT_PAGE_SIZE`] if not set and capped by [`MAX_PAGE_SIZE`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u64>,
+}
+
+/// Page of a track listing, newest first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TrackPage {
+    /// Tracks of the page
+    pub tracks: Vec<TrackResponse>,
+    /// Tracks matching the filters
+    pub total: u64,
+    /// Tracks skipped
+    pub offset: u64,
+    /// Page size
+    pub limit: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let info = TrackInfo {
+            title: "Song".to_string(),
+            ..TrackInfo::default()
+        };
+        assert!(info.validate().is_ok());
+        let blank = TrackInfo {
+            title: " ".to_string(),
+            ..TrackInfo::default()
+        };
+        assert!(blank.validate().is_err());
+        let long = TrackInfo {
+            genre: Some("x".repeat(MAX_NAME_LEN + 1)),
+            ..info.clone()
+        };
+        assert_eq!(
+            long.validate(),
+            Err(format!("genre must not exceed {MAX_NAME_LEN} characters"))
+        );
+        let tagged = TrackInfo {
+            tags: vec!["chill".to_string(), " ".to_string()],
+            ..info.clone()
+        };
+        assert!(tagged.validate().is_err());
+
+        let json = r#"{"upload_id":"id","title":"Song","release_date":"2024-09-01"}"#;
+        let request: CreateTrackRequest = serde_json::from_str(json).expect("Failed to parse");
+        assert_eq!(
+            request.info.release_date,
+            NaiveDate::from_ymd_opt(2024, 9, 1)
+        );
+    }
+}
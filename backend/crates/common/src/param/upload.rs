@@ -0,0 +1,51 @@
+//! # Upload parameters
+//!
+
+use crate::param::audio::AudioMetadata;
+use serde::{Deserialize, Serialize};
+
+/// Upload response
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UploadResponse {
+    /// Upload ID
+    pub id: String,
+    /// Key of the file in the storage backend, e.g. an IPFS CID
+    pub key: String,
+    /// SHA-256 of the file in hex
+    pub content_hash: String,
+    /// Size in bytes
+    pub size: u64,
+    /// Content type detected from the content
+    pub content_type: String,
+    /// Audio metadata
+    pub audio: AudioMetadata,
+}
+
+/// Request to create a resumable upload session
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CreateUploadSessionRequest {
+    /// Total size of the file in bytes
+    pub size: u64,
+}
+
+/// Progress of a resumable upload session
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UploadSessionResponse {
+    /// Session ID, which becomes the upload ID once finalized
+    pub id: String,
+    /// Total size of the file in bytes
+    pub size: u64,
+    /// Bytes received so far, the offset of the next chunk
+    pub offset: u64,
+    /// Max size of a chunk in bytes
+    pub chunk_size: u64,
+    /// Expiration of the session in seconds since the epoch
+    pub expires_at: u64,
+}
+
+/// Query of a chunk upload
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UploadChunkQuery {
+    /// Offset of the chunk, must equal the bytes received so far
+    pub offset: u64,
+}
@@ -0,0 +1,272 @@
+//! # Sign-In-With-Solana
+//!
+//! 人类可读的登录消息，格式参考 EIP-4361（Sign-In with Ethereum）：
+//!
+//! ```text
+//! ${domain} wants you to sign in with your Solana account:
+//! ${address}
+//!
+//! ${statement}
+//!
+//! URI: ${uri}
+//! Version: ${version}
+//! Chain ID: ${chain-id}
+//! Nonce: ${nonce}
+//! Issued At: ${issued-at}
+//! Expiration Time: ${expiration-time}
+//! ```
+//!
+//! 其中 statement、URI、Chain ID 和 Expiration Time 是可选的。
+//!
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use solana_sdk::pubkey::Pubkey;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const HEADER_SUFFIX: &str = " wants you to sign in with your Solana account:";
+const URI: &str = "URI";
+const VERSION: &str = "Version";
+const CHAIN_ID: &str = "Chain ID";
+const NONCE: &str = "Nonce";
+const ISSUED_AT: &str = "Issued At";
+const EXPIRATION_TIME: &str = "Expiration Time";
+
+/// Sign-In-With-Solana message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignInMessage {
+    /// Domain requesting the sign-in
+    pub domain: String,
+    /// Public key of the account
+    pub address: Pubkey,
+    /// Human-readable statement
+    pub statement: Option<String>,
+    /// URI of the resource
+    pub uri: Option<String>,
+    /// Message version, always `1`
+    pub version: String,
+    /// Chain ID, e.g. `mainnet` or `devnet`
+    pub chain_id: Option<String>,
+    /// Nonce
+    pub nonce: String,
+    /// Time of issue
+    pub issued_at: DateTime<Utc>,
+    /// Time after which the message is no longer valid
+    pub expiration_time: Option<DateTime<Utc>>,
+}
+
+/// Error of parsing a sign-in message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSignInMessageError(pub String);
+
+impl Display for ParseSignInMessageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid sign-in message: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseSignInMessageError {}
+
+impl SignInMessage {
+    /// Encode bytes as a nonce, which must be alphanumeric
+    pub fn encode_nonce(nonce: &[u8]) -> String {
+        nonce.iter().map(|b| format!("{b:02x}")).collect()
+    }
+}
+
+impl Display for SignInMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}{}", self.domain, HEADER_SUFFIX)?;
+        writeln!(f, "{}", self.address)?;
+        writeln!(f)?;
+        if let Some(statement) = &self.statement {
+            writeln!(f, "{statement}")?;
+            writeln!(f)?;
+        }
+        if let Some(uri) = &self.uri {
+            writeln!(f, "{URI}: {uri}")?;
+        }
+        writeln!(f, "{VERSION}: {}", self.version)?;
+        if let Some(chain_id) = &self.chain_id {
+            writeln!(f, "{CHAIN_ID}: {chain_id}")?;
+        }
+        writeln!(f, "{NONCE}: {}", self.nonce)?;
+        write!(f, "{ISSUED_AT}: {}", format_time(&self.issued_at))?;
+        if let Some(expiration_time) = &self.expiration_time {
+            write!(f, "\n{EXPIRATION_TIME}: {}", format_time(expiration_time))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SignInMessage {
+    type Err = ParseSignInMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = |msg: &str| ParseSignInMessageError(msg.to_string());
+        let mut lines = s.split('\n').peekable();
+
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(HEADER_SUFFIX))
+            .filter(|domain| !domain.is_empty())
+            .ok_or_else(|| err("missing header"))?
+            .to_string();
+        let address = lines
+            .next()
+            .ok_or_else(|| err("missing address"))?
+            .parse::<Pubkey>()
+            .map_err(|_| err("invalid address"))?;
+        if lines.next() != Some("") {
+            return Err(err("missing empty line after address"));
+        }
+
+        let statement = match lines.peek() {
+            Some(line) if field(line).is_none() => {
+                let statement = line.to_string();
+                lines.next();
+                if lines.next() != Some("") {
+                    return Err(err("missing empty line after statement"));
+                }
+                Some(statement)
+            }
+            _ => None,
+        };
+
+        let mut uri = None;
+        let mut version = None;
+        let mut chain_id = None;
+        let mut nonce = None;
+        let mut issued_at = None;
+        let mut expiration_time = None;
+        for line in lines {
+            let (key, value) = field(line).ok_or_else(|| err("invalid field"))?;
+            let slot = match key {
+                URI => &mut uri,
+                VERSION => &mut version,
+                CHAIN_ID => &mut chain_id,
+                NONCE => &mut nonce,
+                ISSUED_AT => &mut issued_at,
+                EXPIRATION_TIME => &mut expiration_time,
+                _ => return Err(err("unknown field")),
+            };
+            if slot.replace(value.to_string()).is_some() {
+                return Err(err("duplicate field"));
+            }
+        }
+
+        let version = version.ok_or_else(|| err("missing version"))?;
+        if version != "1" {
+            return Err(err("unsupported version"));
+        }
+        let nonce = nonce.ok_or_else(|| err("missing nonce"))?;
+        if nonce.len() < 8 || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(err("nonce must be at least 8 alphanumeric characters"));
+        }
+        let issued_at = parse_time(&issued_at.ok_or_else(|| err("missing issued-at"))?)
+            .ok_or_else(|| err("invalid issued-at"))?;
+        let expiration_time = match expiration_time {
+            Some(time) => Some(parse_time(&time).ok_or_else(|| err("invalid expiration-time"))?),
+            None => None,
+        };
+
+        Ok(Self {
+            domain,
+            address,
+            statement,
+            uri,
+            version,
+            chain_id,
+            nonce,
+            issued_at,
+            expiration_time,
+        })
+    }
+}
+
+fn field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(": ")?;
+    [URI, VERSION, CHAIN_ID, NONCE, ISSUED_AT, EXPIRATION_TIME]
+        .contains(&key)
+        .then_some((key, value))
+}
+
+fn format_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> SignInMessage {
+        SignInMessage {
+            domain: "music3.app".to_string(),
+            address: Pubkey::new_unique(),
+            statement: Some("Sign in to Music3".to_string()),
+            uri: Some("https://music3.app".to_string()),
+            version: "1".to_string(),
+            chain_id: Some("devnet".to_string()),
+            nonce: SignInMessage::encode_nonce(&[0xab; 16]),
+            issued_at: DateTime::from_timestamp(1_700_000_000, 0).expect("Invalid timestamp"),
+            expiration_time: DateTime::from_timestamp(1_700_000_120, 0),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let message = message();
+        let text = message.to_string();
+        assert_eq!(
+            text,
+            format!(
+                "music3.app wants you to sign in with your Solana account:\n\
+                 {}\n\
+                 \n\
+                 Sign in to Music3\n\
+                 \n\
+                 URI: https://music3.app\n\
+                 Version: 1\n\
+                 Chain ID: devnet\n\
+                 Nonce: abababababababababababababababab\n\
+                 Issued At: 2023-11-14T22:13:20Z\n\
+                 Expiration Time: 2023-11-14T22:15:20Z",
+                message.address
+            )
+        );
+        assert_eq!(text.parse::<SignInMessage>(), Ok(message));
+    }
+
+    #[test]
+    fn optional_fields() {
+        let message = SignInMessage {
+            statement: None,
+            uri: None,
+            chain_id: None,
+            expiration_time: None,
+            ..message()
+        };
+        assert_eq!(message.to_string().parse::<SignInMessage>(), Ok(message));
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let text = message().to_string();
+        for invalid in [
+            text.replace("Version: 1", "Version: 2"),
+            text.replace("Nonce: abab", "Nonce: ab-b"),
+            text.replace("Issued At", "Issued"),
+            text.replace(" wants you", " wants"),
+            format!("{text}\nNonce: abababababab"),
+            text.replace("\nVersion: 1", ""),
+        ] {
+            assert!(invalid.parse::<SignInMessage>().is_err(), "{invalid}");
+        }
+    }
+}
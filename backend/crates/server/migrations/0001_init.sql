@@ -0,0 +1,40 @@
+-- Wallets which signed in, with their profiles
+CREATE TABLE users (
+    pubkey TEXT PRIMARY KEY,
+    display_name TEXT,
+    bio TEXT,
+    avatar TEXT,
+    created_at BIGINT NOT NULL,
+    updated_at BIGINT NOT NULL
+);
+
+-- Uploaded tracks, `cid` is the key of the ciphertext in the storage backend
+CREATE TABLE tracks (
+    id TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    cid TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    metadata JSONB NOT NULL,
+    created_at BIGINT NOT NULL
+);
+CREATE INDEX tracks_owner ON tracks (owner, created_at DESC);
+
+-- NFTs minted for the tracks
+CREATE TABLE nfts (
+    mint TEXT PRIMARY KEY,
+    track_id TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    metadata_uri TEXT,
+    minted_at BIGINT NOT NULL
+);
+CREATE INDEX nfts_track_id ON nfts (track_id, minted_at);
+
+-- Sign-in sessions, shared by the tokens issued for one sign-in
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    pubkey TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    expires_at BIGINT NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE
+);
+CREATE INDEX sessions_pubkey ON sessions (pubkey, created_at DESC);
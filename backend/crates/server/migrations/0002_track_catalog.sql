@@ -0,0 +1,6 @@
+-- Catalog information set when the creator registers the track
+ALTER TABLE tracks ADD COLUMN info JSONB;
+ALTER TABLE tracks ADD COLUMN updated_at BIGINT;
+UPDATE tracks SET updated_at = created_at;
+ALTER TABLE tracks ALTER COLUMN updated_at SET NOT NULL;
+CREATE INDEX tracks_catalog ON tracks (created_at DESC, id) WHERE info IS NOT NULL;
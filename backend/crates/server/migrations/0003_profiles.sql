@@ -0,0 +1,3 @@
+-- Social links and the verified-creator flag of the profiles
+ALTER TABLE users ADD COLUMN links JSONB NOT NULL DEFAULT '[]';
+ALTER TABLE users ADD COLUMN verified BOOLEAN NOT NULL DEFAULT FALSE;
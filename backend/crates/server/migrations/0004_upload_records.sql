@@ -0,0 +1,6 @@
+-- Upload records kept with the tracks: the wrapped content key, the NFT unlocking the track and its HLS package
+ALTER TABLE tracks ADD COLUMN wrapped_key BYTEA NOT NULL DEFAULT '';
+ALTER TABLE tracks ALTER COLUMN wrapped_key DROP DEFAULT;
+ALTER TABLE tracks ADD COLUMN mint TEXT;
+ALTER TABLE tracks ADD COLUMN collection TEXT;
+ALTER TABLE tracks ADD COLUMN hls JSONB;
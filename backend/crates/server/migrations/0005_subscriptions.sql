@@ -0,0 +1,6 @@
+-- Paid subscriptions, one row per wallet extended in place on renewal
+CREATE TABLE subscriptions (
+    subscriber TEXT PRIMARY KEY,
+    plan JSONB NOT NULL,
+    expires_at BIGINT NOT NULL
+);
@@ -2,32 +2,58 @@
 //!
 //! # 鉴权流程：
 //! 1. 客户端发送 PubKey 给服务端。
-//! 2. 服务端返回基于 PubKey 和服务端当前时间戳生成的 HMAC，同时返回使用的时间戳。
+//! 2. 服务端返回基于 PubKey、服务端当前时间戳和随机 nonce 生成的 HMAC，同时返回使用的时间戳和 nonce，并记录该 nonce。
 //! 3. 客户端将 HMAC 和服务端返回的时间戳使用私钥签名，即 `sign(hmac + timestamp.to_be_bytes())`。
-//! 4. 客户端发送 PubKey、签名、时间戳和期望的 JWT 有效期给服务端。
-//! 5. 服务端验证时间戳是否是最近的时间（比如 5 分钟），验证 HMAC 和签名，通过后下发 JWT。
+//! 4. 客户端发送 PubKey、签名、时间戳、nonce 和期望的 JWT 有效期给服务端。
+//! 5. 服务端验证时间戳是否是最近的时间（比如 5 分钟），验证 HMAC 和签名，并消费 nonce（每个 nonce 只能使用一次），通过后下发短期有效的访问令牌和刷新令牌。
+//! 6. 访问令牌过期后，客户端使用刷新令牌调用 `/auth/refresh` 换取新的访问令牌和刷新令牌，旧的刷新令牌随即失效；
+//!    已失效的刷新令牌再次被使用时，视为令牌泄露，整个会话被吊销。
+//! 7. 客户端调用 `/auth/logout` 吊销当前会话的所有令牌。
+//!
+//! 首次登录的钱包记录为用户，每次登录的会话记录在数据库中（见 [`crate::db`]），
+//! 会话被吊销后，即使服务重启、拒绝列表丢失，也不能再刷新令牌。
 //!
 
-use crate::auth::claim::Claim;
-use crate::auth::conf::AuthConfig;
+use crate::auth::claim::{Claim, TokenType};
+use crate::auth::conf::{AuthConfig, ChallengeMode, SiwsConfig};
+use crate::auth::nonce::{Consumed, MemoryNonceStore, NonceStore};
+use crate::auth::policy::PolicyConfig;
+use crate::auth::revocation::{MemoryRevocationStore, RevocationStore};
+use crate::db::{Database, Session, User};
 use axum::extract::State;
+use axum::http::StatusCode;
 use axum::Json;
 use error::Result;
 use jsonwebtoken::get_current_timestamp;
-use music3_common::param::auth::{AuthRequest, AuthResponse, ChallengeRequest, ChallengeResponse};
+use jsonwebtoken::jwk::JwkSet;
+use music3_common::param::auth::{
+    AuthRequest, AuthResponse, ChallengeRequest, ChallengeResponse, RefreshRequest,
+};
+use music3_common::siws::SignInMessage;
+use solana_sdk::pubkey::Pubkey;
 use std::sync::Arc;
+use uuid::Uuid;
 
 pub mod claim;
 pub mod conf;
 pub mod error;
 pub mod hmac;
 pub mod jwt;
+pub mod nonce;
+pub mod policy;
+pub mod revocation;
+pub mod scope;
 
 /// Authorizer
 #[derive(Clone)]
 pub struct Authorizer {
     jwt: jwt::Jwt,
     hmac: Arc<hmac::Hmac>,
+    nonces: Arc<dyn NonceStore>,
+    revocations: Arc<dyn RevocationStore>,
+    policy: Arc<PolicyConfig>,
+    challenge: Arc<ChallengeMode>,
+    db: Database,
 }
 
 impl Authorizer {
@@ -36,17 +62,125 @@ impl Authorizer {
         self.hmac.as_ref().clone()
     }
 
-    /// Create a new authorizer
+    /// Create a new authorizer with in-memory stores
     pub fn new(config: AuthConfig) -> anyhow::Result<Self> {
-        let jwt = jwt::Jwt::from(&config.jwt);
+        let jwt = jwt::Jwt::try_from(&config.jwt)?;
         let hmac = Arc::new(hmac::Hmac::try_from(config.hmac_secret.as_bytes())?);
-        Ok(Self { jwt, hmac })
+        Ok(Self {
+            jwt,
+            hmac,
+            nonces: Arc::new(MemoryNonceStore::default()),
+            revocations: Arc::new(MemoryRevocationStore::default()),
+            policy: Arc::new(config.policy),
+            challenge: Arc::new(config.challenge),
+            db: Database::memory(),
+        })
+    }
+
+    /// Replace the nonce store
+    pub fn with_nonce_store(mut self, nonces: impl NonceStore + 'static) -> Self {
+        self.nonces = Arc::new(nonces);
+        self
     }
 
-    /// Generate a challenge
-    pub fn generate_challenge(&self, pub_key: &solana_sdk::pubkey::Pubkey) -> ChallengeResponse {
-        let (hmac, timestamp) = self.hmac_cloned().generate(pub_key);
-        ChallengeResponse { hmac, timestamp }
+    /// Replace the revocation store
+    pub fn with_revocation_store(mut self, revocations: impl RevocationStore + 'static) -> Self {
+        self.revocations = Arc::new(revocations);
+        self
+    }
+
+    /// Replace the database recording the users and sessions
+    pub fn with_database(mut self, db: Database) -> Self {
+        self.db = db;
+        self
+    }
+
+    /// Generate a challenge and record its nonce
+    pub async fn generate_challenge(&self, pub_key: &Pubkey) -> Result<ChallengeResponse> {
+        let (hmac, timestamp, nonce) = self.hmac_cloned().generate(pub_key);
+        self.nonces
+            .insert(&nonce, timestamp + self.jwt.timestamp_timeout_sec())
+            .await;
+        let message = match self.challenge.as_ref() {
+            ChallengeMode::Binary => None,
+            ChallengeMode::Siws(config) => Some(
+                self.sign_in_message(config, pub_key, timestamp, &nonce)?
+                    .to_string(),
+            ),
+        };
+        Ok(ChallengeResponse {
+            hmac,
+            timestamp,
+            nonce,
+            message,
+        })
+    }
+
+    /// Build the sign-in message of a challenge
+    fn sign_in_message(
+        &self,
+        config: &SiwsConfig,
+        pub_key: &Pubkey,
+        timestamp: u64,
+        nonce: &[u8],
+    ) -> Result<SignInMessage> {
+        let time = |timestamp: u64| {
+            i64::try_from(timestamp)
+                .ok()
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                .ok_or(error::Error::InvalidTimestamp)
+        };
+        Ok(SignInMessage {
+            domain: config.domain.clone(),
+            address: *pub_key,
+            statement: config.statement.clone(),
+            uri: config.uri.clone(),
+            version: "1".to_string(),
+            chain_id: config.chain_id.clone(),
+            nonce: SignInMessage::encode_nonce(nonce),
+            issued_at: time(timestamp)?,
+            expiration_time: Some(time(timestamp + self.jwt.timestamp_timeout_sec())?),
+        })
+    }
+
+    /// Check that the signed message matches the challenge mode and the challenge
+    pub fn verify_sign_in_message(&self, request: &AuthRequest) -> Result<()> {
+        let invalid = |msg: &str| Err(error::Error::InvalidSignInMessage(msg.to_string()));
+        let (config, text) = match (self.challenge.as_ref(), &request.message) {
+            (ChallengeMode::Binary, None) => return Ok(()),
+            (ChallengeMode::Binary, Some(_)) => return invalid("binary challenge expected"),
+            (ChallengeMode::Siws(_), None) => return invalid("sign-in message required"),
+            (ChallengeMode::Siws(config), Some(text)) => (config, text),
+        };
+        let message: SignInMessage =
+            text.parse()
+                .map_err(|e: music3_common::siws::ParseSignInMessageError| {
+                    error::Error::InvalidSignInMessage(e.0)
+                })?;
+        let expected =
+            self.sign_in_message(config, &request.pub_key, request.timestamp, &request.nonce)?;
+        if message.domain != expected.domain {
+            return invalid("domain mismatch");
+        }
+        if message.address != expected.address {
+            return invalid("address mismatch");
+        }
+        if message.nonce != expected.nonce {
+            return invalid("nonce mismatch");
+        }
+        if message.issued_at != expected.issued_at {
+            return invalid("issued-at mismatch");
+        }
+        match message.expiration_time {
+            Some(expiration_time)
+                if expiration_time.timestamp() >= chrono::Utc::now().timestamp() => {}
+            Some(_) => return Err(error::Error::InvalidTimestamp),
+            None => return invalid("expiration-time required"),
+        }
+        if message != expected {
+            return invalid("message mismatch");
+        }
+        Ok(())
     }
 
     /// Check if the timestamp is valid
@@ -56,18 +190,22 @@ impl Authorizer {
 
     /// Verify the auth request
     pub fn verify_auth_request(&self, request: &AuthRequest) -> bool {
-        if !self
-            .hmac_cloned()
-            .verify(&request.pub_key, &request.hmac, request.timestamp)
-        {
+        if !self.hmac_cloned().verify(
+            &request.pub_key,
+            &request.hmac,
+            request.timestamp,
+            &request.nonce,
+        ) {
             return false;
         }
         let message = request.build_message();
         request.signature.verify(request.pub_key.as_ref(), &message)
     }
 
-    /// Authorize the request
-    pub fn authorize(&self, request: &AuthRequest) -> Result<AuthResponse> {
+    /// Authorize the request, consuming the nonce of its challenge
+    ///
+    /// `request.duration` is the lifetime of the session, i.e. of the refresh token.
+    pub async fn authorize(&self, request: &AuthRequest) -> Result<AuthResponse> {
         if self.jwt.max_duration_sec() < request.duration {
             return Err(error::Error::InvalidDuration(
                 request.duration,
@@ -77,25 +215,127 @@ impl Authorizer {
         if !self.is_valid_timestamp(request.timestamp) {
             return Err(error::Error::InvalidTimestamp);
         }
+        self.verify_sign_in_message(request)?;
         if !self.verify_auth_request(request) {
             return Err(error::Error::InvalidSignature);
         }
-        let claim = Claim::create(request.pub_key.to_string(), request.duration);
-        let jwt = self.jwt.sign(&claim)?;
+        match self.nonces.consume(&request.nonce).await {
+            Consumed::Fresh => {}
+            Consumed::AlreadyUsed => return Err(error::Error::NonceAlreadyUsed),
+            Consumed::Unknown => return Err(error::Error::UnknownNonce),
+        }
+        let now = get_current_timestamp();
+        let session = Session {
+            id: Uuid::new_v4().to_string(),
+            pubkey: request.pub_key.to_string(),
+            created_at: now,
+            expires_at: now + request.duration,
+            revoked: false,
+        };
+        // a returning wallet keeps its profile
+        self.db
+            .users
+            .insert_user(&User::new(session.pubkey.clone(), now))
+            .await?;
+        self.db.sessions.insert_session(&session).await?;
+        self.issue_tokens(request.pub_key, &session.id, session.expires_at)
+    }
+
+    /// Rotate a refresh token, the old one can no longer be used
+    pub async fn refresh(&self, refresh_token: &str) -> Result<AuthResponse> {
+        let claim: Claim = self.jwt.verify(refresh_token)?;
+        if claim.typ != TokenType::Refresh {
+            return Err(error::Error::UnexpectedTokenType);
+        }
+        if self.revocations.is_revoked(&claim.sid).await || self.is_session_revoked(&claim).await? {
+            return Err(error::Error::TokenRevoked);
+        }
+        if !self.revocations.revoke(&claim.jti, claim.exp).await {
+            // A rotated refresh token is used again, it may have been leaked
+            self.revocations.revoke(&claim.sid, claim.exp).await;
+            self.db.sessions.revoke_session(&claim.sid).await?;
+            return Err(error::Error::TokenRevoked);
+        }
+        let pub_key: Pubkey = claim
+            .sub
+            .parse()
+            .map_err(|_| error::Error::InvalidSubject(claim.sub.clone()))?;
+        self.issue_tokens(pub_key, &claim.sid, claim.exp)
+    }
+
+    /// Revoke the session of an access token and the token itself
+    pub async fn logout(&self, claim: &Claim) -> Result<()> {
+        self.revocations.revoke(&claim.jti, claim.exp).await;
+        // The session can not outlive the max duration
+        let session_exp = get_current_timestamp() + self.jwt.max_duration_sec();
+        self.revocations.revoke(&claim.sid, session_exp).await;
+        self.db.sessions.revoke_session(&claim.sid).await?;
+        Ok(())
+    }
+
+    /// Check the recorded session of a token, which outlives the deny-list
+    async fn is_session_revoked(&self, claim: &Claim) -> Result<bool> {
+        let session = self.db.sessions.get_session(&claim.sid).await?;
+        Ok(session.is_none_or(|session| session.revoked))
+    }
+
+    /// Verify an access token and check it against the revocation store
+    pub async fn verify_access_token(&self, token: &str) -> Result<Claim> {
+        let claim: Claim = self.jwt.verify(token)?;
+        if claim.typ != TokenType::Access {
+            return Err(error::Error::UnexpectedTokenType);
+        }
+        if self.revocations.is_revoked(&claim.jti).await
+            || self.revocations.is_revoked(&claim.sid).await
+        {
+            return Err(error::Error::TokenRevoked);
+        }
+        Ok(claim)
+    }
+
+    fn issue_tokens(
+        &self,
+        pub_key: Pubkey,
+        session_id: &str,
+        session_exp: u64,
+    ) -> Result<AuthResponse> {
+        let access_exp = session_exp.min(get_current_timestamp() + self.jwt.access_duration_sec());
+        let scopes = self.policy.scopes_of(&pub_key);
+        let access = Claim::new(
+            pub_key.to_string(),
+            session_id,
+            access_exp,
+            TokenType::Access,
+        )
+        .with_scopes(scopes.clone());
+        let refresh = Claim::new(
+            pub_key.to_string(),
+            session_id,
+            session_exp,
+            TokenType::Refresh,
+        );
         Ok(AuthResponse {
-            pub_key: request.pub_key,
-            jwt,
-            exp: claim.exp,
+            pub_key,
+            jwt: self.jwt.sign(&access)?,
+            exp: access.exp,
+            refresh_token: self.jwt.sign(&refresh)?,
+            refresh_exp: refresh.exp,
+            scopes,
         })
     }
 }
 
+/// JSON Web Key Set for verifying tokens with the public keys
+pub async fn jwks(State(authorizer): State<Authorizer>) -> Json<JwkSet> {
+    Json(authorizer.jwt.jwks())
+}
+
 /// Get challenge
 pub async fn get_challenge(
     State(authorizer): State<Authorizer>,
     Json(request): Json<ChallengeRequest>,
-) -> Json<ChallengeResponse> {
-    Json(authorizer.generate_challenge(&request.pub_key))
+) -> Result<Json<ChallengeResponse>> {
+    Ok(Json(authorizer.generate_challenge(&request.pub_key).await?))
 }
 
 /// Authorize
@@ -103,11 +343,25 @@ pub async fn authorize(
     authorizer: State<Authorizer>,
     request: Json<AuthRequest>,
 ) -> Result<Json<AuthResponse>> {
-    Ok(Json(authorizer.authorize(&request)?))
+    Ok(Json(authorizer.authorize(&request).await?))
+}
+
+/// Refresh
+pub async fn refresh(
+    State(authorizer): State<Authorizer>,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<AuthResponse>> {
+    Ok(Json(authorizer.refresh(&request.refresh_token).await?))
+}
+
+/// Logout
+pub async fn logout(State(authorizer): State<Authorizer>, claim: Claim) -> Result<StatusCode> {
+    authorizer.logout(&claim).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::auth::jwt::JwtConfig;
     use axum::routing::post;
@@ -117,12 +371,46 @@ mod tests {
     use solana_sdk::signature::Keypair;
     use solana_sdk::signer::Signer;
 
-    #[tokio::test]
-    async fn challenge_and_authorize() {
-        let config = AuthConfig {
+    fn test_config() -> AuthConfig {
+        AuthConfig {
             jwt: JwtConfig::default(),
             hmac_secret: "music3-hmac-secret".to_string(),
-        };
+            policy: PolicyConfig::default(),
+            challenge: ChallengeMode::Binary,
+        }
+    }
+
+    fn siws_config() -> AuthConfig {
+        AuthConfig {
+            challenge: ChallengeMode::Siws(SiwsConfig {
+                domain: "music3.app".to_string(),
+                statement: Some("Sign in to Music3".to_string()),
+                uri: Some("https://music3.app".to_string()),
+                chain_id: Some("devnet".to_string()),
+            }),
+            ..test_config()
+        }
+    }
+
+    pub(crate) async fn sign_challenge(authorizer: &Authorizer, keypair: &Keypair) -> AuthRequest {
+        let challenge = authorizer
+            .generate_challenge(&keypair.pubkey())
+            .await
+            .expect("Failed to generate challenge");
+        AuthRequest {
+            pub_key: keypair.pubkey(),
+            signature: keypair.sign_message(&challenge.build_message()),
+            hmac: challenge.hmac,
+            timestamp: challenge.timestamp,
+            nonce: challenge.nonce,
+            message: challenge.message,
+            duration: 3600,
+        }
+    }
+
+    #[tokio::test]
+    async fn challenge_and_authorize() {
+        let config = test_config();
 
         let max_duration_sec = config.jwt.max_duration_sec;
 
@@ -155,16 +443,304 @@ mod tests {
                 signature,
                 hmac: challenge.hmac.clone(),
                 timestamp: challenge.timestamp,
+                nonce: challenge.nonce.clone(),
+                message: challenge.message.clone(),
                 duration,
             })
             .await;
 
         assert_eq!(response.status_code(), 200);
-        let AuthResponse { pub_key, jwt, exp } = response.json();
+        let AuthResponse {
+            pub_key,
+            jwt,
+            exp,
+            refresh_token,
+            refresh_exp,
+            scopes,
+        } = response.json();
+        assert_eq!(scopes, [music3_common::param::auth::Scope::Listener]);
         assert_eq!(pub_key, keypair.pubkey());
-        assert!(exp >= challenge.timestamp + duration);
+        assert!(refresh_exp >= challenge.timestamp + duration);
+        assert!(exp <= refresh_exp);
 
-        let claim = authorizer.jwt.verify(&jwt).unwrap();
+        let claim = authorizer
+            .verify_access_token(&jwt)
+            .await
+            .expect("Failed to verify jwt");
         assert_eq!(claim.sub, keypair.pubkey().to_string());
+        assert_eq!(claim.scopes, scopes);
+        assert!(authorizer
+            .verify_access_token(&refresh_token)
+            .await
+            .is_err());
+    }
+
+    fn session_app(authorizer: &Authorizer) -> TestServer {
+        async fn me(claim: Claim) -> String {
+            claim.sub
+        }
+        let app = Router::new()
+            .route("/me", axum::routing::get(me))
+            .route("/refresh", post(refresh))
+            .route("/logout", post(logout))
+            .with_state(authorizer.clone());
+        TestServer::new(app).expect("Failed to create test server")
+    }
+
+    #[tokio::test]
+    async fn refresh_rotates_tokens() {
+        let authorizer = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        let server = session_app(&authorizer);
+        let keypair = Keypair::new();
+        let tokens = authorizer
+            .authorize(&sign_challenge(&authorizer, &keypair).await)
+            .await
+            .expect("Failed to authorize");
+
+        let response = server
+            .post("/refresh")
+            .json(&RefreshRequest {
+                refresh_token: tokens.refresh_token.clone(),
+            })
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let rotated: AuthResponse = response.json();
+        assert_eq!(rotated.pub_key, keypair.pubkey());
+        assert_ne!(rotated.refresh_token, tokens.refresh_token);
+        assert_eq!(rotated.refresh_exp, tokens.refresh_exp);
+
+        let response = server.get("/me").authorization_bearer(&rotated.jwt).await;
+        assert_eq!(response.text(), keypair.pubkey().to_string());
+
+        // an access token can not be used to refresh
+        let response = server
+            .post("/refresh")
+            .json(&RefreshRequest {
+                refresh_token: rotated.jwt.clone(),
+            })
+            .await;
+        assert_eq!(response.status_code(), 401);
+
+        // a refresh token can not be used to access the API
+        let response = server
+            .get("/me")
+            .authorization_bearer(&rotated.refresh_token)
+            .await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    #[tokio::test]
+    async fn reused_refresh_token_revokes_session() {
+        let authorizer = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        let server = session_app(&authorizer);
+        let tokens = authorizer
+            .authorize(&sign_challenge(&authorizer, &Keypair::new()).await)
+            .await
+            .expect("Failed to authorize");
+        let rotated = authorizer
+            .refresh(&tokens.refresh_token)
+            .await
+            .expect("Failed to refresh");
+
+        assert!(matches!(
+            authorizer.refresh(&tokens.refresh_token).await,
+            Err(error::Error::TokenRevoked)
+        ));
+        assert!(matches!(
+            authorizer.refresh(&rotated.refresh_token).await,
+            Err(error::Error::TokenRevoked)
+        ));
+        let response = server.get("/me").authorization_bearer(&rotated.jwt).await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_session() {
+        let authorizer = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        let server = session_app(&authorizer);
+        let tokens = authorizer
+            .authorize(&sign_challenge(&authorizer, &Keypair::new()).await)
+            .await
+            .expect("Failed to authorize");
+
+        let response = server
+            .post("/logout")
+            .authorization_bearer(&tokens.jwt)
+            .await;
+        assert_eq!(response.status_code(), 204);
+
+        let response = server.get("/me").authorization_bearer(&tokens.jwt).await;
+        assert_eq!(response.status_code(), 401);
+        let response = server
+            .post("/refresh")
+            .json(&RefreshRequest {
+                refresh_token: tokens.refresh_token,
+            })
+            .await;
+        assert_eq!(response.status_code(), 401);
+    }
+
+    #[tokio::test]
+    async fn sessions_outlive_the_deny_list() {
+        let db = Database::memory();
+        let authorizer = Authorizer::new(test_config())
+            .expect("Failed to create authorizer")
+            .with_database(db.clone());
+        let keypair = Keypair::new();
+        let tokens = authorizer
+            .authorize(&sign_challenge(&authorizer, &keypair).await)
+            .await
+            .expect("Failed to authorize");
+        let pubkey = keypair.pubkey().to_string();
+        let user = db.users.get_user(&pubkey).await.expect("Failed to get");
+        assert!(user.is_some());
+        let sessions = db
+            .sessions
+            .sessions_of(&pubkey)
+            .await
+            .expect("Failed to list");
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].expires_at, tokens.refresh_exp);
+
+        let claim = authorizer
+            .verify_access_token(&tokens.jwt)
+            .await
+            .expect("Failed to verify");
+        authorizer.logout(&claim).await.expect("Failed to logout");
+        let session = db.sessions.get_session(&claim.sid).await;
+        assert!(session.expect("Failed to get").expect("No session").revoked);
+
+        // after a restart the deny-list is empty, the recorded session still refuses
+        let restarted = Authorizer::new(test_config())
+            .expect("Failed to create authorizer")
+            .with_database(db);
+        assert!(matches!(
+            restarted.refresh(&tokens.refresh_token).await,
+            Err(error::Error::TokenRevoked)
+        ));
+    }
+
+    #[tokio::test]
+    async fn replayed_request_is_rejected() {
+        let authorizer = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        let my_app = Router::new()
+            .route("/authorize", post(authorize))
+            .with_state(authorizer.clone());
+        let server = TestServer::new(my_app).expect("Failed to create test server");
+
+        let request = sign_challenge(&authorizer, &Keypair::new()).await;
+
+        let response = server.post("/authorize").json(&request).await;
+        assert_eq!(response.status_code(), 200);
+
+        let response = server.post("/authorize").json(&request).await;
+        assert_eq!(response.status_code(), 401);
+        assert!(matches!(
+            authorizer.authorize(&request).await,
+            Err(error::Error::NonceAlreadyUsed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn unknown_nonce_is_rejected() {
+        let authorizer = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        let request = sign_challenge(&authorizer, &Keypair::new()).await;
+
+        // a challenge signed by the same secret but never issued by this authorizer
+        let other = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        assert!(matches!(
+            other.authorize(&request).await,
+            Err(error::Error::UnknownNonce)
+        ));
+        assert!(authorizer.authorize(&request).await.is_ok());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_double_spend() {
+        let authorizer = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        let request = Arc::new(sign_challenge(&authorizer, &Keypair::new()).await);
+
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let authorizer = authorizer.clone();
+                let request = request.clone();
+                tokio::spawn(async move { authorizer.authorize(&request).await.is_ok() })
+            })
+            .collect();
+
+        let mut succeeded = 0;
+        for task in tasks {
+            if task.await.expect("Task panicked") {
+                succeeded += 1;
+            }
+        }
+        assert_eq!(succeeded, 1);
+    }
+
+    #[tokio::test]
+    async fn sign_in_with_solana() {
+        let authorizer = Authorizer::new(siws_config()).expect("Failed to create authorizer");
+        let keypair = Keypair::new();
+        let request = sign_challenge(&authorizer, &keypair).await;
+
+        let text = request.message.clone().expect("Sign-in message expected");
+        let message: SignInMessage = text.parse().expect("Invalid sign-in message");
+        assert_eq!(message.domain, "music3.app");
+        assert_eq!(message.address, keypair.pubkey());
+        assert_eq!(message.nonce, SignInMessage::encode_nonce(&request.nonce));
+        assert_eq!(message.issued_at.timestamp() as u64, request.timestamp);
+
+        let response = authorizer
+            .authorize(&request)
+            .await
+            .expect("Failed to authorize");
+        assert_eq!(response.pub_key, keypair.pubkey());
+    }
+
+    #[tokio::test]
+    async fn tampered_sign_in_message_is_rejected() {
+        let authorizer = Authorizer::new(siws_config()).expect("Failed to create authorizer");
+        let keypair = Keypair::new();
+
+        let tamper = |request: &mut AuthRequest, from: &str, to: &str| {
+            let text = request.message.as_deref().unwrap_or_default();
+            let text = text.replace(from, to);
+            request.signature = keypair.sign_message(text.as_bytes());
+            request.message = Some(text);
+        };
+
+        let mut request = sign_challenge(&authorizer, &keypair).await;
+        tamper(&mut request, "music3.app wants", "evil.app wants");
+        assert!(matches!(
+            authorizer.authorize(&request).await,
+            Err(error::Error::InvalidSignInMessage(_))
+        ));
+
+        let mut request = sign_challenge(&authorizer, &keypair).await;
+        tamper(&mut request, "Chain ID: devnet", "Chain ID: mainnet");
+        assert!(matches!(
+            authorizer.authorize(&request).await,
+            Err(error::Error::InvalidSignInMessage(_))
+        ));
+
+        // the binary challenge is not accepted in this mode
+        let mut request = sign_challenge(&authorizer, &keypair).await;
+        request.message = None;
+        request.signature = keypair.sign_message(&request.build_message());
+        assert!(matches!(
+            authorizer.authorize(&request).await,
+            Err(error::Error::InvalidSignInMessage(_))
+        ));
+
+        // and the message is not accepted in the binary mode
+        let binary = Authorizer::new(test_config()).expect("Failed to create authorizer");
+        let mut request = sign_challenge(&binary, &keypair).await;
+        let text = "music3.app wants you to sign in with your Solana account:".to_string();
+        request.signature = keypair.sign_message(text.as_bytes());
+        request.message = Some(text);
+        assert!(matches!(
+            binary.authorize(&request).await,
+            Err(error::Error::InvalidSignInMessage(_))
+        ));
     }
 }
@@ -9,7 +9,19 @@ use axum_extra::headers::authorization::Bearer;
 use axum_extra::headers::Authorization;
 use axum_extra::TypedHeader;
 use jsonwebtoken::get_current_timestamp;
+use music3_common::param::auth::Scope;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Token type
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    /// Short-lived token accepted by the API
+    Access,
+    /// Long-lived token only accepted by `/auth/refresh`
+    Refresh,
+}
 
 /// JSON Web Token Claim
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,17 +30,53 @@ pub struct Claim {
     pub sub: String,
     /// Expiration
     pub exp: u64,
+    /// JWT ID
+    pub jti: String,
+    /// Session ID, shared by all tokens issued from one authorization
+    pub sid: String,
+    /// Token type
+    pub typ: TokenType,
+    /// Scopes
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
 }
 
 impl Claim {
-    /// Create a new claim
+    /// Create a new access claim in a new session
     pub fn create(sub: impl Into<String>, duration_sec: u64) -> Self {
+        Self::new(
+            sub,
+            Uuid::new_v4().to_string(),
+            get_current_timestamp() + duration_sec,
+            TokenType::Access,
+        )
+    }
+
+    /// Create a new claim of the session `sid` which expires at `exp`
+    pub fn new(sub: impl Into<String>, sid: impl Into<String>, exp: u64, typ: TokenType) -> Self {
         Self {
             sub: sub.into(),
-            exp: get_current_timestamp() + duration_sec,
+            exp,
+            jti: Uuid::new_v4().to_string(),
+            sid: sid.into(),
+            typ,
+            scopes: Vec::new(),
         }
     }
 
+    /// Set the scopes
+    pub fn with_scopes(mut self, scopes: Vec<Scope>) -> Self {
+        self.scopes = scopes;
+        self
+    }
+
+    /// Check if the claim grants a scope, `admin` grants every scope
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes
+            .iter()
+            .any(|&granted| granted == scope || granted == Scope::Admin)
+    }
+
     /// Check if the token is expired
     pub fn expired(&self) -> bool {
         self.exp < get_current_timestamp()
@@ -49,6 +97,6 @@ where
             .await
             .map_err(Error::JwtNotProvidedOrInvalid)?;
         let authorizer = Authorizer::from_ref(state);
-        authorizer.jwt.verify(bearer.token())
+        authorizer.verify_access_token(bearer.token()).await
     }
 }
@@ -1,6 +1,7 @@
 //! Configuration for the authorization module.
 //!
 use crate::auth::jwt::JwtConfig;
+use crate::auth::policy::PolicyConfig;
 use serde::{Deserialize, Serialize};
 
 /// Authorization configuration
@@ -10,6 +11,10 @@ pub struct AuthConfig {
     pub jwt: JwtConfig,
     /// HMAC secret
     pub hmac_secret: String,
+    /// Access policy
+    pub policy: PolicyConfig,
+    /// Challenge mode
+    pub challenge: ChallengeMode,
 }
 
 impl Default for AuthConfig {
@@ -17,6 +22,32 @@ impl Default for AuthConfig {
         Self {
             jwt: JwtConfig::default(),
             hmac_secret: "music3-hmac-secret".to_string(),
+            policy: PolicyConfig::default(),
+            challenge: ChallengeMode::default(),
         }
     }
 }
+
+/// Format of the message signed by the wallet
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum ChallengeMode {
+    /// Opaque `hmac || timestamp.to_be_bytes()` bytes
+    #[default]
+    Binary,
+    /// Human-readable Sign-In-With-Solana message
+    Siws(SiwsConfig),
+}
+
+/// Sign-In-With-Solana configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SiwsConfig {
+    /// Domain requesting the sign-in
+    pub domain: String,
+    /// Statement shown to the user
+    pub statement: Option<String>,
+    /// URI of the service
+    pub uri: Option<String>,
+    /// Chain ID
+    pub chain_id: Option<String>,
+}
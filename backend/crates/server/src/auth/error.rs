@@ -4,6 +4,7 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum_extra::typed_header::TypedHeaderRejection;
+use music3_common::param::auth::Scope;
 
 /// Error types for the auth module
 #[derive(Debug, thiserror::Error)]
@@ -26,6 +27,39 @@ pub enum Error {
     /// Invalid timestamp
     #[error("Invalid Timestamp")]
     InvalidTimestamp,
+    /// Token of the wrong type
+    #[error("Unexpected token type")]
+    UnexpectedTokenType,
+    /// Token or its session revoked
+    #[error("Token revoked")]
+    TokenRevoked,
+    /// Subject is not a valid public key
+    #[error("Invalid subject: {0}")]
+    InvalidSubject(String),
+    /// No key can sign tokens at the moment
+    #[error("No signing key in effect")]
+    NoSigningKey,
+    /// Token signed by an unknown or retired key
+    #[error("Unknown key id: {0}")]
+    UnknownKeyId(String),
+    /// Invalid key configuration
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    /// Token lacks the required scope
+    #[error("Scope '{0:?}' required")]
+    InsufficientScope(Scope),
+    /// Sign-in message does not match the challenge
+    #[error("Invalid sign-in message: {0}")]
+    InvalidSignInMessage(String),
+    /// Nonce already exchanged for a JWT
+    #[error("Nonce already used")]
+    NonceAlreadyUsed,
+    /// Nonce never issued, or expired
+    #[error("Nonce not issued or expired")]
+    UnknownNonce,
+    /// Database error
+    #[error(transparent)]
+    Database(#[from] crate::db::error::Error),
 }
 
 /// Result type for the auth module
@@ -33,6 +67,14 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
-        (StatusCode::UNAUTHORIZED, self.to_string()).into_response()
+        let status = match self {
+            Error::Database(e) => return e.into_response(),
+            Error::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            Error::NoSigningKey | Error::InvalidKey(_) | Error::FailedToSerializeClaim(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::UNAUTHORIZED,
+        };
+        (status, self.to_string()).into_response()
     }
 }
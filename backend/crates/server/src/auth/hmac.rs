@@ -3,11 +3,15 @@
 use hmac::Mac;
 use jsonwebtoken::get_current_timestamp;
 use music3_common::utils::Base64;
+use rand::RngCore;
 use sha2::Sha256;
 use solana_sdk::pubkey::Pubkey;
 
 type HmacSha256 = hmac::Hmac<Sha256>;
 
+/// Length of the random nonce mixed into a challenge
+pub const NONCE_LEN: usize = 16;
+
 /// HMAC
 #[derive(Clone)]
 pub struct Hmac {
@@ -31,29 +35,38 @@ impl<const L: usize> TryFrom<&[u8; L]> for Hmac {
 }
 
 impl Hmac {
-    /// Create a new HMAC and a timestamp
-    pub fn generate(mut self, pub_key: &Pubkey) -> (Base64, u64) {
+    /// Create a new HMAC, a timestamp and a random nonce
+    pub fn generate(self, pub_key: &Pubkey) -> (Base64, u64, Base64) {
         let timestamp = get_current_timestamp();
-        let message = Self::build_message(pub_key, timestamp);
+        let mut nonce = vec![0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        (
+            self.sign(pub_key, timestamp, &nonce),
+            timestamp,
+            Base64(nonce),
+        )
+    }
+
+    /// Create the HMAC of a given timestamp and nonce
+    pub fn sign(mut self, pub_key: &Pubkey, timestamp: u64, nonce: &[u8]) -> Base64 {
+        let message = Self::build_message(pub_key, timestamp, nonce);
         self.hmac_sha256.update(&message);
         let result = self.hmac_sha256.finalize();
-        let code_bytes = result.into_bytes();
-        (Base64(code_bytes.to_vec()), timestamp)
+        Base64(result.into_bytes().to_vec())
     }
 
     /// Verify the HMAC
-    pub fn verify(mut self, pub_key: &Pubkey, hmac: &Base64, timestamp: u64) -> bool {
-        let message = Self::build_message(pub_key, timestamp);
+    pub fn verify(mut self, pub_key: &Pubkey, hmac: &Base64, timestamp: u64, nonce: &[u8]) -> bool {
+        let message = Self::build_message(pub_key, timestamp, nonce);
         self.hmac_sha256.update(&message);
-        let result = self.hmac_sha256.finalize();
-        let code_bytes = result.into_bytes();
-        hmac.as_slice().eq(code_bytes.as_slice())
+        self.hmac_sha256.verify_slice(hmac.as_slice()).is_ok()
     }
 
-    fn build_message(pub_key: &Pubkey, timestamp: u64) -> Vec<u8> {
-        let mut vec = Vec::with_capacity(size_of::<Pubkey>() + size_of::<u64>());
+    fn build_message(pub_key: &Pubkey, timestamp: u64, nonce: &[u8]) -> Vec<u8> {
+        let mut vec = Vec::with_capacity(size_of::<Pubkey>() + size_of::<u64>() + nonce.len());
         vec.extend_from_slice(pub_key.as_ref());
         vec.extend_from_slice(&timestamp.to_be_bytes());
+        vec.extend_from_slice(nonce);
         vec
     }
 }
@@ -65,9 +78,21 @@ mod tests {
     #[test]
     fn generate_hmac_and_verify() {
         let secret = b"music3-hmac-secret";
-        let hmac = Hmac::try_from(secret).unwrap();
+        let hmac = Hmac::try_from(secret).expect("Failed to create hmac");
+        let pub_key = Pubkey::new_unique();
+        let (hmac_code, ts, nonce) = hmac.clone().generate(&pub_key);
+        assert_eq!(nonce.len(), NONCE_LEN);
+        assert!(hmac.clone().verify(&pub_key, &hmac_code, ts, &nonce));
+        assert!(!hmac.verify(&pub_key, &hmac_code, ts, &[0u8; NONCE_LEN]));
+    }
+
+    #[test]
+    fn nonces_differ() {
+        let hmac = Hmac::try_from(b"music3-hmac-secret").expect("Failed to create hmac");
         let pub_key = Pubkey::new_unique();
-        let (hmac_code, ts) = hmac.clone().generate(&pub_key);
-        assert!(hmac.verify(&pub_key, &hmac_code, ts));
+        let (code1, _, nonce1) = hmac.clone().generate(&pub_key);
+        let (code2, _, nonce2) = hmac.generate(&pub_key);
+        assert_ne!(nonce1, nonce2);
+        assert_ne!(code1, code2);
     }
 }
@@ -0,0 +1,200 @@
+//! # Nonce store
+//!
+//! 服务端在下发挑战时记录其中的随机 nonce，授权成功时消费该 nonce，
+//! 同一个挑战因此只能换取一次 JWT。已消费的 nonce 保留到挑战过期，以便区分重放和未下发的 nonce。
+//!
+
+use jsonwebtoken::get_current_timestamp;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Mutex, PoisonError};
+
+/// Interval between two sweeps of expired entries, in seconds
+const SWEEP_INTERVAL_SEC: u64 = 60;
+
+/// Store of issued challenge nonces
+#[axum::async_trait]
+pub trait NonceStore: Send + Sync {
+    /// Record an issued nonce which stays usable until `expires_at` (unix timestamp in seconds)
+    async fn insert(&self, nonce: &[u8], expires_at: u64);
+
+    /// Consume a nonce, only a [`Consumed::Fresh`] one may be exchanged for a JWT
+    async fn consume(&self, nonce: &[u8]) -> Consumed;
+}
+
+/// Outcome of consuming a nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Consumed {
+    /// Issued, unexpired and consumed for the first time
+    Fresh,
+    /// Consumed before
+    AlreadyUsed,
+    /// Never issued, or expired
+    Unknown,
+}
+
+/// In-memory nonce store
+#[derive(Default)]
+pub struct MemoryNonceStore {
+    nonces: Mutex<Nonces>,
+}
+
+#[derive(Default)]
+struct Nonces {
+    issued: ExpiringSet<Vec<u8>>,
+    used: ExpiringSet<Vec<u8>>,
+}
+
+#[axum::async_trait]
+impl NonceStore for MemoryNonceStore {
+    async fn insert(&self, nonce: &[u8], expires_at: u64) {
+        let mut nonces = self.nonces.lock().unwrap_or_else(PoisonError::into_inner);
+        nonces
+            .issued
+            .insert(nonce.to_vec(), expires_at, get_current_timestamp());
+    }
+
+    async fn consume(&self, nonce: &[u8]) -> Consumed {
+        let mut nonces = self.nonces.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = get_current_timestamp();
+        match nonces.issued.take(nonce, now) {
+            Some(expires_at) => {
+                nonces.used.insert(nonce.to_vec(), expires_at, now);
+                Consumed::Fresh
+            }
+            None if nonces.used.contains(nonce, now) => Consumed::AlreadyUsed,
+            None => Consumed::Unknown,
+        }
+    }
+}
+
+/// A set whose entries drop out once their expiration passes
+///
+/// Expired entries are never observable and are swept lazily on insertion.
+#[derive(Debug)]
+pub struct ExpiringSet<K> {
+    entries: HashMap<K, u64>,
+    next_sweep: u64,
+}
+
+impl<K> Default for ExpiringSet<K> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            next_sweep: 0,
+        }
+    }
+}
+
+impl<K: Hash + Eq> ExpiringSet<K> {
+    /// Insert a key which expires at `expires_at`, returns `false` if an unexpired entry already exists
+    pub fn insert(&mut self, key: K, expires_at: u64, now: u64) -> bool {
+        self.sweep(now);
+        match self.entries.get(&key) {
+            Some(&exp) if exp >= now => false,
+            _ => {
+                self.entries.insert(key, expires_at);
+                true
+            }
+        }
+    }
+
+    /// Check if an unexpired entry exists
+    pub fn contains<Q>(&self, key: &Q, now: u64) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.get(key).is_some_and(|&exp| exp >= now)
+    }
+
+    /// Remove an entry, returns `true` if it existed and had not expired
+    pub fn remove<Q>(&mut self, key: &Q, now: u64) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.take(key, now).is_some()
+    }
+
+    /// Remove an entry, returns its expiration if it existed and had not expired
+    pub fn take<Q>(&mut self, key: &Q, now: u64) -> Option<u64>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.entries.remove(key).filter(|&exp| exp >= now)
+    }
+
+    /// Number of entries, including the expired ones not swept yet
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn sweep(&mut self, now: u64) {
+        if now < self.next_sweep {
+            return;
+        }
+        self.entries.retain(|_, exp| *exp >= now);
+        self.next_sweep = now + SWEEP_INTERVAL_SEC;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiring_set() {
+        let mut set = ExpiringSet::default();
+        assert!(set.insert("a", 100, 10));
+        assert!(!set.insert("a", 100, 20));
+        assert!(set.contains("a", 100));
+        assert!(!set.contains("a", 101));
+        assert!(set.insert("b", 50, 20));
+        assert!(set.remove("b", 50));
+        assert!(!set.remove("b", 50));
+        assert!(set.insert("c", 80, 20));
+        assert!(!set.remove("c", 81));
+
+        // entries expired before the next sweep are dropped
+        assert!(set.insert("d", 200, 90));
+        assert_eq!(set.len(), 2);
+        assert!(set.insert("e", 300, 150));
+        assert_eq!(set.len(), 2);
+        assert!(!set.is_empty());
+    }
+
+    #[tokio::test]
+    async fn consume_once() {
+        let store = MemoryNonceStore::default();
+        let now = get_current_timestamp();
+        store.insert(b"nonce", now + 60).await;
+        store.insert(b"expired", now - 1).await;
+        assert_eq!(store.consume(b"nonce").await, Consumed::Fresh);
+        assert_eq!(store.consume(b"nonce").await, Consumed::AlreadyUsed);
+        assert_eq!(store.consume(b"expired").await, Consumed::Unknown);
+        assert_eq!(store.consume(b"unknown").await, Consumed::Unknown);
+    }
+
+    #[tokio::test]
+    async fn survive_poisoning() {
+        let store = std::sync::Arc::new(MemoryNonceStore::default());
+        let poisoner = store.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.nonces.lock();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(store.nonces.is_poisoned());
+
+        let now = get_current_timestamp();
+        store.insert(b"nonce", now + 60).await;
+        assert_eq!(store.consume(b"nonce").await, Consumed::Fresh);
+    }
+}
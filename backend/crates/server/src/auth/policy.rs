@@ -0,0 +1,60 @@
+//! # Access policy
+//!
+//! 服务端根据策略表决定每个 PubKey 获得哪些权限，签发和刷新令牌时都会重新计算。
+//!
+
+use music3_common::param::auth::Scope;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Policy table
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PolicyConfig {
+    /// Scopes granted to every public key
+    pub default_scopes: Vec<Scope>,
+    /// Additional scopes granted to specific public keys (in base58)
+    pub grants: BTreeMap<String, Vec<Scope>>,
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            default_scopes: vec![Scope::Listener],
+            grants: BTreeMap::new(),
+        }
+    }
+}
+
+impl PolicyConfig {
+    /// Scopes of a public key
+    pub fn scopes_of(&self, pub_key: &Pubkey) -> Vec<Scope> {
+        let mut scopes: BTreeSet<Scope> = self.default_scopes.iter().copied().collect();
+        if let Some(granted) = self.grants.get(&pub_key.to_string()) {
+            scopes.extend(granted.iter().copied());
+        }
+        scopes.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes_of() {
+        let creator = Pubkey::new_unique();
+        let policy = PolicyConfig {
+            default_scopes: vec![Scope::Listener],
+            grants: BTreeMap::from([(
+                creator.to_string(),
+                vec![Scope::Upload, Scope::Creator, Scope::Listener],
+            )]),
+        };
+        assert_eq!(
+            policy.scopes_of(&creator),
+            [Scope::Listener, Scope::Creator, Scope::Upload]
+        );
+        assert_eq!(policy.scopes_of(&Pubkey::new_unique()), [Scope::Listener]);
+    }
+}
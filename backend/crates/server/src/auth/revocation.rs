@@ -0,0 +1,71 @@
+//! # Token revocation
+//!
+//! 被吊销的 JWT ID（`jti`）和会话 ID（`sid`）会记录在拒绝列表中，直到对应的令牌自然过期。
+//!
+
+use crate::auth::nonce::ExpiringSet;
+use jsonwebtoken::get_current_timestamp;
+use std::sync::{Mutex, PoisonError};
+
+/// Deny-list of revoked token and session IDs
+#[axum::async_trait]
+pub trait RevocationStore: Send + Sync {
+    /// Revoke an ID until `expires_at` (unix timestamp in seconds), returns `false` if it was already revoked
+    async fn revoke(&self, id: &str, expires_at: u64) -> bool;
+
+    /// Check if an ID is revoked
+    async fn is_revoked(&self, id: &str) -> bool;
+}
+
+/// In-memory revocation store
+#[derive(Default)]
+pub struct MemoryRevocationStore {
+    revoked: Mutex<ExpiringSet<String>>,
+}
+
+#[axum::async_trait]
+impl RevocationStore for MemoryRevocationStore {
+    async fn revoke(&self, id: &str, expires_at: u64) -> bool {
+        let mut revoked = self.revoked.lock().unwrap_or_else(PoisonError::into_inner);
+        revoked.insert(id.to_string(), expires_at, get_current_timestamp())
+    }
+
+    async fn is_revoked(&self, id: &str) -> bool {
+        let revoked = self.revoked.lock().unwrap_or_else(PoisonError::into_inner);
+        revoked.contains(id, get_current_timestamp())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn revoke_once() {
+        let store = MemoryRevocationStore::default();
+        let now = get_current_timestamp();
+        assert!(!store.is_revoked("jti").await);
+        assert!(store.revoke("jti", now + 60).await);
+        assert!(!store.revoke("jti", now + 60).await);
+        assert!(store.is_revoked("jti").await);
+        assert!(store.revoke("expired", now - 1).await);
+        assert!(!store.is_revoked("expired").await);
+    }
+
+    #[tokio::test]
+    async fn survive_poisoning() {
+        let store = std::sync::Arc::new(MemoryRevocationStore::default());
+        let poisoner = store.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoner.revoked.lock();
+            panic!("poison the lock");
+        })
+        .join();
+        assert!(store.revoked.is_poisoned());
+
+        let now = get_current_timestamp();
+        assert!(!store.is_revoked("jti").await);
+        assert!(store.revoke("jti", now + 60).await);
+        assert!(store.is_revoked("jti").await);
+    }
+}
@@ -0,0 +1,141 @@
+//! # Scope-based authorization
+//!
+//! 在路由处理函数中使用 `RequireScope<Creator>` 等提取器声明所需的权限：
+//! 未登录或令牌无效返回 401，令牌缺少所需权限返回 403。
+//!
+
+use crate::auth::claim::Claim;
+use crate::auth::error::Error;
+use crate::auth::Authorizer;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::request::Parts;
+use music3_common::param::auth::Scope;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+/// Marker type of a scope
+pub trait ScopeMarker: Send + Sync {
+    /// The scope required
+    const SCOPE: Scope;
+}
+
+macro_rules! scope_marker {
+    ($($(#[$meta:meta])* $name:ident),* $(,)?) => {
+        $(
+            $(#[$meta])*
+            pub struct $name;
+
+            impl ScopeMarker for $name {
+                const SCOPE: Scope = Scope::$name;
+            }
+        )*
+    };
+}
+
+scope_marker! {
+    /// Requires the `listener` scope
+    Listener,
+    /// Requires the `creator` scope
+    Creator,
+    /// Requires the `upload` scope
+    Upload,
+    /// Requires the `admin` scope
+    Admin,
+}
+
+/// Claim extractor which rejects tokens without the scope `S`
+pub struct RequireScope<S> {
+    /// The verified claim
+    pub claim: Claim,
+    _scope: PhantomData<S>,
+}
+
+impl<S> Deref for RequireScope<S> {
+    type Target = Claim;
+    fn deref(&self) -> &Self::Target {
+        &self.claim
+    }
+}
+
+impl<S: ScopeMarker> RequireScope<S> {
+    /// Check the scope of a claim
+    pub fn try_from_claim(claim: Claim) -> Result<Self, Error> {
+        if claim.has_scope(S::SCOPE) {
+            Ok(Self {
+                claim,
+                _scope: PhantomData,
+            })
+        } else {
+            Err(Error::InsufficientScope(S::SCOPE))
+        }
+    }
+}
+
+#[axum::async_trait]
+impl<S, T> FromRequestParts<S> for RequireScope<T>
+where
+    Authorizer: FromRef<S>,
+    S: Sync + Send,
+    T: ScopeMarker,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claim = Claim::from_request_parts(parts, state).await?;
+        Self::try_from_claim(claim)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::conf::AuthConfig;
+    use crate::auth::policy::PolicyConfig;
+    use axum::routing::get;
+    use axum::Router;
+    use axum_test::TestServer;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use std::collections::BTreeMap;
+
+    async fn creator_only(claim: RequireScope<Creator>) -> String {
+        claim.sub.clone()
+    }
+
+    #[tokio::test]
+    async fn require_scope() {
+        let creator = Keypair::new();
+        let admin = Keypair::new();
+        let listener = Keypair::new();
+        let config = AuthConfig {
+            policy: PolicyConfig {
+                default_scopes: vec![Scope::Listener],
+                grants: BTreeMap::from([
+                    (creator.pubkey().to_string(), vec![Scope::Creator]),
+                    (admin.pubkey().to_string(), vec![Scope::Admin]),
+                ]),
+            },
+            ..AuthConfig::default()
+        };
+        let authorizer = Authorizer::new(config).expect("Failed to create authorizer");
+        let app = Router::new()
+            .route("/creator", get(creator_only))
+            .with_state(authorizer.clone());
+        let server = TestServer::new(app).expect("Failed to create test server");
+
+        let response = server.get("/creator").await;
+        assert_eq!(response.status_code(), 401);
+
+        for (keypair, status) in [(&creator, 200), (&admin, 200), (&listener, 403)] {
+            let tokens = authorizer
+                .authorize(&crate::auth::tests::sign_challenge(&authorizer, keypair).await)
+                .await
+                .expect("Failed to authorize");
+            let response = server
+                .get("/creator")
+                .authorization_bearer(&tokens.jwt)
+                .await;
+            assert_eq!(response.status_code(), status);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! # Chain
+//!
+//! 查询链上状态：钱包持有的代币、NFT 的 Metaplex 元数据中已验证的集合（collection）和创作者，以及钱包的质押账户。
+//! [`rpc::SolanaRpc`] 通过 Solana JSON-RPC 查询，[`memory::MemoryChain`] 是内存中的替身，用于离线测试。
+//!
+
+use crate::chain::conf::ChainConfig;
+use crate::chain::error::Result;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+pub mod conf;
+pub mod error;
+pub mod memory;
+pub mod rpc;
+
+/// Token held by a wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAccount {
+    /// Mint of the token
+    pub mint: Pubkey,
+    /// Amount in base units
+    pub amount: u64,
+}
+
+/// Delegated stake account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StakeAccount {
+    /// Address of the stake account
+    pub address: Pubkey,
+    /// Vote account the stake is delegated to
+    pub voter: Pubkey,
+    /// Delegated lamports
+    pub lamports: u64,
+    /// Epoch the delegation started activating
+    pub activation_epoch: u64,
+    /// Epoch the delegation started deactivating
+    pub deactivation_epoch: Option<u64>,
+}
+
+/// Read access to the chain
+#[axum::async_trait]
+pub trait Chain: Send + Sync {
+    /// Token accounts of `owner`, empty ones included
+    async fn token_accounts(&self, owner: &Pubkey) -> Result<Vec<TokenAccount>>;
+
+    /// Verified collection of the NFT `mint`
+    async fn collection(&self, mint: &Pubkey) -> Result<Option<Pubkey>>;
+
+    /// Verified creators of the NFT `mint`
+    async fn creators(&self, mint: &Pubkey) -> Result<Vec<Pubkey>>;
+
+    /// Delegated stake accounts with `staker` as the stake authority
+    async fn stake_accounts(&self, staker: &Pubkey) -> Result<Vec<StakeAccount>>;
+
+    /// Current epoch
+    async fn epoch(&self) -> Result<u64>;
+}
+
+/// Find an NFT held by `owner` which is `mint`, or a member of `collection`
+pub async fn held_nft(
+    chain: &dyn Chain,
+    owner: &Pubkey,
+    mint: Option<&Pubkey>,
+    collection: Option<&Pubkey>,
+) -> Result<Option<Pubkey>> {
+    if mint.is_none() && collection.is_none() {
+        return Ok(None);
+    }
+    let held: Vec<Pubkey> = chain
+        .token_accounts(owner)
+        .await?
+        .into_iter()
+        .filter(|account| account.amount > 0)
+        .map(|account| account.mint)
+        .collect();
+    if let Some(mint) = mint.filter(|mint| held.contains(mint)) {
+        return Ok(Some(*mint));
+    }
+    if let Some(collection) = collection {
+        for mint in held {
+            if chain.collection(&mint).await?.as_ref() == Some(collection) {
+                return Ok(Some(mint));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Create the chain access of a configuration
+pub fn from_config(config: &ChainConfig) -> anyhow::Result<Arc<dyn Chain>> {
+    Ok(Arc::new(rpc::SolanaRpc::new(config.rpc_url.parse()?)))
+}
@@ -0,0 +1,19 @@
+//! Configuration for the chain module.
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// Solana configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ChainConfig {
+    /// URL of the JSON-RPC API
+    pub rpc_url: String,
+}
+
+impl Default for ChainConfig {
+    fn default() -> Self {
+        Self {
+            rpc_url: "https://api.devnet.solana.com".to_string(),
+        }
+    }
+}
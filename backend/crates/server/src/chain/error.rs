@@ -0,0 +1,33 @@
+//! # Error types for the chain module
+//!
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use solana_client::client_error::ClientError;
+
+/// Error types for the chain module
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Request to the RPC node failed, or the node returned an error
+    #[error(transparent)]
+    Rpc(Box<ClientError>),
+    /// Response that cannot be understood
+    #[error("Invalid RPC response: {0}")]
+    InvalidResponse(String),
+}
+
+impl From<ClientError> for Error {
+    fn from(e: ClientError) -> Self {
+        // boxed, a client error is hundreds of bytes
+        Self::Rpc(Box::new(e))
+    }
+}
+
+/// Result type for the chain module
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        (StatusCode::BAD_GATEWAY, self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,101 @@
+//! # In-memory chain
+//!
+//! 链的内存替身，测试中直接设置钱包持有的代币、NFT 所属的集合和创作者、质押账户和当前纪元。
+//!
+
+use crate::chain::error::Result;
+use crate::chain::{Chain, StakeAccount, TokenAccount};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// Chain state kept in memory
+#[derive(Default)]
+pub struct MemoryChain {
+    balances: Mutex<HashMap<Pubkey, HashMap<Pubkey, u64>>>,
+    collections: Mutex<HashMap<Pubkey, Pubkey>>,
+    creators: Mutex<HashMap<Pubkey, Vec<Pubkey>>>,
+    stakes: Mutex<HashMap<Pubkey, Vec<StakeAccount>>>,
+    epoch: AtomicU64,
+}
+
+impl MemoryChain {
+    /// Set the balance of `owner` in tokens of `mint`
+    pub fn set_balance(&self, owner: &Pubkey, mint: &Pubkey, amount: u64) {
+        self.balances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(*owner)
+            .or_default()
+            .insert(*mint, amount);
+    }
+
+    /// Add the NFT `mint` to a verified `collection`
+    pub fn set_collection(&self, mint: &Pubkey, collection: &Pubkey) {
+        self.collections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(*mint, *collection);
+    }
+
+    /// Add a verified `creator` to the NFT `mint`
+    pub fn add_creator(&self, mint: &Pubkey, creator: &Pubkey) {
+        self.creators
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(*mint)
+            .or_default()
+            .push(*creator);
+    }
+
+    /// Add a stake account with `staker` as the stake authority
+    pub fn add_stake(&self, staker: &Pubkey, stake: StakeAccount) {
+        self.stakes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(*staker)
+            .or_default()
+            .push(stake);
+    }
+
+    /// Set the current epoch
+    pub fn set_epoch(&self, epoch: u64) {
+        self.epoch.store(epoch, Ordering::SeqCst);
+    }
+}
+
+#[axum::async_trait]
+impl Chain for MemoryChain {
+    async fn token_accounts(&self, owner: &Pubkey) -> Result<Vec<TokenAccount>> {
+        let balances = self.balances.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(balances
+            .get(owner)
+            .into_iter()
+            .flatten()
+            .map(|(&mint, &amount)| TokenAccount { mint, amount })
+            .collect())
+    }
+
+    async fn collection(&self, mint: &Pubkey) -> Result<Option<Pubkey>> {
+        let collections = self
+            .collections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ok(collections.get(mint).copied())
+    }
+
+    async fn creators(&self, mint: &Pubkey) -> Result<Vec<Pubkey>> {
+        let creators = self.creators.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(creators.get(mint).cloned().unwrap_or_default())
+    }
+
+    async fn stake_accounts(&self, staker: &Pubkey) -> Result<Vec<StakeAccount>> {
+        let stakes = self.stakes.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(stakes.get(staker).cloned().unwrap_or_default())
+    }
+
+    async fn epoch(&self) -> Result<u64> {
+        Ok(self.epoch.load(Ordering::SeqCst))
+    }
+}
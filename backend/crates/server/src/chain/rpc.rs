@@ -0,0 +1,459 @@
+//! # Solana RPC
+//!
+//! 通过 [`RpcClient`] 访问节点：代币账户通过 `getTokenAccountsByOwner` 查询（SPL Token 和 Token-2022 两个程序），
+//! NFT 的集合和创作者从 Metaplex 元数据账户（见 [`Metadata::find_pda`]）中解析，
+//! 质押账户通过 `getProgramAccounts` 按质押权限（偏移 12 的 `staker`）过滤。
+//!
+
+use crate::chain::error::{Error, Result};
+use crate::chain::{Chain, StakeAccount, TokenAccount};
+use mpl_token_metadata::accounts::Metadata;
+use reqwest::Url;
+use solana_account_decoder::parse_token::UiTokenAccount;
+use solana_account_decoder::{UiAccountData, UiAccountEncoding};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_client::rpc_request::TokenAccountsFilter;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::stake::state::StakeStateV2;
+use std::str::FromStr;
+
+/// SPL Token program
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+/// SPL Token-2022 program
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Offset of the stake authority in a stake account
+const STAKER_OFFSET: usize = 12;
+
+/// Chain access through an RPC node
+pub struct SolanaRpc {
+    client: RpcClient,
+}
+
+impl SolanaRpc {
+    /// Create a chain access using the node at `url`
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: RpcClient::new(url.to_string()),
+        }
+    }
+
+    /// Metaplex metadata of the NFT `mint`, if any
+    async fn metadata(&self, mint: &Pubkey) -> Result<Option<Metadata>> {
+        let (address, _) = Metadata::find_pda(mint);
+        let account = self
+            .client
+            .get_account_with_commitment(&address, self.client.commitment())
+            .await?
+            .value;
+        let Some(account) = account.filter(|account| account.owner == mpl_token_metadata::ID)
+        else {
+            return Ok(None);
+        };
+        Metadata::safe_deserialize(&account.data)
+            .map(Some)
+            .map_err(|e| Error::InvalidResponse(e.to_string()))
+    }
+}
+
+#[axum::async_trait]
+impl Chain for SolanaRpc {
+    async fn token_accounts(&self, owner: &Pubkey) -> Result<Vec<TokenAccount>> {
+        let mut accounts = Vec::new();
+        for program in [TOKEN_PROGRAM_ID, TOKEN_2022_PROGRAM_ID] {
+            let keyed = self
+                .client
+                .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(program))
+                .await?;
+            for keyed in keyed {
+                let UiAccountData::Json(data) = keyed.account.data else {
+                    return Err(Error::InvalidResponse(
+                        "token account not parsed".to_string(),
+                    ));
+                };
+                let info: UiTokenAccount = serde_json::from_value(data.parsed["info"].clone())
+                    .map_err(|e| Error::InvalidResponse(e.to_string()))?;
+                accounts.push(TokenAccount {
+                    mint: parse_pubkey(&info.mint)?,
+                    amount: info
+                        .token_amount
+                        .amount
+                        .parse()
+                        .map_err(|_| Error::InvalidResponse(info.token_amount.amount))?,
+                });
+            }
+        }
+        Ok(accounts)
+    }
+
+    async fn collection(&self, mint: &Pubkey) -> Result<Option<Pubkey>> {
+        let metadata = self.metadata(mint).await?;
+        Ok(metadata
+            .and_then(|metadata| metadata.collection)
+            .filter(|collection| collection.verified)
+            .map(|collection| collection.key))
+    }
+
+    async fn creators(&self, mint: &Pubkey) -> Result<Vec<Pubkey>> {
+        let metadata = self.metadata(mint).await?;
+        Ok(metadata
+            .and_then(|metadata| metadata.creators)
+            .into_iter()
+            .flatten()
+            .filter(|creator| creator.verified)
+            .map(|creator| creator.address)
+            .collect())
+    }
+
+    async fn stake_accounts(&self, staker: &Pubkey) -> Result<Vec<StakeAccount>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                STAKER_OFFSET,
+                staker.as_ref(),
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let keyed = self
+            .client
+            .get_program_accounts_with_config(&solana_sdk::stake::program::ID, config)
+            .await?;
+        let mut accounts = Vec::new();
+        for (address, account) in keyed {
+            let state: StakeStateV2 = account
+                .deserialize_data()
+                .map_err(|e| Error::InvalidResponse(e.to_string()))?;
+            // initialized but not delegated yet
+            let Some(stake) = state.stake() else {
+                continue;
+            };
+            let delegation = stake.delegation;
+            accounts.push(StakeAccount {
+                address,
+                voter: delegation.voter_pubkey,
+                lamports: delegation.stake,
+                activation_epoch: delegation.activation_epoch,
+                // `u64::MAX` until deactivated
+                deactivation_epoch: (delegation.deactivation_epoch != u64::MAX)
+                    .then_some(delegation.deactivation_epoch),
+            });
+        }
+        Ok(accounts)
+    }
+
+    async fn epoch(&self) -> Result<u64> {
+        Ok(self.client.get_epoch_info().await?.epoch)
+    }
+}
+
+fn parse_pubkey(s: &str) -> Result<Pubkey> {
+    Pubkey::from_str(s).map_err(|e| Error::InvalidResponse(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde_json::{json, Value};
+    use solana_sdk::account::Account;
+    use solana_sdk::stake::stake_flags::StakeFlags;
+    use solana_sdk::stake::state::{Authorized, Delegation, Meta, Stake};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// Borsh-encoded metadata of an NFT by `creator` in `collection`, laid out by hand to pin the account format
+    fn metadata(
+        mint: &Pubkey,
+        creator: (&Pubkey, bool),
+        collection: Option<(&Pubkey, bool)>,
+    ) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+        for field in ["Song", "M3", "https://music3.example/1.json"] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+        data.extend_from_slice(&500u16.to_le_bytes());
+        // one creator
+        data.extend_from_slice(&[1, 1, 0, 0, 0]);
+        data.extend_from_slice(creator.0.as_ref());
+        data.extend_from_slice(&[u8::from(creator.1), 100]);
+        // primary sale happened, is mutable, edition nonce, non-fungible token standard
+        data.extend_from_slice(&[1, 1, 1, 255, 1, 0]);
+        match collection {
+            Some((key, verified)) => {
+                data.extend_from_slice(&[1, u8::from(verified)]);
+                data.extend_from_slice(key.as_ref());
+            }
+            None => data.push(0),
+        }
+        // uses, collection details and programmable config
+        data.extend_from_slice(&[0, 0, 0]);
+        data
+    }
+
+    /// Accounts served by the mock node
+    #[derive(Default)]
+    struct Fixtures {
+        tokens: Vec<Value>,
+        accounts: HashMap<Pubkey, Account>,
+        stakes: Vec<(Pubkey, Account)>,
+        epoch: u64,
+    }
+
+    /// Account in the base64 encoding of the RPC API
+    fn encoded(account: &Account) -> Value {
+        json!({
+            "lamports": account.lamports,
+            "data": [STANDARD.encode(&account.data), "base64"],
+            "owner": account.owner.to_string(),
+            "executable": account.executable,
+            "rentEpoch": account.rent_epoch,
+            "space": account.data.len(),
+        })
+    }
+
+    /// In-process RPC node answering from fixed accounts
+    async fn mock_rpc(fixtures: Fixtures) -> Url {
+        let fixtures = Arc::new(fixtures);
+        let handler = move |Json(request): Json<Value>| {
+            let fixtures = fixtures.clone();
+            async move {
+                let params = &request["params"];
+                let context = json!({ "slot": 1 });
+                let result = match request["method"].as_str() {
+                    Some("getTokenAccountsByOwner")
+                        if params[1]["programId"] == json!(TOKEN_PROGRAM_ID.to_string()) =>
+                    {
+                        json!({ "context": context, "value": fixtures.tokens })
+                    }
+                    Some("getTokenAccountsByOwner") => json!({ "context": context, "value": [] }),
+                    Some("getAccountInfo") => {
+                        let value = params[0]
+                            .as_str()
+                            .and_then(|key| Pubkey::from_str(key).ok())
+                            .and_then(|key| fixtures.accounts.get(&key))
+                            .map(encoded);
+                        json!({ "context": context, "value": value })
+                    }
+                    Some("getProgramAccounts") => fixtures
+                        .stakes
+                        .iter()
+                        .map(|(address, account)| {
+                            json!({ "pubkey": address.to_string(), "account": encoded(account) })
+                        })
+                        .collect(),
+                    Some("getEpochInfo") => json!({
+                        "epoch": fixtures.epoch,
+                        "slotIndex": 0,
+                        "slotsInEpoch": 432_000,
+                        "absoluteSlot": 1,
+                        "blockHeight": 1,
+                    }),
+                    Some("getVersion") => json!({ "solana-core": "2.0.9" }),
+                    _ => {
+                        return Json(json!({
+                            "jsonrpc": "2.0",
+                            "id": request["id"],
+                            "error": { "code": -32601, "message": "Method not found" },
+                        }))
+                    }
+                };
+                Json(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+            }
+        };
+        let app = Router::new().route("/", post(handler));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let addr = listener.local_addr().expect("No local address");
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Url::parse(&format!("http://{addr}")).expect("Invalid URL")
+    }
+
+    fn token_account(owner: &Pubkey, mint: &Pubkey, amount: u64) -> Value {
+        json!({
+            "pubkey": Pubkey::new_unique().to_string(),
+            "account": {
+                "lamports": 2_039_280,
+                "data": {
+                    "program": "spl-token",
+                    "parsed": {
+                        "type": "account",
+                        "info": {
+                            "mint": mint.to_string(),
+                            "owner": owner.to_string(),
+                            "tokenAmount": {
+                                "amount": amount.to_string(),
+                                "decimals": 0,
+                                "uiAmount": amount as f64,
+                                "uiAmountString": amount.to_string(),
+                            },
+                            "state": "initialized",
+                            "isNative": false,
+                        },
+                    },
+                    "space": 165,
+                },
+                "owner": TOKEN_PROGRAM_ID.to_string(),
+                "executable": false,
+                "rentEpoch": 0,
+                "space": 165,
+            },
+        })
+    }
+
+    fn metadata_account(data: Vec<u8>) -> Account {
+        Account {
+            lamports: 5_616_720,
+            data,
+            owner: mpl_token_metadata::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    fn stake_account(staker: &Pubkey, voter: Option<&Pubkey>, deactivation_epoch: u64) -> Account {
+        let meta = Meta {
+            rent_exempt_reserve: 2_282_880,
+            authorized: Authorized {
+                staker: *staker,
+                withdrawer: *staker,
+            },
+            ..Meta::default()
+        };
+        let state = match voter {
+            Some(voter) => StakeStateV2::Stake(
+                meta,
+                Stake {
+                    delegation: Delegation {
+                        voter_pubkey: *voter,
+                        stake: 5_000_000_000,
+                        activation_epoch: 10,
+                        deactivation_epoch,
+                        ..Delegation::default()
+                    },
+                    credits_observed: 0,
+                },
+                StakeFlags::empty(),
+            ),
+            None => StakeStateV2::Initialized(meta),
+        };
+        Account::new_data_with_space(
+            5_002_282_880,
+            &state,
+            StakeStateV2::size_of(),
+            &solana_sdk::stake::program::ID,
+        )
+        .expect("Failed to encode")
+    }
+
+    #[tokio::test]
+    async fn solana_rpc() {
+        let (nft, unverified, collection, empty) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (active, deactivated, voter) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (owner, artist) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let url = mock_rpc(Fixtures {
+            tokens: vec![
+                token_account(&owner, &nft, 1),
+                token_account(&owner, &empty, 0),
+            ],
+            accounts: HashMap::from([
+                (
+                    Metadata::find_pda(&nft).0,
+                    metadata_account(metadata(&nft, (&artist, true), Some((&collection, true)))),
+                ),
+                (
+                    Metadata::find_pda(&unverified).0,
+                    metadata_account(metadata(
+                        &unverified,
+                        (&artist, false),
+                        Some((&collection, false)),
+                    )),
+                ),
+            ]),
+            stakes: vec![
+                (active, stake_account(&owner, Some(&voter), u64::MAX)),
+                (deactivated, stake_account(&owner, Some(&voter), 12)),
+                (Pubkey::new_unique(), stake_account(&owner, None, u64::MAX)),
+            ],
+            epoch: 20,
+        })
+        .await;
+        let rpc = SolanaRpc::new(url);
+        assert_eq!(
+            rpc.token_accounts(&owner)
+                .await
+                .expect("Failed to get tokens"),
+            vec![
+                TokenAccount {
+                    mint: nft,
+                    amount: 1
+                },
+                TokenAccount {
+                    mint: empty,
+                    amount: 0
+                },
+            ]
+        );
+        for (mint, expected) in [(nft, Some(collection)), (unverified, None), (empty, None)] {
+            assert_eq!(
+                rpc.collection(&mint)
+                    .await
+                    .expect("Failed to get collection"),
+                expected
+            );
+        }
+        for (mint, expected) in [(nft, vec![artist]), (unverified, vec![]), (empty, vec![])] {
+            let creators = rpc.creators(&mint).await;
+            assert_eq!(creators.expect("Failed to get creators"), expected);
+        }
+        assert_eq!(
+            crate::chain::held_nft(&rpc, &owner, None, Some(&collection))
+                .await
+                .expect("Failed to check"),
+            Some(nft)
+        );
+        assert_eq!(
+            crate::chain::held_nft(&rpc, &owner, Some(&empty), None)
+                .await
+                .expect("Failed to check"),
+            None
+        );
+
+        let stake = |address, deactivation_epoch| StakeAccount {
+            address,
+            voter,
+            lamports: 5_000_000_000,
+            activation_epoch: 10,
+            deactivation_epoch,
+        };
+        assert_eq!(
+            rpc.stake_accounts(&owner)
+                .await
+                .expect("Failed to get stakes"),
+            vec![stake(active, None), stake(deactivated, Some(12))]
+        );
+        assert_eq!(rpc.epoch().await.expect("Failed to get epoch"), 20);
+    }
+}
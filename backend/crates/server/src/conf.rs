@@ -2,6 +2,16 @@
 //!
 
 use crate::auth::conf::AuthConfig;
+use crate::chain::conf::ChainConfig;
+use crate::crypto::conf::CryptoConfig;
+use crate::db::conf::DatabaseConfig;
+use crate::entitlement::conf::EntitlementConfig;
+use crate::hls::conf::HlsConfig;
+use crate::playback::conf::PlaybackConfig;
+use crate::profile::conf::ProfileConfig;
+use crate::search::conf::SearchConfig;
+use crate::storage::conf::StorageConfig;
+use crate::upload::conf::UploadConfig;
 use serde::{Deserialize, Serialize};
 
 /// Music3 backend configuration
@@ -10,6 +20,26 @@ use serde::{Deserialize, Serialize};
 pub struct Config {
     /// Authorization configuration
     pub auth: AuthConfig,
+    /// Upload configuration
+    pub upload: UploadConfig,
+    /// Storage configuration
+    pub storage: StorageConfig,
+    /// Encryption configuration
+    pub crypto: CryptoConfig,
+    /// Solana configuration
+    pub chain: ChainConfig,
+    /// Entitlement configuration
+    pub entitlement: EntitlementConfig,
+    /// Playback configuration
+    pub playback: PlaybackConfig,
+    /// HLS packaging configuration
+    pub hls: HlsConfig,
+    /// Database configuration
+    pub database: DatabaseConfig,
+    /// Profile configuration
+    pub profile: ProfileConfig,
+    /// Search configuration
+    pub search: SearchConfig,
 }
 
 #[cfg(test)]
@@ -19,8 +49,8 @@ mod tests {
     #[test]
     fn serde() {
         let config = Config::default();
-        let json = serde_json::to_string(&config).unwrap();
-        let config2: Config = serde_json::from_str(&json).unwrap();
+        let json = serde_json::to_string(&config).expect("Failed to serialize");
+        let config2: Config = serde_json::from_str(&json).expect("Failed to deserialize");
         assert_eq!(config, config2);
     }
 }
@@ -0,0 +1,190 @@
+//! # 信封加密
+//!
+//! 上传的音频使用随机生成的内容密钥，按 [`music3_common::crypto`] 的格式分块加密后保存到存储后端。
+//! 内容密钥再由主密钥加密（包装），服务端只保存密文的 CID 和包装后的密钥，不保存明文和内容密钥。
+//!
+//! 主密钥由配置中的 [`CryptoConfig::master_key`] 经 HKDF-SHA256 派生，没有默认值，未配置时服务无法启动；
+//! 更换配置中的密钥后已有的音频将无法解密。
+//!
+//! 上传的文件用 [`Vault::encrypt_file`] 逐块读取、加密，以流的形式写入存储后端，内存中只保留一个分块。
+//!
+
+use crate::crypto::conf::CryptoConfig;
+use crate::storage::ByteStream;
+use axum::body::Bytes;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use futures::StreamExt;
+use hkdf::Hkdf;
+use music3_common::crypto::{ContentKey, Encryptor, Error, Result, KEY_LEN};
+use music3_common::utils::Base64;
+use sha2::Sha256;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub mod conf;
+
+/// Length of the nonce prepended to a wrapped key
+const NONCE_LEN: usize = 12;
+
+/// Associated data binding a wrapped key to its purpose
+const WRAP_AAD: &[u8] = b"music3 content key";
+
+/// Encrypts files with new content keys and wraps the keys with the master key
+#[derive(Clone)]
+pub struct Vault {
+    master_key: ChaCha20Poly1305,
+    chunk_size: u32,
+}
+
+impl Vault {
+    /// Create a vault from the configuration
+    pub fn new(config: &CryptoConfig) -> anyhow::Result<Self> {
+        anyhow::ensure!(config.chunk_size > 0, "Chunk size must not be zero");
+        let secret = config.master_key.as_deref().unwrap_or_default();
+        anyhow::ensure!(!secret.is_empty(), "Master key must be set");
+        let mut master_key = [0; KEY_LEN];
+        Hkdf::<Sha256>::new(None, secret.as_bytes())
+            .expand(b"music3 master key", &mut master_key)
+            .map_err(|e| anyhow::anyhow!("Failed to derive the master key: {e}"))?;
+        Ok(Self {
+            master_key: ChaCha20Poly1305::new(Key::from_slice(&master_key)),
+            chunk_size: config.chunk_size,
+        })
+    }
+
+    /// Encrypt `plaintext` with a new content key, returns the ciphertext and the wrapped key
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<(Vec<u8>, Base64)> {
+        let key = ContentKey::generate();
+        let ciphertext = music3_common::crypto::encrypt(&key, self.chunk_size, plaintext)?;
+        Ok((ciphertext, self.wrap(&key)?))
+    }
+
+    /// Encrypt the `len` bytes of `file` with a new content key one chunk at a time,
+    /// returns the ciphertext as a stream and the wrapped key
+    pub fn encrypt_file<R>(&self, file: R, len: u64) -> Result<(ByteStream, Base64)>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let key = ContentKey::generate();
+        let encryptor = Encryptor::new(&key, self.chunk_size)?;
+        let count = encryptor.chunk_count(len)?;
+        let header = Bytes::copy_from_slice(&encryptor.header().to_bytes());
+        let chunk_size = u64::from(self.chunk_size);
+        let chunks = futures::stream::try_unfold(
+            (file, encryptor, 0u64),
+            move |(mut file, encryptor, index)| async move {
+                if index == count {
+                    return Ok(None);
+                }
+                let mut chunk = vec![0; chunk_size.min(len - index * chunk_size) as usize];
+                file.read_exact(&mut chunk).await?;
+                let sealed = encryptor
+                    .encrypt_chunk(index as u32, index == count - 1, &chunk)
+                    .map_err(std::io::Error::other)?;
+                Ok(Some((Bytes::from(sealed), (file, encryptor, index + 1))))
+            },
+        );
+        let stream = futures::stream::once(async { Ok(header) }).chain(chunks);
+        Ok((Box::pin(stream), self.wrap(&key)?))
+    }
+
+    /// Encrypt a content key with the master key, as `nonce || ciphertext`
+    pub fn wrap(&self, key: &ContentKey) -> Result<Base64> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: key.as_bytes(),
+            aad: WRAP_AAD,
+        };
+        let sealed = self
+            .master_key
+            .encrypt(&nonce, payload)
+            .map_err(|_| Error::InvalidKey)?;
+        Ok(Base64([nonce.as_slice(), &sealed].concat()))
+    }
+
+    /// Decrypt a content key wrapped by [`Vault::wrap`]
+    pub fn unwrap(&self, wrapped: &[u8]) -> Result<ContentKey> {
+        if wrapped.len() < NONCE_LEN {
+            return Err(Error::InvalidKey);
+        }
+        let (nonce, sealed) = wrapped.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: sealed,
+            aad: WRAP_AAD,
+        };
+        let key = self
+            .master_key
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| Error::InvalidKey)?;
+        ContentKey::try_from(key.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    fn vault() -> Vault {
+        Vault::new(&CryptoConfig {
+            master_key: Some("music3-test-master-key".to_string()),
+            chunk_size: 100,
+        })
+        .expect("Invalid config")
+    }
+
+    #[test]
+    fn envelope() {
+        let vault = vault();
+        let plaintext = vec![3; 1000];
+        let (ciphertext, wrapped) = vault.encrypt(&plaintext).expect("Failed to encrypt");
+        assert_ne!(ciphertext[15..115], plaintext[..100]);
+        let key = vault.unwrap(&wrapped).expect("Failed to unwrap");
+        assert_eq!(
+            music3_common::crypto::decrypt(&key, &ciphertext),
+            Ok(plaintext)
+        );
+
+        // a different master key cannot unwrap it
+        let other = Vault::new(&CryptoConfig {
+            master_key: Some("another-master-key".to_string()),
+            ..CryptoConfig::default()
+        })
+        .expect("Invalid config");
+        assert_eq!(other.unwrap(&wrapped), Err(Error::InvalidKey));
+        assert_eq!(vault.unwrap(&wrapped[..8]), Err(Error::InvalidKey));
+    }
+
+    #[tokio::test]
+    async fn encrypt_file() {
+        let vault = vault();
+        for len in [0, 1, 100, 250, 300] {
+            let plaintext: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let (stream, wrapped) = vault
+                .encrypt_file(std::io::Cursor::new(plaintext.clone()), len)
+                .expect("Failed to encrypt");
+            let ciphertext: Vec<Bytes> = stream.try_collect().await.expect("Failed to read");
+            let key = vault.unwrap(&wrapped).expect("Failed to unwrap");
+            assert_eq!(
+                music3_common::crypto::decrypt(&key, &ciphertext.concat()),
+                Ok(plaintext)
+            );
+        }
+
+        // a file shorter than announced fails instead of ending early
+        let (stream, _) = vault
+            .encrypt_file(std::io::Cursor::new(vec![0; 150]), 250)
+            .expect("Failed to encrypt");
+        assert!(stream.try_collect::<Vec<_>>().await.is_err());
+    }
+
+    #[test]
+    fn master_key_required() {
+        assert!(Vault::new(&CryptoConfig::default()).is_err());
+        let config = CryptoConfig {
+            master_key: Some(String::new()),
+            ..CryptoConfig::default()
+        };
+        assert!(Vault::new(&config).is_err());
+    }
+}
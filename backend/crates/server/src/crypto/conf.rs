@@ -0,0 +1,23 @@
+//! Configuration for the crypto module.
+//!
+
+use music3_common::crypto::DEFAULT_CHUNK_SIZE;
+use serde::{Deserialize, Serialize};
+
+/// Encryption configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct CryptoConfig {
+    /// Secret the master key wrapping the content keys is derived from, required to start
+    pub master_key: Option<String>,
+    /// Plaintext size of an encrypted chunk in bytes
+    pub chunk_size: u32,
+}
+
+impl Default for CryptoConfig {
+    fn default() -> Self {
+        Self {
+            master_key: None,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
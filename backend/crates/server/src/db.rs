@@ -0,0 +1,512 @@
+//! # Database
+//!
+//! 持久化的用户、音频、NFT、登录会话和付费订阅，每类数据由一个仓库 trait 读写：
+//! - [`UserRepository`]：钱包公钥和个人资料，首次登录时创建。
+//! - [`TrackRepository`]：上传 ID、音频元数据、密文在存储后端中的键（例如 IPFS CID）、包装后的内容密钥、
+//!   解锁音频的 NFT 和 HLS 打包结果。
+//! - [`NftRepository`]：为音频铸造的 NFT。
+//! - [`SessionRepository`]：登录会话，退出登录或刷新令牌泄露时标记为已吊销。
+//! - [`SubscriptionRepository`]：钱包的付费订阅，续费时原子地顺延到期时间。
+//!
+//! [`Database`] 汇总这些仓库，由 [`conf::DatabaseConfig`] 选择实现：
+//! - `memory`：[`memory::MemoryDatabase`]，保存在内存中，重启后丢失，用于开发和测试。
+//! - `postgres`：[`postgres::PgDatabase`]，通过 sqlx 连接 PostgreSQL，启动时执行 `migrations` 目录中的迁移。
+//!
+
+use crate::db::conf::DatabaseConfig;
+use crate::db::error::Result;
+use crate::db::memory::MemoryDatabase;
+use crate::db::postgres::PgDatabase;
+use crate::hls::HlsPackage;
+use music3_common::param::audio::AudioMetadata;
+use music3_common::param::playback::{Subscription, SubscriptionPlan};
+use music3_common::param::profile::SocialLink;
+use music3_common::param::track::TrackInfo;
+use music3_common::utils::Base64;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub mod conf;
+pub mod error;
+pub mod memory;
+pub mod postgres;
+
+/// Wallet which signed in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct User {
+    /// Pubkey of the wallet
+    pub pubkey: String,
+    /// Name shown instead of the pubkey
+    pub display_name: Option<String>,
+    /// Short self-description
+    pub bio: Option<String>,
+    /// Key of the avatar in the storage backend
+    pub avatar: Option<String>,
+    /// Social links
+    pub links: Vec<SocialLink>,
+    /// Creator verified by the platform
+    pub verified: bool,
+    /// First sign-in in seconds since the epoch
+    pub created_at: u64,
+    /// Last profile change in seconds since the epoch
+    pub updated_at: u64,
+}
+
+impl User {
+    /// User without a profile
+    pub fn new(pubkey: String, now: u64) -> Self {
+        Self {
+            pubkey,
+            display_name: None,
+            bio: None,
+            avatar: None,
+            links: Vec::new(),
+            verified: false,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Uploaded track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Track {
+    /// Upload ID
+    pub id: String,
+    /// Pubkey of the creator
+    pub owner: String,
+    /// Key of the ciphertext in the storage backend, e.g. an IPFS CID
+    pub cid: String,
+    /// Content key wrapped by the master key
+    pub wrapped_key: Base64,
+    /// Content type of the plaintext
+    pub content_type: String,
+    /// Metadata parsed from the upload
+    pub metadata: AudioMetadata,
+    /// Catalog information, `None` until the creator registers the track
+    pub info: Option<TrackInfo>,
+    /// Mint of the NFT unlocking the track
+    pub mint: Option<String>,
+    /// Collection of the NFTs unlocking the track
+    pub collection: Option<String>,
+    /// HLS renditions, once packaged
+    pub hls: Option<HlsPackage>,
+    /// Upload time in seconds since the epoch
+    pub created_at: u64,
+    /// Last change in seconds since the epoch
+    pub updated_at: u64,
+}
+
+/// Filters of a track listing, `None` matches anything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrackFilter {
+    /// Pubkey of the creator
+    pub owner: Option<String>,
+    /// Artist, case-insensitive
+    pub artist: Option<String>,
+    /// Genre, case-insensitive
+    pub genre: Option<String>,
+}
+
+/// NFT minted for a track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Nft {
+    /// Mint address
+    pub mint: String,
+    /// Track the NFT unlocks
+    pub track_id: String,
+    /// Pubkey of the wallet which minted it
+    pub owner: String,
+    /// URI of the Metaplex metadata
+    pub metadata_uri: Option<String>,
+    /// Mint time in seconds since the epoch
+    pub minted_at: u64,
+}
+
+/// Sign-in session, shared by the tokens issued for one sign-in
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Session {
+    /// Session ID, the `sid` of the tokens
+    pub id: String,
+    /// Pubkey of the wallet which signed in
+    pub pubkey: String,
+    /// Sign-in time in seconds since the epoch
+    pub created_at: u64,
+    /// Expiration of the refresh tokens in seconds since the epoch
+    pub expires_at: u64,
+    /// Signed out, or revoked after a leaked refresh token
+    pub revoked: bool,
+}
+
+/// Users
+#[axum::async_trait]
+pub trait UserRepository: Send + Sync {
+    /// User of a pubkey
+    async fn get_user(&self, pubkey: &str) -> Result<Option<User>>;
+
+    /// Insert a user, returns `false` if the pubkey already has one
+    async fn insert_user(&self, user: &User) -> Result<bool>;
+
+    /// Replace the profile and verification of a user, returns `false` if there is none
+    async fn update_user(&self, user: &User) -> Result<bool>;
+}
+
+/// Tracks
+#[axum::async_trait]
+pub trait TrackRepository: Send + Sync {
+    /// Track of an upload ID
+    async fn get_track(&self, id: &str) -> Result<Option<Track>>;
+
+    /// Insert a track, returns `false` if the ID is taken
+    async fn insert_track(&self, track: &Track) -> Result<bool>;
+
+    /// Replace a track, returns `false` if there is none
+    async fn update_track(&self, track: &Track) -> Result<bool>;
+
+    /// Replace the HLS package of a track, returns `false` if there is none
+    async fn set_hls(&self, id: &str, hls: &HlsPackage) -> Result<bool>;
+
+    /// Delete a track, returns `false` if there is none
+    async fn delete_track(&self, id: &str) -> Result<bool>;
+
+    /// Tracks of a creator, newest first
+    async fn tracks_of(&self, owner: &str) -> Result<Vec<Track>>;
+
+    /// Page of the registered tracks matching `filter`, newest first, and how many match
+    async fn list_tracks(
+        &self,
+        filter: &TrackFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Track>, u64)>;
+}
+
+/// Minted NFTs
+#[axum::async_trait]
+pub trait NftRepository: Send + Sync {
+    /// NFT of a mint address
+    async fn get_nft(&self, mint: &str) -> Result<Option<Nft>>;
+
+    /// Insert an NFT, returns `false` if the mint is known
+    async fn insert_nft(&self, nft: &Nft) -> Result<bool>;
+
+    /// NFTs minted for a track, oldest first
+    async fn nfts_of(&self, track_id: &str) -> Result<Vec<Nft>>;
+
+    /// NFTs minted for the tracks of a creator, oldest first
+    async fn nfts_of_creator(&self, owner: &str) -> Result<Vec<Nft>>;
+}
+
+/// Sign-in sessions
+#[axum::async_trait]
+pub trait SessionRepository: Send + Sync {
+    /// Session of an ID
+    async fn get_session(&self, id: &str) -> Result<Option<Session>>;
+
+    /// Insert a session, returns `false` if the ID is taken
+    async fn insert_session(&self, session: &Session) -> Result<bool>;
+
+    /// Mark a session revoked, returns `false` if there is none
+    async fn revoke_session(&self, id: &str) -> Result<bool>;
+
+    /// Sessions of a wallet, newest first
+    async fn sessions_of(&self, pubkey: &str) -> Result<Vec<Session>>;
+}
+
+/// Paid subscriptions
+#[axum::async_trait]
+pub trait SubscriptionRepository: Send + Sync {
+    /// Subscription of a wallet, expired or not
+    async fn get_subscription(&self, subscriber: &str) -> Result<Option<Subscription>>;
+
+    /// Add a period of `plan` to the subscription of a wallet, from its expiration or from `now`
+    /// if it expired, in one step
+    async fn extend_subscription(
+        &self,
+        subscriber: &str,
+        plan: SubscriptionPlan,
+        now: u64,
+    ) -> Result<Subscription>;
+}
+
+/// Repositories of one database
+#[derive(Clone)]
+pub struct Database {
+    /// Users
+    pub users: Arc<dyn UserRepository>,
+    /// Tracks
+    pub tracks: Arc<dyn TrackRepository>,
+    /// Minted NFTs
+    pub nfts: Arc<dyn NftRepository>,
+    /// Sign-in sessions
+    pub sessions: Arc<dyn SessionRepository>,
+    /// Paid subscriptions
+    pub subscriptions: Arc<dyn SubscriptionRepository>,
+    pool: Option<PgPool>,
+}
+
+impl Database {
+    /// Database kept in memory
+    pub fn memory() -> Self {
+        let db = Arc::new(MemoryDatabase::default());
+        Self {
+            users: db.clone(),
+            tracks: db.clone(),
+            nfts: db.clone(),
+            sessions: db.clone(),
+            subscriptions: db,
+            pool: None,
+        }
+    }
+
+    /// PostgreSQL database of a connection pool
+    pub fn postgres(pool: PgPool) -> Self {
+        let db = Arc::new(PgDatabase::new(pool.clone()));
+        Self {
+            users: db.clone(),
+            tracks: db.clone(),
+            nfts: db.clone(),
+            sessions: db.clone(),
+            subscriptions: db,
+            pool: Some(pool),
+        }
+    }
+
+    /// Create the database of a configuration, connecting on first use
+    pub fn from_config(config: &DatabaseConfig) -> anyhow::Result<Self> {
+        Ok(match config {
+            DatabaseConfig::Memory => Self::memory(),
+            DatabaseConfig::Postgres {
+                url,
+                max_connections,
+            } => Self::postgres(
+                sqlx::postgres::PgPoolOptions::new()
+                    .max_connections(*max_connections)
+                    .connect_lazy(url)?,
+            ),
+        })
+    }
+
+    /// Bring the schema up to date, nothing to do in memory
+    pub async fn migrate(&self) -> Result<()> {
+        if let Some(pool) = &self.pool {
+            postgres::MIGRATOR.run(pool).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::memory()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::hls::{HlsRendition, HlsSegment};
+    use music3_common::param::audio::{AudioFormat, AudioTags};
+    use uuid::Uuid;
+
+    pub(crate) fn track(owner: &str, created_at: u64) -> Track {
+        Track {
+            id: Uuid::new_v4().to_string(),
+            owner: owner.to_string(),
+            cid: format!("cid-{created_at}"),
+            wrapped_key: Base64(vec![created_at as u8; 44]),
+            content_type: "audio/flac".to_string(),
+            metadata: AudioMetadata {
+                format: AudioFormat::Flac,
+                duration_ms: 1000,
+                sample_rate: 44100,
+                channels: 2,
+                bitrate: 1_411_200,
+                tags: AudioTags {
+                    title: Some("Song".to_string()),
+                    ..AudioTags::default()
+                },
+            },
+            info: None,
+            mint: None,
+            collection: None,
+            hls: None,
+            created_at,
+            updated_at: created_at,
+        }
+    }
+
+    /// Behavior every implementation shares
+    pub(crate) async fn exercise(db: &Database) {
+        let pubkey = Uuid::new_v4().to_string();
+
+        let mut user = User::new(pubkey.clone(), 1);
+        assert_eq!(db.users.get_user(&pubkey).await.expect("get"), None);
+        assert!(!db.users.update_user(&user).await.expect("update"));
+        assert!(db.users.insert_user(&user).await.expect("insert"));
+        assert!(!db.users.insert_user(&user).await.expect("insert"));
+        user.display_name = Some("DJ".to_string());
+        user.avatar = Some("avatar".to_string());
+        user.links = vec![SocialLink {
+            platform: "twitter".to_string(),
+            url: "https://x.com/dj".to_string(),
+        }];
+        user.verified = true;
+        user.updated_at = 2;
+        assert!(db.users.update_user(&user).await.expect("update"));
+        assert_eq!(db.users.get_user(&pubkey).await.expect("get"), Some(user));
+
+        let (old, mut new) = (track(&pubkey, 1), track(&pubkey, 2));
+        assert!(db.tracks.insert_track(&old).await.expect("insert"));
+        assert!(db.tracks.insert_track(&new).await.expect("insert"));
+        assert!(!db.tracks.insert_track(&new).await.expect("insert"));
+        new.metadata.tags.artist = Some("Artist".to_string());
+        new.mint = Some(Uuid::new_v4().to_string());
+        new.collection = Some(Uuid::new_v4().to_string());
+        assert!(db.tracks.update_track(&new).await.expect("update"));
+        let hls = HlsPackage {
+            renditions: vec![HlsRendition {
+                name: "64k".to_string(),
+                bitrate_kbps: 64,
+                segments: vec![HlsSegment {
+                    key: "segment".to_string(),
+                    duration_ms: 6000,
+                }],
+            }],
+        };
+        assert!(db.tracks.set_hls(&new.id, &hls).await.expect("set"));
+        assert!(!db.tracks.set_hls("missing", &hls).await.expect("set"));
+        new.hls = Some(hls);
+        assert_eq!(
+            db.tracks.get_track(&new.id).await.expect("get"),
+            Some(new.clone())
+        );
+        assert_eq!(
+            db.tracks.tracks_of(&pubkey).await.expect("list"),
+            vec![new.clone(), old.clone()]
+        );
+
+        let filter = |owner: &str, genre: Option<&str>| TrackFilter {
+            owner: Some(owner.to_string()),
+            genre: genre.map(str::to_string),
+            ..TrackFilter::default()
+        };
+        let page = db.tracks.list_tracks(&filter(&pubkey, None), 0, 10).await;
+        assert_eq!(page.expect("list"), (Vec::new(), 0));
+        let mut registered = Vec::new();
+        for (created_at, genre) in [(3, "Jazz"), (4, "Rock"), (5, "jazz")] {
+            let mut track = track(&pubkey, created_at);
+            track.info = Some(TrackInfo {
+                title: format!("Song {created_at}"),
+                genre: Some(genre.to_string()),
+                ..TrackInfo::default()
+            });
+            assert!(db.tracks.insert_track(&track).await.expect("insert"));
+            registered.push(track);
+        }
+        let jazz = filter(&pubkey, Some("JAZZ"));
+        assert_eq!(
+            db.tracks.list_tracks(&jazz, 0, 10).await.expect("list"),
+            (vec![registered[2].clone(), registered[0].clone()], 2)
+        );
+        let page = db.tracks.list_tracks(&filter(&pubkey, None), 1, 1).await;
+        assert_eq!(page.expect("list"), (vec![registered[1].clone()], 3));
+        let page = db.tracks.list_tracks(&filter(&pubkey, None), 3, 1).await;
+        assert_eq!(page.expect("list"), (Vec::new(), 3));
+        for track in &registered {
+            assert!(db.tracks.delete_track(&track.id).await.expect("delete"));
+        }
+
+        assert!(db.tracks.delete_track(&old.id).await.expect("delete"));
+        assert!(!db.tracks.delete_track(&old.id).await.expect("delete"));
+        assert_eq!(db.tracks.get_track(&old.id).await.expect("get"), None);
+
+        let nft = Nft {
+            mint: Uuid::new_v4().to_string(),
+            track_id: new.id.clone(),
+            owner: pubkey.clone(),
+            metadata_uri: Some("ipfs://metadata".to_string()),
+            minted_at: 3,
+        };
+        assert!(db.nfts.insert_nft(&nft).await.expect("insert"));
+        assert!(!db.nfts.insert_nft(&nft).await.expect("insert"));
+        assert_eq!(
+            db.nfts.get_nft(&nft.mint).await.expect("get"),
+            Some(nft.clone())
+        );
+        assert_eq!(
+            db.nfts.nfts_of(&new.id).await.expect("list"),
+            vec![nft.clone()]
+        );
+        assert_eq!(
+            db.nfts.nfts_of_creator(&pubkey).await.expect("list"),
+            vec![nft]
+        );
+        assert_eq!(
+            db.nfts.nfts_of_creator("nobody").await.expect("list"),
+            Vec::new()
+        );
+
+        let session = Session {
+            id: Uuid::new_v4().to_string(),
+            pubkey: pubkey.clone(),
+            created_at: 1,
+            expires_at: 100,
+            revoked: false,
+        };
+        assert!(db.sessions.insert_session(&session).await.expect("insert"));
+        assert!(!db.sessions.insert_session(&session).await.expect("insert"));
+        assert!(db
+            .sessions
+            .revoke_session(&session.id)
+            .await
+            .expect("revoke"));
+        assert!(!db.sessions.revoke_session("missing").await.expect("revoke"));
+        let revoked = Session {
+            revoked: true,
+            ..session
+        };
+        assert_eq!(
+            db.sessions.get_session(&revoked.id).await.expect("get"),
+            Some(revoked.clone())
+        );
+        assert_eq!(
+            db.sessions.sessions_of(&pubkey).await.expect("list"),
+            vec![revoked]
+        );
+
+        let subscriptions = &db.subscriptions;
+        let subscription = subscriptions.get_subscription(&pubkey).await;
+        assert_eq!(subscription.expect("get"), None);
+        let monthly = SubscriptionPlan::Monthly.duration_sec();
+        let first = subscriptions
+            .extend_subscription(&pubkey, SubscriptionPlan::Monthly, 100)
+            .await
+            .expect("extend");
+        assert_eq!(first.expires_at, 100 + monthly);
+        // a renewal adds to the remaining time
+        let renewed = subscriptions
+            .extend_subscription(&pubkey, SubscriptionPlan::Yearly, 200)
+            .await
+            .expect("extend");
+        let yearly = SubscriptionPlan::Yearly.duration_sec();
+        assert_eq!(renewed.plan, SubscriptionPlan::Yearly);
+        assert_eq!(renewed.expires_at, first.expires_at + yearly);
+        // and starts over once expired
+        let restarted = subscriptions
+            .extend_subscription(&pubkey, SubscriptionPlan::Monthly, renewed.expires_at + 1)
+            .await
+            .expect("extend");
+        assert_eq!(restarted.expires_at, renewed.expires_at + 1 + monthly);
+        assert_eq!(
+            subscriptions.get_subscription(&pubkey).await.expect("get"),
+            Some(restarted)
+        );
+    }
+
+    #[tokio::test]
+    async fn memory() {
+        exercise(&Database::memory()).await;
+    }
+}
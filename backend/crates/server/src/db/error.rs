@@ -0,0 +1,28 @@
+//! # Error types for the database module
+//!
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+/// Error types for the database module
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Query error
+    #[error(transparent)]
+    Sqlx(#[from] sqlx::Error),
+    /// Migration error
+    #[error(transparent)]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    /// Stored value that cannot be read back
+    #[error("Invalid stored value: {0}")]
+    InvalidValue(String),
+}
+
+/// Result type for the database module
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,235 @@
+//! # In-memory database
+//!
+//! 数据保存在内存中，重启后丢失，用于开发和测试。
+//!
+
+use crate::db::error::Result;
+use crate::db::{
+    Nft, NftRepository, Session, SessionRepository, SubscriptionRepository, Track, TrackFilter,
+    TrackRepository, User, UserRepository,
+};
+use crate::hls::HlsPackage;
+use music3_common::param::playback::{Subscription, SubscriptionPlan};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Repositories kept in memory
+#[derive(Default)]
+pub struct MemoryDatabase {
+    users: Mutex<HashMap<String, User>>,
+    tracks: Mutex<HashMap<String, Track>>,
+    nfts: Mutex<HashMap<String, Nft>>,
+    sessions: Mutex<HashMap<String, Session>>,
+    subscriptions: Mutex<HashMap<String, Subscription>>,
+}
+
+/// A panic elsewhere leaves the maps consistent, every change is a single insert or remove
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Insert unless the key is taken
+fn insert<T: Clone>(map: &Mutex<HashMap<String, T>>, key: &str, value: &T) -> bool {
+    let mut map = lock(map);
+    if map.contains_key(key) {
+        return false;
+    }
+    map.insert(key.to_string(), value.clone());
+    true
+}
+
+/// Replace the value of a key if there is one
+fn update<T: Clone>(map: &Mutex<HashMap<String, T>>, key: &str, value: &T) -> bool {
+    match lock(map).get_mut(key) {
+        Some(old) => {
+            *old = value.clone();
+            true
+        }
+        None => false,
+    }
+}
+
+#[axum::async_trait]
+impl UserRepository for MemoryDatabase {
+    async fn get_user(&self, pubkey: &str) -> Result<Option<User>> {
+        Ok(lock(&self.users).get(pubkey).cloned())
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<bool> {
+        Ok(insert(&self.users, &user.pubkey, user))
+    }
+
+    async fn update_user(&self, user: &User) -> Result<bool> {
+        Ok(update(&self.users, &user.pubkey, user))
+    }
+}
+
+#[axum::async_trait]
+impl TrackRepository for MemoryDatabase {
+    async fn get_track(&self, id: &str) -> Result<Option<Track>> {
+        Ok(lock(&self.tracks).get(id).cloned())
+    }
+
+    async fn insert_track(&self, track: &Track) -> Result<bool> {
+        Ok(insert(&self.tracks, &track.id, track))
+    }
+
+    async fn update_track(&self, track: &Track) -> Result<bool> {
+        Ok(update(&self.tracks, &track.id, track))
+    }
+
+    async fn set_hls(&self, id: &str, hls: &HlsPackage) -> Result<bool> {
+        match lock(&self.tracks).get_mut(id) {
+            Some(track) => {
+                track.hls = Some(hls.clone());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_track(&self, id: &str) -> Result<bool> {
+        Ok(lock(&self.tracks).remove(id).is_some())
+    }
+
+    async fn tracks_of(&self, owner: &str) -> Result<Vec<Track>> {
+        let mut tracks: Vec<Track> = lock(&self.tracks)
+            .values()
+            .filter(|track| track.owner == owner)
+            .cloned()
+            .collect();
+        tracks.sort_by(|a, b| (Reverse(a.created_at), &a.id).cmp(&(Reverse(b.created_at), &b.id)));
+        Ok(tracks)
+    }
+
+    async fn list_tracks(
+        &self,
+        filter: &TrackFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Track>, u64)> {
+        let matches = |expected: &Option<String>, value: Option<&String>| match expected {
+            Some(expected) => {
+                value.is_some_and(|value| value.to_lowercase() == expected.to_lowercase())
+            }
+            None => true,
+        };
+        let mut tracks: Vec<Track> = lock(&self.tracks)
+            .values()
+            .filter(|track| {
+                let Some(info) = &track.info else {
+                    return false;
+                };
+                filter
+                    .owner
+                    .as_ref()
+                    .is_none_or(|owner| *owner == track.owner)
+                    && matches(&filter.artist, info.artist.as_ref())
+                    && matches(&filter.genre, info.genre.as_ref())
+            })
+            .cloned()
+            .collect();
+        tracks.sort_by(|a, b| (Reverse(a.created_at), &a.id).cmp(&(Reverse(b.created_at), &b.id)));
+        let total = tracks.len() as u64;
+        let page = tracks
+            .into_iter()
+            .skip(usize::try_from(offset).unwrap_or(usize::MAX))
+            .take(usize::try_from(limit).unwrap_or(usize::MAX))
+            .collect();
+        Ok((page, total))
+    }
+}
+
+#[axum::async_trait]
+impl NftRepository for MemoryDatabase {
+    async fn get_nft(&self, mint: &str) -> Result<Option<Nft>> {
+        Ok(lock(&self.nfts).get(mint).cloned())
+    }
+
+    async fn insert_nft(&self, nft: &Nft) -> Result<bool> {
+        Ok(insert(&self.nfts, &nft.mint, nft))
+    }
+
+    async fn nfts_of(&self, track_id: &str) -> Result<Vec<Nft>> {
+        let mut nfts: Vec<Nft> = lock(&self.nfts)
+            .values()
+            .filter(|nft| nft.track_id == track_id)
+            .cloned()
+            .collect();
+        nfts.sort_by(|a, b| (a.minted_at, &a.mint).cmp(&(b.minted_at, &b.mint)));
+        Ok(nfts)
+    }
+
+    async fn nfts_of_creator(&self, owner: &str) -> Result<Vec<Nft>> {
+        let tracks: HashSet<String> = lock(&self.tracks)
+            .values()
+            .filter(|track| track.owner == owner)
+            .map(|track| track.id.clone())
+            .collect();
+        let mut nfts: Vec<Nft> = lock(&self.nfts)
+            .values()
+            .filter(|nft| tracks.contains(&nft.track_id))
+            .cloned()
+            .collect();
+        nfts.sort_by(|a, b| (a.minted_at, &a.mint).cmp(&(b.minted_at, &b.mint)));
+        Ok(nfts)
+    }
+}
+
+#[axum::async_trait]
+impl SessionRepository for MemoryDatabase {
+    async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        Ok(lock(&self.sessions).get(id).cloned())
+    }
+
+    async fn insert_session(&self, session: &Session) -> Result<bool> {
+        Ok(insert(&self.sessions, &session.id, session))
+    }
+
+    async fn revoke_session(&self, id: &str) -> Result<bool> {
+        Ok(match lock(&self.sessions).get_mut(id) {
+            Some(session) => {
+                session.revoked = true;
+                true
+            }
+            None => false,
+        })
+    }
+
+    async fn sessions_of(&self, pubkey: &str) -> Result<Vec<Session>> {
+        let mut sessions: Vec<Session> = lock(&self.sessions)
+            .values()
+            .filter(|session| session.pubkey == pubkey)
+            .cloned()
+            .collect();
+        sessions
+            .sort_by(|a, b| (Reverse(a.created_at), &a.id).cmp(&(Reverse(b.created_at), &b.id)));
+        Ok(sessions)
+    }
+}
+
+#[axum::async_trait]
+impl SubscriptionRepository for MemoryDatabase {
+    async fn get_subscription(&self, subscriber: &str) -> Result<Option<Subscription>> {
+        Ok(lock(&self.subscriptions).get(subscriber).cloned())
+    }
+
+    async fn extend_subscription(
+        &self,
+        subscriber: &str,
+        plan: SubscriptionPlan,
+        now: u64,
+    ) -> Result<Subscription> {
+        let mut subscriptions = lock(&self.subscriptions);
+        let start = subscriptions
+            .get(subscriber)
+            .map_or(now, |subscription| subscription.expires_at.max(now));
+        let subscription = Subscription {
+            plan,
+            expires_at: start + plan.duration_sec(),
+        };
+        subscriptions.insert(subscriber.to_string(), subscription.clone());
+        Ok(subscription)
+    }
+}
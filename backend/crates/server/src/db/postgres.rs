@@ -0,0 +1,541 @@
+//! # PostgreSQL database
+//!
+//! 通过 sqlx 读写 PostgreSQL，表结构见 `migrations` 目录，由 [`MIGRATOR`] 在启动时迁移。
+//! 时间戳保存为 `BIGINT`，包装后的内容密钥保存为 `BYTEA`，音频元数据、HLS 打包结果和订阅方案保存为 `JSONB`。
+//!
+
+use crate::db::error::{Error, Result};
+use crate::db::{
+    Nft, NftRepository, Session, SessionRepository, SubscriptionRepository, Track, TrackFilter,
+    TrackRepository, User, UserRepository,
+};
+use crate::hls::HlsPackage;
+use music3_common::param::audio::AudioMetadata;
+use music3_common::param::playback::{Subscription, SubscriptionPlan};
+use music3_common::param::profile::SocialLink;
+use music3_common::param::track::TrackInfo;
+use music3_common::utils::Base64;
+use sqlx::migrate::Migrator;
+use sqlx::types::Json;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+
+/// Migrations of the schema
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Repositories in a PostgreSQL database
+pub struct PgDatabase {
+    pool: PgPool,
+}
+
+impl PgDatabase {
+    /// Create the repositories of a connection pool, the schema must be migrated
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+fn to_db(timestamp: u64) -> Result<i64> {
+    i64::try_from(timestamp).map_err(|_| Error::InvalidValue(format!("timestamp {timestamp}")))
+}
+
+fn from_db(timestamp: i64) -> Result<u64> {
+    u64::try_from(timestamp).map_err(|_| Error::InvalidValue(format!("timestamp {timestamp}")))
+}
+
+#[derive(FromRow)]
+struct UserRow {
+    pubkey: String,
+    display_name: Option<String>,
+    bio: Option<String>,
+    avatar: Option<String>,
+    links: Json<Vec<SocialLink>>,
+    verified: bool,
+    created_at: i64,
+    updated_at: i64,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = Error;
+
+    fn try_from(row: UserRow) -> Result<Self> {
+        Ok(Self {
+            pubkey: row.pubkey,
+            display_name: row.display_name,
+            bio: row.bio,
+            avatar: row.avatar,
+            links: row.links.0,
+            verified: row.verified,
+            created_at: from_db(row.created_at)?,
+            updated_at: from_db(row.updated_at)?,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct TrackRow {
+    id: String,
+    owner: String,
+    cid: String,
+    wrapped_key: Vec<u8>,
+    content_type: String,
+    metadata: Json<AudioMetadata>,
+    info: Option<Json<TrackInfo>>,
+    mint: Option<String>,
+    collection: Option<String>,
+    hls: Option<Json<HlsPackage>>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+impl TryFrom<TrackRow> for Track {
+    type Error = Error;
+
+    fn try_from(row: TrackRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            owner: row.owner,
+            cid: row.cid,
+            wrapped_key: Base64(row.wrapped_key),
+            content_type: row.content_type,
+            metadata: row.metadata.0,
+            info: row.info.map(|info| info.0),
+            mint: row.mint,
+            collection: row.collection,
+            hls: row.hls.map(|hls| hls.0),
+            created_at: from_db(row.created_at)?,
+            updated_at: from_db(row.updated_at)?,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct NftRow {
+    mint: String,
+    track_id: String,
+    owner: String,
+    metadata_uri: Option<String>,
+    minted_at: i64,
+}
+
+impl TryFrom<NftRow> for Nft {
+    type Error = Error;
+
+    fn try_from(row: NftRow) -> Result<Self> {
+        Ok(Self {
+            mint: row.mint,
+            track_id: row.track_id,
+            owner: row.owner,
+            metadata_uri: row.metadata_uri,
+            minted_at: from_db(row.minted_at)?,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct SessionRow {
+    id: String,
+    pubkey: String,
+    created_at: i64,
+    expires_at: i64,
+    revoked: bool,
+}
+
+impl TryFrom<SessionRow> for Session {
+    type Error = Error;
+
+    fn try_from(row: SessionRow) -> Result<Self> {
+        Ok(Self {
+            id: row.id,
+            pubkey: row.pubkey,
+            created_at: from_db(row.created_at)?,
+            expires_at: from_db(row.expires_at)?,
+            revoked: row.revoked,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct SubscriptionRow {
+    plan: Json<SubscriptionPlan>,
+    expires_at: i64,
+}
+
+impl TryFrom<SubscriptionRow> for Subscription {
+    type Error = Error;
+
+    fn try_from(row: SubscriptionRow) -> Result<Self> {
+        Ok(Self {
+            plan: row.plan.0,
+            expires_at: from_db(row.expires_at)?,
+        })
+    }
+}
+
+#[axum::async_trait]
+impl UserRepository for PgDatabase {
+    async fn get_user(&self, pubkey: &str) -> Result<Option<User>> {
+        sqlx::query_as::<_, UserRow>("SELECT * FROM users WHERE pubkey = $1")
+            .bind(pubkey)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(User::try_from)
+            .transpose()
+    }
+
+    async fn insert_user(&self, user: &User) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO users (pubkey, display_name, bio, avatar, links, verified, created_at, \
+             updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT DO NOTHING",
+        )
+        .bind(&user.pubkey)
+        .bind(&user.display_name)
+        .bind(&user.bio)
+        .bind(&user.avatar)
+        .bind(Json(&user.links))
+        .bind(user.verified)
+        .bind(to_db(user.created_at)?)
+        .bind(to_db(user.updated_at)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn update_user(&self, user: &User) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE users SET display_name = $2, bio = $3, avatar = $4, links = $5, \
+             verified = $6, updated_at = $7 WHERE pubkey = $1",
+        )
+        .bind(&user.pubkey)
+        .bind(&user.display_name)
+        .bind(&user.bio)
+        .bind(&user.avatar)
+        .bind(Json(&user.links))
+        .bind(user.verified)
+        .bind(to_db(user.updated_at)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[axum::async_trait]
+impl TrackRepository for PgDatabase {
+    async fn get_track(&self, id: &str) -> Result<Option<Track>> {
+        sqlx::query_as::<_, TrackRow>("SELECT * FROM tracks WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(Track::try_from)
+            .transpose()
+    }
+
+    async fn insert_track(&self, track: &Track) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO tracks (id, owner, cid, wrapped_key, content_type, metadata, info, mint, \
+             collection, hls, created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) ON CONFLICT DO NOTHING",
+        )
+        .bind(&track.id)
+        .bind(&track.owner)
+        .bind(&track.cid)
+        .bind(&track.wrapped_key.0)
+        .bind(&track.content_type)
+        .bind(Json(&track.metadata))
+        .bind(track.info.as_ref().map(Json))
+        .bind(&track.mint)
+        .bind(&track.collection)
+        .bind(track.hls.as_ref().map(Json))
+        .bind(to_db(track.created_at)?)
+        .bind(to_db(track.updated_at)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn update_track(&self, track: &Track) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE tracks SET owner = $2, cid = $3, wrapped_key = $4, content_type = $5, \
+             metadata = $6, info = $7, mint = $8, collection = $9, hls = $10, created_at = $11, \
+             updated_at = $12 WHERE id = $1",
+        )
+        .bind(&track.id)
+        .bind(&track.owner)
+        .bind(&track.cid)
+        .bind(&track.wrapped_key.0)
+        .bind(&track.content_type)
+        .bind(Json(&track.metadata))
+        .bind(track.info.as_ref().map(Json))
+        .bind(&track.mint)
+        .bind(&track.collection)
+        .bind(track.hls.as_ref().map(Json))
+        .bind(to_db(track.created_at)?)
+        .bind(to_db(track.updated_at)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_hls(&self, id: &str, hls: &HlsPackage) -> Result<bool> {
+        let result = sqlx::query("UPDATE tracks SET hls = $2 WHERE id = $1")
+            .bind(id)
+            .bind(Json(hls))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_track(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM tracks WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn tracks_of(&self, owner: &str) -> Result<Vec<Track>> {
+        sqlx::query_as::<_, TrackRow>(
+            "SELECT * FROM tracks WHERE owner = $1 ORDER BY created_at DESC, id",
+        )
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Track::try_from)
+        .collect()
+    }
+
+    async fn list_tracks(
+        &self,
+        filter: &TrackFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<Track>, u64)> {
+        let mut count = QueryBuilder::new("SELECT COUNT(*) FROM tracks");
+        push_filter(&mut count, filter);
+        let total: i64 = count.build_query_scalar().fetch_one(&self.pool).await?;
+
+        let mut select = QueryBuilder::new("SELECT * FROM tracks");
+        push_filter(&mut select, filter);
+        select
+            .push(" ORDER BY created_at DESC, id LIMIT ")
+            .push_bind(to_db(limit)?)
+            .push(" OFFSET ")
+            .push_bind(to_db(offset)?);
+        let tracks = select
+            .build_query_as::<TrackRow>()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(Track::try_from)
+            .collect::<Result<_>>()?;
+        Ok((tracks, from_db(total)?))
+    }
+}
+
+/// Conditions selecting the registered tracks matching `filter`
+fn push_filter(query: &mut QueryBuilder<'_, Postgres>, filter: &TrackFilter) {
+    query.push(" WHERE info IS NOT NULL");
+    if let Some(owner) = &filter.owner {
+        query.push(" AND owner = ").push_bind(owner.clone());
+    }
+    if let Some(artist) = &filter.artist {
+        query
+            .push(" AND lower(info->>'artist') = lower(")
+            .push_bind(artist.clone())
+            .push(")");
+    }
+    if let Some(genre) = &filter.genre {
+        query
+            .push(" AND lower(info->>'genre') = lower(")
+            .push_bind(genre.clone())
+            .push(")");
+    }
+}
+
+#[axum::async_trait]
+impl NftRepository for PgDatabase {
+    async fn get_nft(&self, mint: &str) -> Result<Option<Nft>> {
+        sqlx::query_as::<_, NftRow>("SELECT * FROM nfts WHERE mint = $1")
+            .bind(mint)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(Nft::try_from)
+            .transpose()
+    }
+
+    async fn insert_nft(&self, nft: &Nft) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO nfts (mint, track_id, owner, metadata_uri, minted_at) \
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+        )
+        .bind(&nft.mint)
+        .bind(&nft.track_id)
+        .bind(&nft.owner)
+        .bind(&nft.metadata_uri)
+        .bind(to_db(nft.minted_at)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn nfts_of(&self, track_id: &str) -> Result<Vec<Nft>> {
+        sqlx::query_as::<_, NftRow>(
+            "SELECT * FROM nfts WHERE track_id = $1 ORDER BY minted_at, mint",
+        )
+        .bind(track_id)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Nft::try_from)
+        .collect()
+    }
+
+    async fn nfts_of_creator(&self, owner: &str) -> Result<Vec<Nft>> {
+        sqlx::query_as::<_, NftRow>(
+            "SELECT nfts.* FROM nfts JOIN tracks ON tracks.id = nfts.track_id \
+             WHERE tracks.owner = $1 ORDER BY nfts.minted_at, nfts.mint",
+        )
+        .bind(owner)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Nft::try_from)
+        .collect()
+    }
+}
+
+#[axum::async_trait]
+impl SessionRepository for PgDatabase {
+    async fn get_session(&self, id: &str) -> Result<Option<Session>> {
+        sqlx::query_as::<_, SessionRow>("SELECT * FROM sessions WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .map(Session::try_from)
+            .transpose()
+    }
+
+    async fn insert_session(&self, session: &Session) -> Result<bool> {
+        let result = sqlx::query(
+            "INSERT INTO sessions (id, pubkey, created_at, expires_at, revoked) \
+             VALUES ($1, $2, $3, $4, $5) ON CONFLICT DO NOTHING",
+        )
+        .bind(&session.id)
+        .bind(&session.pubkey)
+        .bind(to_db(session.created_at)?)
+        .bind(to_db(session.expires_at)?)
+        .bind(session.revoked)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn revoke_session(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("UPDATE sessions SET revoked = TRUE WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn sessions_of(&self, pubkey: &str) -> Result<Vec<Session>> {
+        sqlx::query_as::<_, SessionRow>(
+            "SELECT * FROM sessions WHERE pubkey = $1 ORDER BY created_at DESC, id",
+        )
+        .bind(pubkey)
+        .fetch_all(&self.pool)
+        .await?
+        .into_iter()
+        .map(Session::try_from)
+        .collect()
+    }
+}
+
+#[axum::async_trait]
+impl SubscriptionRepository for PgDatabase {
+    async fn get_subscription(&self, subscriber: &str) -> Result<Option<Subscription>> {
+        sqlx::query_as::<_, SubscriptionRow>(
+            "SELECT plan, expires_at FROM subscriptions WHERE subscriber = $1",
+        )
+        .bind(subscriber)
+        .fetch_optional(&self.pool)
+        .await?
+        .map(Subscription::try_from)
+        .transpose()
+    }
+
+    async fn extend_subscription(
+        &self,
+        subscriber: &str,
+        plan: SubscriptionPlan,
+        now: u64,
+    ) -> Result<Subscription> {
+        sqlx::query_as::<_, SubscriptionRow>(
+            "INSERT INTO subscriptions (subscriber, plan, expires_at) VALUES ($1, $2, $3 + $4) \
+             ON CONFLICT (subscriber) DO UPDATE SET plan = EXCLUDED.plan, \
+             expires_at = GREATEST(subscriptions.expires_at, $3) + $4 \
+             RETURNING plan, expires_at",
+        )
+        .bind(subscriber)
+        .bind(Json(plan))
+        .bind(to_db(now)?)
+        .bind(to_db(plan.duration_sec())?)
+        .fetch_one(&self.pool)
+        .await?
+        .try_into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{exercise, track};
+    use crate::db::Database;
+
+    /// Database of `DATABASE_URL`, migrated, the tests pass without one
+    async fn database() -> Option<Database> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        let pool = PgPool::connect(&url).await.expect("Failed to connect");
+        let db = Database::postgres(pool);
+        db.migrate().await.expect("Failed to migrate");
+        Some(db)
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(to_db(1).expect("Failed to convert"), 1);
+        assert!(to_db(u64::MAX).is_err());
+        assert!(from_db(-1).is_err());
+    }
+
+    #[tokio::test]
+    async fn repositories() {
+        let Some(db) = database().await else {
+            return;
+        };
+        exercise(&db).await;
+        // already up to date
+        db.migrate().await.expect("Failed to migrate");
+    }
+
+    #[tokio::test]
+    async fn upload_record() {
+        let Some(db) = database().await else {
+            return;
+        };
+        let mut track = track("owner", 1);
+        track.wrapped_key = Base64(Vec::new());
+        assert!(db.tracks.insert_track(&track).await.expect("insert"));
+        assert_eq!(
+            db.tracks.get_track(&track.id).await.expect("get"),
+            Some(track.clone())
+        );
+        let hls = HlsPackage {
+            renditions: Vec::new(),
+        };
+        assert!(db.tracks.set_hls(&track.id, &hls).await.expect("set"));
+        let stored = db.tracks.get_track(&track.id).await.expect("get");
+        assert_eq!(stored.and_then(|track| track.hls), Some(hls));
+        assert!(db.tracks.delete_track(&track.id).await.expect("delete"));
+    }
+}
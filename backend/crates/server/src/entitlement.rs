@@ -0,0 +1,264 @@
+//! # 播放权限
+//!
+//! 判断钱包能否播放某个音频，依次检查：
+//! 1. 钱包是音频的上传者；
+//! 2. 钱包持有音频的 NFT，或其集合中的 NFT；
+//! 3. 钱包有未过期的付费订阅（见 [`subscription`]）；
+//! 4. 钱包的质押账户委托给平台的验证节点且数额不低于下限，质押激活后、解除前有效。
+//!
+//! 授权带有原因和过期时间，播放器可以据此向听众说明为什么能播放、何时失效。
+//!
+
+use crate::chain::Chain;
+use crate::db::{Database, Track};
+use crate::entitlement::conf::EntitlementConfig;
+use crate::entitlement::subscription::Subscriptions;
+use crate::error::{Error, Result};
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::playback::{Grant, GrantReason};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub mod conf;
+pub mod subscription;
+
+/// Decides who may play what
+#[derive(Clone)]
+pub struct Entitlements {
+    chain: Arc<dyn Chain>,
+    subscriptions: Subscriptions,
+    stake_vote_account: Option<Pubkey>,
+    min_stake_lamports: u64,
+}
+
+impl Entitlements {
+    /// Create the entitlements from the configuration, with the subscriptions of `db`
+    pub fn new(
+        config: &EntitlementConfig,
+        chain: Arc<dyn Chain>,
+        db: &Database,
+    ) -> anyhow::Result<Self> {
+        let stake_vote_account = config
+            .stake_vote_account
+            .as_deref()
+            .map(Pubkey::from_str)
+            .transpose()?;
+        Ok(Self {
+            chain,
+            subscriptions: Subscriptions::new(db.subscriptions.clone()),
+            stake_vote_account,
+            min_stake_lamports: config.min_stake_lamports,
+        })
+    }
+
+    /// Same entitlements with the subscriptions of `db`
+    pub fn with_database(&self, db: &Database) -> Self {
+        Self {
+            subscriptions: Subscriptions::new(db.subscriptions.clone()),
+            ..self.clone()
+        }
+    }
+
+    /// Paid subscriptions
+    pub fn subscriptions(&self) -> &Subscriptions {
+        &self.subscriptions
+    }
+
+    /// Find a grant allowing `wallet` to play `track`
+    pub async fn check(&self, wallet: &Pubkey, track: &Track) -> Result<Option<Grant>> {
+        if track.owner == wallet.to_string() {
+            return Ok(Some(Grant {
+                reason: GrantReason::Creator,
+                expires_at: None,
+            }));
+        }
+
+        let mint = track.mint.as_deref().map(parse_pubkey).transpose()?;
+        let collection = track.collection.as_deref().map(parse_pubkey).transpose()?;
+        let nft = crate::chain::held_nft(
+            self.chain.as_ref(),
+            wallet,
+            mint.as_ref(),
+            collection.as_ref(),
+        )
+        .await?;
+        if let Some(mint) = nft {
+            // until the NFT is sold
+            return Ok(Some(Grant {
+                reason: GrantReason::Nft {
+                    mint: mint.to_string(),
+                },
+                expires_at: None,
+            }));
+        }
+
+        if let Some(subscription) = self.subscriptions.get(wallet).await? {
+            if subscription.expires_at > get_current_timestamp() {
+                return Ok(Some(Grant {
+                    reason: GrantReason::Subscription {
+                        plan: subscription.plan,
+                    },
+                    expires_at: Some(subscription.expires_at),
+                }));
+            }
+        }
+
+        self.check_stake(wallet).await
+    }
+
+    async fn check_stake(&self, wallet: &Pubkey) -> Result<Option<Grant>> {
+        let Some(vote_account) = self.stake_vote_account else {
+            return Ok(None);
+        };
+        let stakes = self.chain.stake_accounts(wallet).await?;
+        let mut stakes = stakes.into_iter().filter(|stake| {
+            stake.voter == vote_account
+                && stake.lamports >= self.min_stake_lamports
+                && stake.deactivation_epoch.is_none()
+        });
+        let Some(first) = stakes.next() else {
+            return Ok(None);
+        };
+        // a delegation is active from the epoch after it started activating
+        let epoch = self.chain.epoch().await?;
+        let active = std::iter::once(first)
+            .chain(stakes)
+            .find(|stake| stake.activation_epoch < epoch);
+        Ok(active.map(|stake| Grant {
+            // until the stake is deactivated
+            reason: GrantReason::Stake {
+                stake_account: stake.address.to_string(),
+                lamports: stake.lamports,
+            },
+            expires_at: None,
+        }))
+    }
+}
+
+fn parse_pubkey(s: &str) -> Result<Pubkey> {
+    Pubkey::from_str(s).map_err(|_| Error::InvalidPubkey(s.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::memory::MemoryChain;
+    use crate::chain::StakeAccount;
+    use music3_common::param::playback::SubscriptionPlan;
+
+    struct TestEntitlements {
+        entitlements: Entitlements,
+        chain: Arc<MemoryChain>,
+    }
+
+    fn entitlements(vote_account: &Pubkey) -> TestEntitlements {
+        let chain = Arc::new(MemoryChain::default());
+        let config = EntitlementConfig {
+            stake_vote_account: Some(vote_account.to_string()),
+            min_stake_lamports: 100,
+        };
+        let db = Database::memory();
+        TestEntitlements {
+            entitlements: Entitlements::new(&config, chain.clone(), &db).expect("Invalid config"),
+            chain,
+        }
+    }
+
+    fn track(mint: &Pubkey) -> Track {
+        Track {
+            mint: Some(mint.to_string()),
+            ..crate::db::tests::track(&Pubkey::new_unique().to_string(), 0)
+        }
+    }
+
+    #[tokio::test]
+    async fn grants() {
+        let (mint, vote_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let test = entitlements(&vote_account);
+        let track = track(&mint);
+        let check = |wallet: Pubkey| {
+            let (entitlements, track) = (&test.entitlements, &track);
+            async move {
+                entitlements
+                    .check(&wallet, track)
+                    .await
+                    .expect("Failed to check")
+            }
+        };
+
+        let creator = Pubkey::from_str(&track.owner).expect("Invalid pubkey");
+        assert_eq!(
+            check(creator).await.map(|grant| grant.reason),
+            Some(GrantReason::Creator)
+        );
+
+        let holder = Pubkey::new_unique();
+        test.chain.set_balance(&holder, &mint, 1);
+        assert_eq!(
+            check(holder).await,
+            Some(Grant {
+                reason: GrantReason::Nft {
+                    mint: mint.to_string()
+                },
+                expires_at: None,
+            })
+        );
+
+        let subscriber = Pubkey::new_unique();
+        assert_eq!(check(subscriber).await, None);
+        let subscriptions = test.entitlements.subscriptions();
+        let first = subscriptions
+            .extend(&subscriber, SubscriptionPlan::Monthly)
+            .await
+            .expect("Failed to subscribe");
+        // a renewal adds to the remaining time
+        let renewed = subscriptions
+            .extend(&subscriber, SubscriptionPlan::Yearly)
+            .await
+            .expect("Failed to subscribe");
+        assert_eq!(
+            renewed.expires_at,
+            first.expires_at + SubscriptionPlan::Yearly.duration_sec()
+        );
+        assert_eq!(
+            check(subscriber).await,
+            Some(Grant {
+                reason: GrantReason::Subscription {
+                    plan: SubscriptionPlan::Yearly
+                },
+                expires_at: Some(renewed.expires_at),
+            })
+        );
+
+        let staker = Pubkey::new_unique();
+        let stake = |lamports, voter, deactivation_epoch| StakeAccount {
+            address: Pubkey::new_unique(),
+            voter,
+            lamports,
+            activation_epoch: 10,
+            deactivation_epoch,
+        };
+        test.chain.set_epoch(10);
+        test.chain.add_stake(&staker, stake(99, vote_account, None));
+        test.chain
+            .add_stake(&staker, stake(500, Pubkey::new_unique(), None));
+        test.chain
+            .add_stake(&staker, stake(500, vote_account, Some(10)));
+        let delegated = stake(500, vote_account, None);
+        test.chain.add_stake(&staker, delegated);
+        // still activating
+        assert_eq!(check(staker).await, None);
+        test.chain.set_epoch(11);
+        assert_eq!(
+            check(staker).await,
+            Some(Grant {
+                reason: GrantReason::Stake {
+                    stake_account: delegated.address.to_string(),
+                    lamports: 500,
+                },
+                expires_at: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,22 @@
+//! Configuration for the entitlement module.
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// Entitlement configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct EntitlementConfig {
+    /// Vote account of the platform validator, staking unlocks nothing if unset
+    pub stake_vote_account: Option<String>,
+    /// Min lamports a stake account must delegate
+    pub min_stake_lamports: u64,
+}
+
+impl Default for EntitlementConfig {
+    fn default() -> Self {
+        Self {
+            stake_vote_account: None,
+            min_stake_lamports: 1_000_000_000,
+        }
+    }
+}
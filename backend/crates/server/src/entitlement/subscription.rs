@@ -0,0 +1,75 @@
+//! # 付费订阅
+//!
+//! 听众按月或按年付费后，由持有 `admin` 权限的支付服务调用 `POST /subscription/{pubkey}` 记录，
+//! 订阅期从当前到期时间（已过期则从现在）起顺延。订阅保存在数据库中（见 [`SubscriptionRepository`]），
+//! 重新部署后仍然有效。
+//!
+
+use crate::auth::claim::Claim;
+use crate::auth::scope::{Admin, RequireScope};
+use crate::db::SubscriptionRepository;
+use crate::entitlement::Entitlements;
+use crate::error::{Error, Result};
+use axum::extract::{Path, State};
+use axum::Json;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::playback::{SubscribeRequest, Subscription, SubscriptionPlan};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Paid subscriptions
+#[derive(Clone)]
+pub struct Subscriptions {
+    repository: Arc<dyn SubscriptionRepository>,
+}
+
+impl Subscriptions {
+    /// Create the subscriptions kept in a repository
+    pub fn new(repository: Arc<dyn SubscriptionRepository>) -> Self {
+        Self { repository }
+    }
+
+    /// Subscription of a wallet, expired or not
+    pub async fn get(&self, subscriber: &Pubkey) -> Result<Option<Subscription>> {
+        let subscriber = subscriber.to_string();
+        Ok(self.repository.get_subscription(&subscriber).await?)
+    }
+
+    /// Add a paid period to the subscription of a wallet
+    pub async fn extend(
+        &self,
+        subscriber: &Pubkey,
+        plan: SubscriptionPlan,
+    ) -> Result<Subscription> {
+        let subscriber = subscriber.to_string();
+        let now = get_current_timestamp();
+        let repository = &self.repository;
+        Ok(repository
+            .extend_subscription(&subscriber, plan, now)
+            .await?)
+    }
+}
+
+/// 记录付费订阅
+pub async fn subscribe(
+    _: RequireScope<Admin>,
+    State(entitlements): State<Arc<Entitlements>>,
+    Path(subscriber): Path<String>,
+    Json(request): Json<SubscribeRequest>,
+) -> Result<Json<Subscription>> {
+    let subscriber =
+        Pubkey::from_str(&subscriber).map_err(|_| Error::InvalidPubkey(subscriber.clone()))?;
+    let subscriptions = entitlements.subscriptions();
+    Ok(Json(subscriptions.extend(&subscriber, request.plan).await?))
+}
+
+/// 查询自己的订阅
+pub async fn status(
+    claim: Claim,
+    State(entitlements): State<Arc<Entitlements>>,
+) -> Result<Json<Option<Subscription>>> {
+    let subscriber =
+        Pubkey::from_str(&claim.sub).map_err(|_| Error::InvalidPubkey(claim.sub.clone()))?;
+    Ok(Json(entitlements.subscriptions().get(&subscriber).await?))
+}
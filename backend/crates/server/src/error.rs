@@ -11,12 +11,107 @@ pub enum Error {
     /// Authorization error
     #[error(transparent)]
     Auth(#[from] crate::auth::error::Error),
-    ///
+    /// Multipart error
     #[error(transparent)]
     Multipart(#[from] MultipartError),
-    ///
+    /// IO error
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    /// Upload larger than the limit
+    #[error("Upload exceeds the limit of {0} bytes")]
+    PayloadTooLarge(u64),
+    /// Storage error
+    #[error(transparent)]
+    Storage(#[from] crate::storage::error::Error),
+    /// Chain error
+    #[error(transparent)]
+    Chain(#[from] crate::chain::error::Error),
+    /// Database error
+    #[error(transparent)]
+    Database(#[from] crate::db::error::Error),
+    /// Search error
+    #[error(transparent)]
+    Search(#[from] crate::search::error::Error),
+    /// Encryption error
+    #[error(transparent)]
+    Crypto(#[from] music3_common::crypto::Error),
+    /// Audio error
+    #[error(transparent)]
+    Audio(#[from] crate::upload::audio::Error),
+    /// Upload without content
+    #[error("Upload is empty")]
+    EmptyUpload,
+    /// Upload session missing, expired or owned by someone else
+    #[error("Upload session not found")]
+    UploadSessionNotFound,
+    /// Another request of the upload session is in progress
+    #[error("Upload session is busy")]
+    UploadSessionBusy,
+    /// Chunk not at the end of the bytes received
+    #[error("Chunk must start at offset {0}")]
+    UploadOffsetMismatch(u64),
+    /// Finalizing before every byte is received
+    #[error("Upload incomplete, received {offset} of {size} bytes")]
+    UploadIncomplete {
+        /// Bytes received
+        offset: u64,
+        /// Declared size
+        size: u64,
+    },
+    /// Track missing
+    #[error("Track not found")]
+    TrackNotFound,
+    /// Caller may not play the track
+    #[error("The wallet may not play the track")]
+    NotEntitled,
+    /// Signed playback URL tampered with or signed for another track
+    #[error("Invalid playback URL")]
+    InvalidPlaybackUrl,
+    /// Signed playback URL past its expiration
+    #[error("Playback URL expired")]
+    PlaybackUrlExpired,
+    /// Catalog information rejected
+    #[error("Invalid track: {0}")]
+    InvalidTrack(String),
+    /// Upload already registered as a track
+    #[error("The upload is already registered as a track")]
+    TrackAlreadyRegistered,
+    /// Caller did not create the track
+    #[error("Only the creator of the track may do this")]
+    NotTrackOwner,
+    /// Track not packaged, or no such rendition or segment
+    #[error("HLS rendition not found")]
+    HlsNotFound,
+    /// Packaging job missing, forgotten or started by someone else
+    #[error("HLS job not found")]
+    HlsJobNotFound,
+    /// Track already being packaged
+    #[error("The track is already being packaged")]
+    HlsJobRunning,
+    /// Avatar is not a supported image
+    #[error("Unsupported image, use PNG, JPEG, GIF or WebP")]
+    UnsupportedImage,
+    /// Profile rejected
+    #[error("Invalid profile: {0}")]
+    InvalidProfile(String),
+    /// Wallet never signed in, or has no avatar
+    #[error("Profile not found")]
+    ProfileNotFound,
+    /// Search query rejected
+    #[error("Invalid search: {0}")]
+    InvalidSearch(String),
+    /// Mint already linked to another track
+    #[error("The NFT is already linked to another track")]
+    NftAlreadyLinked,
+    /// Wallet not a verified creator in the metadata of the NFT
+    #[error("The wallet is not a verified creator of the NFT")]
+    NotNftCreator,
+    /// Collection not verified in the metadata of the NFT
+    #[error("The NFT is not a verified member of the collection")]
+    NftNotInCollection,
+    /// Malformed Solana pubkey
+    #[error("Invalid pubkey: {0}")]
+    InvalidPubkey(String),
     /// Unexpected error
     #[error("Unexpected error: {0}")]
     Unexpected(Cow<'static, str>),
@@ -29,11 +124,49 @@ impl IntoResponse for Error {
     fn into_response(self) -> Response {
         match self {
             Error::Auth(e) => e.into_response(),
+            Error::Storage(e) => e.into_response(),
+            Error::Chain(e) => e.into_response(),
+            Error::Database(e) => e.into_response(),
+            Error::Search(e) => e.into_response(),
             Error::Unexpected(e) => (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
-            Error::Multipart(e) => {
+            Error::Multipart(e) => (e.status(), e.body_text()).into_response(),
+            Error::Io(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            Error::Crypto(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            e @ Error::PayloadTooLarge(_) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, e.to_string()).into_response()
+            }
+            Error::Audio(e @ crate::upload::audio::Error::UnsupportedFormat) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, e.to_string()).into_response()
+            }
+            Error::Audio(e @ crate::upload::audio::Error::Io(_)) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
             }
-            Error::Io(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+            Error::Audio(e) => (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+            e @ (Error::UploadSessionNotFound
+            | Error::TrackNotFound
+            | Error::HlsNotFound
+            | Error::HlsJobNotFound
+            | Error::ProfileNotFound) => (StatusCode::NOT_FOUND, e.to_string()).into_response(),
+            e @ (Error::NotEntitled
+            | Error::NotTrackOwner
+            | Error::NotNftCreator
+            | Error::InvalidPlaybackUrl
+            | Error::PlaybackUrlExpired) => (StatusCode::FORBIDDEN, e.to_string()).into_response(),
+            e @ (Error::UploadSessionBusy
+            | Error::UploadOffsetMismatch(_)
+            | Error::UploadIncomplete { .. }
+            | Error::HlsJobRunning
+            | Error::TrackAlreadyRegistered
+            | Error::NftAlreadyLinked) => (StatusCode::CONFLICT, e.to_string()).into_response(),
+            e @ Error::UnsupportedImage => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, e.to_string()).into_response()
+            }
+            e @ (Error::EmptyUpload
+            | Error::InvalidPubkey(_)
+            | Error::NftNotInCollection
+            | Error::InvalidTrack(_)
+            | Error::InvalidProfile(_)
+            | Error::InvalidSearch(_)) => (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
         }
     }
 }
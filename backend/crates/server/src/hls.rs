@@ -0,0 +1,607 @@
+//! # HLS 打包
+//!
+//! 创作者调用 `POST /track/{id}/hls` 后，后台任务把音频打包为多码率的 HLS：
+//! 1. 从存储后端流式读取密文，用内容密钥逐块解密到工作目录；
+//! 2. 按 [`conf::HlsConfig`] 中的每个码率转码为 MPEG-TS 分片（见 [`transcode`]）；
+//! 3. 每个分片用 AES-128-CBC 加密后保存到存储后端，分片的键记录在数据库的音频中（见 [`crate::db`]）。
+//!
+//! 任务进度通过 `GET /hls/job/{id}` 查询，任务只保存在内存中。
+//!
+//! 分片密钥由内容密钥经 HKDF-SHA256 派生，不单独保存；IV 为分片序号，即播放列表不指定 IV 时 HLS 的默认值。
+//! 播放列表和密钥的接口见 [`playlist`]，`EXT-X-KEY` 指向检查播放权限的密钥接口。
+//!
+
+use crate::auth::claim::Claim;
+use crate::auth::scope::{Creator, RequireScope};
+use crate::crypto::Vault;
+use crate::db::Database;
+use crate::error::{Error, Result};
+use crate::hls::conf::HlsConfig;
+use crate::hls::transcode::Transcoder;
+use crate::storage::{ByteStream, Storage};
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockEncryptMut, KeyIvInit};
+use anyhow::Context;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use futures::TryStreamExt;
+use hkdf::Hkdf;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::crypto::{ContentKey, Decryptor, Header, HEADER_LEN};
+use music3_common::param::hls::{HlsJob, JobState};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+pub mod conf;
+pub mod playlist;
+pub mod transcode;
+
+/// Length of an AES-128 key
+pub const SEGMENT_KEY_LEN: usize = 16;
+
+/// Finished jobs are forgotten after a day
+const JOB_RETENTION_SEC: u64 = 24 * 60 * 60;
+
+/// HLS renditions of a track
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HlsPackage {
+    /// Renditions from the lowest bitrate
+    pub renditions: Vec<HlsRendition>,
+}
+
+/// Encrypted segments of one bitrate
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HlsRendition {
+    /// Name in the playlist URLs
+    pub name: String,
+    /// Audio bitrate in kbit/s
+    pub bitrate_kbps: u32,
+    /// Segments in playback order
+    pub segments: Vec<HlsSegment>,
+}
+
+/// Encrypted segment
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HlsSegment {
+    /// Key in the storage backend
+    pub key: String,
+    /// Duration in milliseconds
+    pub duration_ms: u64,
+}
+
+/// AES-128 key of the segments of a track
+pub fn segment_key(content_key: &ContentKey) -> [u8; SEGMENT_KEY_LEN] {
+    let hkdf = Hkdf::<Sha256>::new(None, content_key.as_bytes());
+    let mut key = [0; SEGMENT_KEY_LEN];
+    hkdf.expand(b"music3 hls key", &mut key)
+        .expect("16 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+/// Encrypt a segment with AES-128-CBC and PKCS#7 padding, the IV is the media sequence number
+pub fn encrypt_segment(key: &[u8; SEGMENT_KEY_LEN], sequence: u64, segment: &[u8]) -> Vec<u8> {
+    let iv = u128::from(sequence).to_be_bytes();
+    cbc::Encryptor::<aes::Aes128>::new(key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(segment)
+}
+
+/// Job with the wallet which started it
+struct Job {
+    owner: String,
+    status: HlsJob,
+}
+
+/// Runs the packaging jobs
+pub struct Packager {
+    config: HlsConfig,
+    transcoder: Arc<dyn Transcoder>,
+    storage: Arc<dyn Storage>,
+    vault: Arc<Vault>,
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl Packager {
+    /// Create the packager
+    pub fn new(
+        config: HlsConfig,
+        transcoder: Arc<dyn Transcoder>,
+        storage: Arc<dyn Storage>,
+        vault: Arc<Vault>,
+    ) -> Self {
+        Self {
+            config,
+            transcoder,
+            storage,
+            vault,
+            jobs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Status of a job started by `owner`
+    pub fn job(&self, owner: &str, id: &str) -> Option<HlsJob> {
+        let jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.get(id)
+            .filter(|job| job.owner == owner)
+            .map(|job| job.status.clone())
+    }
+
+    /// Queue the packaging of a track of `db`, which runs in the background
+    pub fn start(self: &Arc<Self>, db: &Database, owner: &str, track_id: &str) -> Result<HlsJob> {
+        let now = get_current_timestamp();
+        let status = HlsJob {
+            id: Uuid::new_v4().to_string(),
+            track_id: track_id.to_string(),
+            state: JobState::Queued,
+            progress: 0,
+            error: None,
+            created_at: now,
+            updated_at: now,
+        };
+        {
+            let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+            jobs.retain(|_, job| {
+                !finished(job.status.state) || job.status.updated_at + JOB_RETENTION_SEC > now
+            });
+            let running = jobs
+                .values()
+                .any(|job| job.status.track_id == track_id && !finished(job.status.state));
+            if running {
+                return Err(Error::HlsJobRunning);
+            }
+            let job = Job {
+                owner: owner.to_string(),
+                status: status.clone(),
+            };
+            jobs.insert(status.id.clone(), job);
+        }
+
+        let packager = self.clone();
+        let db = db.clone();
+        let job_id = status.id.clone();
+        tokio::spawn(async move {
+            let work_dir = packager.config.work_dir.join(&job_id);
+            let result = packager.run(&db, &job_id, &work_dir).await;
+            let _ = tokio::fs::remove_dir_all(&work_dir).await;
+            packager.update(&job_id, |job| match result {
+                Ok(()) => {
+                    job.state = JobState::Completed;
+                    job.progress = 100;
+                }
+                Err(e) => {
+                    job.state = JobState::Failed;
+                    job.error = Some(format!("{e:#}"));
+                }
+            });
+        });
+        Ok(status)
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut HlsJob)) {
+        let mut jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(job) = jobs.get_mut(id) {
+            f(&mut job.status);
+            job.status.updated_at = get_current_timestamp();
+        }
+    }
+
+    async fn run(
+        &self,
+        db: &Database,
+        job_id: &str,
+        work_dir: &std::path::Path,
+    ) -> anyhow::Result<()> {
+        let track_id = self
+            .job_track(job_id)
+            .context("Job vanished before it started")?;
+        self.update(job_id, |job| job.state = JobState::Running);
+        // one step to decrypt, then one to transcode and one to store every rendition
+        let steps = 1 + 2 * self.config.renditions.len();
+        let progress = |done: usize| (done * 100 / steps) as u8;
+
+        let track = db
+            .tracks
+            .get_track(&track_id)
+            .await?
+            .context("Track not found")?;
+        let content_key = self.vault.unwrap(&track.wrapped_key)?;
+        tokio::fs::create_dir_all(work_dir).await?;
+        let input = work_dir.join("input");
+        decrypt_to_file(self.storage.as_ref(), &track.cid, &content_key, &input).await?;
+        self.update(job_id, |job| job.progress = progress(1));
+
+        let key = segment_key(&content_key);
+        let mut renditions = Vec::with_capacity(self.config.renditions.len());
+        for (index, rendition) in self.config.renditions.iter().enumerate() {
+            let output: PathBuf = work_dir.join(&rendition.name);
+            tokio::fs::create_dir_all(&output).await?;
+            let transcoded = self
+                .transcoder
+                .transcode(&input, rendition, self.config.segment_sec, &output)
+                .await
+                .with_context(|| format!("Failed to transcode {}", rendition.name))?;
+            self.update(job_id, |job| job.progress = progress(2 + 2 * index));
+
+            let mut segments = Vec::with_capacity(transcoded.len());
+            for (sequence, segment) in transcoded.into_iter().enumerate() {
+                let data = tokio::fs::read(&segment.path).await?;
+                let encrypted = encrypt_segment(&key, sequence as u64, &data);
+                let key = self
+                    .storage
+                    .put(Bytes::from(encrypted), "video/mp2t")
+                    .await?;
+                segments.push(HlsSegment {
+                    key,
+                    duration_ms: segment.duration_ms,
+                });
+            }
+            renditions.push(HlsRendition {
+                name: rendition.name.clone(),
+                bitrate_kbps: rendition.bitrate_kbps,
+                segments,
+            });
+            self.update(job_id, |job| job.progress = progress(3 + 2 * index));
+        }
+
+        // only one job of a track runs at a time, so the package read above is still the previous one
+        let package = HlsPackage { renditions };
+        if !db.tracks.set_hls(&track_id, &package).await? {
+            anyhow::bail!("Track deleted while packaging");
+        }
+        // best effort, some backends never delete
+        for rendition in track.hls.iter().flat_map(|package| &package.renditions) {
+            for segment in &rendition.segments {
+                let _ = self.storage.delete(&segment.key).await;
+            }
+        }
+        Ok(())
+    }
+
+    fn job_track(&self, id: &str) -> Option<String> {
+        let jobs = self.jobs.lock().unwrap_or_else(PoisonError::into_inner);
+        jobs.get(id).map(|job| job.status.track_id.clone())
+    }
+}
+
+/// Decrypt the stored ciphertext `cid` into `path`, holding one chunk at a time
+async fn decrypt_to_file(
+    storage: &dyn Storage,
+    cid: &str,
+    content_key: &ContentKey,
+    path: &std::path::Path,
+) -> anyhow::Result<()> {
+    let size = storage.stat(cid).await?.size;
+    let mut ciphertext = storage.get_range(cid, 0..size).await?;
+    let mut buf = Vec::new();
+    fill(&mut ciphertext, &mut buf, HEADER_LEN).await?;
+    let header = Header::parse(&buf)?;
+    buf.drain(..HEADER_LEN);
+    let decryptor = Decryptor::new(content_key, header);
+    let count = header.chunk_count(header.plaintext_len(size)?);
+    let mut file = tokio::fs::File::create(path).await?;
+    for index in 0..count {
+        let len = (size - header.chunk_offset(index)).min(decryptor.sealed_chunk_size() as u64);
+        fill(&mut ciphertext, &mut buf, len as usize).await?;
+        let last = index == count - 1;
+        let chunk = decryptor.decrypt_chunk(u32::try_from(index)?, last, &buf[..len as usize])?;
+        buf.drain(..len as usize);
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+/// Read from `stream` until `buf` holds at least `len` bytes
+async fn fill(stream: &mut ByteStream, buf: &mut Vec<u8>, len: usize) -> anyhow::Result<()> {
+    while buf.len() < len {
+        let chunk = stream.try_next().await?.context("Ciphertext ended early")?;
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(())
+}
+
+fn finished(state: JobState) -> bool {
+    matches!(state, JobState::Completed | JobState::Failed)
+}
+
+/// 开始 HLS 打包
+pub async fn start(
+    claim: RequireScope<Creator>,
+    State(packager): State<Arc<Packager>>,
+    State(db): State<Database>,
+    Path(id): Path<String>,
+) -> Result<(StatusCode, Json<HlsJob>)> {
+    let track = db
+        .tracks
+        .get_track(&id)
+        .await?
+        .ok_or(Error::TrackNotFound)?;
+    if track.owner != claim.sub {
+        return Err(Error::NotTrackOwner);
+    }
+    let job = packager.start(&db, &claim.sub, &id)?;
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// 查询打包进度
+pub async fn status(
+    claim: Claim,
+    State(packager): State<Arc<Packager>>,
+    Path(id): Path<String>,
+) -> Result<Json<HlsJob>> {
+    packager
+        .job(&claim.sub, &id)
+        .map(Json)
+        .ok_or(Error::HlsJobNotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hls::conf::Rendition;
+    use crate::hls::transcode::TranscodedSegment;
+    use crate::route::routes;
+    use crate::testing::{app, TestApp};
+    use aes::cipher::BlockDecryptMut;
+    use axum_test::TestServer;
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use std::time::Duration;
+
+    /// Splits the input into segments of 10 bytes, prefixed by the rendition name
+    struct SplitTranscoder;
+
+    #[axum::async_trait]
+    impl Transcoder for SplitTranscoder {
+        async fn transcode(
+            &self,
+            input: &std::path::Path,
+            rendition: &Rendition,
+            _segment_sec: u32,
+            output: &std::path::Path,
+        ) -> anyhow::Result<Vec<TranscodedSegment>> {
+            let input = tokio::fs::read(input).await?;
+            let mut segments = Vec::new();
+            for (index, chunk) in input.chunks(10).enumerate() {
+                let path = output.join(format!("{index}.ts"));
+                tokio::fs::write(&path, [rendition.name.as_bytes(), chunk].concat()).await?;
+                segments.push(TranscodedSegment {
+                    path,
+                    duration_ms: 1500,
+                });
+            }
+            Ok(segments)
+        }
+    }
+
+    struct FailingTranscoder;
+
+    #[axum::async_trait]
+    impl Transcoder for FailingTranscoder {
+        async fn transcode(
+            &self,
+            _input: &std::path::Path,
+            _rendition: &Rendition,
+            _segment_sec: u32,
+            _output: &std::path::Path,
+        ) -> anyhow::Result<Vec<TranscodedSegment>> {
+            anyhow::bail!("unsupported codec")
+        }
+    }
+
+    /// Server packaging with `transcoder`, and a track created by the admin
+    async fn packaging(
+        app: &TestApp,
+        transcoder: Arc<dyn Transcoder>,
+        plaintext: &[u8],
+    ) -> (TestServer, String) {
+        let mut state = app.state.clone();
+        let config = HlsConfig {
+            work_dir: app.dir.join("hls"),
+            renditions: ["64k", "128k"]
+                .into_iter()
+                .map(|name| Rendition {
+                    name: name.to_string(),
+                    bitrate_kbps: name.trim_end_matches('k').parse().expect("Invalid name"),
+                })
+                .collect(),
+            ..HlsConfig::default()
+        };
+        state.hls = Arc::new(Packager::new(
+            config,
+            transcoder,
+            state.storage.clone(),
+            state.vault.clone(),
+        ));
+        let server = TestServer::new(routes(state)).expect("Failed to create test server");
+        let id = app
+            .track(plaintext, Some(&Pubkey::new_unique()), None)
+            .await;
+        let tracks = &app.state.db.tracks;
+        let mut track = tracks
+            .get_track(&id)
+            .await
+            .expect("Failed to get")
+            .expect("No track");
+        track.owner = app.admin.pubkey().to_string();
+        tracks.update_track(&track).await.expect("Failed to update");
+        (server, id)
+    }
+
+    async fn wait(server: &TestServer, jwt: &str, job: &HlsJob) -> HlsJob {
+        for _ in 0..200 {
+            let response = server
+                .get(&format!("/hls/job/{}", job.id))
+                .authorization_bearer(jwt)
+                .await;
+            let job: HlsJob = response.json();
+            if finished(job.state) {
+                return job;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Job did not finish");
+    }
+
+    /// Resolve a playlist URI against the directory of `base`
+    fn resolve(base: &str, uri: &str) -> String {
+        let mut dir = &base[..base.rfind('/').expect("No directory")];
+        let mut uri = uri;
+        while let Some(rest) = uri.strip_prefix("../") {
+            dir = &dir[..dir.rfind('/').expect("No parent")];
+            uri = rest;
+        }
+        format!("{dir}/{uri}")
+    }
+
+    fn uris(playlist: &str) -> Vec<&str> {
+        playlist
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn package_and_play() {
+        let app = app().await;
+        let plaintext: Vec<u8> = (0..25u8).collect();
+        let (server, id) = packaging(&app, Arc::new(SplitTranscoder), &plaintext).await;
+        let jwt = app.jwt(&app.admin).await;
+
+        // only the creator may package
+        let listener = Keypair::new();
+        let listener_jwt = app.jwt(&listener).await;
+        let start = format!("/track/{id}/hls");
+        let response = server
+            .post(&start)
+            .authorization_bearer(&listener_jwt)
+            .await;
+        assert_eq!(response.status_code(), 403);
+        let master = format!("/track/{id}/hls/master.m3u8");
+        let response = server.get(&master).authorization_bearer(&jwt).await;
+        assert_eq!(response.status_code(), 404);
+
+        let response = server.post(&start).authorization_bearer(&jwt).await;
+        assert_eq!(response.status_code(), 202);
+        let job: HlsJob = response.json();
+        assert_eq!(job.track_id, id);
+        let response = server
+            .get(&format!("/hls/job/{}", job.id))
+            .authorization_bearer(&listener_jwt)
+            .await;
+        assert_eq!(response.status_code(), 404);
+        let job = wait(&server, &jwt, &job).await;
+        assert_eq!(
+            (job.state, job.progress, job.error),
+            (JobState::Completed, 100, None)
+        );
+        assert!(!app.dir.join("hls").join(&job.id).exists());
+
+        let response = server.get(&master).authorization_bearer(&jwt).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(
+            response.header(axum::http::header::CONTENT_TYPE),
+            "application/vnd.apple.mpegurl"
+        );
+        let master_playlist = response.text();
+        let renditions = uris(&master_playlist);
+        assert_eq!(renditions.len(), 2);
+        assert!(master_playlist.contains("BANDWIDTH=128000"));
+
+        // the signed URIs need no bearer token
+        let media = resolve(&master, renditions[1]);
+        let response = server.get(&media).await;
+        assert_eq!(response.status_code(), 200);
+        let media_playlist = response.text();
+        let key_uri = media_playlist
+            .lines()
+            .find_map(|line| line.strip_prefix("#EXT-X-KEY:METHOD=AES-128,URI=\""))
+            .and_then(|uri| uri.strip_suffix('"'))
+            .expect("No key");
+        let key = server.get(&resolve(&media, key_uri)).await;
+        assert_eq!(key.status_code(), 200);
+        let key: [u8; SEGMENT_KEY_LEN] = key.as_bytes()[..].try_into().expect("16 bytes");
+
+        let segments = uris(&media_playlist);
+        assert_eq!(segments.len(), 3);
+        for (sequence, uri) in segments.into_iter().enumerate() {
+            let response = server.get(&resolve(&media, uri)).await;
+            assert_eq!(response.status_code(), 200);
+            let iv = (sequence as u128).to_be_bytes();
+            let segment = cbc::Decryptor::<aes::Aes128>::new(&key.into(), &iv.into())
+                .decrypt_padded_vec_mut::<Pkcs7>(response.as_bytes())
+                .expect("Failed to decrypt");
+            let chunk = &plaintext[sequence * 10..(sequence * 10 + 10).min(plaintext.len())];
+            assert_eq!(segment, [b"128k".as_slice(), chunk].concat());
+        }
+
+        // no key without an entitlement
+        let response = server
+            .get(&format!("/track/{id}/hls/key"))
+            .authorization_bearer(&listener_jwt)
+            .await;
+        assert_eq!(response.status_code(), 403);
+        let response = server
+            .get(&format!("/track/{id}/hls/256k/index.m3u8"))
+            .authorization_bearer(&jwt)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    }
+
+    #[tokio::test]
+    async fn report_failures() {
+        let app = app().await;
+        let (server, id) = packaging(&app, Arc::new(FailingTranscoder), b"song").await;
+        let jwt = app.jwt(&app.admin).await;
+
+        let response = server
+            .post(&format!("/track/{id}/hls"))
+            .authorization_bearer(&jwt)
+            .await;
+        let job = wait(&server, &jwt, &response.json()).await;
+        assert_eq!(job.state, JobState::Failed);
+        assert_eq!(
+            job.error.as_deref(),
+            Some("Failed to transcode 64k: unsupported codec")
+        );
+        let track = app.state.db.tracks.get_track(&id).await;
+        assert_eq!(track.expect("Failed to get").expect("No track").hls, None);
+    }
+
+    #[tokio::test]
+    async fn decrypt_chunk_by_chunk() {
+        let app = app().await;
+        // three chunks of the test vault
+        let plaintext: Vec<u8> = (0..2500).map(|i| i as u8).collect();
+        let id = app.track(&plaintext, None, None).await;
+        let track = app.state.db.tracks.get_track(&id).await;
+        let track = track.expect("Failed to get").expect("No track");
+        let key = app
+            .state
+            .vault
+            .unwrap(&track.wrapped_key)
+            .expect("Failed to unwrap");
+        let path = app.dir.join("input");
+        decrypt_to_file(app.state.storage.as_ref(), &track.cid, &key, &path)
+            .await
+            .expect("Failed to decrypt");
+        assert_eq!(
+            tokio::fs::read(&path).await.expect("Failed to read"),
+            plaintext
+        );
+
+        let wrong = ContentKey::generate();
+        assert!(
+            decrypt_to_file(app.state.storage.as_ref(), &track.cid, &wrong, &path)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,44 @@
+//! Configuration for the HLS module.
+//!
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// HLS packaging configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct HlsConfig {
+    /// Path of the ffmpeg executable
+    pub ffmpeg: PathBuf,
+    /// Directory of the files being transcoded, created if missing
+    pub work_dir: PathBuf,
+    /// Target duration of a segment in seconds
+    pub segment_sec: u32,
+    /// Renditions of every track, from the lowest bitrate
+    pub renditions: Vec<Rendition>,
+}
+
+impl Default for HlsConfig {
+    fn default() -> Self {
+        Self {
+            ffmpeg: PathBuf::from("ffmpeg"),
+            work_dir: PathBuf::from("hls"),
+            segment_sec: 6,
+            renditions: [64, 128, 256]
+                .into_iter()
+                .map(|bitrate_kbps| Rendition {
+                    name: format!("{bitrate_kbps}k"),
+                    bitrate_kbps,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// AAC rendition
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct Rendition {
+    /// Name in the playlist URLs
+    pub name: String,
+    /// Audio bitrate in kbit/s
+    pub bitrate_kbps: u32,
+}
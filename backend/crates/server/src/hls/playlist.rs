@@ -0,0 +1,210 @@
+//! # HLS 播放列表
+//!
+//! - `GET /track/{id}/hls/master.m3u8`：主播放列表，列出各码率。
+//! - `GET /track/{id}/hls/{rendition}/index.m3u8`：媒体播放列表，`EXT-X-KEY` 指向密钥接口。
+//! - `GET /track/{id}/hls/key`：16 字节的 AES-128 分片密钥。
+//! - `GET /track/{id}/hls/{rendition}/segment/{index}`：加密的分片。
+//!
+//! 与 [`crate::playback::stream`] 一样接受 JWT 或签名地址。原生播放器请求子资源时不会携带 JWT，
+//! 因此播放列表中的地址都带有签名：请求本身使用签名地址时沿用该签名，否则签发新的签名。
+//! 地址为相对路径，放在 CDN 后面也能使用。
+//!
+
+use crate::crypto::Vault;
+use crate::db::{Database, Track};
+use crate::entitlement::Entitlements;
+use crate::error::{Error, Result};
+use crate::hls::{segment_key, HlsPackage, HlsRendition};
+use crate::playback::url::{Listener, SigningContext, UrlSigner};
+use crate::storage::Storage;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use std::fmt::Write;
+use std::sync::Arc;
+
+const PLAYLIST_CONTENT_TYPE: &str = "application/vnd.apple.mpegurl";
+
+/// 主播放列表
+pub async fn master(
+    listener: Listener,
+    State(signer): State<UrlSigner>,
+    State(db): State<Database>,
+    State(entitlements): State<Arc<Entitlements>>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let (track, package) = load(&db, &id).await?;
+    let context = SigningContext {
+        signer: &signer,
+        entitlements: &entitlements,
+        track_id: &id,
+    };
+    let query = listener.query(&context, &track).await?.to_query();
+    Ok(playlist(render_master(&package, &query)))
+}
+
+/// 媒体播放列表
+pub async fn media(
+    listener: Listener,
+    State(signer): State<UrlSigner>,
+    State(db): State<Database>,
+    State(entitlements): State<Arc<Entitlements>>,
+    Path((id, rendition)): Path<(String, String)>,
+) -> Result<Response> {
+    let (track, package) = load(&db, &id).await?;
+    let rendition = find(&package, &rendition)?;
+    let context = SigningContext {
+        signer: &signer,
+        entitlements: &entitlements,
+        track_id: &id,
+    };
+    let query = listener.query(&context, &track).await?.to_query();
+    Ok(playlist(render_media(rendition, &query)))
+}
+
+/// 分片密钥
+pub async fn key(
+    listener: Listener,
+    State(db): State<Database>,
+    State(entitlements): State<Arc<Entitlements>>,
+    State(vault): State<Arc<Vault>>,
+    Path(id): Path<String>,
+) -> Result<Response> {
+    let track = db
+        .tracks
+        .get_track(&id)
+        .await?
+        .ok_or(Error::TrackNotFound)?;
+    listener.check(&entitlements, &track).await?;
+    let key = segment_key(&vault.unwrap(&track.wrapped_key)?);
+    let mut response = key.to_vec().into_response();
+    response.headers_mut().insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-store"),
+    );
+    Ok(response)
+}
+
+/// 加密的分片
+pub async fn segment(
+    listener: Listener,
+    State(db): State<Database>,
+    State(entitlements): State<Arc<Entitlements>>,
+    State(storage): State<Arc<dyn Storage>>,
+    Path((id, rendition, index)): Path<(String, String, usize)>,
+) -> Result<Response> {
+    let (track, package) = load(&db, &id).await?;
+    listener.check(&entitlements, &track).await?;
+    let segment = find(&package, &rendition)?
+        .segments
+        .get(index)
+        .ok_or(Error::HlsNotFound)?;
+    let data = storage.get(&segment.key).await?;
+    let mut response = data.into_response();
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("video/mp2t"));
+    Ok(response)
+}
+
+async fn load(db: &Database, id: &str) -> Result<(Track, HlsPackage)> {
+    let mut track = db.tracks.get_track(id).await?.ok_or(Error::TrackNotFound)?;
+    let package = track.hls.take().ok_or(Error::HlsNotFound)?;
+    Ok((track, package))
+}
+
+fn find<'a>(package: &'a HlsPackage, name: &str) -> Result<&'a HlsRendition> {
+    package
+        .renditions
+        .iter()
+        .find(|rendition| rendition.name == name)
+        .ok_or(Error::HlsNotFound)
+}
+
+fn playlist(body: String) -> Response {
+    let mut response = body.into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(PLAYLIST_CONTENT_TYPE),
+    );
+    response
+}
+
+/// Master playlist, `query` signs the URLs
+fn render_master(package: &HlsPackage, query: &str) -> String {
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+    for rendition in &package.renditions {
+        let bandwidth = u64::from(rendition.bitrate_kbps) * 1000;
+        // writing to a string never fails
+        let _ = writeln!(
+            playlist,
+            "#EXT-X-STREAM-INF:BANDWIDTH={bandwidth},CODECS=\"mp4a.40.2\"\n{}/index.m3u8?{query}",
+            rendition.name
+        );
+    }
+    playlist
+}
+
+/// Media playlist of a rendition, `query` signs the URLs
+fn render_media(rendition: &HlsRendition, query: &str) -> String {
+    let target_duration = rendition
+        .segments
+        .iter()
+        .map(|segment| segment.duration_ms.div_ceil(1000))
+        .max()
+        .unwrap_or(1);
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:{target_duration}\n\
+         #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-KEY:METHOD=AES-128,URI=\"../key?{query}\"\n"
+    );
+    for (index, segment) in rendition.segments.iter().enumerate() {
+        let _ = writeln!(
+            playlist,
+            "#EXTINF:{}.{:03},\nsegment/{index}?{query}",
+            segment.duration_ms / 1000,
+            segment.duration_ms % 1000
+        );
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hls::HlsSegment;
+
+    #[test]
+    fn render() {
+        let segment = |duration_ms| HlsSegment {
+            key: "key".to_string(),
+            duration_ms,
+        };
+        let package = HlsPackage {
+            renditions: vec![
+                HlsRendition {
+                    name: "64k".to_string(),
+                    bitrate_kbps: 64,
+                    segments: vec![segment(6000), segment(2345)],
+                },
+                HlsRendition {
+                    name: "128k".to_string(),
+                    bitrate_kbps: 128,
+                    segments: Vec::new(),
+                },
+            ],
+        };
+        assert_eq!(
+            render_master(&package, "q=1"),
+            "#EXTM3U\n#EXT-X-VERSION:3\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\n64k/index.m3u8?q=1\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=128000,CODECS=\"mp4a.40.2\"\n128k/index.m3u8?q=1\n"
+        );
+        assert_eq!(
+            render_media(&package.renditions[0], "q=1"),
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-TARGETDURATION:6\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-KEY:METHOD=AES-128,URI=\"../key?q=1\"\n\
+             #EXTINF:6.000,\nsegment/0?q=1\n#EXTINF:2.345,\nsegment/1?q=1\n#EXT-X-ENDLIST\n"
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! # 转码
+//!
+//! 把音频转码为一个码率的 MPEG-TS 分片，默认调用 ffmpeg（AAC 编码），分片由 [`crate::hls`] 加密后保存。
+//!
+
+use crate::hls::conf::Rendition;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Segment written by a transcoder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscodedSegment {
+    /// Path of the plaintext segment
+    pub path: PathBuf,
+    /// Duration in milliseconds
+    pub duration_ms: u64,
+}
+
+/// Transcodes a track into the segments of a rendition
+#[axum::async_trait]
+pub trait Transcoder: Send + Sync {
+    /// Transcode `input` into segments of about `segment_sec` seconds in the directory `output`
+    async fn transcode(
+        &self,
+        input: &Path,
+        rendition: &Rendition,
+        segment_sec: u32,
+        output: &Path,
+    ) -> anyhow::Result<Vec<TranscodedSegment>>;
+}
+
+/// Transcoder running ffmpeg
+pub struct Ffmpeg {
+    program: PathBuf,
+}
+
+impl Ffmpeg {
+    /// Create the transcoder running the ffmpeg executable at `program`
+    pub fn new(program: PathBuf) -> Self {
+        Self { program }
+    }
+}
+
+#[axum::async_trait]
+impl Transcoder for Ffmpeg {
+    async fn transcode(
+        &self,
+        input: &Path,
+        rendition: &Rendition,
+        segment_sec: u32,
+        output: &Path,
+    ) -> anyhow::Result<Vec<TranscodedSegment>> {
+        let playlist = output.join("index.m3u8");
+        let result = Command::new(&self.program)
+            .args(["-nostdin", "-hide_banner", "-loglevel", "error", "-y", "-i"])
+            .arg(input)
+            .args(["-map", "0:a:0", "-vn", "-c:a", "aac", "-b:a"])
+            .arg(format!("{}k", rendition.bitrate_kbps))
+            .args(["-f", "hls", "-hls_playlist_type", "vod", "-hls_time"])
+            .arg(segment_sec.to_string())
+            .arg("-hls_segment_filename")
+            .arg(output.join("%05d.ts"))
+            .arg(&playlist)
+            .output()
+            .await
+            .with_context(|| format!("Failed to run {}", self.program.display()))?;
+        anyhow::ensure!(
+            result.status.success(),
+            "ffmpeg exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+        let playlist = tokio::fs::read_to_string(&playlist).await?;
+        parse_playlist(&playlist, output)
+    }
+}
+
+/// Segments of a media playlist written by ffmpeg
+fn parse_playlist(playlist: &str, dir: &Path) -> anyhow::Result<Vec<TranscodedSegment>> {
+    let mut segments = Vec::new();
+    let mut duration_ms = None;
+    for line in playlist.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let seconds = info.split(',').next().unwrap_or_default();
+            let seconds: f64 = seconds
+                .parse()
+                .with_context(|| format!("Invalid segment duration: {info}"))?;
+            duration_ms = Some((seconds * 1000.0).round() as u64);
+        } else if !line.is_empty() && !line.starts_with('#') {
+            let duration_ms = duration_ms
+                .take()
+                .with_context(|| format!("Segment without a duration: {line}"))?;
+            segments.push(TranscodedSegment {
+                path: dir.join(line),
+                duration_ms,
+            });
+        }
+    }
+    anyhow::ensure!(!segments.is_empty(), "ffmpeg wrote no segment");
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ffmpeg_playlist() {
+        let playlist = "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n\
+            #EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n\
+            #EXTINF:6.000000,\n00000.ts\n#EXTINF:2.345000,\n00001.ts\n#EXT-X-ENDLIST\n";
+        let dir = Path::new("/tmp/out");
+        assert_eq!(
+            parse_playlist(playlist, dir).expect("Failed to parse"),
+            vec![
+                TranscodedSegment {
+                    path: dir.join("00000.ts"),
+                    duration_ms: 6000,
+                },
+                TranscodedSegment {
+                    path: dir.join("00001.ts"),
+                    duration_ms: 2345,
+                },
+            ]
+        );
+        assert!(parse_playlist("#EXTM3U\n00000.ts\n", dir).is_err());
+        assert!(parse_playlist("#EXTM3U\n#EXT-X-ENDLIST\n", dir).is_err());
+    }
+}
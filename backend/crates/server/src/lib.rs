@@ -2,7 +2,20 @@
 #![deny(unsafe_code, missing_docs, clippy::unwrap_used)]
 
 pub mod auth;
+pub mod chain;
 pub mod conf;
+pub mod crypto;
+pub mod db;
+pub mod entitlement;
 pub mod error;
+pub mod hls;
+pub mod playback;
+pub mod profile;
 pub mod route;
+pub mod search;
+pub mod state;
+pub mod storage;
+#[cfg(test)]
+mod testing;
+pub mod track;
 pub mod upload;
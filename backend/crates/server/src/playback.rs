@@ -0,0 +1,233 @@
+//! # 播放
+//!
+//! 播放器调用 `GET /track/{id}/key` 获取音频的解密密钥：服务端确认 JWT 中的钱包有播放权限
+//! （见 [`crate::entitlement`]），再用主密钥解开内容密钥，加密给该钱包后连同授权一起返回，
+//! 内容密钥不会以明文离开服务端。
+//!
+//! 密文通过 `GET /track/{id}/stream` 按范围下载，见 [`stream`]；无法携带 JWT 的播放器使用签名地址，见 [`url`]。
+//!
+
+use crate::auth::claim::Claim;
+use crate::crypto::Vault;
+use crate::db::Database;
+use crate::entitlement::Entitlements;
+use crate::error::{Error, Result};
+use axum::extract::{Path, State};
+use axum::Json;
+use music3_common::crypto::seal_key;
+use music3_common::param::playback::TrackKeyResponse;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub mod conf;
+pub mod stream;
+pub mod url;
+
+/// 获取解密密钥
+pub async fn key(
+    claim: Claim,
+    State(db): State<Database>,
+    State(vault): State<Arc<Vault>>,
+    State(entitlements): State<Arc<Entitlements>>,
+    Path(id): Path<String>,
+) -> Result<Json<TrackKeyResponse>> {
+    let track = db
+        .tracks
+        .get_track(&id)
+        .await?
+        .ok_or(Error::TrackNotFound)?;
+    let wallet =
+        Pubkey::from_str(&claim.sub).map_err(|_| Error::InvalidPubkey(claim.sub.clone()))?;
+    let grant = entitlements
+        .check(&wallet, &track)
+        .await?
+        .ok_or(Error::NotEntitled)?;
+    let key = vault.unwrap(&track.wrapped_key)?;
+    Ok(Json(TrackKeyResponse {
+        id,
+        key: seal_key(&key, &wallet.to_bytes())?,
+        grant,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain::StakeAccount;
+    use crate::entitlement::conf::EntitlementConfig;
+    use crate::testing::app;
+    use music3_common::crypto::{decrypt, open_key};
+    use music3_common::param::playback::{
+        GrantReason, SubscribeRequest, Subscription, SubscriptionPlan,
+    };
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn key_for_holders() {
+        let app = app().await;
+        let (mint, collection, member) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let by_mint = app.track(b"by mint", Some(&mint), None).await;
+        let by_collection = app.track(b"by collection", None, Some(&collection)).await;
+        app.chain.set_collection(&member, &collection);
+
+        let holder = Keypair::new();
+        app.chain.set_balance(&holder.pubkey(), &mint, 1);
+        app.chain.set_balance(&holder.pubkey(), &member, 1);
+        let jwt = app.jwt(&holder).await;
+        let secret: [u8; 32] = holder.to_bytes()[..32].try_into().expect("32 bytes");
+
+        for (id, plaintext) in [
+            (&by_mint, &b"by mint"[..]),
+            (&by_collection, &b"by collection"[..]),
+        ] {
+            let response = app
+                .server
+                .get(&format!("/track/{id}/key"))
+                .authorization_bearer(&jwt)
+                .await;
+            assert_eq!(response.status_code(), 200);
+            let response: TrackKeyResponse = response.json();
+            assert_eq!(&response.id, id);
+            assert_eq!(
+                response.grant.reason,
+                GrantReason::Nft {
+                    mint: if id == &by_mint { mint } else { member }.to_string()
+                }
+            );
+            let key = open_key(&response.key, &secret).expect("Failed to open the key");
+            let track = app
+                .state
+                .db
+                .tracks
+                .get_track(id)
+                .await
+                .expect("Failed to get")
+                .expect("No track");
+            let ciphertext = app
+                .state
+                .storage
+                .get(&track.cid)
+                .await
+                .expect("Failed to read");
+            assert_eq!(decrypt(&key, &ciphertext), Ok(plaintext.to_vec()));
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_non_holders() {
+        let app = app().await;
+        let mint = Pubkey::new_unique();
+        let id = app.track(b"song", Some(&mint), None).await;
+        let unlocked = app.track(b"song", None, None).await;
+
+        let response = app.server.get(&format!("/track/{id}/key")).await;
+        assert_eq!(response.status_code(), 401);
+
+        // an emptied token account does not count
+        let seller = Keypair::new();
+        app.chain.set_balance(&seller.pubkey(), &mint, 0);
+        let jwt = app.jwt(&seller).await;
+        for id in [&id, &unlocked] {
+            let response = app
+                .server
+                .get(&format!("/track/{id}/key"))
+                .authorization_bearer(&jwt)
+                .await;
+            assert_eq!(response.status_code(), 403);
+        }
+
+        let response = app
+            .server
+            .get(&format!("/track/{}/key", Uuid::new_v4()))
+            .authorization_bearer(&jwt)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    }
+
+    #[tokio::test]
+    async fn key_for_subscribers_and_stakers() {
+        let app = app().await;
+        let id = app.track(b"song", Some(&Pubkey::new_unique()), None).await;
+        let listener = Keypair::new();
+        let jwt = app.jwt(&listener).await;
+        let get_key = || {
+            app.server
+                .get(&format!("/track/{id}/key"))
+                .authorization_bearer(&jwt)
+        };
+        assert_eq!(get_key().await.status_code(), 403);
+
+        // only the payment service may record subscriptions
+        let subscribe = format!("/subscription/{}", listener.pubkey());
+        let request = SubscribeRequest {
+            plan: SubscriptionPlan::Monthly,
+        };
+        let response = app
+            .server
+            .post(&subscribe)
+            .authorization_bearer(&jwt)
+            .json(&request)
+            .await;
+        assert_eq!(response.status_code(), 403);
+        let admin_jwt = app.jwt(&app.admin).await;
+        let response = app
+            .server
+            .post(&subscribe)
+            .authorization_bearer(&admin_jwt)
+            .json(&request)
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let subscription: Subscription = response.json();
+
+        let response = app
+            .server
+            .get("/subscription")
+            .authorization_bearer(&jwt)
+            .await;
+        assert_eq!(
+            response.json::<Option<Subscription>>(),
+            Some(subscription.clone())
+        );
+        let response: TrackKeyResponse = get_key().await.json();
+        assert_eq!(
+            response.grant.reason,
+            GrantReason::Subscription {
+                plan: SubscriptionPlan::Monthly
+            }
+        );
+        assert_eq!(response.grant.expires_at, Some(subscription.expires_at));
+
+        let staker = Keypair::new();
+        let stake = StakeAccount {
+            address: Pubkey::new_unique(),
+            voter: app.vote_account,
+            lamports: EntitlementConfig::default().min_stake_lamports,
+            activation_epoch: 1,
+            deactivation_epoch: None,
+        };
+        app.chain.add_stake(&staker.pubkey(), stake);
+        app.chain.set_epoch(2);
+        let response = app
+            .server
+            .get(&format!("/track/{id}/key"))
+            .authorization_bearer(&app.jwt(&staker).await)
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let response: TrackKeyResponse = response.json();
+        assert_eq!(
+            response.grant.reason,
+            GrantReason::Stake {
+                stake_account: stake.address.to_string(),
+                lamports: stake.lamports,
+            }
+        );
+        assert_eq!(response.grant.expires_at, None);
+    }
+}
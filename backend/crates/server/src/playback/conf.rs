@@ -0,0 +1,22 @@
+//! Configuration for the playback module.
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// Playback configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct PlaybackConfig {
+    /// Secret signing the playback URLs, required to start
+    pub secret: Option<String>,
+    /// Lifetime of a signed playback URL in seconds
+    pub url_ttl_sec: u64,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> Self {
+        Self {
+            secret: None,
+            url_ttl_sec: 300,
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! # 流式播放
+//!
+//! `GET /track/{id}/stream` 原样返回存储后端中的密文，支持 `Range` 请求（单个范围），
+//! 播放器按 [`music3_common::crypto`] 的格式计算分块的偏移量，只下载并解密需要的分块，拖动进度时重新请求。
+//!
+//! 密文的键由内容决定，因此用作强 `ETag`；上传时间作为 `Last-Modified`。
+//! 支持 `If-None-Match`、`If-Modified-Since` 和 `If-Range`。
+//!
+//! 请求需要携带 JWT，或者使用 [`super::url`] 签发的地址。
+//!
+
+use crate::db::Database;
+use crate::entitlement::Entitlements;
+use crate::error::{Error, Result};
+use crate::playback::url::Listener;
+use crate::storage::Storage;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum_extra::headers::{
+    AcceptRanges, ContentLength, ContentRange, ETag, HeaderMapExt, IfModifiedSince, IfNoneMatch,
+    IfRange, LastModified, Range,
+};
+use std::ops::Bound;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// 流式读取音频密文
+pub async fn stream(
+    listener: Listener,
+    State(db): State<Database>,
+    State(storage): State<Arc<dyn Storage>>,
+    State(entitlements): State<Arc<Entitlements>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let track = db
+        .tracks
+        .get_track(&id)
+        .await?
+        .ok_or(Error::TrackNotFound)?;
+    listener.check(&entitlements, &track).await?;
+
+    let size = storage.stat(&track.cid).await?.size;
+    let etag = ETag::from_str(&format!("\"{}\"", track.cid))
+        .map_err(|_| Error::Unexpected(format!("Invalid ETag {}", track.cid).into()))?;
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(track.created_at);
+    let last_modified = LastModified::from(modified);
+
+    let mut response = match resolve(&headers, &etag, &last_modified, size) {
+        Resolved::NotModified => StatusCode::NOT_MODIFIED.into_response(),
+        Resolved::Unsatisfiable => {
+            let mut response = StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+            let headers = response.headers_mut();
+            headers.typed_insert(ContentRange::unsatisfied_bytes(size));
+            response
+        }
+        Resolved::Full => {
+            let body = storage.get_range(&track.cid, 0..size).await?;
+            let mut response = Body::from_stream(body).into_response();
+            response.headers_mut().typed_insert(ContentLength(size));
+            response
+        }
+        Resolved::Partial(start, end) => {
+            let body = storage.get_range(&track.cid, start..end + 1).await?;
+            let mut response =
+                (StatusCode::PARTIAL_CONTENT, Body::from_stream(body)).into_response();
+            let headers = response.headers_mut();
+            headers.typed_insert(ContentLength(end + 1 - start));
+            headers.typed_insert(
+                ContentRange::bytes(start..=end, size)
+                    .map_err(|_| Error::Unexpected("Invalid content range".into()))?,
+            );
+            response
+        }
+    };
+    let headers = response.headers_mut();
+    headers.typed_insert(AcceptRanges::bytes());
+    headers.typed_insert(etag);
+    headers.typed_insert(last_modified);
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    Ok(response)
+}
+
+/// What to send for a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resolved {
+    /// The cached copy of the client is fresh
+    NotModified,
+    /// The whole object
+    Full,
+    /// Bytes from the first to the last one, inclusive
+    Partial(u64, u64),
+    /// The range lies outside the object
+    Unsatisfiable,
+}
+
+/// Resolve the conditional and range headers against an object of `size` bytes
+fn resolve(headers: &HeaderMap, etag: &ETag, last_modified: &LastModified, size: u64) -> Resolved {
+    if let Some(if_none_match) = headers.typed_get::<IfNoneMatch>() {
+        if !if_none_match.precondition_passes(etag) {
+            return Resolved::NotModified;
+        }
+    } else if let Some(if_modified_since) = headers.typed_get::<IfModifiedSince>() {
+        if !if_modified_since.is_modified(SystemTime::from(*last_modified)) {
+            return Resolved::NotModified;
+        }
+    }
+
+    let Some(range) = headers.typed_get::<Range>() else {
+        return Resolved::Full;
+    };
+    // a range of an outdated copy cannot be patched in, send everything
+    if let Some(if_range) = headers.typed_get::<IfRange>() {
+        if if_range.is_modified(Some(etag), Some(last_modified)) {
+            return Resolved::Full;
+        }
+    }
+    let mut ranges = range.satisfiable_ranges(size);
+    let (Some(bounds), None) = (ranges.next(), ranges.next()) else {
+        // multiple ranges are not supported, which a server may ignore
+        return Resolved::Full;
+    };
+    let start = match bounds.0 {
+        Bound::Included(start) => start,
+        Bound::Excluded(start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match bounds.1 {
+        Bound::Included(end) => end.min(size.saturating_sub(1)),
+        Bound::Excluded(end) => end.min(size).saturating_sub(1),
+        Bound::Unbounded => size.saturating_sub(1),
+    };
+    if start >= size || start > end {
+        return Resolved::Unsatisfiable;
+    }
+    Resolved::Partial(start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::app;
+    use music3_common::crypto::{Decryptor, Header, HEADER_LEN};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use uuid::Uuid;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.clone(),
+                    HeaderValue::from_str(value).expect("Invalid header"),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resolve_ranges() {
+        let etag = ETag::from_str("\"key\"").expect("Invalid ETag");
+        let last_modified = LastModified::from(SystemTime::UNIX_EPOCH + Duration::from_secs(1000));
+        let resolve = |pairs: &[(header::HeaderName, &str)]| {
+            resolve(&headers(pairs), &etag, &last_modified, 100)
+        };
+
+        assert_eq!(resolve(&[]), Resolved::Full);
+        assert_eq!(
+            resolve(&[(header::RANGE, "bytes=10-19")]),
+            Resolved::Partial(10, 19)
+        );
+        assert_eq!(
+            resolve(&[(header::RANGE, "bytes=90-")]),
+            Resolved::Partial(90, 99)
+        );
+        assert_eq!(
+            resolve(&[(header::RANGE, "bytes=-10")]),
+            Resolved::Partial(90, 99)
+        );
+        assert_eq!(
+            resolve(&[(header::RANGE, "bytes=50-500")]),
+            Resolved::Partial(50, 99)
+        );
+        assert_eq!(
+            resolve(&[(header::RANGE, "bytes=100-")]),
+            Resolved::Unsatisfiable
+        );
+        assert_eq!(resolve(&[(header::RANGE, "bytes=0-1,5-6")]), Resolved::Full);
+
+        assert_eq!(
+            resolve(&[(header::IF_NONE_MATCH, "\"key\"")]),
+            Resolved::NotModified
+        );
+        assert_eq!(
+            resolve(&[(header::IF_NONE_MATCH, "\"other\"")]),
+            Resolved::Full
+        );
+        assert_eq!(
+            resolve(&[(header::IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:16:40 GMT")]),
+            Resolved::NotModified
+        );
+        assert_eq!(
+            resolve(&[(header::IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:01 GMT")]),
+            Resolved::Full
+        );
+
+        assert_eq!(
+            resolve(&[(header::RANGE, "bytes=0-9"), (header::IF_RANGE, "\"key\"")]),
+            Resolved::Partial(0, 9)
+        );
+        assert_eq!(
+            resolve(&[(header::RANGE, "bytes=0-9"), (header::IF_RANGE, "\"old\"")]),
+            Resolved::Full
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_chunks() {
+        let app = app().await;
+        let mint = Pubkey::new_unique();
+        let plaintext: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+        let id = app.track(&plaintext, Some(&mint), None).await;
+        let url = format!("/track/{id}/stream");
+
+        let response = app.server.get(&url).await;
+        assert_eq!(response.status_code(), 401);
+        let listener = Keypair::new();
+        let jwt = app.jwt(&listener).await;
+        let response = app.server.get(&url).authorization_bearer(&jwt).await;
+        assert_eq!(response.status_code(), 403);
+
+        app.chain.set_balance(&listener.pubkey(), &mint, 1);
+        let response = app.server.get(&url).authorization_bearer(&jwt).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.header(header::ACCEPT_RANGES), "bytes");
+        let ciphertext = response.as_bytes().to_vec();
+        let etag = response.header(header::ETAG);
+        let last_modified = response.header(header::LAST_MODIFIED);
+        let response = app
+            .server
+            .get(&url)
+            .authorization_bearer(&jwt)
+            .add_header(header::IF_NONE_MATCH, etag)
+            .await;
+        assert_eq!(response.status_code(), 304);
+        let response = app
+            .server
+            .get(&url)
+            .authorization_bearer(&jwt)
+            .add_header(header::IF_MODIFIED_SINCE, last_modified)
+            .await;
+        assert_eq!(response.status_code(), 304);
+
+        // seek to the third chunk
+        let key = app
+            .state
+            .vault
+            .unwrap(
+                &app.state
+                    .db
+                    .tracks
+                    .get_track(&id)
+                    .await
+                    .expect("Failed to get")
+                    .expect("No track")
+                    .wrapped_key,
+            )
+            .expect("Failed to unwrap");
+        let header = Header::parse(&ciphertext).expect("Invalid header");
+        let decryptor = Decryptor::new(&key, header);
+        let (start, end) = (header.chunk_offset(2), header.chunk_offset(3));
+        let response = app
+            .server
+            .get(&url)
+            .authorization_bearer(&jwt)
+            .add_header(
+                header::RANGE,
+                HeaderValue::from_str(&format!("bytes={start}-{}", end - 1))
+                    .expect("Invalid header"),
+            )
+            .await;
+        assert_eq!(response.status_code(), 206);
+        assert_eq!(
+            response.header(header::CONTENT_RANGE),
+            format!("bytes {start}-{}/{}", end - 1, ciphertext.len())
+        );
+        let chunk = decryptor
+            .decrypt_chunk(2, false, response.as_bytes())
+            .expect("Failed to decrypt");
+        let chunk_size = header.chunk_size as usize;
+        assert_eq!(chunk, plaintext[2 * chunk_size..3 * chunk_size]);
+
+        let response = app
+            .server
+            .get(&url)
+            .authorization_bearer(&jwt)
+            .add_header(header::RANGE, HeaderValue::from_static("bytes=0-14"))
+            .await;
+        assert_eq!(response.as_bytes().len(), HEADER_LEN);
+        let response = app
+            .server
+            .get(&url)
+            .authorization_bearer(&jwt)
+            .add_header(
+                header::RANGE,
+                HeaderValue::from_str(&format!("bytes={}-", ciphertext.len()))
+                    .expect("Invalid header"),
+            )
+            .await;
+        assert_eq!(response.status_code(), 416);
+        assert_eq!(
+            response.header(header::CONTENT_RANGE),
+            format!("bytes */{}", ciphertext.len())
+        );
+
+        let response = app
+            .server
+            .get(&format!("/track/{}/stream", Uuid::new_v4()))
+            .authorization_bearer(&jwt)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    }
+}
@@ -0,0 +1,342 @@
+//! # 签名播放地址
+//!
+//! 播放器不必在每个分块请求中携带长期有效的 JWT：调用 `POST /track/{id}/url` 后，服务端检查播放权限，
+//! 签发一个短期有效的地址：
+//!
+//! ```text
+//! /track/{id}/stream?wallet={pubkey}&expires={timestamp}&signature={hmac}
+//! ```
+//!
+//! 签名使用 [`Hmac`]，密钥是单独配置的 [`PlaybackConfig::secret`]，没有默认值，未配置时服务无法启动。
+//! 以过期时间作为时间戳、`playback:{id}` 作为 nonce，绑定钱包、音频和过期时间，编码为无填充的 URL 安全 Base64。CDN 或原生播放器可以直接请求该地址，过期后被拒绝。
+//! 签发时已检查过播放权限，因此有效期内不再重复检查。HLS 播放列表中的地址使用同样的签名（见 [`crate::hls::playlist`]）。
+//!
+
+use crate::auth::claim::Claim;
+use crate::auth::hmac::Hmac;
+use crate::auth::Authorizer;
+use crate::db::{Database, Track};
+use crate::entitlement::Entitlements;
+use crate::error::{Error, Result};
+use crate::playback::conf::PlaybackConfig;
+use axum::extract::{FromRef, FromRequestParts, Path, Query, State};
+use axum::http::request::Parts;
+use axum::Json;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::playback::{Grant, PlaybackUrlResponse};
+use music3_common::utils::Base64;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Query of a signed playback URL
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignedQuery {
+    /// Wallet the URL was signed for
+    pub wallet: String,
+    /// Expiration in seconds since the epoch
+    pub expires: u64,
+    /// HMAC in unpadded URL-safe Base64
+    pub signature: String,
+}
+
+impl SignedQuery {
+    /// Sign the stream of a track for a wallet
+    pub fn sign(hmac: Hmac, wallet: &Pubkey, track_id: &str, expires: u64) -> Self {
+        let signature = hmac.sign(wallet, expires, &nonce(track_id));
+        Self {
+            wallet: wallet.to_string(),
+            expires,
+            signature: URL_SAFE_NO_PAD.encode(signature),
+        }
+    }
+
+    /// Check the signature and expiration, returning the wallet
+    pub fn verify(&self, hmac: Hmac, track_id: &str) -> Result<Pubkey> {
+        let wallet = Pubkey::from_str(&self.wallet).map_err(|_| Error::InvalidPlaybackUrl)?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(&self.signature)
+            .map_err(|_| Error::InvalidPlaybackUrl)?;
+        if !hmac.verify(&wallet, &Base64(signature), self.expires, &nonce(track_id)) {
+            return Err(Error::InvalidPlaybackUrl);
+        }
+        if self.expires < get_current_timestamp() {
+            return Err(Error::PlaybackUrlExpired);
+        }
+        Ok(wallet)
+    }
+
+    /// Sign for the configured lifetime, never outliving `grant`
+    pub fn issue(context: &SigningContext<'_>, wallet: &Pubkey, grant: &Grant) -> Self {
+        let mut expires = get_current_timestamp() + context.signer.ttl_sec;
+        if let Some(grant_expires_at) = grant.expires_at {
+            expires = expires.min(grant_expires_at);
+        }
+        let hmac = context.signer.hmac_cloned();
+        Self::sign(hmac, wallet, context.track_id, expires)
+    }
+
+    /// Path and query of the stream
+    pub fn url(&self, track_id: &str) -> String {
+        format!("/track/{track_id}/stream?{}", self.to_query())
+    }
+
+    /// Query string, without the leading `?`
+    pub fn to_query(&self) -> String {
+        format!(
+            "wallet={}&expires={}&signature={}",
+            self.wallet, self.expires, self.signature
+        )
+    }
+}
+
+/// Signs the playback URLs with the playback secret
+#[derive(Clone)]
+pub struct UrlSigner {
+    hmac: Arc<Hmac>,
+    ttl_sec: u64,
+}
+
+impl UrlSigner {
+    /// Create the signer, failing without a playback secret
+    pub fn new(config: &PlaybackConfig) -> anyhow::Result<Self> {
+        let secret = config.secret.as_deref().unwrap_or_default();
+        anyhow::ensure!(!secret.is_empty(), "Playback secret must be set");
+        Ok(Self {
+            hmac: Arc::new(Hmac::try_from(secret.as_bytes())?),
+            ttl_sec: config.url_ttl_sec,
+        })
+    }
+
+    /// Create a new hmac instance from the playback secret
+    pub fn hmac_cloned(&self) -> Hmac {
+        self.hmac.as_ref().clone()
+    }
+}
+
+/// Ties a signature to a track, and keeps it apart from the challenge HMACs
+fn nonce(track_id: &str) -> Vec<u8> {
+    [b"playback:".as_slice(), track_id.as_bytes()].concat()
+}
+
+/// Wallet requesting a stream, from a signed URL or a bearer token
+pub struct Listener {
+    /// Wallet of the listener
+    pub wallet: Pubkey,
+    /// Query of the signed URL, `None` with a bearer token
+    pub signed: Option<SignedQuery>,
+}
+
+impl Listener {
+    /// Check that the listener may play `track`, a signed URL is trusted until it expires
+    pub async fn check(&self, entitlements: &Entitlements, track: &Track) -> Result<()> {
+        if self.signed.is_none() {
+            entitlements
+                .check(&self.wallet, track)
+                .await?
+                .ok_or(Error::NotEntitled)?;
+        }
+        Ok(())
+    }
+
+    /// Query signing the URLs of further requests, the one of the request if signed
+    pub async fn query(&self, context: &SigningContext<'_>, track: &Track) -> Result<SignedQuery> {
+        if let Some(query) = &self.signed {
+            return Ok(query.clone());
+        }
+        let grant = context
+            .entitlements
+            .check(&self.wallet, track)
+            .await?
+            .ok_or(Error::NotEntitled)?;
+        Ok(SignedQuery::issue(context, &self.wallet, &grant))
+    }
+}
+
+/// What signing a playback URL takes
+pub struct SigningContext<'a> {
+    /// Signer of the URLs
+    pub signer: &'a UrlSigner,
+    /// Entitlements of the wallets
+    pub entitlements: &'a Entitlements,
+    /// Track signed for
+    pub track_id: &'a str,
+}
+
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for Listener
+where
+    Authorizer: FromRef<S>,
+    UrlSigner: FromRef<S>,
+    S: Sync + Send,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self> {
+        let signer = UrlSigner::from_ref(state);
+        if let Ok(Query(query)) = Query::<SignedQuery>::try_from_uri(&parts.uri) {
+            let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+                .await
+                .map_err(|e| Error::Unexpected(e.to_string().into()))?;
+            let track_id = params.get("id").ok_or(Error::InvalidPlaybackUrl)?;
+            return Ok(Self {
+                wallet: query.verify(signer.hmac_cloned(), track_id)?,
+                signed: Some(query),
+            });
+        }
+        let claim = Claim::from_request_parts(parts, state).await?;
+        let wallet =
+            Pubkey::from_str(&claim.sub).map_err(|_| Error::InvalidPubkey(claim.sub.clone()))?;
+        Ok(Self {
+            wallet,
+            signed: None,
+        })
+    }
+}
+
+/// 签发播放地址
+pub async fn sign(
+    claim: Claim,
+    State(signer): State<UrlSigner>,
+    State(db): State<Database>,
+    State(entitlements): State<Arc<Entitlements>>,
+    Path(id): Path<String>,
+) -> Result<Json<PlaybackUrlResponse>> {
+    let track = db
+        .tracks
+        .get_track(&id)
+        .await?
+        .ok_or(Error::TrackNotFound)?;
+    let wallet =
+        Pubkey::from_str(&claim.sub).map_err(|_| Error::InvalidPubkey(claim.sub.clone()))?;
+    let grant = entitlements
+        .check(&wallet, &track)
+        .await?
+        .ok_or(Error::NotEntitled)?;
+    let context = SigningContext {
+        signer: &signer,
+        entitlements: &entitlements,
+        track_id: &id,
+    };
+    let query = SignedQuery::issue(&context, &wallet, &grant);
+    Ok(Json(PlaybackUrlResponse {
+        url: query.url(&id),
+        expires_at: query.expires,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::app;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    #[test]
+    fn sign_and_verify() {
+        let hmac = Hmac::try_from(b"music3-hmac-secret").expect("Failed to create hmac");
+        let wallet = Pubkey::new_unique();
+        let expires = get_current_timestamp() + 60;
+        let query = SignedQuery::sign(hmac.clone(), &wallet, "track", expires);
+        assert_eq!(query.verify(hmac.clone(), "track").ok(), Some(wallet));
+        assert!(matches!(
+            query.verify(hmac.clone(), "other"),
+            Err(Error::InvalidPlaybackUrl)
+        ));
+
+        let tampered = SignedQuery {
+            expires: expires + 3600,
+            ..query.clone()
+        };
+        assert!(matches!(
+            tampered.verify(hmac.clone(), "track"),
+            Err(Error::InvalidPlaybackUrl)
+        ));
+        let tampered = SignedQuery {
+            wallet: Pubkey::new_unique().to_string(),
+            ..query
+        };
+        assert!(matches!(
+            tampered.verify(hmac.clone(), "track"),
+            Err(Error::InvalidPlaybackUrl)
+        ));
+
+        let expired = SignedQuery::sign(hmac.clone(), &wallet, "track", expires - 120);
+        assert!(matches!(
+            expired.verify(hmac, "track"),
+            Err(Error::PlaybackUrlExpired)
+        ));
+    }
+
+    #[test]
+    fn signer_requires_secret() {
+        assert!(UrlSigner::new(&PlaybackConfig::default()).is_err());
+        let config = PlaybackConfig {
+            secret: Some(String::new()),
+            ..PlaybackConfig::default()
+        };
+        assert!(UrlSigner::new(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn stream_signed_url() {
+        let app = app().await;
+        let mint = Pubkey::new_unique();
+        let id = app.track(b"song", Some(&mint), None).await;
+        let listener = Keypair::new();
+        let jwt = app.jwt(&listener).await;
+        let sign_url = format!("/track/{id}/url");
+
+        let response = app.server.post(&sign_url).await;
+        assert_eq!(response.status_code(), 401);
+        let response = app.server.post(&sign_url).authorization_bearer(&jwt).await;
+        assert_eq!(response.status_code(), 403);
+
+        app.chain.set_balance(&listener.pubkey(), &mint, 1);
+        let response = app.server.post(&sign_url).authorization_bearer(&jwt).await;
+        assert_eq!(response.status_code(), 200);
+        let signed: PlaybackUrlResponse = response.json();
+        let ttl = PlaybackConfig::default().url_ttl_sec;
+        assert!(signed.expires_at <= get_current_timestamp() + ttl);
+
+        // no bearer token, and no chain lookup while the URL is valid
+        app.chain.set_balance(&listener.pubkey(), &mint, 0);
+        let response = app.server.get(&signed.url).await;
+        assert_eq!(response.status_code(), 200);
+        let track = app.state.db.tracks.get_track(&id).await;
+        let key = track.expect("Failed to get").expect("No track").cid;
+        let ciphertext = app.state.storage.get(&key).await.expect("Failed to read");
+        assert_eq!(response.as_bytes(), &ciphertext);
+
+        // the URL only opens its own track
+        let other = app.track(b"other", Some(&mint), None).await;
+        let response = app
+            .server
+            .get(&signed.url.replace(id.as_str(), &other))
+            .await;
+        assert_eq!(response.status_code(), 403);
+
+        let expired = SignedQuery::sign(
+            app.state.signer.hmac_cloned(),
+            &listener.pubkey(),
+            &id,
+            get_current_timestamp() - 1,
+        );
+        let response = app.server.get(&expired.url(&id)).await;
+        assert_eq!(response.status_code(), 403);
+
+        // the login challenge secret does not sign playback URLs
+        let forged = SignedQuery::sign(
+            app.state.authorizer.hmac_cloned(),
+            &listener.pubkey(),
+            &id,
+            get_current_timestamp() + 60,
+        );
+        let response = app.server.get(&forged.url(&id)).await;
+        assert_eq!(response.status_code(), 403);
+    }
+}
@@ -0,0 +1,279 @@
+//! # 个人资料
+//!
+//! 钱包首次登录时创建用户（见 [`crate::auth`]），之后可以设置昵称、简介、社交链接和头像（见 [`avatar`]）：
+//! - `GET /profile`、`PUT /profile`：读取、修改调用者自己的资料，需要 JWT。
+//! - `GET /profiles/{pubkey}`：公开的主页，包含资料、目录中最新的音频和关联到这些音频的 NFT（见 [`crate::track::nft`]）。
+//! - `PUT /profiles/{pubkey}/verified`：由持有 `admin` 权限的运营人员认证创作者或取消认证。
+//!
+
+use crate::auth::claim::Claim;
+use crate::auth::scope::{Admin, RequireScope};
+use crate::db::{Database, Nft, TrackFilter, User};
+use crate::error::{Error, Result};
+use axum::extract::{Path, State};
+use axum::Json;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::profile::{
+    NftResponse, Profile, PublicProfile, UpdateProfileRequest, VerifyRequest,
+};
+use music3_common::param::track::MAX_PAGE_SIZE;
+
+pub mod avatar;
+pub mod conf;
+
+/// 读取自己的资料
+pub async fn me(claim: Claim, State(db): State<Database>) -> Result<Json<Profile>> {
+    Ok(Json(profile(load_or_new(&db, &claim.sub).await?)))
+}
+
+/// 修改自己的资料
+pub async fn update(
+    claim: Claim,
+    State(db): State<Database>,
+    Json(request): Json<UpdateProfileRequest>,
+) -> Result<Json<Profile>> {
+    request.validate().map_err(Error::InvalidProfile)?;
+    let mut user = load_or_new(&db, &claim.sub).await?;
+    user.display_name = request.display_name;
+    user.bio = request.bio;
+    user.links = request.links;
+    user.updated_at = get_current_timestamp();
+    db.users.update_user(&user).await?;
+    Ok(Json(profile(user)))
+}
+
+/// 公开主页
+pub async fn public(
+    State(db): State<Database>,
+    Path(pubkey): Path<String>,
+) -> Result<Json<PublicProfile>> {
+    let user = db
+        .users
+        .get_user(&pubkey)
+        .await?
+        .ok_or(Error::ProfileNotFound)?;
+    let filter = TrackFilter {
+        owner: Some(pubkey.clone()),
+        ..TrackFilter::default()
+    };
+    let (tracks, _) = db.tracks.list_tracks(&filter, 0, MAX_PAGE_SIZE).await?;
+    let nfts = db.nfts.nfts_of_creator(&pubkey).await?;
+    Ok(Json(PublicProfile {
+        profile: profile(user),
+        tracks: tracks
+            .into_iter()
+            .map(crate::track::response)
+            .collect::<Result<_>>()?,
+        nfts: nfts.into_iter().map(nft).collect(),
+    }))
+}
+
+/// 认证创作者
+pub async fn verify(
+    _claim: RequireScope<Admin>,
+    State(db): State<Database>,
+    Path(pubkey): Path<String>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<Profile>> {
+    let mut user = load_or_new(&db, &pubkey).await?;
+    user.verified = request.verified;
+    user.updated_at = get_current_timestamp();
+    db.users.update_user(&user).await?;
+    Ok(Json(profile(user)))
+}
+
+/// User of a pubkey, created if it has none, e.g. signed in before users were recorded
+pub(crate) async fn load_or_new(db: &Database, pubkey: &str) -> Result<User> {
+    if let Some(user) = db.users.get_user(pubkey).await? {
+        return Ok(user);
+    }
+    let user = User::new(pubkey.to_string(), get_current_timestamp());
+    if !db.users.insert_user(&user).await? {
+        // inserted by a concurrent request
+        return db
+            .users
+            .get_user(pubkey)
+            .await?
+            .ok_or(Error::ProfileNotFound);
+    }
+    Ok(user)
+}
+
+pub(crate) fn profile(user: User) -> Profile {
+    Profile {
+        avatar_url: user
+            .avatar
+            .as_ref()
+            .map(|_| format!("/profiles/{}/avatar", user.pubkey)),
+        pubkey: user.pubkey,
+        display_name: user.display_name,
+        bio: user.bio,
+        links: user.links,
+        verified: user.verified,
+        created_at: user.created_at,
+        updated_at: user.updated_at,
+    }
+}
+
+/// Response of an NFT
+pub(crate) fn nft(nft: Nft) -> NftResponse {
+    NftResponse {
+        mint: nft.mint,
+        track_id: nft.track_id,
+        metadata_uri: nft.metadata_uri,
+        minted_at: nft.minted_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::app;
+    use axum::http::{header, HeaderValue};
+    use music3_common::param::profile::SocialLink;
+    use music3_common::param::track::{TrackInfo, TrackNftRequest};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[tokio::test]
+    async fn edit_and_show() {
+        let app = app().await;
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey().to_string();
+        let jwt = app.jwt(&keypair).await;
+
+        assert_eq!(app.server.get("/profile").await.status_code(), 401);
+        let profile: Profile = app
+            .server
+            .get("/profile")
+            .authorization_bearer(&jwt)
+            .await
+            .json();
+        assert_eq!(profile.pubkey, pubkey);
+        assert_eq!((profile.display_name, profile.verified), (None, false));
+
+        let request = UpdateProfileRequest {
+            display_name: Some("DJ".to_string()),
+            bio: Some("Beats".to_string()),
+            links: vec![SocialLink {
+                platform: "twitter".to_string(),
+                url: "https://x.com/dj".to_string(),
+            }],
+        };
+        let response = app.server.put("/profile").json(&request).await;
+        assert_eq!(response.status_code(), 401);
+        let invalid = UpdateProfileRequest {
+            bio: Some("x".repeat(5000)),
+            ..request.clone()
+        };
+        let response = app
+            .server
+            .put("/profile")
+            .authorization_bearer(&jwt)
+            .json(&invalid)
+            .await;
+        assert_eq!(response.status_code(), 400);
+        let response = app
+            .server
+            .put("/profile")
+            .authorization_bearer(&jwt)
+            .json(&request)
+            .await;
+        assert_eq!(response.status_code(), 200);
+
+        // avatars are checked by content and size
+        let response = app
+            .server
+            .put("/profile/avatar")
+            .authorization_bearer(&jwt)
+            .add_header(header::CONTENT_TYPE, HeaderValue::from_static("image/png"))
+            .bytes(b"<svg/>".as_slice().into())
+            .await;
+        assert_eq!(response.status_code(), 415);
+        let mut huge = PNG.to_vec();
+        huge.resize(2 * 1024 * 1024, 0);
+        let response = app
+            .server
+            .put("/profile/avatar")
+            .authorization_bearer(&jwt)
+            .bytes(huge.into())
+            .await;
+        assert_eq!(response.status_code(), 413);
+        let response = app
+            .server
+            .put("/profile/avatar")
+            .authorization_bearer(&jwt)
+            .bytes(PNG.into())
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let profile: Profile = response.json();
+        let avatar_url = profile.avatar_url.expect("No avatar");
+        let response = app.server.get(&avatar_url).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.header(header::CONTENT_TYPE), "image/png");
+        assert_eq!(response.as_bytes(), PNG);
+
+        // the public page lists the registered tracks and the NFTs linked to them
+        let creator = app.admin.pubkey().to_string();
+        let admin = app.jwt(&app.admin).await;
+        let mut track = crate::db::tests::track(&creator, 1);
+        track.info = Some(TrackInfo {
+            title: "Song".to_string(),
+            ..TrackInfo::default()
+        });
+        let inserted = app.state.db.tracks.insert_track(&track).await;
+        assert!(inserted.expect("Failed to insert"));
+        let unregistered = crate::db::tests::track(&creator, 2);
+        let inserted = app.state.db.tracks.insert_track(&unregistered).await;
+        assert!(inserted.expect("Failed to insert"));
+        let mint = Pubkey::new_unique();
+        app.chain.add_creator(&mint, &app.admin.pubkey());
+        let link = TrackNftRequest {
+            mint: mint.to_string(),
+            collection: None,
+            metadata_uri: Some("ipfs://metadata".to_string()),
+        };
+        let response = app
+            .server
+            .put(&format!("/tracks/{}/nft", track.id))
+            .authorization_bearer(&admin)
+            .json(&link)
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let page: PublicProfile = app.server.get(&format!("/profiles/{creator}")).await.json();
+        let ids: Vec<&str> = page.tracks.iter().map(|track| track.id.as_str()).collect();
+        assert_eq!(ids, [track.id.as_str()]);
+        assert_eq!(page.nfts.len(), 1);
+        assert_eq!(page.nfts[0].mint, link.mint);
+        assert_eq!(page.nfts[0].metadata_uri, link.metadata_uri);
+        let page: PublicProfile = app.server.get(&format!("/profiles/{pubkey}")).await.json();
+        assert_eq!(page.profile.display_name.as_deref(), Some("DJ"));
+        assert_eq!(page.profile.links, request.links);
+        assert!(page.tracks.is_empty() && page.nfts.is_empty());
+        let response = app.server.get("/profiles/nobody").await;
+        assert_eq!(response.status_code(), 404);
+
+        // only an admin verifies creators
+        let url = format!("/profiles/{pubkey}/verified");
+        let verify = VerifyRequest { verified: true };
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&jwt)
+            .json(&verify)
+            .await;
+        assert_eq!(response.status_code(), 403);
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&admin)
+            .json(&verify)
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let page: PublicProfile = app.server.get(&format!("/profiles/{pubkey}")).await.json();
+        assert!(page.profile.verified);
+    }
+}
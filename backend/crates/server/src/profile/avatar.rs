@@ -0,0 +1,101 @@
+//! # 头像
+//!
+//! `PUT /profile/avatar` 的请求体是图片本身，格式根据文件头识别（PNG、JPEG、GIF、WebP），
+//! 与声明的 `Content-Type` 无关，超过 [`ProfileConfig::max_avatar_size`] 的图片被拒绝。
+//! 头像不加密，与音频保存在同一个存储后端中，通过 `GET /profiles/{pubkey}/avatar` 公开读取。
+//!
+//! 按内容寻址的存储后端中，不同用户可能共用同一个对象，因此更换头像时不删除旧的对象。
+//!
+
+use crate::auth::claim::Claim;
+use crate::db::Database;
+use crate::error::{Error, Result};
+use crate::profile::conf::ProfileConfig;
+use crate::profile::{load_or_new, profile};
+use crate::storage::Storage;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::profile::Profile;
+use std::sync::Arc;
+
+/// MIME type of a supported image, detected from its first bytes
+pub fn image_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else {
+        None
+    }
+}
+
+/// 上传头像
+pub async fn upload(
+    claim: Claim,
+    State(config): State<Arc<ProfileConfig>>,
+    State(storage): State<Arc<dyn Storage>>,
+    State(db): State<Database>,
+    data: Bytes,
+) -> Result<Json<Profile>> {
+    if data.len() as u64 > config.max_avatar_size {
+        return Err(Error::PayloadTooLarge(config.max_avatar_size));
+    }
+    let content_type = image_type(&data).ok_or(Error::UnsupportedImage)?;
+    let key = storage.put(data, content_type).await?;
+    let mut user = load_or_new(&db, &claim.sub).await?;
+    user.avatar = Some(key);
+    user.updated_at = get_current_timestamp();
+    db.users.update_user(&user).await?;
+    Ok(Json(profile(user)))
+}
+
+/// 读取头像
+pub async fn get(
+    State(storage): State<Arc<dyn Storage>>,
+    State(db): State<Database>,
+    Path(pubkey): Path<String>,
+) -> Result<Response> {
+    let key = db
+        .users
+        .get_user(&pubkey)
+        .await?
+        .and_then(|user| user.avatar)
+        .ok_or(Error::ProfileNotFound)?;
+    let data = storage.get(&key).await?;
+    let content_type = image_type(&data).unwrap_or("application/octet-stream");
+    let mut response = data.into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("public, max-age=300"),
+    );
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(image_type(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(image_type(&[0xff, 0xd8, 0xff, 0xe0]), Some("image/jpeg"));
+        assert_eq!(image_type(b"GIF89a...."), Some("image/gif"));
+        assert_eq!(image_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(image_type(b"RIFF\0\0\0\0WAVEfmt "), None);
+        assert_eq!(
+            image_type(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"),
+            None
+        );
+        assert_eq!(image_type(b""), None);
+    }
+}
@@ -0,0 +1,19 @@
+//! Configuration for the profile module.
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// Profile configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct ProfileConfig {
+    /// Max size of an avatar in bytes
+    pub max_avatar_size: u64,
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            max_avatar_size: 1024 * 1024,
+        }
+    }
+}
@@ -3,28 +3,109 @@
 
 use crate::auth::claim::Claim;
 use crate::conf::Config;
+use crate::state::AppState;
+use axum::extract::DefaultBodyLimit;
 use axum::response::IntoResponse;
-use axum::routing::{get, post};
+use axum::routing::{get, post, put};
 use axum::Router;
 
-/// Music3 backend routes
-pub fn router(config: Config) -> anyhow::Result<Router> {
-    let authorizer = crate::auth::Authorizer::new(config.auth)?;
-    let router = Router::new()
+/// Music3 backend routes, migrating the database and indexing the catalog first
+pub async fn router(config: Config) -> anyhow::Result<Router> {
+    let state = AppState::new(config)?;
+    state.db.migrate().await?;
+    state.search.rebuild(&state.db).await?;
+    Ok(routes(state))
+}
+
+/// Music3 backend routes with the given state
+pub fn routes(state: AppState) -> Router {
+    let upload_limit = state.upload.max_size + crate::upload::MULTIPART_OVERHEAD;
+    let chunk_limit = state.upload.chunk_size;
+    let avatar_limit = state.profile.max_avatar_size;
+    Router::new()
         .route("/", get(index))
+        .route("/.well-known/jwks.json", get(crate::auth::jwks))
         .nest(
             "/auth",
             Router::new()
                 .route("/challenge", post(crate::auth::get_challenge))
-                .route("/authorize", post(crate::auth::authorize)),
+                .route("/authorize", post(crate::auth::authorize))
+                .route("/refresh", post(crate::auth::refresh))
+                .route("/logout", post(crate::auth::logout)),
         )
         .nest(
             "/file",
-            Router::new().route("/upload", post(crate::upload::upload)),
+            Router::new()
+                .route(
+                    "/upload",
+                    post(crate::upload::upload).layer(body_limit(upload_limit)),
+                )
+                .route("/upload/session", post(crate::upload::session::create))
+                .route(
+                    "/upload/session/:id",
+                    get(crate::upload::session::status)
+                        .put(crate::upload::session::put_chunk)
+                        .layer(body_limit(chunk_limit)),
+                )
+                .route(
+                    "/upload/session/:id/finalize",
+                    post(crate::upload::session::finalize),
+                ),
+        )
+        .route(
+            "/tracks",
+            get(crate::track::list).post(crate::track::create),
+        )
+        .route(
+            "/tracks/:id",
+            get(crate::track::get)
+                .put(crate::track::update)
+                .delete(crate::track::delete),
+        )
+        .route("/tracks/:id/nft", put(crate::track::nft))
+        .route(
+            "/profile",
+            get(crate::profile::me).put(crate::profile::update),
+        )
+        .route(
+            "/profile/avatar",
+            put(crate::profile::avatar::upload).layer(body_limit(avatar_limit)),
         )
-        .with_state(authorizer);
+        .route("/profiles/:pubkey", get(crate::profile::public))
+        .route("/profiles/:pubkey/avatar", get(crate::profile::avatar::get))
+        .route("/profiles/:pubkey/verified", put(crate::profile::verify))
+        .route("/search", get(crate::search::search))
+        .route("/track/:id/key", get(crate::playback::key))
+        .route("/track/:id/stream", get(crate::playback::stream::stream))
+        .route("/track/:id/url", post(crate::playback::url::sign))
+        .route("/track/:id/hls", post(crate::hls::start))
+        .route(
+            "/track/:id/hls/master.m3u8",
+            get(crate::hls::playlist::master),
+        )
+        .route("/track/:id/hls/key", get(crate::hls::playlist::key))
+        .route(
+            "/track/:id/hls/:rendition/index.m3u8",
+            get(crate::hls::playlist::media),
+        )
+        .route(
+            "/track/:id/hls/:rendition/segment/:index",
+            get(crate::hls::playlist::segment),
+        )
+        .route("/hls/job/:id", get(crate::hls::status))
+        .route(
+            "/subscription",
+            get(crate::entitlement::subscription::status),
+        )
+        .route(
+            "/subscription/:pubkey",
+            post(crate::entitlement::subscription::subscribe),
+        )
+        .with_state(state)
+}
 
-    Ok(router)
+fn body_limit(limit: u64) -> DefaultBodyLimit {
+    DefaultBodyLimit::max(usize::try_from(limit).unwrap_or(usize::MAX))
 }
 
 /// Index route
@@ -0,0 +1,494 @@
+//! # 搜索
+//!
+//! `GET /search` 在音频目录中全文搜索标题、艺术家、歌词和标签，容忍拼写错误（见 [`conf::SearchConfig::max_typos`]），
+//! 并可按流派、价格、发行日期和是否标价筛选。响应中附带所有匹配的音频按流派、发行年份和价格区间的数量，
+//! 参数和响应的结构见 [`music3_common::param::search`]。
+//!
+//! 索引由 tantivy 保存在内存中：启动时从数据库重建（见 [`SearchIndex::rebuild`]），
+//! 之后随目录的登记、修改和移除更新（见 [`crate::track`]）。
+//!
+
+use crate::db::{Database, TrackFilter};
+use crate::error::Error;
+use crate::search::conf::SearchConfig;
+use crate::search::error::Result;
+use axum::extract::{Query, State};
+use axum::Json;
+use chrono::Datelike;
+use music3_common::param::search::{
+    FacetCount, PriceCount, SearchFacets, SearchQuery, SearchResults, MAX_OFFSET, PRICE_BUCKETS,
+};
+use music3_common::param::track::{TrackResponse, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, PoisonError};
+use tantivy::collector::{Count, FacetCollector, FacetCounts, TopDocs};
+use tantivy::query::{AllQuery, BooleanQuery, Occur, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{
+    Facet, FacetOptions, Field, IndexRecordOption, Schema, Value, FAST, INDEXED, STORED, STRING,
+    TEXT,
+};
+use tantivy::{doc, Index, IndexReader, IndexWriter, Order, ReloadPolicy, TantivyDocument, Term};
+
+pub mod conf;
+pub mod error;
+
+/// Genres counted in the facets
+const MAX_GENRE_FACETS: usize = 20;
+
+/// 搜索音频
+pub async fn search(
+    State(index): State<Arc<SearchIndex>>,
+    Query(query): Query<SearchQuery>,
+) -> crate::error::Result<Json<SearchResults>> {
+    if query.offset > MAX_OFFSET {
+        return Err(Error::InvalidSearch(format!(
+            "offset must not exceed {MAX_OFFSET}"
+        )));
+    }
+    Ok(Json(index.search(&query)?))
+}
+
+/// Full-text index of the registered tracks
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+    max_typos: u8,
+}
+
+struct Fields {
+    /// Track ID, to replace or remove the track
+    id: Field,
+    /// [`TrackResponse`] as JSON, returned as is
+    track: Field,
+    title: Field,
+    artist: Field,
+    lyrics: Field,
+    tags: Field,
+    /// `/genre/{genre}`, `/year/{year}`, `/price/{bucket}` and `/priced/{priced}`
+    facets: Field,
+    price: Field,
+    priced: Field,
+    /// Days since the common era
+    released: Field,
+    created_at: Field,
+}
+
+impl SearchIndex {
+    /// Create an empty index
+    pub fn new(config: &SearchConfig) -> anyhow::Result<Self> {
+        let mut schema = Schema::builder();
+        let fields = Fields {
+            id: schema.add_text_field("id", STRING),
+            track: schema.add_text_field("track", STORED),
+            title: schema.add_text_field("title", TEXT),
+            artist: schema.add_text_field("artist", TEXT),
+            lyrics: schema.add_text_field("lyrics", TEXT),
+            tags: schema.add_text_field("tags", TEXT),
+            facets: schema.add_facet_field("facets", FacetOptions::default()),
+            price: schema.add_u64_field("price", INDEXED | FAST),
+            priced: schema.add_bool_field("priced", INDEXED),
+            released: schema.add_i64_field("released", INDEXED | FAST),
+            created_at: schema.add_u64_field("created_at", FAST),
+        };
+        let index = Index::create_in_ram(schema.build());
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let writer = index.writer_with_num_threads(1, config.writer_memory)?;
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+            max_typos: config.max_typos.min(2),
+        })
+    }
+
+    /// Replace the index with the registered tracks of the database
+    pub async fn rebuild(&self, db: &Database) -> crate::error::Result<()> {
+        let mut tracks = Vec::new();
+        loop {
+            let offset = tracks.len() as u64;
+            let (page, _) = db
+                .tracks
+                .list_tracks(&TrackFilter::default(), offset, MAX_PAGE_SIZE)
+                .await?;
+            if page.is_empty() {
+                break;
+            }
+            for track in page {
+                tracks.push(crate::track::response(track)?);
+            }
+        }
+        Ok(self.replace(&tracks)?)
+    }
+
+    fn replace(&self, tracks: &[TrackResponse]) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        writer.delete_all_documents()?;
+        for track in tracks {
+            writer.add_document(self.document(track)?)?;
+        }
+        self.commit(&mut writer)
+    }
+
+    /// Add a track, replacing the previous version
+    pub fn index(&self, track: &TrackResponse) -> Result<()> {
+        let document = self.document(track)?;
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        writer.delete_term(Term::from_field_text(self.fields.id, &track.id));
+        writer.add_document(document)?;
+        self.commit(&mut writer)
+    }
+
+    /// Remove a track
+    pub fn remove(&self, id: &str) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        writer.delete_term(Term::from_field_text(self.fields.id, id));
+        self.commit(&mut writer)
+    }
+
+    /// Tracks matching the query, best match first, or newest first without words
+    pub fn search(&self, query: &SearchQuery) -> Result<SearchResults> {
+        let offset = query.offset.min(MAX_OFFSET);
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE);
+        let mut facets = FacetCollector::for_field("facets");
+        for facet in ["/genre", "/year", "/price", "/priced"] {
+            facets.add_facet(facet);
+        }
+        let top = TopDocs::with_limit(limit as usize).and_offset(offset as usize);
+        let words = query.q.as_deref().filter(|q| !q.trim().is_empty());
+        let searcher = self.reader.searcher();
+        let parsed = self.query(words, query);
+        let (addresses, total, counts) = if words.is_some() {
+            let (docs, total, counts) = searcher.search(&parsed, &(top, Count, facets))?;
+            let addresses: Vec<_> = docs.into_iter().map(|(_, address)| address).collect();
+            (addresses, total, counts)
+        } else {
+            let newest = top.order_by_u64_field("created_at", Order::Desc);
+            let (docs, total, counts) = searcher.search(&parsed, &(newest, Count, facets))?;
+            let addresses: Vec<_> = docs.into_iter().map(|(_, address)| address).collect();
+            (addresses, total, counts)
+        };
+        let mut tracks = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let document: TantivyDocument = searcher.doc(address)?;
+            let json = document
+                .get_first(self.fields.track)
+                .and_then(|value| value.as_str())
+                .unwrap_or_default();
+            tracks.push(serde_json::from_str(json)?);
+        }
+        Ok(SearchResults {
+            tracks,
+            total: total as u64,
+            offset,
+            limit,
+            facets: facets_of(&counts),
+        })
+    }
+
+    fn query(&self, words: Option<&str>, query: &SearchQuery) -> Box<dyn tantivy::query::Query> {
+        let mut clauses: Vec<(Occur, Box<dyn tantivy::query::Query>)> = Vec::new();
+        if let Some(words) = words {
+            let fields = [
+                self.fields.title,
+                self.fields.artist,
+                self.fields.lyrics,
+                self.fields.tags,
+            ];
+            let mut exact = QueryParser::for_index(&self.index, fields.to_vec());
+            exact.set_field_boost(self.fields.title, 3.0);
+            exact.set_field_boost(self.fields.artist, 2.0);
+            // syntax errors are ignored, the words are searched anyway
+            let (exact, _) = exact.parse_query_lenient(words);
+            let mut text = vec![(Occur::Should, exact)];
+            if self.max_typos > 0 {
+                let mut fuzzy = QueryParser::for_index(&self.index, fields.to_vec());
+                for field in fields {
+                    fuzzy.set_field_fuzzy(field, false, self.max_typos, true);
+                }
+                text.push((Occur::Should, fuzzy.parse_query_lenient(words).0));
+            }
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(text))));
+        }
+        if let Some(genre) = &query.genre {
+            let facet = Facet::from_path(["genre", genre.to_lowercase().as_str()]);
+            let term = Term::from_facet(self.fields.facets, &facet);
+            let genre = TermQuery::new(term, IndexRecordOption::Basic);
+            clauses.push((Occur::Must, Box::new(genre)));
+        }
+        if query.min_price.is_some() || query.max_price.is_some() {
+            let price = RangeQuery::new_u64_bounds(
+                "price".to_string(),
+                query.min_price.map_or(Bound::Unbounded, Bound::Included),
+                query.max_price.map_or(Bound::Unbounded, Bound::Included),
+            );
+            clauses.push((Occur::Must, Box::new(price)));
+        }
+        if query.released_from.is_some() || query.released_until.is_some() {
+            let day = |date: chrono::NaiveDate| Bound::Included(i64::from(date.num_days_from_ce()));
+            let released = RangeQuery::new_i64_bounds(
+                "released".to_string(),
+                query.released_from.map_or(Bound::Unbounded, day),
+                query.released_until.map_or(Bound::Unbounded, day),
+            );
+            clauses.push((Occur::Must, Box::new(released)));
+        }
+        if let Some(priced) = query.priced {
+            let term = Term::from_field_bool(self.fields.priced, priced);
+            let priced = TermQuery::new(term, IndexRecordOption::Basic);
+            clauses.push((Occur::Must, Box::new(priced)));
+        }
+        if clauses.is_empty() {
+            Box::new(AllQuery)
+        } else {
+            Box::new(BooleanQuery::new(clauses))
+        }
+    }
+
+    fn document(&self, track: &TrackResponse) -> Result<TantivyDocument> {
+        let fields = &self.fields;
+        let info = &track.info;
+        let mut document = doc!(
+            fields.id => track.id.as_str(),
+            fields.track => serde_json::to_string(track)?,
+            fields.title => info.title.as_str(),
+            fields.priced => info.price.is_some(),
+            fields.created_at => track.created_at,
+            fields.facets => Facet::from_path(["priced", &info.price.is_some().to_string()]),
+        );
+        if let Some(artist) = &info.artist {
+            document.add_text(fields.artist, artist);
+        }
+        if let Some(lyrics) = &info.lyrics {
+            document.add_text(fields.lyrics, lyrics);
+        }
+        for tag in &info.tags {
+            document.add_text(fields.tags, tag);
+        }
+        if let Some(genre) = &info.genre {
+            let facet = Facet::from_path(["genre", genre.to_lowercase().as_str()]);
+            document.add_facet(fields.facets, facet);
+        }
+        if let Some(date) = info.release_date {
+            document.add_i64(fields.released, i64::from(date.num_days_from_ce()));
+            let facet = Facet::from_path(["year", date.year().to_string().as_str()]);
+            document.add_facet(fields.facets, facet);
+        }
+        if let Some(price) = info.price {
+            document.add_u64(fields.price, price);
+            let bucket = PRICE_BUCKETS.iter().rposition(|min| price >= *min);
+            let facet = Facet::from_path(["price", bucket.unwrap_or(0).to_string().as_str()]);
+            document.add_facet(fields.facets, facet);
+        }
+        Ok(document)
+    }
+
+    fn commit(&self, writer: &mut IndexWriter) -> Result<()> {
+        writer.commit()?;
+        // make the change visible to the next search
+        self.reader.reload()?;
+        Ok(())
+    }
+}
+
+fn facets_of(counts: &FacetCounts) -> SearchFacets {
+    let value = |facet: &Facet| {
+        facet
+            .to_path()
+            .last()
+            .copied()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let genre = counts
+        .top_k("/genre", MAX_GENRE_FACETS)
+        .into_iter()
+        .map(|(facet, count)| FacetCount {
+            value: value(facet),
+            count,
+        })
+        .collect();
+    let mut release_year: Vec<_> = counts
+        .get("/year")
+        .map(|(facet, count)| FacetCount {
+            value: value(facet),
+            count,
+        })
+        .collect();
+    release_year.reverse();
+    let mut price: Vec<_> = PRICE_BUCKETS
+        .iter()
+        .enumerate()
+        .map(|(bucket, min)| PriceCount {
+            min: *min,
+            max: PRICE_BUCKETS.get(bucket + 1).copied(),
+            count: 0,
+        })
+        .collect();
+    for (facet, count) in counts.get("/price") {
+        if let Some(bucket) = value(facet)
+            .parse()
+            .ok()
+            .and_then(|b: usize| price.get_mut(b))
+        {
+            bucket.count = count;
+        }
+    }
+    let priced = counts
+        .get("/priced")
+        .find(|(facet, _)| value(facet) == "true")
+        .map_or(0, |(_, count)| count);
+    SearchFacets {
+        genre,
+        release_year,
+        price,
+        priced,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track::response;
+    use chrono::NaiveDate;
+    use music3_common::param::track::TrackInfo;
+
+    fn track(created_at: u64, title: &str, genre: &str, price: Option<u64>) -> TrackResponse {
+        let mut track = crate::db::tests::track("owner", created_at);
+        track.info = Some(TrackInfo {
+            title: title.to_string(),
+            artist: Some("The Band".to_string()),
+            genre: Some(genre.to_string()),
+            lyrics: Some("dancing under the stars".to_string()),
+            release_date: NaiveDate::from_ymd_opt(2020 + created_at as i32, 1, 1),
+            tags: vec!["chill".to_string()],
+            price,
+            ..TrackInfo::default()
+        });
+        response(track).expect("Registered")
+    }
+
+    fn titles(results: &SearchResults) -> Vec<&str> {
+        let titles = results.tracks.iter().map(|track| track.info.title.as_str());
+        titles.collect()
+    }
+
+    #[tokio::test]
+    async fn search() {
+        let index = SearchIndex::new(&SearchConfig::default()).expect("Failed to create");
+        let tracks = [
+            track(1, "Moonlight Sonata", "Classical", None),
+            track(2, "Blue Moon", "Jazz", Some(50_000_000)),
+            track(3, "Sunrise", "jazz", Some(2_000_000_000)),
+        ];
+        for track in &tracks {
+            index.index(track).expect("Failed to index");
+        }
+        let search = |query: SearchQuery| index.search(&query).expect("Failed to search");
+
+        // newest first without words
+        let all = search(SearchQuery::default());
+        assert_eq!(titles(&all), ["Sunrise", "Blue Moon", "Moonlight Sonata"]);
+        assert_eq!(all.total, 3);
+        assert_eq!(all.tracks[0], tracks[2]);
+        assert_eq!(
+            all.facets.genre,
+            [
+                FacetCount {
+                    value: "jazz".to_string(),
+                    count: 2
+                },
+                FacetCount {
+                    value: "classical".to_string(),
+                    count: 1
+                },
+            ]
+        );
+        let years: Vec<&str> = all
+            .facets
+            .release_year
+            .iter()
+            .map(|year| year.value.as_str())
+            .collect();
+        assert_eq!(years, ["2023", "2022", "2021"]);
+        let prices: Vec<u64> = all.facets.price.iter().map(|price| price.count).collect();
+        assert_eq!(prices, [1, 0, 1, 0]);
+        assert_eq!(all.facets.price[3].max, None);
+        assert_eq!(all.facets.priced, 2);
+
+        // typos are tolerated, the title weighs the most
+        let query = |q: &str| SearchQuery {
+            q: Some(q.to_string()),
+            ..SearchQuery::default()
+        };
+        assert_eq!(titles(&search(query("sonata"))), ["Moonlight Sonata"]);
+        assert_eq!(titles(&search(query("sonnata"))), ["Moonlight Sonata"]);
+        assert_eq!(search(query("stars chill band")).total, 3);
+        assert_eq!(search(query("\"unbalanced")).total, 0);
+        let blue = search(SearchQuery {
+            limit: Some(1),
+            ..query("blue")
+        });
+        assert_eq!((titles(&blue), blue.total), (vec!["Blue Moon"], 1));
+
+        // filters
+        let jazz = search(SearchQuery {
+            genre: Some("JAZZ".to_string()),
+            ..SearchQuery::default()
+        });
+        assert_eq!(titles(&jazz), ["Sunrise", "Blue Moon"]);
+        assert_eq!(jazz.facets.genre.len(), 1);
+        let cheap = search(SearchQuery {
+            max_price: Some(1_000_000_000),
+            ..SearchQuery::default()
+        });
+        assert_eq!(titles(&cheap), ["Blue Moon"]);
+        let released = search(SearchQuery {
+            released_from: NaiveDate::from_ymd_opt(2021, 6, 1),
+            released_until: NaiveDate::from_ymd_opt(2022, 1, 1),
+            ..SearchQuery::default()
+        });
+        assert_eq!(titles(&released), ["Blue Moon"]);
+        let unpriced = search(SearchQuery {
+            priced: Some(false),
+            ..SearchQuery::default()
+        });
+        assert_eq!(titles(&unpriced), ["Moonlight Sonata"]);
+        let priced = search(SearchQuery {
+            priced: Some(true),
+            ..query("sunrise moon")
+        });
+        assert_eq!(priced.total, 2);
+
+        // changes replace the indexed version
+        let mut renamed = tracks[1].clone();
+        renamed.info.title = "Red Moon".to_string();
+        index.index(&renamed).expect("Failed to index");
+        index.remove(&tracks[2].id).expect("Failed to remove");
+        let all = search(SearchQuery::default());
+        assert_eq!(titles(&all), ["Red Moon", "Moonlight Sonata"]);
+
+        // rebuilt from the registered tracks of the database
+        let db = Database::memory();
+        let mut registered = crate::db::tests::track("owner", 1);
+        registered.info = Some(TrackInfo {
+            title: "Stored".to_string(),
+            ..TrackInfo::default()
+        });
+        let unregistered = crate::db::tests::track("owner", 2);
+        for track in [&registered, &unregistered] {
+            let inserted = db.tracks.insert_track(track).await;
+            assert!(inserted.expect("Failed to insert"));
+        }
+        index.rebuild(&db).await.expect("Failed to rebuild");
+        assert_eq!(titles(&search(SearchQuery::default())), ["Stored"]);
+    }
+}
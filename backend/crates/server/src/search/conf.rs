@@ -0,0 +1,22 @@
+//! Configuration for the search module.
+//!
+
+use serde::{Deserialize, Serialize};
+
+/// Search configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SearchConfig {
+    /// Memory budget of the index writer in bytes, at least 15 MB
+    pub writer_memory: usize,
+    /// Typos tolerated in a word of the query, up to 2
+    pub max_typos: u8,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            writer_memory: 15_000_000,
+            max_typos: 1,
+        }
+    }
+}
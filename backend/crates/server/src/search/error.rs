@@ -0,0 +1,25 @@
+//! # Error types for the search module
+//!
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+/// Error types for the search module
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Index error
+    #[error(transparent)]
+    Tantivy(#[from] tantivy::TantivyError),
+    /// Indexed track that cannot be read back
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Result type for the search module
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,88 @@
+//! # Application state
+//!
+
+use crate::auth::Authorizer;
+use crate::chain::Chain;
+use crate::conf::Config;
+use crate::crypto::Vault;
+use crate::db::Database;
+use crate::entitlement::Entitlements;
+use crate::hls::transcode::Ffmpeg;
+use crate::hls::Packager;
+use crate::playback::url::UrlSigner;
+use crate::profile::conf::ProfileConfig;
+use crate::search::SearchIndex;
+use crate::storage::Storage;
+use crate::upload::conf::UploadConfig;
+use crate::upload::session::Sessions;
+use axum::extract::FromRef;
+use std::sync::Arc;
+
+/// State shared by the routes, each field can be extracted with `State<T>`
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    /// Authorizer
+    pub authorizer: Authorizer,
+    /// Upload configuration
+    pub upload: Arc<UploadConfig>,
+    /// Resumable upload sessions
+    pub sessions: Arc<Sessions>,
+    /// Storage backend of the uploaded files
+    pub storage: Arc<dyn Storage>,
+    /// Encryption of the stored files
+    pub vault: Arc<Vault>,
+    /// Read access to the chain
+    pub chain: Arc<dyn Chain>,
+    /// Who may play what
+    pub entitlements: Arc<Entitlements>,
+    /// Signer of the playback URLs
+    pub signer: UrlSigner,
+    /// HLS packaging
+    pub hls: Arc<Packager>,
+    /// Users, tracks, NFTs, sessions and subscriptions
+    pub db: Database,
+    /// Profile configuration
+    pub profile: Arc<ProfileConfig>,
+    /// Full-text index of the catalog
+    pub search: Arc<SearchIndex>,
+}
+
+impl AppState {
+    /// Create the state from the configuration
+    pub fn new(config: Config) -> anyhow::Result<Self> {
+        let chain = crate::chain::from_config(&config.chain)?;
+        Self::with_chain(config, chain)
+    }
+
+    /// Create the state reading the chain through `chain`
+    pub fn with_chain(config: Config, chain: Arc<dyn Chain>) -> anyhow::Result<Self> {
+        let upload = Arc::new(config.upload);
+        let storage = crate::storage::from_config(&config.storage)?;
+        let vault = Arc::new(Vault::new(&config.crypto)?);
+        let db = Database::from_config(&config.database)?;
+        let transcoder = Arc::new(Ffmpeg::new(config.hls.ffmpeg.clone()));
+        let hls = Packager::new(config.hls, transcoder, storage.clone(), vault.clone());
+        Ok(Self {
+            authorizer: Authorizer::new(config.auth)?.with_database(db.clone()),
+            sessions: Arc::new(Sessions::new(upload.clone())),
+            upload,
+            storage,
+            vault,
+            entitlements: Arc::new(Entitlements::new(&config.entitlement, chain.clone(), &db)?),
+            chain,
+            signer: UrlSigner::new(&config.playback)?,
+            hls: Arc::new(hls),
+            db,
+            profile: Arc::new(config.profile),
+            search: Arc::new(SearchIndex::new(&config.search)?),
+        })
+    }
+
+    /// Replace the database, e.g. with one provisioned by the platform
+    pub fn with_database(mut self, db: Database) -> Self {
+        self.authorizer = self.authorizer.with_database(db.clone());
+        self.entitlements = Arc::new(self.entitlements.with_database(&db));
+        self.db = db;
+        self
+    }
+}
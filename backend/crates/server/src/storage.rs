@@ -0,0 +1,172 @@
+//! # Storage
+//!
+//! 上传完成的文件保存在可替换的存储后端中，由 [`conf::StorageConfig`] 选择：
+//! - `local`：本地目录，以 SHA-256 作为键。
+//! - `ipfs`：通过 IPFS HTTP API（Kubo RPC）添加并固定，以 CID 作为键。
+//! - `arweave`：签名为 ANS-104 数据项后提交给打包服务（bundler），以数据项 ID 作为键，数据永久保存，不能删除。
+//!
+
+use crate::storage::error::{Error, Result};
+use axum::body::Bytes;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
+
+pub mod arweave;
+pub mod conf;
+pub mod error;
+pub mod ipfs;
+pub mod local;
+
+/// Stream of the bytes of an object
+pub type ByteStream = BoxStream<'static, Result<Bytes>>;
+
+/// Bytes read at a time when streaming a file
+const READ_SIZE: u64 = 64 * 1024;
+
+/// Metadata of an object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    /// Key
+    pub key: String,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// Storage backend
+#[axum::async_trait]
+pub trait Storage: Send + Sync {
+    /// Store an object, returning the key to read it back
+    async fn put(&self, data: Bytes, content_type: &str) -> Result<String> {
+        let data = futures::stream::once(async { Ok(data) });
+        self.put_stream(Box::pin(data), content_type).await
+    }
+
+    /// Store an object streamed in chunks, without holding all of it in memory
+    async fn put_stream(&self, data: ByteStream, content_type: &str) -> Result<String>;
+
+    /// Read a whole object
+    async fn get(&self, key: &str) -> Result<Bytes> {
+        let size = self.stat(key).await?.size;
+        let chunks: Vec<Bytes> = self.get_range(key, 0..size).await?.try_collect().await?;
+        Ok(chunks.concat().into())
+    }
+
+    /// Delete an object
+    async fn delete(&self, key: &str) -> Result<()>;
+
+    /// Metadata of an object
+    async fn stat(&self, key: &str) -> Result<ObjectInfo>;
+
+    /// Stream the bytes of an object in `range`, which must lie within the object
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<ByteStream>;
+}
+
+/// Create the storage backend of a configuration
+pub fn from_config(config: &conf::StorageConfig) -> anyhow::Result<Arc<dyn Storage>> {
+    Ok(match config {
+        conf::StorageConfig::Local { dir } => Arc::new(local::LocalStorage::new(dir.clone())),
+        conf::StorageConfig::Ipfs { api_url } => Arc::new(ipfs::IpfsStorage::new(api_url.parse()?)),
+        conf::StorageConfig::Arweave {
+            bundler_url,
+            gateway_url,
+            keypair,
+        } => Arc::new(arweave::ArweaveStorage::new(
+            bundler_url.parse()?,
+            gateway_url.parse()?,
+            keypair,
+        )?),
+    })
+}
+
+/// Check that `range` lies within an object of `size` bytes
+fn check_range(range: &Range<u64>, size: u64) -> Result<()> {
+    if range.start > range.end || range.end > size {
+        return Err(Error::InvalidRange {
+            start: range.start,
+            end: range.end,
+            size,
+        });
+    }
+    Ok(())
+}
+
+/// Stream the body of an HTTP response
+fn response_stream(response: reqwest::Response) -> ByteStream {
+    Box::pin(futures::stream::try_unfold(
+        response,
+        |mut response| async move { Ok(response.chunk().await?.map(|chunk| (chunk, response))) },
+    ))
+}
+
+/// Stream the next `len` bytes of a file
+fn file_stream(file: tokio::fs::File, len: u64) -> ByteStream {
+    let stream = futures::stream::try_unfold((file, len), |(mut file, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+        let mut buf = vec![0; remaining.min(READ_SIZE) as usize];
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+        }
+        buf.truncate(n);
+        Ok(Some((Bytes::from(buf), (file, remaining - n as u64))))
+    });
+    Box::pin(stream)
+}
+
+/// Stream of nothing, for empty ranges
+fn empty_stream() -> ByteStream {
+    Box::pin(futures::stream::empty())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Behavior every backend must have
+    pub(crate) async fn check_storage(storage: &dyn Storage) {
+        let data: Bytes = (0..=255u8).cycle().take(200_000).collect::<Vec<_>>().into();
+        // streamed in uneven chunks
+        let chunks: Vec<Result<Bytes>> = [0..70_000, 70_000..70_001, 70_001..200_000]
+            .into_iter()
+            .map(|range| Ok(data.slice(range)))
+            .collect();
+        let key = storage
+            .put_stream(Box::pin(futures::stream::iter(chunks)), "audio/wav")
+            .await
+            .expect("Failed to put");
+        let info = storage.stat(&key).await.expect("Failed to stat");
+        assert_eq!(info.size, data.len() as u64);
+        assert_eq!(storage.get(&key).await.expect("Failed to get"), data);
+
+        let range: Vec<Bytes> = storage
+            .get_range(&key, 1000..150_000)
+            .await
+            .expect("Failed to get range")
+            .try_collect()
+            .await
+            .expect("Failed to read range");
+        assert_eq!(range.concat(), data[1000..150_000]);
+        let empty: Vec<Bytes> = storage
+            .get_range(&key, 5..5)
+            .await
+            .expect("Failed to get empty range")
+            .try_collect()
+            .await
+            .expect("Failed to read empty range");
+        assert!(empty.concat().is_empty());
+        assert!(matches!(
+            storage.get_range(&key, 10..300_000).await,
+            Err(Error::InvalidRange { .. })
+        ));
+
+        storage.delete(&key).await.expect("Failed to delete");
+        assert!(matches!(storage.stat(&key).await, Err(Error::NotFound(_))));
+        assert!(matches!(storage.get(&key).await, Err(Error::NotFound(_))));
+    }
+}
@@ -0,0 +1,537 @@
+//! # Arweave storage
+//!
+//! 文件被签名为 [ANS-104](https://github.com/ArweaveTeam/arweave-standards/blob/master/ans/ANS-104.md)
+//! 数据项后提交给打包服务（bundler），由打包服务合并成一笔 Arweave 交易上链，读取则通过网关。
+//! 数据项使用 Solana 密钥签名（签名类型 4），数据上链后永久保存，无法删除。
+//!
+//! 签名覆盖全部数据，而数据项的头部在数据之前，所以上传的流先写入临时文件并同时计算哈希，
+//! 签名后再从临时文件流式提交，内存中不保留整个文件。
+//!
+//! 按范围读取时，网关返回的 `Content-Range` 必须与请求的范围一致；网关忽略 `Range` 返回整个文件时，在本地跳到起始位置。
+//!
+
+use crate::storage::error::{Error, Result};
+use crate::storage::{
+    check_range, empty_stream, file_stream, response_stream, ByteStream, ObjectInfo, Storage,
+};
+use axum::body::Bytes;
+use axum_extra::headers::{ContentRange, HeaderMapExt};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use futures::{StreamExt, TryStreamExt};
+use rand::RngCore;
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE, RANGE};
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256, Sha384};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature};
+use solana_sdk::signer::keypair::keypair_from_seed;
+use solana_sdk::signer::Signer;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::path::Path;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// Signature type of Solana (Ed25519) keys
+const SOLANA_SIGNATURE_TYPE: u16 = 4;
+
+/// Objects stored on Arweave, keyed by data item ID
+pub struct ArweaveStorage {
+    client: reqwest::Client,
+    bundler_url: Url,
+    gateway_url: Url,
+    signer: Keypair,
+}
+
+impl ArweaveStorage {
+    /// Create a storage posting data items signed by the base58 `keypair` to `bundler_url`
+    pub fn new(bundler_url: Url, gateway_url: Url, keypair: &str) -> anyhow::Result<Self> {
+        let bytes = solana_sdk::bs58::decode(keypair)
+            .into_vec()
+            .map_err(|e| anyhow::anyhow!("Invalid Arweave keypair: {e}"))?;
+        // a keypair is the secret seed followed by the public key
+        anyhow::ensure!(bytes.len() == 64, "Invalid Arweave keypair length");
+        let signer = keypair_from_seed(&bytes[..32])
+            .map_err(|e| anyhow::anyhow!("Invalid Arweave keypair: {e}"))?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            bundler_url,
+            gateway_url,
+            signer,
+        })
+    }
+
+    fn url(&self, key: &str) -> Result<Url> {
+        // IDs are 32 bytes in base64url
+        let valid = URL_SAFE_NO_PAD.decode(key).is_ok_and(|id| id.len() == 32);
+        if !valid {
+            return Err(Error::NotFound(key.to_string()));
+        }
+        self.gateway_url
+            .join(key)
+            .map_err(|e| Error::Backend(e.to_string()))
+    }
+}
+
+/// Skip to `range` in a stream of a whole object
+fn slice_stream(stream: ByteStream, range: Range<u64>) -> ByteStream {
+    let stream = futures::stream::try_unfold((stream, 0u64), move |(mut stream, mut pos)| {
+        let range = range.clone();
+        async move {
+            while pos < range.end {
+                let chunk = stream
+                    .try_next()
+                    .await?
+                    .ok_or_else(|| Error::Backend("Object ended before the range".to_string()))?;
+                let chunk_start = pos;
+                pos += chunk.len() as u64;
+                let start = range
+                    .start
+                    .saturating_sub(chunk_start)
+                    .min(chunk.len() as u64);
+                let end = (range.end - chunk_start).min(chunk.len() as u64);
+                if start < end {
+                    let chunk = chunk.slice(start as usize..end as usize);
+                    return Ok(Some((chunk, (stream, pos))));
+                }
+            }
+            Ok(None)
+        }
+    });
+    Box::pin(stream)
+}
+
+impl ArweaveStorage {
+    /// Spool `data` to compute the signature, then post the data item streamed from the spool
+    async fn post(&self, spool: &Path, mut data: ByteStream, content_type: &str) -> Result<String> {
+        let mut file = tokio::fs::File::create_new(spool).await?;
+        let mut hasher = BlobHasher::default();
+        while let Some(chunk) = data.try_next().await? {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+        file.seek(SeekFrom::Start(0)).await?;
+
+        let tags = vec![("Content-Type".to_string(), content_type.to_string())];
+        let item = DataItem::sign(&self.signer, tags, hasher.finalize());
+        let header = Bytes::from(item.header());
+        let len = header.len() as u64 + item.data_len;
+        let body =
+            futures::stream::once(async { Ok(header) }).chain(file_stream(file, item.data_len));
+        let response = self
+            .client
+            .post(self.bundler_url.clone())
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .header(CONTENT_LENGTH, len)
+            .body(reqwest::Body::wrap_stream(body))
+            .send()
+            .await?;
+        check(response, "").await?;
+        Ok(item.id())
+    }
+}
+
+async fn check(response: reqwest::Response, key: &str) -> Result<reqwest::Response> {
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        StatusCode::NOT_FOUND => Err(Error::NotFound(key.to_string())),
+        status => Err(Error::Backend(format!(
+            "{status}: {}",
+            response.text().await.unwrap_or_default()
+        ))),
+    }
+}
+
+#[axum::async_trait]
+impl Storage for ArweaveStorage {
+    async fn put_stream(&self, data: ByteStream, content_type: &str) -> Result<String> {
+        let spool = std::env::temp_dir().join(format!("music3-arweave-{}.tmp", Uuid::new_v4()));
+        let result = self.post(&spool, data, content_type).await;
+        let _ = tokio::fs::remove_file(&spool).await;
+        result
+    }
+
+    async fn delete(&self, _key: &str) -> Result<()> {
+        Err(Error::Unsupported("data on Arweave is permanent"))
+    }
+
+    async fn stat(&self, key: &str) -> Result<ObjectInfo> {
+        let response = self.client.head(self.url(key)?).send().await?;
+        let response = check(response, key).await?;
+        let size = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse().ok())
+            .ok_or_else(|| Error::Backend("missing Content-Length".to_string()))?;
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size,
+        })
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<ByteStream> {
+        check_range(&range, self.stat(key).await?.size)?;
+        if range.is_empty() {
+            return Ok(empty_stream());
+        }
+        let response = self
+            .client
+            .get(self.url(key)?)
+            .header(RANGE, format!("bytes={}-{}", range.start, range.end - 1))
+            .send()
+            .await?;
+        let response = check(response, key).await?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            // the gateway ignored the range and sends the whole object
+            return Ok(slice_stream(response_stream(response), range));
+        }
+        let content_range = response.headers().typed_get::<ContentRange>();
+        let sent = content_range.as_ref().and_then(ContentRange::bytes_range);
+        if sent != Some((range.start, range.end - 1)) {
+            return Err(Error::Backend(format!(
+                "Gateway sent {content_range:?} for bytes {}-{}",
+                range.start,
+                range.end - 1
+            )));
+        }
+        Ok(response_stream(response))
+    }
+}
+
+/// ANS-104 data item signed by a Solana key, without its data
+#[derive(Debug, Clone)]
+pub struct DataItem {
+    signature: Signature,
+    owner: Pubkey,
+    anchor: [u8; 32],
+    tags: Vec<u8>,
+    data_len: u64,
+}
+
+impl DataItem {
+    /// Sign the data hashed by `data` with its tags as `(name, value)` pairs
+    pub fn sign(signer: &Keypair, tags: Vec<(String, String)>, data: BlobHash) -> Self {
+        // a random anchor keeps the ID unique when the same file is uploaded twice
+        let mut anchor = [0; 32];
+        rand::thread_rng().fill_bytes(&mut anchor);
+        let tags = encode_tags(&tags);
+        let owner = signer.pubkey();
+        let message = signing_message(&owner, &anchor, &tags, &data);
+        Self {
+            signature: signer.sign_message(&message),
+            owner,
+            anchor,
+            tags,
+            data_len: data.len,
+        }
+    }
+
+    /// ID of the data item, the base64url SHA-256 of the signature
+    pub fn id(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.signature))
+    }
+
+    /// Check the signature against the data hashed by `data`
+    pub fn verify(&self, data: &BlobHash) -> bool {
+        let message = signing_message(&self.owner, &self.anchor, &self.tags, data);
+        data.len == self.data_len && self.signature.verify(self.owner.as_ref(), &message)
+    }
+
+    /// Binary encoding of everything before the data
+    pub fn header(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.tags.len() + 180);
+        bytes.extend_from_slice(&SOLANA_SIGNATURE_TYPE.to_le_bytes());
+        bytes.extend_from_slice(self.signature.as_ref());
+        bytes.extend_from_slice(self.owner.as_ref());
+        // no target
+        bytes.push(0);
+        bytes.push(1);
+        bytes.extend_from_slice(&self.anchor);
+        bytes.extend_from_slice(&(tag_count(&self.tags)).to_le_bytes());
+        bytes.extend_from_slice(&(self.tags.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.tags);
+        bytes
+    }
+}
+
+/// Deep hash of a blob, with its length
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlobHash {
+    hash: [u8; 48],
+    len: u64,
+}
+
+/// Computes the deep hash of a blob fed in chunks
+#[derive(Default)]
+pub struct BlobHasher {
+    data: Sha384,
+    len: u64,
+}
+
+impl BlobHasher {
+    /// Hash the next chunk
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.data.update(chunk);
+        self.len += chunk.len() as u64;
+    }
+
+    /// Deep hash of everything hashed so far
+    pub fn finalize(self) -> BlobHash {
+        // the tag needs the length, so it is hashed last and prepended
+        let tag = Sha384::digest(format!("blob{}", self.len));
+        let hash = Sha384::new()
+            .chain_update(tag)
+            .chain_update(self.data.finalize())
+            .finalize()
+            .into();
+        BlobHash {
+            hash,
+            len: self.len,
+        }
+    }
+}
+
+/// Deep hash of a whole blob
+fn blob_hash(blob: &[u8]) -> BlobHash {
+    let mut hasher = BlobHasher::default();
+    hasher.update(blob);
+    hasher.finalize()
+}
+
+fn signing_message(owner: &Pubkey, anchor: &[u8; 32], tags: &[u8], data: &BlobHash) -> [u8; 48] {
+    let signature_type = SOLANA_SIGNATURE_TYPE.to_string();
+    let fields = [
+        b"dataitem".as_slice(),
+        b"1",
+        signature_type.as_bytes(),
+        owner.as_ref(),
+        // no target
+        b"",
+        anchor,
+        tags,
+    ];
+    let mut blobs: Vec<BlobHash> = fields.iter().map(|field| blob_hash(field)).collect();
+    blobs.push(*data);
+    deep_hash(&blobs)
+}
+
+/// Arweave deep hash of a list of hashed blobs
+fn deep_hash(blobs: &[BlobHash]) -> [u8; 48] {
+    let list_tag: [u8; 48] = Sha384::digest(format!("list{}", blobs.len())).into();
+    blobs.iter().fold(list_tag, |acc, blob| {
+        Sha384::new()
+            .chain_update(acc)
+            .chain_update(blob.hash)
+            .finalize()
+            .into()
+    })
+}
+
+/// Avro encoding of the tags as an array of `{ name: bytes, value: bytes }` records
+fn encode_tags(tags: &[(String, String)]) -> Vec<u8> {
+    fn long(buf: &mut Vec<u8>, n: i64) {
+        let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+        while zigzag >= 0x80 {
+            buf.push((zigzag as u8 & 0x7F) | 0x80);
+            zigzag >>= 7;
+        }
+        buf.push(zigzag as u8);
+    }
+
+    let mut buf = Vec::new();
+    if tags.is_empty() {
+        return buf;
+    }
+    long(&mut buf, tags.len() as i64);
+    for (name, value) in tags {
+        for field in [name, value] {
+            long(&mut buf, field.len() as i64);
+            buf.extend_from_slice(field.as_bytes());
+        }
+    }
+    long(&mut buf, 0);
+    buf
+}
+
+/// Number of tags of Avro-encoded tags, which start with the count of the only block
+fn tag_count(tags: &[u8]) -> u64 {
+    let mut zigzag = 0u64;
+    for (i, byte) in tags.iter().take(10).enumerate() {
+        zigzag |= u64::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return zigzag >> 1;
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path as AxumPath, State};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::ops::Bound;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn data_item() {
+        let signer = Keypair::new();
+        let tags = vec![("Content-Type".to_string(), "audio/flac".to_string())];
+        let item = DataItem::sign(&signer, tags, blob_hash(b"music"));
+        assert!(item.verify(&blob_hash(b"music")));
+        assert_eq!(item.id().len(), 43);
+
+        let bytes = item.header();
+        assert_eq!(bytes[..2], [4, 0]);
+        assert_eq!(bytes[66..98], signer.pubkey().to_bytes());
+        // target absent, anchor present
+        assert_eq!(bytes[98..100], [0, 1]);
+        assert_eq!(bytes[132..140], 1u64.to_le_bytes());
+        let tag_bytes = u64::from_le_bytes(bytes[140..148].try_into().expect("8 bytes"));
+        assert_eq!(bytes[148..], *b"\x02\x18Content-Type\x14audio/flac\x00");
+        assert_eq!(bytes.len(), 148 + tag_bytes as usize);
+
+        assert!(!item.verify(&blob_hash(b"noise")));
+        // hashing in chunks gives the same deep hash
+        let mut hasher = BlobHasher::default();
+        hasher.update(b"mu");
+        hasher.update(b"sic");
+        assert_eq!(hasher.finalize(), blob_hash(b"music"));
+    }
+
+    type Items = Arc<Mutex<HashMap<String, Bytes>>>;
+
+    /// Check and keep a posted data item like a bundler
+    async fn bundle(State(items): State<Items>, body: Bytes) -> Response {
+        let tags_len = u64::from_le_bytes(body[140..148].try_into().expect("8 bytes")) as usize;
+        let data = body.slice(148 + tags_len..);
+        let item = DataItem {
+            signature: Signature::try_from(&body[2..66]).expect("64 bytes"),
+            owner: Pubkey::try_from(&body[66..98]).expect("32 bytes"),
+            anchor: body[100..132].try_into().expect("32 bytes"),
+            tags: body[148..148 + tags_len].to_vec(),
+            data_len: data.len() as u64,
+        };
+        if !item.verify(&blob_hash(&data)) {
+            return (StatusCode::BAD_REQUEST, "invalid signature").into_response();
+        }
+        items.lock().expect("Poisoned").insert(item.id(), data);
+        Json(json!({ "id": item.id() })).into_response()
+    }
+
+    /// Serve whole data items, ignoring ranges
+    async fn gateway(State(items): State<Items>, AxumPath(id): AxumPath<String>) -> Response {
+        match items.lock().expect("Poisoned").get(&id) {
+            Some(data) => data.clone().into_response(),
+            None => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    /// Serve the requested range, shifted by `shift` bytes to mimic a broken gateway
+    async fn ranged(
+        items: Items,
+        id: String,
+        headers: axum::http::HeaderMap,
+        shift: u64,
+    ) -> Response {
+        let Some(data) = items.lock().expect("Poisoned").get(&id).cloned() else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let Some(range) = headers.typed_get::<axum_extra::headers::Range>() else {
+            return data.into_response();
+        };
+        let size = data.len() as u64;
+        let Some((Bound::Included(start), Bound::Included(end))) =
+            range.satisfiable_ranges(size).next()
+        else {
+            return StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+        };
+        let (start, end) = (start + shift, (end + shift).min(size - 1));
+        let mut response = data.slice(start as usize..=end as usize).into_response();
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+        let content_range = ContentRange::bytes(start..=end, size).expect("Invalid range");
+        response.headers_mut().typed_insert(content_range);
+        response
+    }
+
+    /// In-process bundler and gateways sharing their data items
+    async fn mock_arweave() -> Url {
+        let app = Router::new()
+            .route("/tx", post(bundle))
+            .route("/:id", get(gateway))
+            .route(
+                "/ranged/:id",
+                get(|State(items), AxumPath(id), headers| ranged(items, id, headers, 0)),
+            )
+            .route(
+                "/shifted/:id",
+                get(|State(items), AxumPath(id), headers| ranged(items, id, headers, 1)),
+            )
+            .with_state(Items::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let addr = listener.local_addr().expect("No local address");
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Url::parse(&format!("http://{addr}")).expect("Invalid URL")
+    }
+
+    #[tokio::test]
+    async fn arweave_storage() {
+        let url = mock_arweave().await;
+        let keypair = Keypair::new().to_base58_string();
+        let storage = |gateway| {
+            let bundler_url = url.join("/tx").expect("Invalid URL");
+            let gateway_url = url.join(gateway).expect("Invalid URL");
+            ArweaveStorage::new(bundler_url, gateway_url, &keypair).expect("Invalid keypair")
+        };
+        let data: Bytes = (0..=255u8).cycle().take(200_000).collect::<Vec<_>>().into();
+        let chunks: Vec<Result<Bytes>> = vec![Ok(data.slice(..1000)), Ok(data.slice(1000..))];
+        let key = storage("/")
+            .put_stream(Box::pin(futures::stream::iter(chunks)), "audio/wav")
+            .await
+            .expect("Failed to put");
+
+        // with or without range support at the gateway
+        for gateway in ["/", "/ranged/"] {
+            let storage = storage(gateway);
+            assert_eq!(
+                storage.stat(&key).await.expect("Failed to stat").size,
+                200_000
+            );
+            assert_eq!(storage.get(&key).await.expect("Failed to get"), data);
+            let range: Vec<Bytes> = storage
+                .get_range(&key, 70_000..150_000)
+                .await
+                .expect("Failed to get range")
+                .try_collect()
+                .await
+                .expect("Failed to read range");
+            assert_eq!(range.concat(), data[70_000..150_000]);
+            assert!(matches!(
+                storage.delete(&key).await,
+                Err(Error::Unsupported(_))
+            ));
+        }
+        // a range other than the one requested
+        assert!(matches!(
+            storage("/shifted/").get_range(&key, 10..20).await,
+            Err(Error::Backend(_))
+        ));
+    }
+
+    #[test]
+    fn avro_long() {
+        let tags: Vec<_> = (0..70).map(|i| (i.to_string(), String::new())).collect();
+        // 70 zigzags to 140, two bytes
+        assert_eq!(encode_tags(&tags)[..2], [0x8C, 0x01]);
+        assert_eq!(tag_count(&encode_tags(&tags)), 70);
+        assert!(encode_tags(&[]).is_empty());
+    }
+}
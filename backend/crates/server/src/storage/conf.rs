@@ -0,0 +1,38 @@
+//! Configuration for the storage module.
+//!
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Storage backend configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// Local directory
+    Local {
+        /// Directory of the objects, created if missing
+        dir: PathBuf,
+    },
+    /// IPFS node
+    Ipfs {
+        /// URL of the HTTP API, e.g. `http://127.0.0.1:5001`
+        api_url: String,
+    },
+    /// Arweave through a bundler
+    Arweave {
+        /// URL accepting signed data items, e.g. `https://upload.ardrive.io/v1/tx`
+        bundler_url: String,
+        /// URL of the gateway, e.g. `https://arweave.net`
+        gateway_url: String,
+        /// Solana keypair in base58 signing the data items
+        keypair: String,
+    },
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self::Local {
+            dir: PathBuf::from("storage"),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! # Error types for the storage module
+//!
+
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+
+/// Error types for the storage module
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Object not found
+    #[error("Object not found: {0}")]
+    NotFound(String),
+    /// Range outside of the object
+    #[error("Invalid range {start}..{end} of an object of {size} bytes")]
+    InvalidRange {
+        /// Start of the range
+        start: u64,
+        /// End of the range, exclusive
+        end: u64,
+        /// Size of the object
+        size: u64,
+    },
+    /// Operation the backend cannot do
+    #[error("Unsupported operation: {0}")]
+    Unsupported(&'static str),
+    /// IO error
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// HTTP error
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// Error returned by the backend
+    #[error("Storage backend error: {0}")]
+    Backend(String),
+}
+
+/// Result type for the storage module
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = match self {
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::InvalidRange { .. } => StatusCode::RANGE_NOT_SATISFIABLE,
+            Error::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            Error::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::Http(_) | Error::Backend(_) => StatusCode::BAD_GATEWAY,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
@@ -0,0 +1,217 @@
+//! # IPFS storage
+//!
+//! 通过 Kubo RPC（`/api/v0`）添加并固定文件，删除只是取消固定，数据在节点垃圾回收后才会消失。
+//!
+
+use crate::storage::error::{Error, Result};
+use crate::storage::{check_range, empty_stream, response_stream, ByteStream, ObjectInfo, Storage};
+use reqwest::multipart::{Form, Part};
+use reqwest::Url;
+use serde::Deserialize;
+use std::ops::Range;
+
+/// Objects added to an IPFS node, keyed by CID
+pub struct IpfsStorage {
+    client: reqwest::Client,
+    api_url: Url,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AddResponse {
+    hash: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StatResponse {
+    size: u64,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ErrorResponse {
+    message: String,
+}
+
+impl IpfsStorage {
+    /// Create a storage using the HTTP API at `api_url`
+    pub fn new(api_url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_url,
+        }
+    }
+
+    /// Call an RPC command, every command is a POST
+    async fn call(
+        &self,
+        command: &str,
+        key: &str,
+        query: &[(&str, &str)],
+        form: Option<Form>,
+    ) -> Result<reqwest::Response> {
+        let url = self
+            .api_url
+            .join(&format!("/api/v0/{command}"))
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        let mut request = self.client.post(url).query(query);
+        if let Some(form) = form {
+            request = request.multipart(form);
+        }
+        let response = request.send().await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let message = match response.json::<ErrorResponse>().await {
+            Ok(error) => error.message,
+            Err(e) => e.to_string(),
+        };
+        if message.contains("not found") || message.contains("not pinned") {
+            Err(Error::NotFound(key.to_string()))
+        } else {
+            Err(Error::Backend(message))
+        }
+    }
+}
+
+#[axum::async_trait]
+impl Storage for IpfsStorage {
+    async fn put_stream(&self, data: ByteStream, content_type: &str) -> Result<String> {
+        let part = Part::stream(reqwest::Body::wrap_stream(data)).mime_str(content_type)?;
+        let form = Form::new().part("file", part);
+        let query = [("pin", "true"), ("cid-version", "1")];
+        let response = self.call("add", "", &query, Some(form)).await?;
+        Ok(response.json::<AddResponse>().await?.hash)
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        self.call("pin/rm", key, &[("arg", key)], None).await?;
+        Ok(())
+    }
+
+    async fn stat(&self, key: &str) -> Result<ObjectInfo> {
+        let path = format!("/ipfs/{key}");
+        let response = self
+            .call("files/stat", key, &[("arg", &path)], None)
+            .await?;
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size: response.json::<StatResponse>().await?.size,
+        })
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<ByteStream> {
+        check_range(&range, self.stat(key).await?.size)?;
+        if range.is_empty() {
+            return Ok(empty_stream());
+        }
+        let offset = range.start.to_string();
+        let length = (range.end - range.start).to_string();
+        let query = [("arg", key), ("offset", &offset), ("length", &length)];
+        Ok(response_stream(self.call("cat", key, &query, None).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Bytes;
+    use axum::extract::{Multipart, Query, State};
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    type Blocks = Arc<Mutex<HashMap<String, Bytes>>>;
+
+    #[derive(Deserialize)]
+    struct Args {
+        arg: String,
+        offset: Option<usize>,
+        length: Option<usize>,
+    }
+
+    fn error(message: &str) -> Response {
+        let body = json!({ "Message": message, "Code": 0, "Type": "error" });
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+    }
+
+    async fn add(State(blocks): State<Blocks>, mut multipart: Multipart) -> Response {
+        let Ok(Some(field)) = multipart.next_field().await else {
+            return error("file argument 'path' is required");
+        };
+        let Ok(data) = field.bytes().await else {
+            return error("failed to read file");
+        };
+        let cid = format!("bafk{:x}", Sha256::digest(&data));
+        let size = data.len().to_string();
+        blocks.lock().expect("Poisoned").insert(cid.clone(), data);
+        Json(json!({ "Name": cid, "Hash": cid, "Size": size })).into_response()
+    }
+
+    async fn cat(State(blocks): State<Blocks>, Query(args): Query<Args>) -> Response {
+        let blocks = blocks.lock().expect("Poisoned");
+        let Some(data) = blocks.get(&args.arg) else {
+            return error("block was not found locally (offline)");
+        };
+        let start = args.offset.unwrap_or(0).min(data.len());
+        let end = args
+            .length
+            .map_or(data.len(), |length| (start + length).min(data.len()));
+        data.slice(start..end).into_response()
+    }
+
+    async fn stat(State(blocks): State<Blocks>, Query(args): Query<Args>) -> Response {
+        let cid = args.arg.trim_start_matches("/ipfs/");
+        match blocks.lock().expect("Poisoned").get(cid) {
+            Some(data) => Json(json!({
+                "Hash": cid,
+                "Size": data.len(),
+                "CumulativeSize": data.len() + 11,
+                "Blocks": 1,
+                "Type": "file",
+            }))
+            .into_response(),
+            None => error("merkledag: not found"),
+        }
+    }
+
+    async fn unpin(State(blocks): State<Blocks>, Query(args): Query<Args>) -> Response {
+        // the mock collects garbage right away
+        match blocks.lock().expect("Poisoned").remove(&args.arg) {
+            Some(_) => Json(json!({ "Pins": [args.arg] })).into_response(),
+            None => error("not pinned or pinned indirectly"),
+        }
+    }
+
+    /// In-process IPFS node with the commands used by [`IpfsStorage`]
+    async fn mock_ipfs() -> Url {
+        let app = Router::new()
+            .route("/api/v0/add", post(add))
+            .route("/api/v0/cat", post(cat))
+            .route("/api/v0/files/stat", post(stat))
+            .route("/api/v0/pin/rm", post(unpin))
+            .with_state(Blocks::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind");
+        let addr = listener.local_addr().expect("No local address");
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Url::parse(&format!("http://{addr}")).expect("Invalid URL")
+    }
+
+    #[tokio::test]
+    async fn ipfs_storage() {
+        let storage = IpfsStorage::new(mock_ipfs().await);
+        crate::storage::tests::check_storage(&storage).await;
+        assert!(matches!(
+            storage.delete("bafkmissing").await,
+            Err(Error::NotFound(_))
+        ));
+    }
+}
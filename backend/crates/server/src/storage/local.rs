@@ -0,0 +1,119 @@
+//! # Local storage
+//!
+
+use crate::storage::error::{Error, Result};
+use crate::storage::{check_range, file_stream, ByteStream, ObjectInfo, Storage};
+use axum::body::Bytes;
+use futures::TryStreamExt;
+use sha2::{Digest, Sha256};
+use std::io::{ErrorKind, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+
+/// Objects in a local directory, keyed by the SHA-256 of their content
+pub struct LocalStorage {
+    dir: PathBuf,
+}
+
+impl LocalStorage {
+    /// Create a storage in `dir`
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        // the key becomes a file name, so only accept what `put` generates
+        if key.len() != 64 || !key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::NotFound(key.to_string()));
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+fn not_found(key: &str) -> impl FnOnce(std::io::Error) -> Error + '_ {
+    move |e| match e.kind() {
+        ErrorKind::NotFound => Error::NotFound(key.to_string()),
+        _ => e.into(),
+    }
+}
+
+#[axum::async_trait]
+impl Storage for LocalStorage {
+    async fn put_stream(&self, data: ByteStream, _content_type: &str) -> Result<String> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        // write then rename, so readers never see a partial object
+        let tmp = self.dir.join(format!("{}.tmp", Uuid::new_v4()));
+        let result = async {
+            let key = write_hashed(&tmp, data).await?;
+            tokio::fs::rename(&tmp, self.path(&key)?).await?;
+            Ok(key)
+        }
+        .await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&tmp).await;
+        }
+        result
+    }
+
+    async fn get(&self, key: &str) -> Result<Bytes> {
+        let data = tokio::fs::read(self.path(key)?)
+            .await
+            .map_err(not_found(key))?;
+        Ok(data.into())
+    }
+
+    async fn delete(&self, key: &str) -> Result<()> {
+        tokio::fs::remove_file(self.path(key)?)
+            .await
+            .map_err(not_found(key))
+    }
+
+    async fn stat(&self, key: &str) -> Result<ObjectInfo> {
+        let metadata = tokio::fs::metadata(self.path(key)?)
+            .await
+            .map_err(not_found(key))?;
+        Ok(ObjectInfo {
+            key: key.to_string(),
+            size: metadata.len(),
+        })
+    }
+
+    async fn get_range(&self, key: &str, range: Range<u64>) -> Result<ByteStream> {
+        let mut file = tokio::fs::File::open(self.path(key)?)
+            .await
+            .map_err(not_found(key))?;
+        check_range(&range, file.metadata().await?.len())?;
+        file.seek(SeekFrom::Start(range.start)).await?;
+        Ok(file_stream(file, range.end - range.start))
+    }
+}
+
+/// Write a stream to `path`, returning the SHA-256 of what was written
+async fn write_hashed(path: &Path, mut data: ByteStream) -> Result<String> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = data.try_next().await? {
+        hasher.update(&chunk);
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn local_storage() {
+        let dir = crate::testing::TempDir::new("storage");
+        let storage = LocalStorage::new(dir.to_path_buf());
+        crate::storage::tests::check_storage(&storage).await;
+        assert!(matches!(
+            storage.stat("../../etc/passwd").await,
+            Err(Error::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,137 @@
+//! # Test fixtures
+//!
+//! 测试共用的配置和测试服务：上传、存储和 HLS 的数据都放在同一个临时目录中，测试结束时删除；
+//! 主密钥和播放地址密钥使用 `music3-test-support` 中的测试值。
+//!
+
+use crate::chain::memory::MemoryChain;
+use crate::conf::Config;
+use crate::crypto::conf::CryptoConfig;
+use crate::db::Track;
+use crate::hls::conf::HlsConfig;
+use crate::playback::conf::PlaybackConfig;
+use crate::route::routes;
+use crate::state::AppState;
+use crate::storage::conf::StorageConfig;
+use crate::upload::conf::UploadConfig;
+use axum_test::TestServer;
+use music3_common::param::auth::Scope;
+pub(crate) use music3_test_support::TempDir;
+use music3_test_support::{MASTER_KEY, PLAYBACK_SECRET};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Configuration keeping every file in `dir`, with test secrets and small encryption chunks
+pub(crate) fn config(dir: &Path) -> Config {
+    Config {
+        upload: UploadConfig {
+            dir: dir.join("uploads"),
+            ..UploadConfig::default()
+        },
+        storage: StorageConfig::Local {
+            dir: dir.join("storage"),
+        },
+        crypto: CryptoConfig {
+            master_key: Some(MASTER_KEY.to_string()),
+            chunk_size: 1000,
+        },
+        playback: PlaybackConfig {
+            secret: Some(PLAYBACK_SECRET.to_string()),
+            ..PlaybackConfig::default()
+        },
+        hls: HlsConfig {
+            work_dir: dir.join("hls"),
+            ..HlsConfig::default()
+        },
+        ..Config::default()
+    }
+}
+
+/// Server of the whole API on an in-memory chain and database
+pub(crate) struct TestApp {
+    pub(crate) server: TestServer,
+    pub(crate) dir: TempDir,
+    pub(crate) state: AppState,
+    pub(crate) chain: Arc<MemoryChain>,
+    /// Holds the `admin` scope, which includes the creator rights
+    pub(crate) admin: Keypair,
+    /// Vote account of the stakes granting playback
+    pub(crate) vote_account: Pubkey,
+}
+
+pub(crate) async fn app() -> TestApp {
+    app_with(|_| {}).await
+}
+
+/// Test app with the [`config`] changed by `configure`
+pub(crate) async fn app_with(configure: impl FnOnce(&mut Config)) -> TestApp {
+    let dir = TempDir::new("test");
+    let (admin, vote_account) = (Keypair::new(), Pubkey::new_unique());
+    let mut config = config(&dir);
+    config
+        .auth
+        .policy
+        .grants
+        .insert(admin.pubkey().to_string(), vec![Scope::Admin]);
+    config.entitlement.stake_vote_account = Some(vote_account.to_string());
+    configure(&mut config);
+    let chain = Arc::new(MemoryChain::default());
+    let state = AppState::with_chain(config, chain.clone()).expect("Invalid config");
+    TestApp {
+        server: TestServer::new(routes(state.clone())).expect("Failed to create test server"),
+        dir,
+        state,
+        chain,
+        admin,
+        vote_account,
+    }
+}
+
+impl TestApp {
+    /// Store an encrypted track unlocked by `mint` or `collection`
+    pub(crate) async fn track(
+        &self,
+        plaintext: &[u8],
+        mint: Option<&Pubkey>,
+        collection: Option<&Pubkey>,
+    ) -> String {
+        let (ciphertext, wrapped_key) = self
+            .state
+            .vault
+            .encrypt(plaintext)
+            .expect("Failed to encrypt");
+        let key = self
+            .state
+            .storage
+            .put(ciphertext.into(), "application/octet-stream")
+            .await
+            .expect("Failed to store");
+        let track = Track {
+            cid: key,
+            wrapped_key,
+            mint: mint.map(Pubkey::to_string),
+            collection: collection.map(Pubkey::to_string),
+            ..crate::db::tests::track(&Pubkey::new_unique().to_string(), 0)
+        };
+        self.state
+            .db
+            .tracks
+            .insert_track(&track)
+            .await
+            .expect("Failed to insert");
+        track.id
+    }
+
+    /// JWT of a wallet signing in
+    pub(crate) async fn jwt(&self, keypair: &Keypair) -> String {
+        let authorizer = &self.state.authorizer;
+        authorizer
+            .authorize(&crate::auth::tests::sign_challenge(authorizer, keypair).await)
+            .await
+            .expect("Failed to authorize")
+            .jwt
+    }
+}
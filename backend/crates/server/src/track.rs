@@ -0,0 +1,477 @@
+//! # 音频目录
+//!
+//! 上传完成的文件需要由创作者登记为音频，填写标题、艺术家、流派、封面、歌词和发行日期：
+//! - `POST /tracks`：登记上传的文件，只有上传者可以登记。
+//! - `GET /tracks`：分页列出已登记的音频，可按创作者、艺术家和流派筛选，最新的在前。
+//! - `GET /tracks/{id}`：读取一首音频。
+//! - `PUT /tracks/{id}`：修改目录信息，只有创作者可以修改。
+//! - `DELETE /tracks/{id}`：从目录中移除，上传的文件保留，可以重新登记。
+//! - `PUT /tracks/{id}/nft`：关联为音频铸造的 NFT，持有者（或其已验证集合的成员持有者）可以播放（见 [`crate::entitlement`]），
+//!   NFT 也会出现在创作者的公开主页上。只有创作者可以关联，且必须是 NFT 链上元数据中已验证的创作者，
+//!   指定集合时集合也需在链上元数据中已验证。
+//!
+//! 请求和响应的结构见 [`music3_common::param::track`]，目录的变化同步到搜索索引（见 [`crate::search`]）。
+//!
+
+use crate::auth::scope::{Creator, RequireScope};
+use crate::chain::Chain;
+use crate::db::{Database, Nft, Track, TrackFilter};
+use crate::error::{Error, Result};
+use crate::search::SearchIndex;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::Json;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::profile::NftResponse;
+use music3_common::param::track::{
+    CreateTrackRequest, ListTracksQuery, TrackInfo, TrackNftRequest, TrackPage, TrackResponse,
+    DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// 登记音频
+pub async fn create(
+    claim: RequireScope<Creator>,
+    State(db): State<Database>,
+    State(search): State<Arc<SearchIndex>>,
+    Json(request): Json<CreateTrackRequest>,
+) -> Result<(StatusCode, Json<TrackResponse>)> {
+    request.info.validate().map_err(Error::InvalidTrack)?;
+    let mut track = owned(&db, &request.upload_id, &claim.sub).await?;
+    if track.info.is_some() {
+        return Err(Error::TrackAlreadyRegistered);
+    }
+    track.info = Some(request.info);
+    track.updated_at = get_current_timestamp();
+    save(&db, &track).await?;
+    let track = response(track)?;
+    search.index(&track)?;
+    Ok((StatusCode::CREATED, Json(track)))
+}
+
+/// 列出音频
+pub async fn list(
+    State(db): State<Database>,
+    Query(query): Query<ListTracksQuery>,
+) -> Result<Json<TrackPage>> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let filter = TrackFilter {
+        owner: query.owner,
+        artist: query.artist,
+        genre: query.genre,
+    };
+    let (tracks, total) = db.tracks.list_tracks(&filter, query.offset, limit).await?;
+    Ok(Json(TrackPage {
+        tracks: tracks.into_iter().map(response).collect::<Result<_>>()?,
+        total,
+        offset: query.offset,
+        limit,
+    }))
+}
+
+/// 读取音频
+pub async fn get(
+    State(db): State<Database>,
+    Path(id): Path<String>,
+) -> Result<Json<TrackResponse>> {
+    let track = db
+        .tracks
+        .get_track(&id)
+        .await?
+        .ok_or(Error::TrackNotFound)?;
+    Ok(Json(response(track)?))
+}
+
+/// 修改音频
+pub async fn update(
+    claim: RequireScope<Creator>,
+    State(db): State<Database>,
+    State(search): State<Arc<SearchIndex>>,
+    Path(id): Path<String>,
+    Json(info): Json<TrackInfo>,
+) -> Result<Json<TrackResponse>> {
+    info.validate().map_err(Error::InvalidTrack)?;
+    let mut track = owned(&db, &id, &claim.sub).await?;
+    if track.info.is_none() {
+        return Err(Error::TrackNotFound);
+    }
+    track.info = Some(info);
+    track.updated_at = get_current_timestamp();
+    save(&db, &track).await?;
+    let track = response(track)?;
+    search.index(&track)?;
+    Ok(Json(track))
+}
+
+/// 移除音频
+pub async fn delete(
+    claim: RequireScope<Creator>,
+    State(db): State<Database>,
+    State(search): State<Arc<SearchIndex>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode> {
+    let mut track = owned(&db, &id, &claim.sub).await?;
+    if track.info.take().is_none() {
+        return Err(Error::TrackNotFound);
+    }
+    track.updated_at = get_current_timestamp();
+    save(&db, &track).await?;
+    search.remove(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// 关联 NFT
+pub async fn nft(
+    claim: RequireScope<Creator>,
+    State(db): State<Database>,
+    State(chain): State<Arc<dyn Chain>>,
+    Path(id): Path<String>,
+    Json(request): Json<TrackNftRequest>,
+) -> Result<Json<NftResponse>> {
+    let mint = parse_pubkey(&request.mint)?;
+    let mut track = owned(&db, &id, &claim.sub).await?;
+    if !chain
+        .creators(&mint)
+        .await?
+        .contains(&parse_pubkey(&claim.sub)?)
+    {
+        return Err(Error::NotNftCreator);
+    }
+    if let Some(collection) = &request.collection {
+        if chain.collection(&mint).await? != Some(parse_pubkey(collection)?) {
+            return Err(Error::NftNotInCollection);
+        }
+    }
+    let now = get_current_timestamp();
+    let mut nft = Nft {
+        mint: mint.to_string(),
+        track_id: id,
+        owner: claim.sub.clone(),
+        metadata_uri: request.metadata_uri,
+        minted_at: now,
+    };
+    if !db.nfts.insert_nft(&nft).await? {
+        // linking again keeps the first record
+        let known = db.nfts.get_nft(&nft.mint).await?;
+        nft = known
+            .filter(|known| known.track_id == nft.track_id)
+            .ok_or(Error::NftAlreadyLinked)?;
+    }
+    track.mint = Some(nft.mint.clone());
+    track.collection = request.collection;
+    track.updated_at = now;
+    save(&db, &track).await?;
+    Ok(Json(crate::profile::nft(nft)))
+}
+
+fn parse_pubkey(s: &str) -> Result<Pubkey> {
+    Pubkey::from_str(s).map_err(|_| Error::InvalidPubkey(s.to_string()))
+}
+
+/// Track of an upload, which `owner` must have uploaded
+async fn owned(db: &Database, id: &str, owner: &str) -> Result<Track> {
+    let track = db.tracks.get_track(id).await?.ok_or(Error::TrackNotFound)?;
+    if track.owner != owner {
+        return Err(Error::NotTrackOwner);
+    }
+    Ok(track)
+}
+
+async fn save(db: &Database, track: &Track) -> Result<()> {
+    if !db.tracks.update_track(track).await? {
+        // deleted by a concurrent request
+        return Err(Error::TrackNotFound);
+    }
+    Ok(())
+}
+
+/// Response of a registered track, uploads not registered yet are not found
+pub(crate) fn response(track: Track) -> Result<TrackResponse> {
+    let info = track.info.ok_or(Error::TrackNotFound)?;
+    Ok(TrackResponse {
+        id: track.id,
+        owner: track.owner,
+        info,
+        audio: track.metadata,
+        created_at: track.created_at,
+        updated_at: track.updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::app;
+    use chrono::NaiveDate;
+    use music3_common::param::search::SearchResults;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+
+    fn info(title: &str, genre: &str) -> TrackInfo {
+        TrackInfo {
+            title: title.to_string(),
+            artist: Some("Artist".to_string()),
+            genre: Some(genre.to_string()),
+            cover_url: Some("ipfs://cover".to_string()),
+            lyrics: Some("La la la".to_string()),
+            release_date: NaiveDate::from_ymd_opt(2024, 9, 1),
+            tags: vec!["live".to_string()],
+            price: Some(1_000_000_000),
+        }
+    }
+
+    #[tokio::test]
+    async fn catalog() {
+        let app = app().await;
+        let creator = app.jwt(&app.admin).await;
+        // a listener, who may not create tracks
+        let other = app.jwt(&Keypair::new()).await;
+        let mut uploads = Vec::new();
+        for created_at in 1..=4 {
+            let track = crate::db::tests::track(&app.admin.pubkey().to_string(), created_at);
+            let inserted = app.state.db.tracks.insert_track(&track).await;
+            assert!(inserted.expect("Failed to insert"));
+            uploads.push(track.id);
+        }
+        let foreign = crate::db::tests::track(&Keypair::new().pubkey().to_string(), 5);
+        let inserted = app.state.db.tracks.insert_track(&foreign).await;
+        assert!(inserted.expect("Failed to insert"));
+
+        let create = |upload_id: &str, info: TrackInfo| CreateTrackRequest {
+            upload_id: upload_id.to_string(),
+            info,
+        };
+        let response = app
+            .server
+            .post("/tracks")
+            .json(&create(&uploads[0], info("One", "Jazz")))
+            .await;
+        assert_eq!(response.status_code(), 401);
+        let response = app
+            .server
+            .post("/tracks")
+            .authorization_bearer(&other)
+            .json(&create(&uploads[0], info("One", "Jazz")))
+            .await;
+        assert_eq!(response.status_code(), 403);
+        let response = app
+            .server
+            .post("/tracks")
+            .authorization_bearer(&creator)
+            .json(&create(&uploads[0], info(" ", "Jazz")))
+            .await;
+        assert_eq!(response.status_code(), 400);
+        let response = app
+            .server
+            .post("/tracks")
+            .authorization_bearer(&creator)
+            .json(&create("missing", info("One", "Jazz")))
+            .await;
+        assert_eq!(response.status_code(), 404);
+        // only the uploader may register
+        let response = app
+            .server
+            .post("/tracks")
+            .authorization_bearer(&creator)
+            .json(&create(&foreign.id, info("Foreign", "Jazz")))
+            .await;
+        assert_eq!(response.status_code(), 403);
+
+        for (upload, (title, genre)) in
+            uploads
+                .iter()
+                .zip([("One", "Jazz"), ("Two", "Rock"), ("Three", "jazz")])
+        {
+            let response = app
+                .server
+                .post("/tracks")
+                .authorization_bearer(&creator)
+                .json(&create(upload, info(title, genre)))
+                .await;
+            assert_eq!(response.status_code(), 201);
+            let track: TrackResponse = response.json();
+            assert_eq!(track.info, info(title, genre));
+            assert_eq!(track.owner, app.admin.pubkey().to_string());
+        }
+        let response = app
+            .server
+            .post("/tracks")
+            .authorization_bearer(&creator)
+            .json(&create(&uploads[0], info("One", "Jazz")))
+            .await;
+        assert_eq!(response.status_code(), 409);
+
+        let response = app.server.get(&format!("/tracks/{}", uploads[1])).await;
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.json::<TrackResponse>().info.title, "Two");
+
+        let titles = |page: TrackPage| -> Vec<String> {
+            page.tracks
+                .into_iter()
+                .map(|track| track.info.title)
+                .collect()
+        };
+        let page: TrackPage = app.server.get("/tracks?genre=JAZZ").await.json();
+        assert_eq!(page.total, 2);
+        assert_eq!(titles(page), ["Three", "One"]);
+        let owner = format!("/tracks?owner={}&offset=1&limit=1", app.admin.pubkey());
+        let page: TrackPage = app.server.get(&owner).await.json();
+        assert_eq!((page.total, page.offset, page.limit), (3, 1, 1));
+        assert_eq!(titles(page), ["Two"]);
+        let page: TrackPage = app.server.get("/tracks?limit=1000").await.json();
+        assert_eq!(page.limit, MAX_PAGE_SIZE);
+
+        let url = format!("/tracks/{}", uploads[1]);
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&other)
+            .json(&info("Mine", "Pop"))
+            .await;
+        assert_eq!(response.status_code(), 403);
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&creator)
+            .json(&info("Two (Remix)", "Pop"))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let track: TrackResponse = app.server.get(&url).await.json();
+        assert_eq!(track.info, info("Two (Remix)", "Pop"));
+        assert!(track.updated_at >= track.created_at);
+
+        let response = app.server.delete(&url).authorization_bearer(&other).await;
+        assert_eq!(response.status_code(), 403);
+        let response = app.server.delete(&url).authorization_bearer(&creator).await;
+        assert_eq!(response.status_code(), 204);
+        assert_eq!(app.server.get(&url).await.status_code(), 404);
+        let response = app.server.delete(&url).authorization_bearer(&creator).await;
+        assert_eq!(response.status_code(), 404);
+        let page: TrackPage = app.server.get("/tracks").await.json();
+        assert_eq!(page.total, 2);
+
+        // the upload can be registered again
+        let response = app
+            .server
+            .post("/tracks")
+            .authorization_bearer(&creator)
+            .json(&create(&uploads[1], info("Two", "Rock")))
+            .await;
+        assert_eq!(response.status_code(), 201);
+        // unregistered uploads are not listed
+        let page: TrackPage = app.server.get("/tracks").await.json();
+        assert_eq!(page.total, 3);
+        // the catalog is searchable
+        let results: SearchResults = app.server.get("/search?q=remix").await.json();
+        assert_eq!(results.total, 0);
+        let results: SearchResults = app.server.get("/search?q=two&genre=rock").await.json();
+        assert_eq!(results.tracks[0].id, uploads[1]);
+        let response = app.server.get("/search?offset=1000000").await;
+        assert_eq!(response.status_code(), 400);
+        let url = format!("/tracks/{}", uploads[3]);
+        assert_eq!(app.server.get(&url).await.status_code(), 404);
+    }
+
+    #[tokio::test]
+    async fn link_nft() {
+        let app = app().await;
+        let creator = app.jwt(&app.admin).await;
+        let other = app.jwt(&Keypair::new()).await;
+        let tracks = &app.state.db.tracks;
+        let mut ids = Vec::new();
+        for plaintext in [&b"song"[..], b"other"] {
+            let id = app.track(plaintext, None, None).await;
+            let mut track = tracks
+                .get_track(&id)
+                .await
+                .expect("Failed to get")
+                .expect("No track");
+            track.owner = app.admin.pubkey().to_string();
+            tracks.update_track(&track).await.expect("Failed to update");
+            ids.push(id);
+        }
+        let (mint, collection, member) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let (holder, member_holder) = (Keypair::new(), Keypair::new());
+        app.chain.set_balance(&holder.pubkey(), &mint, 1);
+        app.chain.set_balance(&member_holder.pubkey(), &member, 1);
+        let key_status = |keypair: &Keypair| {
+            let (app, url) = (&app, format!("/track/{}/key", ids[0]));
+            let keypair = keypair.insecure_clone();
+            async move {
+                let jwt = app.jwt(&keypair).await;
+                let response = app.server.get(&url).authorization_bearer(&jwt).await;
+                response.status_code()
+            }
+        };
+        assert_eq!(key_status(&holder).await, 403);
+
+        let url = format!("/tracks/{}/nft", ids[0]);
+        let link = |collection: Option<&Pubkey>| TrackNftRequest {
+            mint: mint.to_string(),
+            collection: collection.map(Pubkey::to_string),
+            metadata_uri: Some("ipfs://metadata".to_string()),
+        };
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&other)
+            .json(&link(None))
+            .await;
+        assert_eq!(response.status_code(), 403);
+        // the creator must be verified on chain
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&creator)
+            .json(&link(None))
+            .await;
+        assert_eq!(response.status_code(), 403);
+        app.chain.add_creator(&mint, &app.admin.pubkey());
+        // the collection must be verified on chain
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&creator)
+            .json(&link(Some(&collection)))
+            .await;
+        assert_eq!(response.status_code(), 400);
+        app.chain.set_collection(&mint, &collection);
+        app.chain.set_collection(&member, &collection);
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&creator)
+            .json(&link(Some(&collection)))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let nft: NftResponse = response.json();
+        assert_eq!((nft.mint, nft.track_id), (mint.to_string(), ids[0].clone()));
+        assert_eq!(key_status(&holder).await, 200);
+        assert_eq!(key_status(&member_holder).await, 200);
+        assert_eq!(key_status(&Keypair::new()).await, 403);
+
+        // linking again is fine, the mint unlocks a single track
+        let response = app
+            .server
+            .put(&url)
+            .authorization_bearer(&creator)
+            .json(&link(Some(&collection)))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let response = app
+            .server
+            .put(&format!("/tracks/{}/nft", ids[1]))
+            .authorization_bearer(&creator)
+            .json(&link(None))
+            .await;
+        assert_eq!(response.status_code(), 409);
+        let nfts = app.state.db.nfts.nfts_of(&ids[0]).await;
+        assert_eq!(nfts.expect("Failed to list").len(), 1);
+    }
+}
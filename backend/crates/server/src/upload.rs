@@ -1,21 +1,342 @@
 //! # 上传
+//!
+//! 持有 `upload` 权限的用户携带 JWT 以 multipart 表单上传文件，服务端逐块写入临时文件，
+//! 根据文件头和文件尾的有限窗口识别音频格式并解析元数据，再逐块计算 SHA-256，
+//! 使用新的内容密钥逐块加密后以流的形式保存到存储后端（见 [`crate::crypto`]），音频元数据、密文的键和包装后的密钥写入数据库
+//! （见 [`crate::db`]），创作者随后在目录中登记音频（见 [`crate::track`]）。
+//! 超过大小限制、内容为空或不是受支持的音频时删除临时文件并返回错误。
+//!
+//! 大文件可以通过 [`session`] 分块上传，断线后从已接收的位置继续。
+//!
 
-use axum::response::IntoResponse;
-
-use crate::error::Result;
-use axum::{extract::multipart::Multipart, response::Html};
+use crate::auth::scope::{RequireScope, Upload};
+use crate::crypto::Vault;
+use crate::db::{Database, Track};
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+use crate::upload::conf::UploadConfig;
+use axum::extract::multipart::{Field, Multipart};
+use axum::extract::State;
+use axum::Json;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::audio::AudioMetadata;
+use music3_common::param::upload::UploadResponse;
+use sha2::{Digest, Sha256};
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use uuid::Uuid;
+
+pub mod audio;
+pub mod conf;
+pub mod session;
+
+/// Name of the multipart field carrying the file
+pub const FILE_FIELD: &str = "file";
+
+/// Extra bytes allowed in a multipart body besides the file itself
+pub const MULTIPART_OVERHEAD: u64 = 64 * 1024;
+
+/// Where a fully received file goes
+#[derive(Clone, Copy)]
+struct Destination<'a> {
+    storage: &'a dyn Storage,
+    vault: &'a Vault,
+    db: &'a Database,
+}
+
 /// 上传文件
-pub async fn upload(mut multipart: Multipart) -> Result<impl IntoResponse> {
+pub async fn upload(
+    claim: RequireScope<Upload>,
+    State(config): State<Arc<UploadConfig>>,
+    State(storage): State<Arc<dyn Storage>>,
+    State(vault): State<Arc<Vault>>,
+    State(db): State<Database>,
+    mut multipart: Multipart,
+) -> Result<Json<UploadResponse>> {
+    let destination = Destination {
+        storage: storage.as_ref(),
+        vault: &vault,
+        db: &db,
+    };
     while let Some(field) = multipart.next_field().await? {
-        let unique_filename = format!("uploads/{}.mp3", Uuid::new_v4());
+        if field.name() == Some(FILE_FIELD) {
+            let response = save_field(&config, destination, &claim.sub, field).await?;
+            return Ok(Json(response));
+        }
+    }
+    Err(Error::EmptyUpload)
+}
 
-        let data = field.bytes().await?;
-        let mut file = File::create(unique_filename).await?;
-        file.write_all(&data).await?;
+async fn save_field(
+    config: &UploadConfig,
+    destination: Destination<'_>,
+    owner: &str,
+    mut field: Field<'_>,
+) -> Result<UploadResponse> {
+    tokio::fs::create_dir_all(&config.dir).await?;
+    let id = Uuid::new_v4().to_string();
+    let part_path = part_path(config, &id);
+
+    let mut file = File::create(&part_path).await?;
+    let mut size = 0u64;
+    let mut header = Vec::with_capacity(audio::SNIFF_LEN);
+    let result = async {
+        while let Some(chunk) = field.chunk().await? {
+            size += chunk.len() as u64;
+            if size > config.max_size {
+                return Err(Error::PayloadTooLarge(config.max_size));
+            }
+            // reject anything but audio before writing the rest of it
+            if header.len() < audio::SNIFF_LEN {
+                let needed = (audio::SNIFF_LEN - header.len()).min(chunk.len());
+                header.extend_from_slice(&chunk[..needed]);
+                if header.len() == audio::SNIFF_LEN {
+                    audio::sniff(&header).ok_or(audio::Error::UnsupportedFormat)?;
+                }
+            }
+            file.write_all(&chunk).await?;
+        }
+        if size == 0 {
+            return Err(Error::EmptyUpload);
+        }
+        file.flush().await?;
+        Ok(())
+    }
+    .await;
+    drop(file);
+    let result = match result {
+        Ok(()) => complete(destination, owner, id, &part_path).await,
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        remove_quietly(&part_path).await;
     }
+    result
+}
+
+/// Path of the file being received
+fn part_path(config: &UploadConfig, id: &str) -> PathBuf {
+    config.dir.join(format!("{id}.part"))
+}
+
+/// Check a fully received file, then encrypt it into the storage
+async fn complete(
+    destination: Destination<'_>,
+    owner: &str,
+    id: String,
+    part_path: &Path,
+) -> Result<UploadResponse> {
+    let size = tokio::fs::metadata(part_path).await?.len();
+    if size == 0 {
+        return Err(Error::EmptyUpload);
+    }
+    let (metadata, content_hash) = inspect(part_path.to_path_buf(), size).await?;
+    let file = File::open(part_path).await?;
+    let (ciphertext, wrapped_key) = destination.vault.encrypt_file(file, size)?;
+    // the ciphertext says nothing about the format
+    let key = destination
+        .storage
+        .put_stream(ciphertext, "application/octet-stream")
+        .await?;
+    let now = get_current_timestamp();
+    let track = Track {
+        id: id.clone(),
+        owner: owner.to_string(),
+        cid: key.clone(),
+        wrapped_key,
+        content_type: metadata.format.mime_type().to_string(),
+        metadata: metadata.clone(),
+        info: None,
+        mint: None,
+        collection: None,
+        hls: None,
+        created_at: now,
+        updated_at: now,
+    };
+    destination.db.tracks.insert_track(&track).await?;
+    remove_quietly(part_path).await;
+    Ok(UploadResponse {
+        id,
+        key,
+        content_hash,
+        size,
+        content_type: metadata.format.mime_type().to_string(),
+        audio: metadata,
+    })
+}
+
+/// Parse the audio metadata from bounded windows of the file, then hash all of it a buffer at a time
+async fn inspect(path: PathBuf, size: u64) -> Result<(AudioMetadata, String)> {
+    let task = tokio::task::spawn_blocking(move || -> Result<_> {
+        let mut file = std::fs::File::open(path)?;
+        let metadata = audio::parse(&mut file, size)?;
+        file.seek(SeekFrom::Start(0))?;
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut file, &mut hasher)?;
+        Ok((metadata, format!("{:x}", hasher.finalize())))
+    });
+    task.await.map_err(std::io::Error::other)?
+}
+
+async fn remove_quietly(path: &Path) {
+    // The upload has already failed, a leftover part file is not worth another error
+    let _ = tokio::fs::remove_file(path).await;
+}
 
-    Ok(Html("<h1>File uploaded successfully!</h1>"))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{app_with, TestApp};
+    use crate::upload::audio::tests::wav;
+    use axum_test::multipart::{MultipartForm, Part};
+    use music3_common::param::audio::AudioFormat;
+    use music3_common::param::auth::Scope;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use std::ops::Deref;
+
+    /// Test app with the JWTs of a wallet granted the `upload` scope and a listener
+    struct Uploads {
+        app: TestApp,
+        uploader: String,
+        listener: String,
+    }
+
+    impl Deref for Uploads {
+        type Target = TestApp;
+        fn deref(&self) -> &Self::Target {
+            &self.app
+        }
+    }
+
+    async fn app(max_size: u64) -> Uploads {
+        let uploader = Keypair::new();
+        let app = app_with(|config| {
+            config.upload.max_size = max_size;
+            let grant = (uploader.pubkey().to_string(), vec![Scope::Upload]);
+            config.auth.policy.grants.extend([grant]);
+        })
+        .await;
+        Uploads {
+            uploader: app.jwt(&uploader).await,
+            listener: app.jwt(&Keypair::new()).await,
+            app,
+        }
+    }
+
+    fn form(data: impl Into<Vec<u8>>) -> MultipartForm {
+        MultipartForm::new().add_part(
+            FILE_FIELD,
+            Part::bytes(data.into())
+                .file_name("song.mp3")
+                .mime_type("audio/mpeg"),
+        )
+    }
+
+    #[tokio::test]
+    async fn upload_file() {
+        let app = app(1024 * 1024).await;
+        let data = wav(8000, 1, 4000, "Song");
+        let response = app
+            .server
+            .post("/file/upload")
+            .authorization_bearer(&app.uploader)
+            .multipart(form(data.clone()))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let upload: UploadResponse = response.json();
+        assert_eq!(upload.size, data.len() as u64);
+        // detected from the content, not the declared type or name
+        assert_eq!(upload.content_type, "audio/wav");
+        assert_eq!(upload.audio.format, AudioFormat::Wav);
+        assert_eq!(upload.audio.duration_ms, 500);
+        assert_eq!(upload.audio.tags.title.as_deref(), Some("Song"));
+        assert_eq!(upload.content_hash, format!("{:x}", Sha256::digest(&data)));
+
+        // only the ciphertext is stored
+        let saved = app
+            .state
+            .storage
+            .get(&upload.key)
+            .await
+            .expect("File not saved");
+        assert!(!saved.windows(4).any(|window| window == b"WAVE"));
+        let track = app
+            .state
+            .db
+            .tracks
+            .get_track(&upload.id)
+            .await
+            .expect("Failed to get the track")
+            .expect("No track");
+        assert_eq!(track.cid, upload.key);
+        assert_eq!(track.content_type, "audio/wav");
+        assert_eq!(track.metadata, upload.audio);
+        let key = app
+            .state
+            .vault
+            .unwrap(&track.wrapped_key)
+            .expect("Invalid wrapped key");
+        let plaintext = music3_common::crypto::decrypt(&key, &saved).expect("Failed to decrypt");
+        assert_eq!(plaintext, data);
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_uploads() {
+        let app = app(1024).await;
+
+        let response = app
+            .server
+            .post("/file/upload")
+            .multipart(form(wav(8000, 1, 8, "")))
+            .await;
+        assert_eq!(response.status_code(), 401);
+
+        let response = app
+            .server
+            .post("/file/upload")
+            .authorization_bearer(&app.listener)
+            .multipart(form(wav(8000, 1, 8, "")))
+            .await;
+        assert_eq!(response.status_code(), 403);
+
+        let response = app
+            .server
+            .post("/file/upload")
+            .authorization_bearer(&app.uploader)
+            .multipart(form(vec![0; 2048]))
+            .await;
+        assert_eq!(response.status_code(), 413);
+
+        let response = app
+            .server
+            .post("/file/upload")
+            .authorization_bearer(&app.uploader)
+            .multipart(form(b"definitely not audio".repeat(4)))
+            .await;
+        assert_eq!(response.status_code(), 415);
+
+        let response = app
+            .server
+            .post("/file/upload")
+            .authorization_bearer(&app.uploader)
+            .multipart(form(Vec::new()))
+            .await;
+        assert_eq!(response.status_code(), 400);
+
+        let response = app
+            .server
+            .post("/file/upload")
+            .authorization_bearer(&app.uploader)
+            .multipart(MultipartForm::new().add_text("title", "no file"))
+            .await;
+        assert_eq!(response.status_code(), 400);
+
+        // nothing is left behind
+        let entries = std::fs::read_dir(app.dir.join("uploads")).map_or(0, |dir| dir.count());
+        assert_eq!(entries, 0);
+    }
 }
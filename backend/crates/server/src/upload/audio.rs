@@ -0,0 +1,549 @@
+//! # 音频格式识别
+//!
+//! 根据文件头的魔数识别 MP3、FLAC、WAV、OGG/Opus 和 AAC/M4A，不信任客户端声明的类型和文件名，
+//! 并解析时长、采样率、声道数、平均码率以及 ID3、Vorbis comment、RIFF INFO、iTunes 等标签。
+//!
+//! 解析时不读入整个文件：只读取开头的 [`HEAD_LEN`] 字节、结尾的 [`TAIL_LEN`] 字节，
+//! 以及按偏移定位的元数据块（每块不超过 [`MAX_BLOCK_LEN`] 字节）。
+//! MP3 和 ADTS 没有 Xing 头时，根据开头扫描到的帧按比例估算整个文件的时长，固定码率时是准确的。
+//!
+
+use music3_common::param::audio::{AudioFormat, AudioMetadata, AudioTags};
+use std::io::{Read, Seek, SeekFrom};
+
+mod flac;
+mod mp4;
+mod mpeg;
+mod ogg;
+mod wav;
+
+/// Bytes needed by [`sniff`]
+pub const SNIFF_LEN: usize = 64;
+
+/// Bytes read at the start of a file, holding the ID3v2 tag, the Ogg header pages and the scanned frames
+pub const HEAD_LEN: usize = 1024 * 1024;
+
+/// Bytes read at the end of a file, holding the ID3v1 tag and the last Ogg page
+pub const TAIL_LEN: usize = 64 * 1024;
+
+/// Max bytes of a metadata block read at once, e.g. the MP4 `moov` box or a FLAC block
+pub const MAX_BLOCK_LEN: usize = 16 * 1024 * 1024;
+
+/// Audio error
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Not one of the supported formats
+    #[error("Unsupported audio format")]
+    UnsupportedFormat,
+    /// Recognized but invalid file
+    #[error("Malformed {0:?} file: {1}")]
+    Malformed(AudioFormat, &'static str),
+    /// Failed to read the file
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// Detect the format from the first [`SNIFF_LEN`] bytes, or fewer if the file is shorter
+pub fn sniff(header: &[u8]) -> Option<AudioFormat> {
+    match header {
+        [b'I', b'D', b'3', ..] => Some(AudioFormat::Mp3),
+        [b'f', b'L', b'a', b'C', ..] => Some(AudioFormat::Flac),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some(AudioFormat::Wav),
+        [b'O', b'g', b'g', b'S', ..] => ogg::is_opus(header).then_some(AudioFormat::Opus),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(AudioFormat::Aac),
+        _ if mpeg::adts_header(header).is_some() => Some(AudioFormat::Aac),
+        _ if mpeg::frame_header(header).is_some() => Some(AudioFormat::Mp3),
+        _ => None,
+    }
+}
+
+/// Parse the metadata of a file of `len` bytes, reading bounded windows of it
+pub fn parse<R: Read + Seek>(file: &mut R, len: u64) -> Result<AudioMetadata, Error> {
+    let mut input = Input { file, len };
+    let head = input.read_at(0, HEAD_LEN)?;
+    match sniff(&head[..head.len().min(SNIFF_LEN)]) {
+        Some(AudioFormat::Aac) if is_mp4(&head) => mp4::parse(&mut input),
+        Some(AudioFormat::Mp3 | AudioFormat::Aac) => mpeg::parse(&mut input, &head),
+        Some(AudioFormat::Flac) => flac::parse(&mut input),
+        Some(AudioFormat::Wav) => wav::parse(&mut input),
+        Some(AudioFormat::Opus) => ogg::parse(&mut input, &head),
+        None => Err(Error::UnsupportedFormat),
+    }
+}
+
+fn is_mp4(data: &[u8]) -> bool {
+    data.get(4..8) == Some(b"ftyp")
+}
+
+/// File being parsed
+struct Input<'r, R> {
+    file: &'r mut R,
+    len: u64,
+}
+
+impl<R: Read + Seek> Input<'_, R> {
+    /// Read up to `max` bytes at `offset`, fewer at the end of the file
+    fn read_at(&mut self, offset: u64, max: usize) -> std::io::Result<Vec<u8>> {
+        let len = self.len.saturating_sub(offset).min(max as u64) as usize;
+        let mut buf = vec![0; len];
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// Average bitrate in bits per second
+fn bitrate(bytes: u64, duration_ms: u64) -> u32 {
+    match duration_ms {
+        0 => 0,
+        ms => u32::try_from(bytes * 8 * 1000 / ms).unwrap_or(u32::MAX),
+    }
+}
+
+/// Duration in milliseconds
+fn duration_ms(samples: u64, sample_rate: u32) -> u64 {
+    match sample_rate {
+        0 => 0,
+        rate => samples * 1000 / u64::from(rate),
+    }
+}
+
+/// Little cursor over a byte slice, every read returns `None` past the end
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.bytes(N)?.try_into().ok()
+    }
+
+    fn skip(&mut self, n: usize) -> Option<()> {
+        self.bytes(n).map(|_| ())
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.array::<1>().map(|[b]| b)
+    }
+
+    fn u16_be(&mut self) -> Option<u16> {
+        self.array().map(u16::from_be_bytes)
+    }
+
+    fn u16_le(&mut self) -> Option<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32_be(&mut self) -> Option<u32> {
+        self.array().map(u32::from_be_bytes)
+    }
+
+    fn u32_le(&mut self) -> Option<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64_be(&mut self) -> Option<u64> {
+        self.array().map(u64::from_be_bytes)
+    }
+}
+
+/// Tag fields shared by every tag format
+#[derive(Debug, Clone, Copy)]
+enum Tag {
+    Title,
+    Artist,
+    Album,
+    Date,
+    Genre,
+    Track,
+}
+
+/// Set a tag unless it is already set, so the first (usually richest) source wins
+fn set_tag(tags: &mut AudioTags, tag: Tag, value: &str) {
+    let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if value.is_empty() {
+        return;
+    }
+    let slot = match tag {
+        Tag::Title => &mut tags.title,
+        Tag::Artist => &mut tags.artist,
+        Tag::Album => &mut tags.album,
+        Tag::Date => &mut tags.date,
+        Tag::Genre => &mut tags.genre,
+        Tag::Track => {
+            // "3/12" means track 3 of 12
+            let track = value.split('/').next().and_then(|n| n.trim().parse().ok());
+            tags.track = tags.track.or(track);
+            return;
+        }
+    };
+    slot.get_or_insert_with(|| value.to_string());
+}
+
+/// Vorbis comments, used by FLAC and Opus
+fn vorbis_comments(data: &[u8], tags: &mut AudioTags) -> Option<()> {
+    let mut reader = Reader::new(data);
+    let vendor_len = reader.u32_le()?;
+    reader.skip(vendor_len as usize)?;
+    for _ in 0..reader.u32_le()? {
+        let len = reader.u32_le()?;
+        let comment = String::from_utf8_lossy(reader.bytes(len as usize)?);
+        let Some((key, value)) = comment.split_once('=') else {
+            continue;
+        };
+        let tag = match key.to_ascii_uppercase().as_str() {
+            "TITLE" => Tag::Title,
+            "ARTIST" => Tag::Artist,
+            "ALBUM" => Tag::Album,
+            "DATE" | "YEAR" => Tag::Date,
+            "GENRE" => Tag::Genre,
+            "TRACKNUMBER" => Tag::Track,
+            _ => continue,
+        };
+        set_tag(tags, tag, value);
+    }
+    Some(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn parse(data: &[u8]) -> Result<AudioMetadata, Error> {
+        super::parse(&mut Cursor::new(data), data.len() as u64)
+    }
+
+    fn comments(entries: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(b"test");
+        data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for entry in entries {
+            data.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+            data.extend_from_slice(entry.as_bytes());
+        }
+        data
+    }
+
+    fn mp4_box(kind: &[u8; 4], children: &[&[u8]]) -> Vec<u8> {
+        let body = children.concat();
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(&body);
+        data
+    }
+
+    /// M4A of 180 s with an iTunes title and `mdat_len` bytes of audio, `moov` before or after `mdat`
+    fn m4a(mdat_len: usize, moov_first: bool) -> Vec<u8> {
+        let mut mvhd = vec![0; 12];
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&180_000u32.to_be_bytes());
+        let mut mp4a = vec![0; 16];
+        mp4a.extend_from_slice(&2u16.to_be_bytes());
+        mp4a.extend_from_slice(&[0; 6]);
+        mp4a.extend_from_slice(&(48000u32 << 16).to_be_bytes());
+        let stsd = [&[0, 0, 0, 0, 0, 0, 0, 1][..], &mp4_box(b"mp4a", &[&mp4a])].concat();
+        let hdlr = [&[0; 8][..], b"soun", &[0; 12]].concat();
+        let trak = mp4_box(
+            b"trak",
+            &[&mp4_box(
+                b"mdia",
+                &[
+                    &mp4_box(b"hdlr", &[&hdlr]),
+                    &mp4_box(
+                        b"minf",
+                        &[&mp4_box(b"stbl", &[&mp4_box(b"stsd", &[&stsd])])],
+                    ),
+                ],
+            )],
+        );
+        let title = mp4_box(
+            b"\xa9nam",
+            &[&mp4_box(b"data", &[&[0, 0, 0, 1, 0, 0, 0, 0], b"Song"])],
+        );
+        let udta = mp4_box(
+            b"udta",
+            &[&mp4_box(b"meta", &[&[0; 4], &mp4_box(b"ilst", &[&title])])],
+        );
+        let moov = mp4_box(b"moov", &[&mp4_box(b"mvhd", &[&mvhd]), &trak, &udta]);
+        let mdat = mp4_box(b"mdat", &[&vec![0; mdat_len]]);
+        let (first, second) = if moov_first {
+            (moov, mdat)
+        } else {
+            (mdat, moov)
+        };
+        [mp4_box(b"ftyp", &[b"M4A \0\0\0\0"]), first, second].concat()
+    }
+
+    fn ogg_page(header_type: u8, granule: i64, sequence: u32, packet: &[u8]) -> Vec<u8> {
+        let mut lacing = vec![255; packet.len() / 255];
+        lacing.push((packet.len() % 255) as u8);
+        let mut data = b"OggS\0".to_vec();
+        data.push(header_type);
+        data.extend_from_slice(&granule.to_le_bytes());
+        data.extend_from_slice(&7u32.to_le_bytes());
+        data.extend_from_slice(&sequence.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.push(lacing.len() as u8);
+        data.extend_from_slice(&lacing);
+        data.extend_from_slice(packet);
+        data
+    }
+
+    /// 16-bit PCM WAV with a RIFF INFO title
+    pub(crate) fn wav(sample_rate: u32, channels: u16, frames: u32, title: &str) -> Vec<u8> {
+        let block_align = channels * 2;
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes());
+        fmt.extend_from_slice(&channels.to_le_bytes());
+        fmt.extend_from_slice(&sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(sample_rate * u32::from(block_align)).to_le_bytes());
+        fmt.extend_from_slice(&block_align.to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+        let mut name = title.as_bytes().to_vec();
+        name.push(0);
+        if name.len() % 2 == 1 {
+            name.push(0);
+        }
+        let mut info = b"INFOINAM".to_vec();
+        info.extend_from_slice(&(title.len() as u32 + 1).to_le_bytes());
+        info.extend_from_slice(&name);
+
+        let mut body = b"WAVE".to_vec();
+        for (id, chunk) in [
+            (b"fmt ", fmt),
+            (b"LIST", info),
+            (b"data", vec![0; (frames * u32::from(block_align)) as usize]),
+        ] {
+            body.extend_from_slice(id);
+            body.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+            body.extend_from_slice(&chunk);
+        }
+        let mut data = b"RIFF".to_vec();
+        data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        data.extend_from_slice(&body);
+        data
+    }
+
+    #[test]
+    fn wav_metadata() {
+        let metadata = parse(&wav(44100, 2, 88200, "Song")).expect("Failed to parse");
+        assert_eq!(metadata.format, AudioFormat::Wav);
+        assert_eq!(metadata.duration_ms, 2000);
+        assert_eq!(metadata.sample_rate, 44100);
+        assert_eq!(metadata.channels, 2);
+        assert_eq!(metadata.bitrate, 1_411_200);
+        assert_eq!(metadata.tags.title.as_deref(), Some("Song"));
+    }
+
+    #[test]
+    fn mp3_metadata() {
+        // ID3v2.4 with a UTF-8 title and a Latin-1 track number
+        let mut frames = Vec::new();
+        for (id, text) in [(b"TIT2", &b"\x03Caf\xc3\xa9"[..]), (b"TRCK", b"\x003/12")] {
+            frames.extend_from_slice(id);
+            frames.extend_from_slice(&(text.len() as u32).to_be_bytes());
+            frames.extend_from_slice(&[0, 0]);
+            frames.extend_from_slice(text);
+        }
+        let mut data = b"ID3\x04\x00\x00\x00\x00\x00".to_vec();
+        data.push(frames.len() as u8);
+        data.extend_from_slice(&frames);
+        // 10 MPEG-1 Layer III frames at 128 kbps, 44.1 kHz, joint stereo
+        for _ in 0..10 {
+            data.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+            data.extend_from_slice(&[0; 413]);
+        }
+
+        let metadata = parse(&data).expect("Failed to parse");
+        assert_eq!(metadata.format, AudioFormat::Mp3);
+        assert_eq!(metadata.duration_ms, 261);
+        assert_eq!(metadata.sample_rate, 44100);
+        assert_eq!(metadata.channels, 2);
+        assert_eq!(metadata.bitrate / 1000, 127);
+        assert_eq!(metadata.tags.title.as_deref(), Some("Café"));
+        assert_eq!(metadata.tags.track, Some(3));
+
+        // a Xing header gives the number of frames without scanning
+        let mut xing = [0xFF, 0xFB, 0x90, 0xC4].to_vec();
+        xing.extend_from_slice(&[0; 17]);
+        xing.extend_from_slice(b"Xing\0\0\0\x01");
+        xing.extend_from_slice(&1000u32.to_be_bytes());
+        xing.resize(417, 0);
+        xing.extend_from_slice(&[0xFF, 0xFB, 0x90, 0xC4]);
+        xing.resize(834, 0);
+        let metadata = parse(&xing).expect("Failed to parse");
+        assert_eq!(metadata.channels, 1);
+        assert_eq!(metadata.duration_ms, 26122);
+    }
+
+    #[test]
+    fn aac_metadata() {
+        // 43 ADTS frames of AAC-LC, 44.1 kHz, stereo
+        let mut data = Vec::new();
+        for _ in 0..43 {
+            data.extend_from_slice(&[0xFF, 0xF1, 0x50, 0x80, 0x20, 0x1F, 0xFC]);
+            data.extend_from_slice(&[0; 249]);
+        }
+        let metadata = parse(&data).expect("Failed to parse");
+        assert_eq!(metadata.format, AudioFormat::Aac);
+        assert_eq!(metadata.duration_ms, 998);
+        assert_eq!(metadata.sample_rate, 44100);
+        assert_eq!(metadata.channels, 2);
+
+        // M4A with an iTunes title
+        let data = m4a(3600, true);
+        let metadata = parse(&data).expect("Failed to parse");
+        assert_eq!(metadata.format, AudioFormat::Aac);
+        assert_eq!(metadata.duration_ms, 180_000);
+        assert_eq!(metadata.sample_rate, 48000);
+        assert_eq!(metadata.channels, 2);
+        assert_eq!(metadata.bitrate, 160);
+        assert_eq!(metadata.tags.title.as_deref(), Some("Song"));
+    }
+
+    #[test]
+    fn flac_metadata() {
+        // 96 kHz, 2 channels, 24 bits, 288000 samples
+        let mut stream_info = vec![0; 10];
+        stream_info.extend_from_slice(&[0x17, 0x70, 0x03, 0x70, 0x00, 0x04, 0x65, 0x00]);
+        stream_info.resize(34, 0);
+        let comments = comments(&["ARTIST=Someone", "title=Song", "TRACKNUMBER=7"]);
+        let mut data = b"fLaC\x00\x00\x00\x22".to_vec();
+        data.extend_from_slice(&stream_info);
+        data.extend_from_slice(&[0x84, 0, 0, comments.len() as u8]);
+        data.extend_from_slice(&comments);
+        data.extend_from_slice(&[0; 1200]);
+
+        let metadata = parse(&data).expect("Failed to parse");
+        assert_eq!(metadata.format, AudioFormat::Flac);
+        assert_eq!(metadata.duration_ms, 3000);
+        assert_eq!(metadata.sample_rate, 96000);
+        assert_eq!(metadata.channels, 2);
+        assert_eq!(metadata.bitrate, 3200);
+        assert_eq!(metadata.tags.artist.as_deref(), Some("Someone"));
+        assert_eq!(metadata.tags.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.tags.track, Some(7));
+    }
+
+    #[test]
+    fn opus_metadata() {
+        let mut head = b"OpusHead\x01\x02".to_vec();
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&44100u32.to_le_bytes());
+        head.extend_from_slice(&[0, 0, 0]);
+        let tags = [&b"OpusTags"[..], &comments(&["ALBUM=Record"])].concat();
+        let data = [
+            ogg_page(2, 0, 0, &head),
+            ogg_page(0, 0, 1, &tags),
+            ogg_page(0, 48000, 2, &[0; 300]),
+            ogg_page(4, 312 + 96000, 3, &[0; 300]),
+        ]
+        .concat();
+
+        let metadata = parse(&data).expect("Failed to parse");
+        assert_eq!(metadata.format, AudioFormat::Opus);
+        assert_eq!(metadata.duration_ms, 2000);
+        assert_eq!(metadata.sample_rate, 44100);
+        assert_eq!(metadata.channels, 2);
+        assert_eq!(metadata.tags.album.as_deref(), Some("Record"));
+    }
+
+    #[test]
+    fn unsupported_formats() {
+        let vorbis = ogg_page(2, 0, 0, b"\x01vorbis\0\0\0\0\x02\x44\xac\0\0");
+        for data in [&b"not audio at all"[..], b"", b"ID3", &vorbis] {
+            assert!(
+                matches!(
+                    parse(data),
+                    Err(Error::UnsupportedFormat | Error::Malformed(..))
+                ),
+                "{data:?}"
+            );
+            if !data.starts_with(b"ID3") {
+                assert_eq!(sniff(data), None);
+            }
+        }
+    }
+
+    /// File counting the bytes read from it
+    struct Counting<'a> {
+        file: Cursor<&'a [u8]>,
+        read: usize,
+    }
+
+    impl Read for Counting<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.file.read(buf)?;
+            self.read += n;
+            Ok(n)
+        }
+    }
+
+    impl Seek for Counting<'_> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.file.seek(pos)
+        }
+    }
+
+    #[test]
+    fn bounded_reads() {
+        let parse = |data: &[u8]| {
+            let mut file = Counting {
+                file: Cursor::new(data),
+                read: 0,
+            };
+            let metadata = super::parse(&mut file, data.len() as u64).expect("Failed to parse");
+            assert!(
+                file.read <= 2 * HEAD_LEN + TAIL_LEN,
+                "read {} bytes",
+                file.read
+            );
+            metadata
+        };
+
+        // 3000 frames at a constant 128 kbps, estimated from the scanned ones
+        let mut mp3 = Vec::new();
+        for _ in 0..3000 {
+            mp3.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x64]);
+            mp3.extend_from_slice(&[0; 413]);
+        }
+        let metadata = parse(&mp3);
+        assert!(metadata.duration_ms.abs_diff(78_367) <= 2, "{metadata:?}");
+        assert_eq!(metadata.bitrate / 1000, 127);
+
+        // moov after the audio
+        let metadata = parse(&m4a(3 * 1024 * 1024, false));
+        assert_eq!(metadata.duration_ms, 180_000);
+        assert_eq!(metadata.tags.title.as_deref(), Some("Song"));
+
+        // the length is in the last page
+        let mut head = b"OpusHead\x01\x02".to_vec();
+        head.extend_from_slice(&312u16.to_le_bytes());
+        head.extend_from_slice(&48000u32.to_le_bytes());
+        head.extend_from_slice(&[0, 0, 0]);
+        let tags = [&b"OpusTags"[..], &comments(&[])].concat();
+        let mut opus = [ogg_page(2, 0, 0, &head), ogg_page(0, 0, 1, &tags)].concat();
+        for sequence in 2..42 {
+            opus.extend_from_slice(&ogg_page(0, -1, sequence, &[0; 60_000]));
+        }
+        opus.extend_from_slice(&ogg_page(4, 312 + 48000 * 50, 42, &[0; 300]));
+        assert_eq!(parse(&opus).duration_ms, 50_000);
+    }
+}
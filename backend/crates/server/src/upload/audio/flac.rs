@@ -0,0 +1,68 @@
+//! FLAC: STREAMINFO and VORBIS_COMMENT metadata blocks
+
+use super::{bitrate, duration_ms, vorbis_comments, Error, Input};
+use music3_common::param::audio::{AudioFormat, AudioMetadata, AudioTags};
+use std::io::{Read, Seek};
+
+const STREAMINFO: u8 = 0;
+const VORBIS_COMMENT: u8 = 4;
+
+/// Metadata blocks walked before giving up, real files have a handful
+const MAX_BLOCKS: usize = 1024;
+
+pub(super) fn parse<R: Read + Seek>(input: &mut Input<R>) -> Result<AudioMetadata, Error> {
+    let malformed = |reason| Error::Malformed(AudioFormat::Flac, reason);
+    if input.len < 4 {
+        return Err(malformed("missing magic"));
+    }
+
+    let mut tags = AudioTags::default();
+    let mut stream_info = None;
+    let mut pos = 4;
+    // a block is at most 16 MiB, its length has 24 bits
+    for blocks in 1.. {
+        if blocks > MAX_BLOCKS {
+            return Err(malformed("too many metadata blocks"));
+        }
+        let header = input.read_at(pos, 4)?;
+        let &[header, a, b, c] = header.as_slice() else {
+            return Err(malformed("truncated metadata"));
+        };
+        let len = u64::from(u32::from_be_bytes([0, a, b, c]));
+        let body = pos + 4;
+        if len > input.len - body {
+            return Err(malformed("truncated metadata"));
+        }
+        match header & 0x7F {
+            STREAMINFO => stream_info = Some(input.read_at(body, len as usize)?),
+            VORBIS_COMMENT => {
+                let block = input.read_at(body, len as usize)?;
+                vorbis_comments(&block, &mut tags).ok_or(malformed("invalid Vorbis comment"))?
+            }
+            _ => {}
+        }
+        pos = body + len;
+        if header & 0x80 != 0 {
+            break;
+        }
+    }
+
+    // sample rate: 20 bits, channels - 1: 3 bits, bits per sample - 1: 5 bits, samples: 36 bits
+    let info = stream_info
+        .filter(|info| info.len() >= 18)
+        .ok_or(malformed("missing STREAMINFO"))?;
+    let sample_rate =
+        (u32::from(info[10]) << 12) | (u32::from(info[11]) << 4) | (u32::from(info[12]) >> 4);
+    let channels = u16::from((info[12] >> 1) & 7) + 1;
+    let samples = (u64::from(info[13] & 0xF) << 32)
+        | u64::from(u32::from_be_bytes([info[14], info[15], info[16], info[17]]));
+    let duration_ms = duration_ms(samples, sample_rate);
+    Ok(AudioMetadata {
+        format: AudioFormat::Flac,
+        duration_ms,
+        sample_rate,
+        channels,
+        bitrate: bitrate(input.len - pos, duration_ms),
+        tags,
+    })
+}
@@ -0,0 +1,145 @@
+//! MP4/M4A: `mvhd` duration, the `mp4a` sample entry and iTunes `ilst` tags
+
+use super::{bitrate, set_tag, Error, Input, Reader, Tag, MAX_BLOCK_LEN};
+use music3_common::param::audio::{AudioFormat, AudioMetadata, AudioTags};
+use std::io::{Read, Seek};
+
+/// Top-level boxes walked looking for `moov`
+const MAX_BOXES: usize = 1024;
+
+/// Iterate the boxes of a container as `(type, body)`
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut reader = Reader::new(data);
+    std::iter::from_fn(move || {
+        let size = reader.u32_be()?;
+        let kind = reader.bytes(4)?;
+        let len = match size {
+            0 => reader.remaining(),
+            1 => usize::try_from(reader.u64_be()?).ok()?.checked_sub(16)?,
+            size => (size as usize).checked_sub(8)?,
+        };
+        Some((kind, reader.bytes(len)?))
+    })
+}
+
+/// Find a box by its path
+fn find<'a>(data: &'a [u8], path: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    path.iter().try_fold(data, |data, kind| {
+        boxes(data).find_map(|(k, body)| (k == *kind).then_some(body))
+    })
+}
+
+pub(super) fn parse<R: Read + Seek>(input: &mut Input<R>) -> Result<AudioMetadata, Error> {
+    let malformed = |reason| Error::Malformed(AudioFormat::Aac, reason);
+    // walk the top-level box headers, `moov` may follow the audio in `mdat`
+    let mut moov = None;
+    let mut audio_bytes = 0;
+    let mut pos = 0;
+    for _ in 0..MAX_BOXES {
+        let header = input.read_at(pos, 16)?;
+        let mut header = Reader::new(&header);
+        let (Some(size), Some(kind)) = (header.u32_be(), header.array::<4>()) else {
+            break;
+        };
+        let (header_len, len) = match size {
+            0 => (8, input.len - pos),
+            1 => (16, header.u64_be().ok_or(malformed("truncated box"))?),
+            size => (8, u64::from(size)),
+        };
+        let body_len = len
+            .checked_sub(header_len)
+            .ok_or(malformed("invalid box size"))?;
+        let body = pos + header_len;
+        match &kind {
+            b"moov" if body_len > MAX_BLOCK_LEN as u64 => return Err(malformed("moov too large")),
+            b"moov" => moov = Some(input.read_at(body, body_len as usize)?),
+            b"mdat" => audio_bytes += body_len.min(input.len.saturating_sub(body)),
+            _ => {}
+        }
+        pos = body.saturating_add(body_len);
+        if pos >= input.len {
+            break;
+        }
+    }
+    let moov = moov.ok_or(malformed("missing moov"))?;
+    let moov = moov.as_slice();
+
+    let mut mvhd = Reader::new(find(moov, &[b"mvhd"]).ok_or(malformed("missing mvhd"))?);
+    let (timescale, duration) = match mvhd.u8() {
+        Some(1) => mvhd
+            .skip(19)
+            .and_then(|_| Some((mvhd.u32_be()?, mvhd.u64_be()?))),
+        Some(_) => mvhd
+            .skip(11)
+            .and_then(|_| Some((mvhd.u32_be()?, u64::from(mvhd.u32_be()?)))),
+        None => None,
+    }
+    .ok_or(malformed("truncated mvhd"))?;
+    let duration_ms = match timescale {
+        0 => 0,
+        timescale => duration * 1000 / u64::from(timescale),
+    };
+
+    let entry = boxes(moov)
+        .filter(|(kind, _)| *kind == b"trak")
+        .filter_map(|(_, trak)| find(trak, &[b"mdia"]))
+        .find(|mdia| find(mdia, &[b"hdlr"]).and_then(|hdlr| hdlr.get(8..12)) == Some(b"soun"))
+        .and_then(|mdia| find(mdia, &[b"minf", b"stbl", b"stsd"]))
+        .and_then(|stsd| boxes(stsd.get(8..)?).next())
+        .ok_or(malformed("missing audio track"))?;
+    if entry.0 != b"mp4a" {
+        // e.g. ALAC
+        return Err(Error::UnsupportedFormat);
+    }
+    let mut entry = Reader::new(entry.1);
+    entry.skip(16).ok_or(malformed("truncated mp4a"))?;
+    let channels = entry.u16_be().ok_or(malformed("truncated mp4a"))?;
+    entry.skip(6).ok_or(malformed("truncated mp4a"))?;
+    let sample_rate = entry.u32_be().ok_or(malformed("truncated mp4a"))? >> 16;
+
+    let mut tags = AudioTags::default();
+    if let Some(meta) = find(moov, &[b"udta", b"meta"]) {
+        // ISO meta is a full box, QuickTime meta starts with the children directly
+        let children = if meta.get(4..8) == Some(b"hdlr") {
+            meta
+        } else {
+            meta.get(4..).unwrap_or_default()
+        };
+        if let Some(ilst) = find(children, &[b"ilst"]) {
+            ilst_tags(ilst, &mut tags);
+        }
+    }
+
+    Ok(AudioMetadata {
+        format: AudioFormat::Aac,
+        duration_ms,
+        sample_rate,
+        channels,
+        bitrate: bitrate(audio_bytes, duration_ms),
+        tags,
+    })
+}
+
+fn ilst_tags(ilst: &[u8], tags: &mut AudioTags) {
+    for (kind, item) in boxes(ilst) {
+        // type indicator and locale precede the value
+        let Some(value) = find(item, &[b"data"]).and_then(|data| data.get(8..)) else {
+            continue;
+        };
+        let tag = match kind {
+            b"\xa9nam" => Tag::Title,
+            b"\xa9ART" => Tag::Artist,
+            b"\xa9alb" => Tag::Album,
+            b"\xa9day" => Tag::Date,
+            b"\xa9gen" => Tag::Genre,
+            b"trkn" => {
+                if let Some(&[a, b]) = value.get(2..4) {
+                    tags.track = tags.track.or(Some(u32::from(u16::from_be_bytes([a, b]))));
+                }
+                continue;
+            }
+            _ => continue,
+        };
+        set_tag(tags, tag, &String::from_utf8_lossy(value));
+    }
+}
@@ -0,0 +1,386 @@
+//! MPEG audio streams: MP3 frames or ADTS AAC frames, optionally behind ID3 tags
+
+use super::{bitrate, duration_ms, set_tag, Error, Input, Reader, Tag, HEAD_LEN};
+use music3_common::param::audio::{AudioFormat, AudioMetadata, AudioTags};
+use std::io::{Read, Seek};
+
+/// How far to look for the first frame after the ID3 tag
+const SYNC_WINDOW: usize = 64 * 1024;
+
+const ID3V1_LEN: u64 = 128;
+
+/// MP3 frame header
+pub(super) struct FrameHeader {
+    sample_rate: u32,
+    channels: u16,
+    samples: u32,
+    len: usize,
+    /// Offset of the Xing header
+    xing_offset: usize,
+}
+
+/// Parse an MPEG-1/2/2.5 Layer III frame header
+pub(super) fn frame_header(header: &[u8]) -> Option<FrameHeader> {
+    const MPEG1_BITRATES: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const MPEG2_BITRATES: [u32; 15] =
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    let &[0xFF, b1, b2, b3, ..] = header else {
+        return None;
+    };
+    // sync bits and layer III only
+    if b1 & 0xE0 != 0xE0 || (b1 >> 1) & 3 != 1 {
+        return None;
+    }
+    let (mpeg1, rate_divisor) = match (b1 >> 3) & 3 {
+        0 => (false, 4),
+        2 => (false, 2),
+        3 => (true, 1),
+        _ => return None,
+    };
+    let kbps = match usize::from(b2 >> 4) {
+        // free format and bad index
+        0 | 15 => return None,
+        i if mpeg1 => MPEG1_BITRATES[i],
+        i => MPEG2_BITRATES[i],
+    };
+    let sample_rate = match (b2 >> 2) & 3 {
+        0 => 44100,
+        1 => 48000,
+        2 => 32000,
+        _ => return None,
+    } / rate_divisor;
+    let padding = usize::from((b2 >> 1) & 1);
+    let mono = b3 >> 6 == 3;
+    let samples = if mpeg1 { 1152 } else { 576 };
+    let len = (samples / 8 * kbps * 1000 / sample_rate) as usize + padding;
+    let side_info = match (mpeg1, mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+    Some(FrameHeader {
+        sample_rate,
+        channels: if mono { 1 } else { 2 },
+        samples,
+        len,
+        xing_offset: 4 + side_info,
+    })
+}
+
+/// ADTS frame header
+pub(super) struct AdtsHeader {
+    sample_rate: u32,
+    channels: u16,
+    samples: u32,
+    len: usize,
+}
+
+/// Parse an ADTS frame header
+pub(super) fn adts_header(header: &[u8]) -> Option<AdtsHeader> {
+    const SAMPLE_RATES: [u32; 13] = [
+        96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+    ];
+
+    let &[0xFF, b1, b2, b3, b4, b5, b6, ..] = header else {
+        return None;
+    };
+    // sync bits and layer 0
+    if b1 & 0xF6 != 0xF0 {
+        return None;
+    }
+    let sample_rate = *SAMPLE_RATES.get(usize::from((b2 >> 2) & 0xF))?;
+    let len = (usize::from(b3 & 3) << 11) | (usize::from(b4) << 3) | usize::from(b5 >> 5);
+    if len < 7 {
+        return None;
+    }
+    Some(AdtsHeader {
+        sample_rate,
+        channels: u16::from(((b2 & 1) << 2) | (b3 >> 6)),
+        samples: 1024 * (u32::from(b6 & 3) + 1),
+        len,
+    })
+}
+
+pub(super) fn parse<R: Read + Seek>(
+    input: &mut Input<R>,
+    head: &[u8],
+) -> Result<AudioMetadata, Error> {
+    let mut tags = AudioTags::default();
+    let mut start = 0;
+    if head.starts_with(b"ID3") {
+        start = id3v2(head, &mut tags)
+            .ok_or(Error::Malformed(AudioFormat::Mp3, "invalid ID3v2 tag"))? as u64;
+    }
+    let mut end = input.len;
+    if end >= start + ID3V1_LEN {
+        let tag = input.read_at(end - ID3V1_LEN, ID3V1_LEN as usize)?;
+        if tag.starts_with(b"TAG") {
+            end -= ID3V1_LEN;
+            id3v1(&tag, &mut tags);
+        }
+    }
+    let audio_len = end.saturating_sub(start);
+    // frames are scanned at the start of the audio only
+    let audio = input.read_at(start, audio_len.min(HEAD_LEN as u64) as usize)?;
+    let audio = audio.as_slice();
+
+    let window = &audio[..audio.len().min(SYNC_WINDOW)];
+    let first = (0..window.len()).find_map(|pos| {
+        if let Some(frame) = adts_header(&audio[pos..]) {
+            (continues(audio, pos + frame.len, |h| adts_header(h).is_some())).then_some((pos, true))
+        } else {
+            let frame = frame_header(&audio[pos..])?;
+            (continues(audio, pos + frame.len, |h| frame_header(h).is_some()))
+                .then_some((pos, false))
+        }
+    });
+    match first {
+        Some((pos, true)) => Ok(adts(&audio[pos..], audio_len - pos as u64, tags)),
+        Some((pos, false)) => Ok(mp3(&audio[pos..], audio_len - pos as u64, tags)),
+        None if head.starts_with(b"ID3") => Err(Error::Malformed(AudioFormat::Mp3, "no frame")),
+        None => Err(Error::UnsupportedFormat),
+    }
+}
+
+/// The next frame is valid too, or the stream ends right after this one
+fn continues(audio: &[u8], next: usize, valid: impl Fn(&[u8]) -> bool) -> bool {
+    next == audio.len() || audio.get(next..).is_some_and(valid)
+}
+
+/// Duration and size of a stream of `len` bytes, whose `scanned` bytes at the start of the `window` last `scanned_ms`
+fn extrapolate(scanned_ms: u64, scanned: usize, window: &[u8], len: u64) -> (u64, u64) {
+    if window.len() as u64 >= len || scanned == 0 {
+        return (scanned_ms, scanned as u64);
+    }
+    // the frames past the window are like the scanned ones, exact at a constant bitrate
+    (scanned_ms * len / scanned as u64, len)
+}
+
+/// MP3 stream of `len` bytes starting at a valid frame, `audio` is its start
+fn mp3(audio: &[u8], len: u64, tags: AudioTags) -> AudioMetadata {
+    let mut pos = 0;
+    let mut frames = 0u64;
+    let mut first = None;
+    while let Some(frame) = audio.get(pos..).and_then(frame_header) {
+        if pos + frame.len > audio.len() {
+            break;
+        }
+        if first.is_none() {
+            if let Some(count) = xing_frames(&audio[..frame.len], frame.xing_offset) {
+                // the Xing frame itself carries no audio
+                let audio_bytes = len - frame.len as u64;
+                let duration_ms = duration_ms(count * u64::from(frame.samples), frame.sample_rate);
+                return AudioMetadata {
+                    format: AudioFormat::Mp3,
+                    duration_ms,
+                    sample_rate: frame.sample_rate,
+                    channels: frame.channels,
+                    bitrate: bitrate(audio_bytes, duration_ms),
+                    tags,
+                };
+            }
+        }
+        frames += 1;
+        pos += frame.len;
+        first.get_or_insert(frame);
+    }
+
+    // `first` is always set, `parse` only calls this at a valid frame
+    let (sample_rate, channels, samples) = first
+        .map(|frame| (frame.sample_rate, frame.channels, frame.samples))
+        .unwrap_or_default();
+    let scanned_ms = duration_ms(frames * u64::from(samples), sample_rate);
+    let (duration_ms, audio_bytes) = extrapolate(scanned_ms, pos, audio, len);
+    AudioMetadata {
+        format: AudioFormat::Mp3,
+        duration_ms,
+        sample_rate,
+        channels,
+        bitrate: bitrate(audio_bytes, duration_ms),
+        tags,
+    }
+}
+
+/// Number of frames from a Xing/Info or VBRI header
+fn xing_frames(frame: &[u8], xing_offset: usize) -> Option<u64> {
+    let mut reader = Reader::new(frame);
+    reader.skip(xing_offset)?;
+    if let Some(b"Xing" | b"Info") = reader.bytes(4) {
+        let flags = reader.u32_be()?;
+        return (flags & 1 == 1)
+            .then(|| reader.u32_be())
+            .flatten()
+            .map(u64::from);
+    }
+    let mut reader = Reader::new(frame);
+    reader.skip(36)?;
+    if reader.bytes(4)? == b"VBRI" {
+        // version, delay, quality, bytes
+        reader.skip(10)?;
+        return reader.u32_be().map(u64::from);
+    }
+    None
+}
+
+/// ADTS stream of `len` bytes starting at a valid frame, `audio` is its start
+fn adts(audio: &[u8], len: u64, tags: AudioTags) -> AudioMetadata {
+    let mut pos = 0;
+    let mut samples = 0u64;
+    let mut first = None;
+    while let Some(frame) = audio.get(pos..).and_then(adts_header) {
+        if pos + frame.len > audio.len() {
+            break;
+        }
+        samples += u64::from(frame.samples);
+        pos += frame.len;
+        first.get_or_insert(frame);
+    }
+    let (sample_rate, channels) = first
+        .map(|frame| (frame.sample_rate, frame.channels))
+        .unwrap_or_default();
+    let (duration_ms, audio_bytes) =
+        extrapolate(duration_ms(samples, sample_rate), pos, audio, len);
+    AudioMetadata {
+        format: AudioFormat::Aac,
+        duration_ms,
+        sample_rate,
+        channels,
+        bitrate: bitrate(audio_bytes, duration_ms),
+        tags,
+    }
+}
+
+/// Parse an ID3v2 tag, returning its total length
+fn id3v2(data: &[u8], tags: &mut AudioTags) -> Option<usize> {
+    let mut reader = Reader::new(data);
+    reader.skip(3)?;
+    let major = reader.u8()?;
+    reader.skip(1)?;
+    let flags = reader.u8()?;
+    let size = syncsafe(reader.array()?);
+    let footer = if flags & 0x10 != 0 { 10 } else { 0 };
+    let total = 10 + size + footer;
+
+    // the window may cut a large tag, e.g. with embedded pictures, the frames past it are left out
+    let window = data.get(10..)?;
+    let truncated = window.len() < size;
+    let mut frames = Reader::new(&window[..size.min(window.len())]);
+    if flags & 0x40 != 0 {
+        // extended header, its size excludes itself in v2.3 only
+        match major {
+            3 => {
+                let len = frames.u32_be()? as usize;
+                frames.skip(len)?
+            }
+            4 => {
+                let len = syncsafe(frames.array()?);
+                frames.skip(len.checked_sub(4)?)?
+            }
+            _ => {}
+        }
+    }
+    while frames.remaining() > 0 {
+        if truncated && frames.remaining() < 10 {
+            break;
+        }
+        let (id, len) = match major {
+            2 => {
+                let id = frames.bytes(3)?;
+                let [a, b, c] = frames.array()?;
+                (id, u32::from_be_bytes([0, a, b, c]) as usize)
+            }
+            3 => {
+                let id = frames.bytes(4)?;
+                let len = frames.u32_be()? as usize;
+                frames.skip(2)?;
+                (id, len)
+            }
+            4 => {
+                let id = frames.bytes(4)?;
+                let len = syncsafe(frames.array()?);
+                frames.skip(2)?;
+                (id, len)
+            }
+            _ => break,
+        };
+        if id[0] == 0 {
+            // padding
+            break;
+        }
+        let Some(body) = frames.bytes(len) else {
+            if truncated {
+                break;
+            }
+            return None;
+        };
+        let tag = match id {
+            b"TIT2" | b"TT2" => Tag::Title,
+            b"TPE1" | b"TP1" => Tag::Artist,
+            b"TALB" | b"TAL" => Tag::Album,
+            b"TDRC" | b"TYER" | b"TYE" => Tag::Date,
+            b"TCON" | b"TCO" => Tag::Genre,
+            b"TRCK" | b"TRK" => Tag::Track,
+            _ => continue,
+        };
+        if let Some(text) = id3_text(body) {
+            set_tag(tags, tag, &text);
+        }
+    }
+    Some(total)
+}
+
+/// ID3v1 tag, only filling fields missing from ID3v2
+fn id3v1(tag: &[u8], tags: &mut AudioTags) {
+    let text = |range: std::ops::Range<usize>| latin1(&tag[range]);
+    set_tag(tags, Tag::Title, &text(3..33));
+    set_tag(tags, Tag::Artist, &text(33..63));
+    set_tag(tags, Tag::Album, &text(63..93));
+    set_tag(tags, Tag::Date, &text(93..97));
+    // ID3v1.1 stores the track in the last byte of the comment
+    if tag[125] == 0 && tag[126] != 0 {
+        tags.track = tags.track.or(Some(u32::from(tag[126])));
+    }
+}
+
+fn syncsafe(bytes: [u8; 4]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |size, &b| (size << 7) | usize::from(b & 0x7F))
+}
+
+/// Decode a text frame, keeping the first value only
+fn id3_text(body: &[u8]) -> Option<String> {
+    let (&encoding, text) = body.split_first()?;
+    let text = match encoding {
+        0 => latin1(text),
+        1 | 2 => {
+            let (big_endian, text) = match text {
+                [0xFE, 0xFF, rest @ ..] => (true, rest),
+                [0xFF, 0xFE, rest @ ..] => (false, rest),
+                _ => (encoding == 2, text),
+            };
+            let units: Vec<u16> = text
+                .chunks_exact(2)
+                .map(|unit| match big_endian {
+                    true => u16::from_be_bytes([unit[0], unit[1]]),
+                    false => u16::from_le_bytes([unit[0], unit[1]]),
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        3 => String::from_utf8_lossy(text).into_owned(),
+        _ => return None,
+    };
+    Some(text.split('\0').next().unwrap_or_default().to_string())
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| char::from(b))
+        .collect()
+}
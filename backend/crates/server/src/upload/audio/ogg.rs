@@ -0,0 +1,122 @@
+//! Ogg Opus: `OpusHead` and `OpusTags` packets and the granule position of the last page
+
+use super::{bitrate, vorbis_comments, Error, Input, Reader, TAIL_LEN};
+use music3_common::param::audio::{AudioFormat, AudioMetadata, AudioTags};
+use std::io::{Read, Seek};
+
+const PAGE_HEADER_LEN: usize = 27;
+
+/// Opus always decodes at 48 kHz, granule positions count 48 kHz samples
+const OPUS_RATE: u32 = 48000;
+
+struct Page<'a> {
+    granule: i64,
+    serial: u32,
+    lacing: &'a [u8],
+    body: &'a [u8],
+}
+
+fn page<'a>(reader: &mut Reader<'a>) -> Option<Page<'a>> {
+    if reader.bytes(4)? != b"OggS" {
+        return None;
+    }
+    // version and header type
+    reader.skip(2)?;
+    let granule = i64::from_le_bytes(reader.array()?);
+    let serial = reader.u32_le()?;
+    // sequence number and checksum
+    reader.skip(8)?;
+    let segments = reader.u8()?;
+    let lacing = reader.bytes(usize::from(segments))?;
+    let body = reader.bytes(lacing.iter().map(|&len| usize::from(len)).sum())?;
+    Some(Page {
+        granule,
+        serial,
+        lacing,
+        body,
+    })
+}
+
+/// Check that the first packet of the first page is an `OpusHead`
+pub(super) fn is_opus(header: &[u8]) -> bool {
+    let start = header
+        .get(PAGE_HEADER_LEN - 1)
+        .map(|&segments| PAGE_HEADER_LEN + usize::from(segments));
+    start.and_then(|start| header.get(start..start + 8)) == Some(b"OpusHead")
+}
+
+/// Granule position of the last page of the stream `serial` in the `tail` of the file
+fn last_granule(tail: &[u8], serial: u32) -> Option<i64> {
+    (0..tail.len())
+        .rev()
+        .filter(|&pos| tail[pos..].starts_with(b"OggS"))
+        .filter_map(|pos| page(&mut Reader::new(&tail[pos..])))
+        .find(|page| page.serial == serial && page.granule >= 0)
+        .map(|page| page.granule)
+}
+
+pub(super) fn parse<R: Read + Seek>(
+    input: &mut Input<R>,
+    head: &[u8],
+) -> Result<AudioMetadata, Error> {
+    let malformed = |reason| Error::Malformed(AudioFormat::Opus, reason);
+    let mut reader = Reader::new(head);
+    let first = page(&mut Reader::new(head)).ok_or(malformed("invalid page"))?;
+
+    // the first two packets of the logical stream, a packet ends at a lacing value below 255
+    let mut packets: Vec<Vec<u8>> = Vec::new();
+    let mut packet = Vec::new();
+    while packets.len() < 2 && reader.remaining() > 0 {
+        let page = page(&mut reader).ok_or(malformed("invalid page"))?;
+        if page.serial != first.serial {
+            continue;
+        }
+        let mut offset = 0;
+        for &len in page.lacing {
+            let len = usize::from(len);
+            if packets.len() < 2 {
+                packet.extend_from_slice(&page.body[offset..offset + len]);
+                if len < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+            offset += len;
+        }
+    }
+
+    let [head, comments] = packets
+        .try_into()
+        .map_err(|_| malformed("missing header packets"))?;
+    let mut head = Reader::new(&head);
+    if head.bytes(8) != Some(b"OpusHead") {
+        return Err(Error::UnsupportedFormat);
+    }
+    head.skip(1).ok_or(malformed("truncated OpusHead"))?;
+    let channels = head.u8().ok_or(malformed("truncated OpusHead"))?;
+    let pre_skip = head.u16_le().ok_or(malformed("truncated OpusHead"))?;
+    let input_rate = head.u32_le().ok_or(malformed("truncated OpusHead"))?;
+
+    let mut tags = AudioTags::default();
+    let comments = comments
+        .strip_prefix(b"OpusTags")
+        .ok_or(malformed("missing OpusTags"))?;
+    vorbis_comments(comments, &mut tags).ok_or(malformed("invalid OpusTags"))?;
+
+    // the last page gives the length of the stream
+    let tail = input.read_at(input.len.saturating_sub(TAIL_LEN as u64), TAIL_LEN)?;
+    let granule = last_granule(&tail, first.serial);
+    let samples = u64::try_from(granule.unwrap_or_default() - i64::from(pre_skip)).unwrap_or(0);
+    let duration_ms = super::duration_ms(samples, OPUS_RATE);
+    Ok(AudioMetadata {
+        format: AudioFormat::Opus,
+        duration_ms,
+        sample_rate: if input_rate == 0 {
+            OPUS_RATE
+        } else {
+            input_rate
+        },
+        channels: u16::from(channels),
+        bitrate: bitrate(input.len, duration_ms),
+        tags,
+    })
+}
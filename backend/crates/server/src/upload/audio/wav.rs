@@ -0,0 +1,93 @@
+//! RIFF WAVE: `fmt `, `data` and `LIST`/`INFO` chunks
+
+use super::{duration_ms, set_tag, Error, Input, Reader, Tag, MAX_BLOCK_LEN};
+use music3_common::param::audio::{AudioFormat, AudioMetadata, AudioTags};
+use std::io::{Read, Seek};
+
+/// Chunks walked before giving up on finding the others
+const MAX_CHUNKS: usize = 1024;
+
+pub(super) fn parse<R: Read + Seek>(input: &mut Input<R>) -> Result<AudioMetadata, Error> {
+    let malformed = |reason| Error::Malformed(AudioFormat::Wav, reason);
+    if input.len < 12 {
+        return Err(malformed("missing header"));
+    }
+
+    let mut tags = AudioTags::default();
+    let mut format = None;
+    let mut data_len = None;
+    let mut pos = 12;
+    for _ in 0..MAX_CHUNKS {
+        if input.len - pos < 8 {
+            break;
+        }
+        let header = input.read_at(pos, 8)?;
+        let mut header = Reader::new(&header);
+        let id = header.array::<4>().ok_or(malformed("truncated chunk"))?;
+        let len = u64::from(header.u32_le().ok_or(malformed("truncated chunk"))?);
+        let body = pos + 8;
+        let remaining = input.len - body;
+        if &id == b"data" {
+            // streaming writers leave the size unset, the data runs to the end of the file
+            data_len = Some(len.min(remaining));
+            pos = body + len.min(remaining);
+        } else {
+            if len > remaining {
+                return Err(malformed("truncated chunk"));
+            }
+            // only the small chunks are read, e.g. not embedded pictures
+            let small = len <= MAX_BLOCK_LEN as u64;
+            match &id {
+                b"fmt " if small => format = Some(input.read_at(body, len as usize)?),
+                b"LIST" if small => {
+                    let chunk = input.read_at(body, len as usize)?;
+                    if let Some(chunk) = chunk.strip_prefix(b"INFO") {
+                        info(chunk, &mut tags);
+                    }
+                }
+                _ => {}
+            }
+            pos = body + len;
+        }
+        // chunks are word aligned
+        pos = (pos + len % 2).min(input.len);
+    }
+
+    let format = format.ok_or(malformed("missing fmt chunk"))?;
+    let mut format = Reader::new(&format);
+    format.skip(2).ok_or(malformed("truncated fmt chunk"))?;
+    let channels = format.u16_le().ok_or(malformed("truncated fmt chunk"))?;
+    let sample_rate = format.u32_le().ok_or(malformed("truncated fmt chunk"))?;
+    let byte_rate = format.u32_le().ok_or(malformed("truncated fmt chunk"))?;
+    let data_len = data_len.ok_or(malformed("missing data chunk"))?;
+    Ok(AudioMetadata {
+        format: AudioFormat::Wav,
+        duration_ms: duration_ms(data_len, byte_rate),
+        sample_rate,
+        channels,
+        bitrate: byte_rate.saturating_mul(8),
+        tags,
+    })
+}
+
+fn info(data: &[u8], tags: &mut AudioTags) {
+    let mut reader = Reader::new(data);
+    while let (Some(id), Some(len)) = (reader.bytes(4), reader.u32_le()) {
+        let Some(value) = reader.bytes(len as usize) else {
+            break;
+        };
+        if len % 2 == 1 {
+            reader.skip(1);
+        }
+        let tag = match id {
+            b"INAM" => Tag::Title,
+            b"IART" => Tag::Artist,
+            b"IPRD" => Tag::Album,
+            b"ICRD" => Tag::Date,
+            b"IGNR" => Tag::Genre,
+            b"ITRK" | b"IPRT" => Tag::Track,
+            _ => continue,
+        };
+        set_tag(tags, tag, &String::from_utf8_lossy(value));
+    }
+}
@@ -0,0 +1,32 @@
+//! Configuration for the upload module.
+//!
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Upload configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+pub struct UploadConfig {
+    /// Directory of the files being received, created if missing
+    pub dir: PathBuf,
+    /// Max size of a file uploaded in one request in bytes
+    pub max_size: u64,
+    /// Max size of a file uploaded through a resumable session in bytes
+    pub session_max_size: u64,
+    /// Max size of a chunk of a resumable upload in bytes
+    pub chunk_size: u64,
+    /// Lifetime of a resumable upload session in seconds
+    pub session_ttl_sec: u64,
+}
+
+impl Default for UploadConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("uploads"),
+            max_size: 100 * 1024 * 1024,
+            session_max_size: 4 * 1024 * 1024 * 1024,
+            chunk_size: 8 * 1024 * 1024,
+            session_ttl_sec: 24 * 60 * 60,
+        }
+    }
+}
@@ -0,0 +1,448 @@
+//! # 断点续传
+//!
+//! 1. 持有 `upload` 权限的用户调用 `POST /file/upload/session` 声明文件大小，创建上传会话。
+//! 2. 按顺序调用 `PUT /file/upload/session/{id}?offset=N` 上传分块，`offset` 必须等于已接收的字节数。
+//! 3. 断线后调用 `GET /file/upload/session/{id}` 查询已接收的字节数，从该位置继续上传。
+//! 4. 全部接收后调用 `POST /file/upload/session/{id}/finalize`，识别音频格式并返回与普通上传相同的结果。
+//!
+//! 会话信息保存在上传目录中，已接收的字节数就是临时文件的长度，因此服务重启后会话仍然有效。
+//! 会话只对创建者可见，过期的会话在创建新会话时清理。
+//!
+
+use crate::auth::scope::{RequireScope, Upload};
+use crate::crypto::Vault;
+use crate::db::Database;
+use crate::error::{Error, Result};
+use crate::storage::Storage;
+use crate::upload::conf::UploadConfig;
+use crate::upload::{complete, part_path, remove_quietly, Destination};
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::Json;
+use jsonwebtoken::get_current_timestamp;
+use music3_common::param::upload::{
+    CreateUploadSessionRequest, UploadChunkQuery, UploadResponse, UploadSessionResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+const SESSION_EXTENSION: &str = "session";
+
+/// Resumable upload sessions
+pub struct Sessions {
+    config: Arc<UploadConfig>,
+    /// Sessions with a request in progress
+    busy: Mutex<HashSet<String>>,
+}
+
+/// Session stored next to its part file
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    owner: String,
+    size: u64,
+    expires_at: u64,
+}
+
+/// Marks a session busy until dropped
+struct Busy<'a> {
+    sessions: &'a Sessions,
+    id: String,
+}
+
+impl Drop for Busy<'_> {
+    fn drop(&mut self) {
+        self.sessions
+            .busy
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.id);
+    }
+}
+
+impl Sessions {
+    /// Create the sessions of an upload directory
+    pub fn new(config: Arc<UploadConfig>) -> Self {
+        Self {
+            config,
+            busy: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn session_path(&self, id: &str) -> PathBuf {
+        self.config.dir.join(format!("{id}.{SESSION_EXTENSION}"))
+    }
+
+    /// Reject concurrent requests of a session, which would race on the offset
+    fn lock(&self, id: &str) -> Result<Busy<'_>> {
+        let mut busy = self.busy.lock().unwrap_or_else(PoisonError::into_inner);
+        if !busy.insert(id.to_string()) {
+            return Err(Error::UploadSessionBusy);
+        }
+        Ok(Busy {
+            sessions: self,
+            id: id.to_string(),
+        })
+    }
+
+    /// Load a session of `owner`, other users see it as missing
+    async fn load(&self, id: &str, owner: &str) -> Result<Session> {
+        // the ID becomes a file name, so only accept what `create` generates
+        Uuid::parse_str(id).map_err(|_| Error::UploadSessionNotFound)?;
+        let session = match tokio::fs::read(self.session_path(id)).await {
+            Ok(data) => serde_json::from_slice::<Session>(&data)
+                .map_err(|e| Error::Unexpected(e.to_string().into()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::UploadSessionNotFound)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if session.expires_at < get_current_timestamp() {
+            self.remove(id).await;
+            return Err(Error::UploadSessionNotFound);
+        }
+        if session.owner != owner {
+            return Err(Error::UploadSessionNotFound);
+        }
+        Ok(session)
+    }
+
+    /// Bytes received so far
+    async fn offset(&self, id: &str) -> Result<u64> {
+        Ok(tokio::fs::metadata(part_path(&self.config, id))
+            .await?
+            .len())
+    }
+
+    async fn remove(&self, id: &str) {
+        remove_quietly(&self.session_path(id)).await;
+        remove_quietly(&part_path(&self.config, id)).await;
+    }
+
+    /// Remove the expired sessions
+    async fn sweep(&self) -> Result<()> {
+        let now = get_current_timestamp();
+        let mut entries = tokio::fs::read_dir(&self.config.dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(SESSION_EXTENSION) {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let expired = tokio::fs::read(&path)
+                .await
+                .ok()
+                .and_then(|data| serde_json::from_slice::<Session>(&data).ok())
+                .is_none_or(|session| session.expires_at < now);
+            if expired {
+                self.remove(id).await;
+            }
+        }
+        Ok(())
+    }
+
+    fn response(&self, id: String, session: &Session, offset: u64) -> UploadSessionResponse {
+        UploadSessionResponse {
+            id,
+            size: session.size,
+            offset,
+            chunk_size: self.config.chunk_size,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+/// 创建上传会话
+pub async fn create(
+    claim: RequireScope<Upload>,
+    State(sessions): State<Arc<Sessions>>,
+    Json(request): Json<CreateUploadSessionRequest>,
+) -> Result<Json<UploadSessionResponse>> {
+    let config = &sessions.config;
+    if request.size > config.session_max_size {
+        return Err(Error::PayloadTooLarge(config.session_max_size));
+    }
+    if request.size == 0 {
+        return Err(Error::EmptyUpload);
+    }
+    tokio::fs::create_dir_all(&config.dir).await?;
+    sessions.sweep().await?;
+
+    let id = Uuid::new_v4().to_string();
+    let session = Session {
+        owner: claim.sub.clone(),
+        size: request.size,
+        expires_at: get_current_timestamp() + config.session_ttl_sec,
+    };
+    tokio::fs::File::create(part_path(config, &id)).await?;
+    let data = serde_json::to_vec(&session).map_err(|e| Error::Unexpected(e.to_string().into()))?;
+    tokio::fs::write(sessions.session_path(&id), data).await?;
+    Ok(Json(sessions.response(id, &session, 0)))
+}
+
+/// 查询上传进度
+pub async fn status(
+    claim: RequireScope<Upload>,
+    State(sessions): State<Arc<Sessions>>,
+    Path(id): Path<String>,
+) -> Result<Json<UploadSessionResponse>> {
+    let session = sessions.load(&id, &claim.sub).await?;
+    let offset = sessions.offset(&id).await?;
+    Ok(Json(sessions.response(id, &session, offset)))
+}
+
+/// 上传分块
+pub async fn put_chunk(
+    claim: RequireScope<Upload>,
+    State(sessions): State<Arc<Sessions>>,
+    Path(id): Path<String>,
+    Query(UploadChunkQuery { offset }): Query<UploadChunkQuery>,
+    chunk: Bytes,
+) -> Result<Json<UploadSessionResponse>> {
+    let _busy = sessions.lock(&id)?;
+    let session = sessions.load(&id, &claim.sub).await?;
+    let received = sessions.offset(&id).await?;
+    if offset != received {
+        return Err(Error::UploadOffsetMismatch(received));
+    }
+    let end = offset + chunk.len() as u64;
+    if end > session.size {
+        return Err(Error::PayloadTooLarge(session.size));
+    }
+
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(part_path(&sessions.config, &id))
+        .await?;
+    file.write_all(&chunk).await?;
+    file.flush().await?;
+    Ok(Json(sessions.response(id, &session, end)))
+}
+
+/// 完成上传
+pub async fn finalize(
+    claim: RequireScope<Upload>,
+    State(sessions): State<Arc<Sessions>>,
+    State(storage): State<Arc<dyn Storage>>,
+    State(vault): State<Arc<Vault>>,
+    State(db): State<Database>,
+    Path(id): Path<String>,
+) -> Result<Json<UploadResponse>> {
+    let _busy = sessions.lock(&id)?;
+    let session = sessions.load(&id, &claim.sub).await?;
+    let offset = sessions.offset(&id).await?;
+    if offset != session.size {
+        return Err(Error::UploadIncomplete {
+            offset,
+            size: session.size,
+        });
+    }
+
+    let part_path = part_path(&sessions.config, &id);
+    let destination = Destination {
+        storage: storage.as_ref(),
+        vault: &vault,
+        db: &db,
+    };
+    let result = complete(destination, &claim.sub, id.clone(), &part_path).await;
+    // finalizing again cannot fix the content, but can retry after an IO or storage error
+    if matches!(result, Ok(_) | Err(Error::Audio(_) | Error::EmptyUpload)) {
+        sessions.remove(&id).await;
+    }
+    Ok(Json(result?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{app_with, TestApp};
+    use crate::upload::audio::tests::wav;
+    use music3_common::param::audio::AudioFormat;
+    use music3_common::param::auth::Scope;
+    use solana_sdk::signature::Keypair;
+    use solana_sdk::signer::Signer;
+    use std::ops::Deref;
+
+    /// Test app with the JWTs of two creators
+    struct Sessions {
+        app: TestApp,
+        jwts: [String; 2],
+    }
+
+    impl Deref for Sessions {
+        type Target = TestApp;
+        fn deref(&self) -> &Self::Target {
+            &self.app
+        }
+    }
+
+    async fn app() -> Sessions {
+        let creator = Keypair::new();
+        let app = app_with(|config| {
+            // sessions take files over the limit of a single request
+            config.upload.max_size = 1024;
+            config.upload.session_max_size = 64 * 1024;
+            config.upload.chunk_size = 1024;
+            let grant = (creator.pubkey().to_string(), vec![Scope::Upload]);
+            config.auth.policy.grants.extend([grant]);
+        })
+        .await;
+        Sessions {
+            jwts: [app.jwt(&app.admin).await, app.jwt(&creator).await],
+            app,
+        }
+    }
+
+    impl Sessions {
+        async fn create(&self, size: usize) -> UploadSessionResponse {
+            self.server
+                .post("/file/upload/session")
+                .authorization_bearer(&self.jwts[0])
+                .json(&CreateUploadSessionRequest { size: size as u64 })
+                .await
+                .json()
+        }
+
+        async fn put(&self, id: &str, offset: usize, chunk: &[u8]) -> axum_test::TestResponse {
+            self.server
+                .put(&format!("/file/upload/session/{id}"))
+                .add_query_params(UploadChunkQuery {
+                    offset: offset as u64,
+                })
+                .authorization_bearer(&self.jwts[0])
+                .bytes(Bytes::copy_from_slice(chunk))
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn resumable_upload() {
+        let app = app().await;
+        let data = wav(8000, 1, 5000, "Master");
+        let session = app.create(data.len()).await;
+        assert_eq!(session.offset, 0);
+        assert_eq!(session.chunk_size, 1024);
+
+        // the first half, then "reconnect" and ask where to continue
+        for (offset, chunk) in data[..data.len() / 2].chunks(1000).enumerate() {
+            let progress: UploadSessionResponse =
+                app.put(&session.id, offset * 1000, chunk).await.json();
+            assert_eq!(progress.offset as usize, offset * 1000 + chunk.len());
+        }
+        let progress: UploadSessionResponse = app
+            .server
+            .get(&format!("/file/upload/session/{}", session.id))
+            .authorization_bearer(&app.jwts[0])
+            .await
+            .json();
+        let offset = progress.offset as usize;
+        assert_eq!(offset, data.len() / 2);
+        for (i, chunk) in data[offset..].chunks(1000).enumerate() {
+            app.put(&session.id, offset + i * 1000, chunk)
+                .await
+                .assert_status_ok();
+        }
+
+        let upload: UploadResponse = app
+            .server
+            .post(&format!("/file/upload/session/{}/finalize", session.id))
+            .authorization_bearer(&app.jwts[0])
+            .await
+            .json();
+        assert_eq!(upload.id, session.id);
+        assert_eq!(upload.size, data.len() as u64);
+        assert_eq!(upload.audio.format, AudioFormat::Wav);
+        assert_eq!(upload.audio.tags.title.as_deref(), Some("Master"));
+        let saved = app.state.storage.get(&upload.key).await.expect("Not saved");
+        let track = app
+            .state
+            .db
+            .tracks
+            .get_track(&upload.id)
+            .await
+            .expect("Failed to get the track")
+            .expect("No track");
+        let key = app
+            .state
+            .vault
+            .unwrap(&track.wrapped_key)
+            .expect("Invalid wrapped key");
+        let plaintext = music3_common::crypto::decrypt(&key, &saved).expect("Failed to decrypt");
+        assert_eq!(plaintext, data);
+        let session_path = app
+            .dir
+            .join("uploads")
+            .join(format!("{}.session", upload.id));
+        assert!(!session_path.exists());
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_chunks() {
+        let app = app().await;
+        let data = wav(8000, 1, 1000, "");
+        let session = app.create(data.len()).await;
+        let status_url = format!("/file/upload/session/{}", session.id);
+
+        let response = app
+            .server
+            .post("/file/upload/session")
+            .authorization_bearer(&app.jwts[0])
+            .json(&CreateUploadSessionRequest { size: 1 << 20 })
+            .await;
+        assert_eq!(response.status_code(), 413);
+
+        // offsets must follow the bytes received
+        app.put(&session.id, 0, &data[..500])
+            .await
+            .assert_status_ok();
+        let response = app.put(&session.id, 0, &data[..500]).await;
+        assert_eq!(response.status_code(), 409);
+        let response = app.put(&session.id, 1000, &data[1000..1500]).await;
+        assert_eq!(response.status_code(), 409);
+
+        // chunks over the limit or past the declared size
+        app.put(&session.id, 500, &data[500..1500])
+            .await
+            .assert_status_ok();
+        let response = app.put(&session.id, 1500, &[0; 1100]).await;
+        assert_eq!(response.status_code(), 413);
+        let response = app.put(&session.id, 1500, &[0; 1000]).await;
+        assert_eq!(response.status_code(), 413);
+
+        // not finished yet
+        let response = app
+            .server
+            .post(&format!("{status_url}/finalize"))
+            .authorization_bearer(&app.jwts[0])
+            .await;
+        assert_eq!(response.status_code(), 409);
+
+        // invisible to other creators
+        let response = app
+            .server
+            .get(&status_url)
+            .authorization_bearer(&app.jwts[1])
+            .await;
+        assert_eq!(response.status_code(), 404);
+        let response = app
+            .server
+            .get("/file/upload/session/..%2F..%2Fetc")
+            .authorization_bearer(&app.jwts[0])
+            .await;
+        assert_eq!(response.status_code(), 404);
+
+        let progress: UploadSessionResponse = app
+            .server
+            .get(&status_url)
+            .authorization_bearer(&app.jwts[0])
+            .await
+            .json();
+        assert_eq!(progress.offset, 1500);
+    }
+}
@@ -1,6 +1,34 @@
+use anyhow::Context;
 use music3_server::conf::Config;
+use music3_server::db::Database;
+use music3_server::state::AppState;
+use shuttle_runtime::SecretStore;
 
 #[shuttle_runtime::main]
-async fn main() -> shuttle_axum::ShuttleAxum {
-    Ok(music3_server::route::router(Config::default())?.into())
+async fn main(
+    #[shuttle_shared_db::Postgres] pool: sqlx::PgPool,
+    #[shuttle_runtime::Secrets] secrets: SecretStore,
+) -> shuttle_axum::ShuttleAxum {
+    // the defaults of the auth secrets are for development only, production must set them
+    let secret = |name: &str| {
+        secrets
+            .get(name)
+            .filter(|value| !value.is_empty())
+            .with_context(|| format!("{name} must be set"))
+    };
+    let mut config = Config::default();
+    config.auth.jwt.keys =
+        serde_json::from_str(&secret("JWT_KEYS")?).context("Invalid JWT_KEYS")?;
+    anyhow::ensure!(!config.auth.jwt.keys.is_empty(), "JWT_KEYS must not be empty");
+    config.auth.hmac_secret = secret("HMAC_SECRET")?;
+    config.crypto.master_key = secrets.get("MASTER_KEY");
+    config.playback.secret = secrets.get("PLAYBACK_SECRET");
+    let state = AppState::new(config)?.with_database(Database::postgres(pool));
+    state.db.migrate().await.map_err(anyhow::Error::from)?;
+    state
+        .search
+        .rebuild(&state.db)
+        .await
+        .map_err(anyhow::Error::from)?;
+    Ok(music3_server::route::routes(state).into())
 }
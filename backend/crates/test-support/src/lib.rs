@@ -0,0 +1,40 @@
+//! # Test support
+//!
+//! 只作为 dev-dependency 使用的测试工具：测试结束时删除的临时目录，以及测试用的固定密钥。
+//! 这些密钥不能出现在发布的 crate 中。
+//!
+#![deny(unsafe_code, missing_docs, clippy::unwrap_used)]
+
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Master key of the test vaults
+pub const MASTER_KEY: &str = "music3-test-master-key";
+
+/// Secret signing the playback URLs in tests
+pub const PLAYBACK_SECRET: &str = "music3-test-playback-secret";
+
+/// Directory under the system temporary directory, removed with its content on drop
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Unique path named after `name`, created by whoever writes into it first
+    pub fn new(name: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("music3-{name}-{}", Uuid::new_v4())))
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,84 @@
+//! # Error codes
+//!
+
+use anchor_lang::prelude::*;
+
+/// Error codes of the program
+#[error_code]
+pub enum Music3Error {
+    /// Name longer than Metaplex accepts
+    #[msg("Name is too long")]
+    NameTooLong,
+    /// Symbol longer than Metaplex accepts
+    #[msg("Symbol is too long")]
+    SymbolTooLong,
+    /// URI longer than Metaplex accepts
+    #[msg("URI is too long")]
+    UriTooLong,
+    /// Royalty above 100%
+    #[msg("Royalty must not exceed 10000 basis points")]
+    InvalidRoyalty,
+    /// Platform fee above 100%
+    #[msg("Fee must not exceed 10000 basis points")]
+    InvalidFee,
+    /// More editions than the master edition may print
+    #[msg("Edition supply exceeds the max supply of the song")]
+    SupplyExceeded,
+    /// No editions left for sale
+    #[msg("Sold out")]
+    SoldOut,
+    /// Payment accounts missing or not of the currency of the sale
+    #[msg("Payment accounts do not match the currency")]
+    InvalidPayment,
+    /// Token neither the master edition nor an edition of the song
+    #[msg("The token is not an edition of the song")]
+    NotSongToken,
+    /// Price above what the buyer accepts
+    #[msg("Price is higher than the buyer accepts")]
+    PriceTooHigh,
+    /// Royalty and platform fee above 100% of the price
+    #[msg("Royalty and fee exceed the price")]
+    SharesExceedPrice,
+    /// Auction times or prices out of order
+    #[msg("Invalid auction times or prices")]
+    InvalidAuction,
+    /// Bid before the start of the auction
+    #[msg("Auction has not started")]
+    AuctionNotStarted,
+    /// Bid after the end of the auction or once a Dutch auction is won
+    #[msg("Auction has ended")]
+    AuctionEnded,
+    /// Settlement before the end of the auction
+    #[msg("Auction has not ended")]
+    AuctionNotEnded,
+    /// Bid under the reserve price or the minimum increment
+    #[msg("Bid is too low")]
+    BidTooLow,
+    /// Refund of the highest bid
+    #[msg("The highest bid cannot be refunded")]
+    WinningBid,
+    /// Boost ratio of 100% or more, or revenue cut above 100%
+    #[msg("Boost ratio must be below 10000 basis points")]
+    InvalidBoostRatio,
+    /// Subsidy above the max ratio of the boost pool
+    #[msg("Requested subsidy exceeds the max boost ratio")]
+    SubsidyTooHigh,
+    /// Boost pool missing or without enough lamports for the subsidy
+    #[msg("Not enough funds in the boost pool")]
+    InsufficientBoost,
+    /// Split without recipients, with too many, duplicated or empty ones, or shares not adding up to 100%
+    #[msg("Split recipients must be distinct and share 10000 basis points")]
+    InvalidSplit,
+    /// Split or balance of the song missing or not matching the currency
+    #[msg("Split accounts do not match the song")]
+    InvalidSplitAccounts,
+    /// Claim by a wallet not in the split
+    #[msg("Not a recipient of the split")]
+    NotRecipient,
+    /// Bid left from an earlier auction of the token in another currency, or above the new bid
+    #[msg("Bid from an earlier auction must be refunded first")]
+    StaleBid,
+    /// Sale in another currency than the buyer expects
+    #[msg("Currency differs from what the buyer expects")]
+    CurrencyMismatch,
+}
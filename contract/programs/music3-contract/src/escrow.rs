@@ -0,0 +1,51 @@
+//! # 托管
+//!
+//! 挂单、拍卖和出价把代币存放在 PDA 的关联代币账户中，由 PDA 签名取出并关闭账户。
+//!
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, CloseAccount, Token, TokenAccount, Transfer};
+
+/// Move every token out of `vault`, held by the PDA `authority` signing with `seeds`, to `to`,
+/// then close `vault` to `rent_to`
+pub(crate) fn release<'info>(
+    vault: &Account<'info, TokenAccount>,
+    to: &AccountInfo<'info>,
+    rent_to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: vault.to_account_info(),
+                to: to.clone(),
+                authority: authority.clone(),
+            },
+            &[seeds],
+        ),
+        vault.amount,
+    )?;
+    close(vault, rent_to, authority, seeds, token_program)
+}
+
+/// Close the empty `vault`, held by the PDA `authority` signing with `seeds`, to `rent_to`
+pub(crate) fn close<'info>(
+    vault: &Account<'info, TokenAccount>,
+    rent_to: &AccountInfo<'info>,
+    authority: &AccountInfo<'info>,
+    seeds: &[&[u8]],
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    token::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        CloseAccount {
+            account: vault.to_account_info(),
+            destination: rent_to.clone(),
+            authority: authority.clone(),
+        },
+        &[seeds],
+    ))
+}
@@ -0,0 +1,509 @@
+//! # 拍卖
+//!
+//! 持有者把歌曲的主版本或印制的版本交给拍卖账户托管，价格由出价决定：
+//! - 英式拍卖：出价不低于保留价，之后每次出价至少比最高价高出最小加价。临近结束时的出价把结束时间推迟到出价后
+//!   [`Auction::extension`] 秒，防止最后一刻抢拍；
+//! - 荷式拍卖：价格从起拍价随时间线性下降到保留价，第一个接受当前价格的出价成交，拍卖随即结束。
+//!
+//! 每个出价者的资金托管在自己的 [`Bid`] 账户中，SOL 存放在账户本身，SPL 代币存放在它的关联代币账户。
+//! 被超过的出价者可以随时通过 `refund_bid` 取回资金，再次出价只需补足差额。同一代币再次拍卖时沿用之前的出价账户，
+//! 上一次拍卖中没有取回、币种不同或金额高于本次出价的出价需要先取回才能出价。
+//!
+//! 两种拍卖都通过 `settle_auction` 结算：任何人都可以在结束后调用，最高出价按与二级市场相同的比例
+//! 支付版税、平台手续费和卖家收入，代币转给出价最高者；没有出价时代币退还卖家。
+//!
+
+use crate::error::Music3Error;
+use crate::escrow;
+use crate::payment::{accrue, recipient, Funds, Proceeds, Revenue};
+use crate::state::{
+    Auction, AuctionKind, Bid, BoostPool, Config, Song, Split, SplitBalance, AUCTION_SEED,
+    BID_SEED, BOOST_SEED, CONFIG_SEED, SONG_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+/// Arguments of [`crate::music3_contract::create_auction`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CreateAuctionArgs {
+    /// English or Dutch
+    pub kind: AuctionKind,
+    /// SPL token mint the bids are paid in, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Price at the start of a Dutch auction, ignored by English auctions
+    pub start_price: u64,
+    /// Lowest price the token sells for
+    pub reserve_price: u64,
+    /// Least amount a bid of an English auction must raise the highest bid by
+    pub min_increment: u64,
+    /// Unix timestamp bids open at
+    pub start_time: i64,
+    /// Unix timestamp bids close at
+    pub end_time: i64,
+    /// Seconds a bid close to the end of an English auction keeps it open for
+    pub extension: i64,
+}
+
+/// Accounts of [`crate::music3_contract::create_auction`]
+#[derive(Accounts)]
+pub struct CreateAuction<'info> {
+    /// Holder of the token
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    /// Song of the token
+    #[account(seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Mint of the token
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// CHECK: edition account of the token, checked against the song
+    pub token_edition: UncheckedAccount<'info>,
+    /// Token account of the seller holding the token
+    #[account(mut, token::mint = token_mint, token::authority = seller)]
+    pub seller_token: Box<Account<'info, TokenAccount>>,
+    /// Auction of the token
+    #[account(
+        init,
+        payer = seller,
+        space = 8 + Auction::INIT_SPACE,
+        seeds = [AUCTION_SEED, token_mint.key().as_ref()],
+        bump,
+    )]
+    pub auction: Box<Account<'info, Auction>>,
+    /// Token account of the auction holding the token
+    #[account(
+        init,
+        payer = seller,
+        associated_token::mint = token_mint,
+        associated_token::authority = auction,
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::place_bid`]
+#[derive(Accounts)]
+pub struct PlaceBid<'info> {
+    /// Bidder
+    #[account(mut)]
+    pub bidder: Signer<'info>,
+    /// Auction bid in
+    #[account(
+        mut,
+        seeds = [AUCTION_SEED, auction.token_mint.as_ref()],
+        bump = auction.bump,
+    )]
+    pub auction: Box<Account<'info, Auction>>,
+    /// Funds of the bidder in the auction
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = 8 + Bid::INIT_SPACE,
+        seeds = [BID_SEED, auction.key().as_ref(), bidder.key().as_ref()],
+        bump,
+    )]
+    pub bid: Box<Account<'info, Bid>>,
+    /// Token account of the bidder paying an SPL token bid
+    #[account(mut)]
+    pub bidder_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Associated token account of the bid holding an SPL token bid
+    #[account(mut)]
+    pub bid_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::refund_bid`]
+#[derive(Accounts)]
+pub struct RefundBid<'info> {
+    /// CHECK: wallet of the bidder, checked against the bid
+    #[account(mut, address = bid.bidder)]
+    pub bidder: UncheckedAccount<'info>,
+    /// CHECK: auction of the bid, closed once settled
+    #[account(address = bid.auction)]
+    pub auction: UncheckedAccount<'info>,
+    /// Funds of the bidder, closed to the bidder
+    #[account(
+        mut,
+        close = bidder,
+        seeds = [BID_SEED, bid.auction.as_ref(), bid.bidder.as_ref()],
+        bump = bid.bump,
+    )]
+    pub bid: Box<Account<'info, Bid>>,
+    /// Associated token account of the bid holding an SPL token bid
+    #[account(mut)]
+    pub bid_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the bidder receiving an SPL token bid
+    #[account(mut)]
+    pub bidder_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts of [`crate::music3_contract::settle_auction`]
+#[derive(Accounts)]
+pub struct SettleAuction<'info> {
+    /// Anyone settling the auction, paying for the token account of the receiver if missing
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// Platform settings
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+    /// Song of the token
+    #[account(seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Auction to settle, closed to the seller
+    #[account(
+        mut,
+        has_one = song,
+        has_one = seller,
+        has_one = token_mint,
+        close = seller,
+        seeds = [AUCTION_SEED, token_mint.key().as_ref()],
+        bump = auction.bump,
+    )]
+    pub auction: Box<Account<'info, Auction>>,
+    /// CHECK: wallet of the seller, checked against the auction
+    #[account(mut)]
+    pub seller: UncheckedAccount<'info>,
+    /// CHECK: wallet of the creator, checked against the song
+    #[account(mut, address = song.creator)]
+    pub creator: UncheckedAccount<'info>,
+    /// CHECK: wallet of the platform, checked against the settings
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    /// Mint of the token
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// Token account of the auction holding the token
+    #[account(mut, associated_token::mint = token_mint, associated_token::authority = auction)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: the highest bidder, or the seller if nobody bid
+    #[account(mut, address = auction.highest_bidder.unwrap_or(auction.seller))]
+    pub receiver: UncheckedAccount<'info>,
+    /// Token account of the receiver receiving the token
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = token_mint,
+        associated_token::authority = receiver,
+    )]
+    pub receiver_token: Box<Account<'info, TokenAccount>>,
+    /// Funds of the highest bidder, closed to the bidder
+    #[account(mut)]
+    pub winning_bid: Option<Box<Account<'info, Bid>>>,
+    /// Associated token account of the winning bid holding an SPL token bid
+    #[account(mut)]
+    pub bid_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the seller receiving an SPL token price
+    #[account(mut)]
+    pub seller_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the creator receiving an SPL token royalty
+    #[account(mut)]
+    pub creator_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the platform receiving an SPL token fee
+    #[account(mut)]
+    pub treasury_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Revenue split of the song, required if it has one
+    pub split: Option<Box<Account<'info, Split>>>,
+    /// Balance of the split in the currency of the sale
+    #[account(mut)]
+    pub split_balance: Option<Box<Account<'info, SplitBalance>>>,
+    /// Associated token account of the split balance receiving SPL token revenue
+    #[account(mut)]
+    pub split_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Boost pool receiving the revenue cut of a boosted song paid in SOL
+    #[account(mut, seeds = [BOOST_SEED], bump = boost_pool.bump)]
+    pub boost_pool: Option<Box<Account<'info, BoostPool>>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// A bid became the highest of an auction
+#[event]
+pub struct BidPlaced {
+    /// Auction bid in
+    pub auction: Pubkey,
+    /// Bidder
+    pub bidder: Pubkey,
+    /// Amount of the bid
+    pub amount: u64,
+    /// Unix timestamp bids close at, after any extension
+    pub end_time: i64,
+}
+
+/// An auction was settled
+#[event]
+pub struct AuctionSettled {
+    /// Auction settled
+    pub auction: Pubkey,
+    /// Song of the token
+    pub song: Pubkey,
+    /// Mint of the token
+    pub token_mint: Pubkey,
+    /// Seller
+    pub seller: Pubkey,
+    /// Highest bidder, not set if the token went back to the seller
+    pub winner: Option<Pubkey>,
+    /// Price paid
+    pub price: u64,
+    /// Royalty paid to the creator out of the price
+    pub royalty: u64,
+    /// Platform fee out of the price
+    pub fee: u64,
+}
+
+pub(crate) fn create(ctx: Context<CreateAuction>, args: CreateAuctionArgs) -> Result<()> {
+    let accounts = &ctx.accounts;
+    accounts
+        .song
+        .check_token(&accounts.token_mint.key(), &accounts.token_edition)?;
+    require!(
+        args.start_time < args.end_time
+            && args.end_time > Clock::get()?.unix_timestamp
+            && args.extension >= 0,
+        Music3Error::InvalidAuction
+    );
+    match args.kind {
+        AuctionKind::English => require!(args.min_increment > 0, Music3Error::InvalidAuction),
+        AuctionKind::Dutch => require!(
+            args.start_price >= args.reserve_price,
+            Music3Error::InvalidAuction
+        ),
+    }
+    token::transfer(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            Transfer {
+                from: accounts.seller_token.to_account_info(),
+                to: accounts.vault.to_account_info(),
+                authority: accounts.seller.to_account_info(),
+            },
+        ),
+        1,
+    )?;
+    let auction = &mut ctx.accounts.auction;
+    auction.seller = ctx.accounts.seller.key();
+    auction.song = ctx.accounts.song.key();
+    auction.token_mint = ctx.accounts.token_mint.key();
+    auction.currency = args.currency;
+    auction.kind = args.kind;
+    auction.start_price = args.start_price;
+    auction.reserve_price = args.reserve_price;
+    auction.min_increment = args.min_increment;
+    auction.start_time = args.start_time;
+    auction.end_time = args.end_time;
+    auction.extension = args.extension;
+    auction.highest_bid = 0;
+    auction.highest_bidder = None;
+    auction.bump = ctx.bumps.auction;
+    Ok(())
+}
+
+/// Bid `amount` in an English auction, or accept the current price of a Dutch auction up to `amount`
+pub(crate) fn place_bid(ctx: Context<PlaceBid>, amount: u64) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let accounts = &ctx.accounts;
+    let auction = &accounts.auction;
+    require!(now >= auction.start_time, Music3Error::AuctionNotStarted);
+    // a won Dutch auction ends at the time of the bid
+    require!(now < auction.end_time, Music3Error::AuctionEnded);
+    let (price, end_time) = match auction.kind {
+        AuctionKind::English => {
+            require!(amount >= auction.min_bid(), Music3Error::BidTooLow);
+            let end_time = auction.end_time.max(now + auction.extension);
+            (amount, end_time)
+        }
+        AuctionKind::Dutch => {
+            let price = auction.price_at(now);
+            require!(price <= amount, Music3Error::PriceTooHigh);
+            (price, now)
+        }
+    };
+
+    // a bidder outbid and not refunded only adds the difference, in the same currency
+    require!(
+        accounts.bid.amount == 0 || accounts.bid.currency == auction.currency,
+        Music3Error::StaleBid
+    );
+    let funds = Funds::new(
+        auction.currency,
+        &accounts.bidder,
+        accounts.bidder_payment.as_deref(),
+        &accounts.system_program,
+        &accounts.token_program,
+    )?;
+    let escrow = recipient(
+        auction.currency,
+        &accounts.bid.to_account_info(),
+        accounts.bid_payment.as_deref(),
+    )?;
+    // a larger bid can only be left from an earlier auction
+    let top_up = price
+        .checked_sub(accounts.bid.amount)
+        .ok_or(Music3Error::StaleBid)?;
+    funds.pay(&escrow, top_up)?;
+
+    let auction_key = auction.key();
+    let bidder = accounts.bidder.key();
+    let currency = auction.currency;
+    let bid = &mut ctx.accounts.bid;
+    bid.auction = auction_key;
+    bid.bidder = bidder;
+    bid.currency = currency;
+    bid.amount = price;
+    bid.bump = ctx.bumps.bid;
+    let auction = &mut ctx.accounts.auction;
+    auction.highest_bid = price;
+    auction.highest_bidder = Some(bidder);
+    auction.end_time = end_time;
+    emit!(BidPlaced {
+        auction: auction_key,
+        bidder,
+        amount: price,
+        end_time,
+    });
+    Ok(())
+}
+
+pub(crate) fn refund_bid(ctx: Context<RefundBid>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let bid = &accounts.bid;
+    // the auction is closed once settled, when only outbid bidders are left
+    let auction = &accounts.auction;
+    if auction.owner == &crate::ID && !auction.data_is_empty() {
+        let data = auction.try_borrow_data()?;
+        let auction = Auction::try_deserialize(&mut &data[..])?;
+        require!(
+            auction.highest_bidder != Some(bid.bidder),
+            Music3Error::WinningBid
+        );
+    }
+    // lamports go back when the bid is closed
+    if bid.currency.is_some() {
+        let bid_payment = accounts
+            .bid_payment
+            .as_deref()
+            .ok_or(Music3Error::InvalidPayment)?;
+        require_keys_eq!(bid_payment.owner, bid.key(), Music3Error::InvalidPayment);
+        let to = recipient(
+            bid.currency,
+            &accounts.bidder,
+            accounts.bidder_payment.as_deref(),
+        )?;
+        let seeds: &[&[u8]] = &[
+            BID_SEED,
+            bid.auction.as_ref(),
+            bid.bidder.as_ref(),
+            &[bid.bump],
+        ];
+        escrow::release(
+            bid_payment,
+            &to,
+            &accounts.bidder,
+            &bid.to_account_info(),
+            seeds,
+            &accounts.token_program,
+        )?;
+    }
+    Ok(())
+}
+
+pub(crate) fn settle(ctx: Context<SettleAuction>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let auction = &accounts.auction;
+    require!(
+        Clock::get()?.unix_timestamp >= auction.end_time,
+        Music3Error::AuctionNotEnded
+    );
+
+    let mut proceeds = Proceeds::default();
+    let mut credited = 0;
+    if let Some(winner) = auction.highest_bidder {
+        let bid = accounts
+            .winning_bid
+            .as_deref()
+            .ok_or(Music3Error::WinningBid)?;
+        require!(
+            bid.auction == auction.key() && bid.bidder == winner,
+            Music3Error::WinningBid
+        );
+        proceeds = Proceeds::of(
+            auction.highest_bid,
+            accounts.song.royalty_bps,
+            accounts.config.fee_bps,
+        )?;
+        let auction_key = auction.key();
+        let seeds: &[&[u8]] = &[BID_SEED, auction_key.as_ref(), winner.as_ref(), &[bid.bump]];
+        let funds = Funds::escrow(
+            auction.currency,
+            bid.to_account_info(),
+            accounts.bid_payment.as_deref(),
+            seeds.iter().map(|seed| seed.to_vec()).collect(),
+            &accounts.token_program,
+        )?;
+        let revenue = Revenue {
+            song: &accounts.song,
+            creator: (&accounts.creator, accounts.creator_payment.as_deref()),
+            treasury: (&accounts.treasury, accounts.treasury_payment.as_deref()),
+            split: accounts.split.as_deref(),
+            balance: accounts.split_balance.as_deref(),
+            balance_token: accounts.split_payment.as_deref(),
+            boost_pool: accounts.boost_pool.as_deref(),
+        };
+        credited = proceeds.pay(
+            &funds,
+            auction.currency,
+            (&accounts.seller, accounts.seller_payment.as_deref()),
+            &revenue,
+        )?;
+        let receiver = accounts.receiver.to_account_info();
+        if let Some(bid_payment) = accounts.bid_payment.as_deref() {
+            escrow::close(
+                bid_payment,
+                &receiver,
+                &bid.to_account_info(),
+                seeds,
+                &accounts.token_program,
+            )?;
+        }
+        bid.close(receiver)?;
+    }
+
+    let token_mint = accounts.token_mint.key();
+    let seeds: &[&[u8]] = &[AUCTION_SEED, token_mint.as_ref(), &[auction.bump]];
+    escrow::release(
+        &accounts.vault,
+        &accounts.receiver_token.to_account_info(),
+        &accounts.seller,
+        &auction.to_account_info(),
+        seeds,
+        &accounts.token_program,
+    )?;
+    emit!(AuctionSettled {
+        auction: auction.key(),
+        song: auction.song,
+        token_mint,
+        seller: auction.seller,
+        winner: auction.highest_bidder,
+        price: auction.highest_bid,
+        royalty: proceeds.royalty,
+        fee: proceeds.fee,
+    });
+    accrue(
+        ctx.accounts.split.as_deref(),
+        ctx.accounts.split_balance.as_deref_mut(),
+        credited,
+    );
+    Ok(())
+}
@@ -0,0 +1,83 @@
+//! # 助力账户
+//!
+//! 平台把部分收益存入助力池，创作者铸造歌曲时可以让助力池承担一部分铸造费用：
+//! - 管理员通过 `set_boost` 设置助力池最多承担的比例（低于 100%，不能全额承担）和全额承担时创作者让出的收益比例；
+//! - 任何人都可以通过 `deposit_boost` 向助力池存入 SOL；
+//! - 铸造时按实际承担的比例降低创作者之后在这首歌上的收益分成，记录在 [`crate::state::Song::revenue_cut_bps`]。
+//!
+
+use crate::error::Music3Error;
+use crate::state::{BoostPool, Config, BOOST_SEED, BPS, CONFIG_SEED};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+
+/// Arguments of [`crate::music3_contract::set_boost`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BoostArgs {
+    /// Highest share of a mint cost the pool pays, in basis points, below 100%
+    pub max_ratio_bps: u16,
+    /// Share of the revenue of the creator given up for a mint fully paid by the pool, in basis points
+    pub revenue_cut_bps: u16,
+}
+
+/// Accounts of [`crate::music3_contract::set_boost`]
+#[derive(Accounts)]
+pub struct SetBoost<'info> {
+    /// Admin of the platform, paying for the pool on first use
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// Platform settings
+    #[account(has_one = admin, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    /// Boost pool
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + BoostPool::INIT_SPACE,
+        seeds = [BOOST_SEED],
+        bump,
+    )]
+    pub boost_pool: Account<'info, BoostPool>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::deposit_boost`]
+#[derive(Accounts)]
+pub struct DepositBoost<'info> {
+    /// Depositor
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+    /// Boost pool
+    #[account(mut, seeds = [BOOST_SEED], bump = boost_pool.bump)]
+    pub boost_pool: Account<'info, BoostPool>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+pub(crate) fn set(ctx: Context<SetBoost>, args: BoostArgs) -> Result<()> {
+    require!(
+        args.max_ratio_bps < BPS && args.revenue_cut_bps <= BPS,
+        Music3Error::InvalidBoostRatio
+    );
+    let pool = &mut ctx.accounts.boost_pool;
+    pool.max_ratio_bps = args.max_ratio_bps;
+    pool.revenue_cut_bps = args.revenue_cut_bps;
+    pool.bump = ctx.bumps.boost_pool;
+    Ok(())
+}
+
+pub(crate) fn deposit(ctx: Context<DepositBoost>, amount: u64) -> Result<()> {
+    system_program::transfer(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            Transfer {
+                from: ctx.accounts.depositor.to_account_info(),
+                to: ctx.accounts.boost_pool.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+    ctx.accounts.boost_pool.deposited += amount;
+    Ok(())
+}
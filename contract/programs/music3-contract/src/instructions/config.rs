@@ -0,0 +1,68 @@
+//! # 平台设置
+//!
+//! 第一次调用 `initialize` 的钱包成为管理员，之后由管理员修改平台手续费和收款钱包。
+//!
+
+use crate::error::Music3Error;
+use crate::state::{Config, BPS, CONFIG_SEED};
+use anchor_lang::prelude::*;
+
+/// Arguments of [`crate::music3_contract::initialize`] and [`crate::music3_contract::update_config`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ConfigArgs {
+    /// Wallet that may change the settings
+    pub admin: Pubkey,
+    /// Wallet receiving the platform fees
+    pub treasury: Pubkey,
+    /// Share of every sale paid to the platform, in basis points
+    pub fee_bps: u16,
+}
+
+/// Accounts of [`crate::music3_contract::initialize`]
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    /// Payer of the account
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// Platform settings
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::update_config`]
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    /// Current admin
+    pub admin: Signer<'info>,
+    /// Platform settings
+    #[account(mut, has_one = admin, seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+pub(crate) fn initialize(ctx: Context<Initialize>, args: ConfigArgs) -> Result<()> {
+    require!(args.fee_bps <= BPS, Music3Error::InvalidFee);
+    ctx.accounts.config.set_inner(Config {
+        admin: args.admin,
+        treasury: args.treasury,
+        fee_bps: args.fee_bps,
+        bump: ctx.bumps.config,
+    });
+    Ok(())
+}
+
+pub(crate) fn update(ctx: Context<UpdateConfig>, args: ConfigArgs) -> Result<()> {
+    require!(args.fee_bps <= BPS, Music3Error::InvalidFee);
+    let config = &mut ctx.accounts.config;
+    config.admin = args.admin;
+    config.treasury = args.treasury;
+    config.fee_bps = args.fee_bps;
+    Ok(())
+}
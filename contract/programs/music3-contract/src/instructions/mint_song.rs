@@ -0,0 +1,263 @@
+//! # 铸造歌曲
+//!
+//! 创建数量为 1 的 mint 并铸造到创作者的关联代币账户，再通过 Metaplex 创建元数据和主版本（master edition）：
+//! - 元数据的 URI 为音频的存储 URI，版税为 `royalty_bps`；
+//! - 创作者签名并作为唯一的已验证创作者（verified creator），同时是元数据的更新权限；
+//! - 主版本最多可以印制 `max_supply` 个版本，铸造权限随之转移给主版本账户。
+//!
+//! 内容哈希、版税等记录在歌曲的 [`Song`] 账户中，供之后的交易使用。
+//!
+//! 创作者可以通过 `boost_bps` 请求助力池（见 [`crate::instructions::boost`]）承担一部分铸造费用，即本次创建的各个账户按大小计算的
+//! 免租金额（不看账户的实际余额，以免预先转入的 SOL 被计入），比例不超过助力池设定的上限。承担的比例决定这首歌之后的收益分成调整。
+//!
+
+use crate::error::Music3Error;
+use crate::state::{
+    share, BoostPool, Song, BOOST_SEED, BPS, MAX_NAME_LEN, MAX_SYMBOL_LEN, MAX_URI_LEN, SONG_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::metadata::mpl_token_metadata::types::{Creator, DataV2};
+use anchor_spl::metadata::{
+    create_master_edition_v3, create_metadata_accounts_v3, CreateMasterEditionV3,
+    CreateMetadataAccountsV3, Metadata,
+};
+use anchor_spl::token::{mint_to, Mint, MintTo, Token, TokenAccount};
+
+/// Arguments of [`crate::music3_contract::mint_song`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MintSongArgs {
+    /// Name of the NFT, usually the title
+    pub name: String,
+    /// Symbol of the NFT
+    pub symbol: String,
+    /// Storage URI of the track
+    pub uri: String,
+    /// SHA-256 of the track content
+    pub content_hash: [u8; 32],
+    /// Share of every resale paid to the creator, in basis points
+    pub royalty_bps: u16,
+    /// Editions that may be printed, 0 for a one of one
+    pub max_supply: u64,
+    /// Share of the mint cost requested from the boost pool, in basis points
+    pub boost_bps: u16,
+}
+
+/// Accounts of [`crate::music3_contract::mint_song`]
+#[derive(Accounts)]
+pub struct MintSong<'info> {
+    /// Creator paying for the accounts
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    /// New mint, a fresh keypair
+    #[account(
+        init,
+        payer = creator,
+        mint::decimals = 0,
+        mint::authority = creator,
+        mint::freeze_authority = creator,
+    )]
+    pub mint: Box<Account<'info, Mint>>,
+    /// Token account of the creator receiving the NFT
+    #[account(
+        init,
+        payer = creator,
+        associated_token::mint = mint,
+        associated_token::authority = creator,
+    )]
+    pub token: Box<Account<'info, TokenAccount>>,
+    /// Song record
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + Song::INIT_SPACE,
+        seeds = [SONG_SEED, mint.key().as_ref()],
+        bump,
+    )]
+    pub song: Box<Account<'info, Song>>,
+    /// CHECK: created by the token metadata program
+    #[account(
+        mut,
+        seeds = [b"metadata", token_metadata_program.key().as_ref(), mint.key().as_ref()],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    pub metadata: UncheckedAccount<'info>,
+    /// CHECK: created by the token metadata program
+    #[account(
+        mut,
+        seeds = [
+            b"metadata",
+            token_metadata_program.key().as_ref(),
+            mint.key().as_ref(),
+            b"edition",
+        ],
+        bump,
+        seeds::program = token_metadata_program.key(),
+    )]
+    pub master_edition: UncheckedAccount<'info>,
+    /// Boost pool, required if a share of the mint cost is requested from it
+    #[account(mut, seeds = [BOOST_SEED], bump = boost_pool.bump)]
+    pub boost_pool: Option<Box<Account<'info, BoostPool>>>,
+    /// Metaplex token metadata program
+    pub token_metadata_program: Program<'info, Metadata>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+    /// Rent sysvar, still required by the token metadata program
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// A song was minted
+#[event]
+pub struct SongMinted {
+    /// Song record
+    pub song: Pubkey,
+    /// Mint of the master edition
+    pub mint: Pubkey,
+    /// Creator
+    pub creator: Pubkey,
+    /// SHA-256 of the track content
+    pub content_hash: [u8; 32],
+    /// Lamports of the mint cost paid by the boost pool
+    pub subsidy: u64,
+}
+
+pub(crate) fn handler(ctx: Context<MintSong>, args: MintSongArgs) -> Result<()> {
+    require!(args.name.len() <= MAX_NAME_LEN, Music3Error::NameTooLong);
+    require!(
+        args.symbol.len() <= MAX_SYMBOL_LEN,
+        Music3Error::SymbolTooLong
+    );
+    require!(args.uri.len() <= MAX_URI_LEN, Music3Error::UriTooLong);
+    require!(args.royalty_bps <= BPS, Music3Error::InvalidRoyalty);
+
+    let accounts = &ctx.accounts;
+    mint_to(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            MintTo {
+                mint: accounts.mint.to_account_info(),
+                to: accounts.token.to_account_info(),
+                authority: accounts.creator.to_account_info(),
+            },
+        ),
+        1,
+    )?;
+
+    let data = DataV2 {
+        name: args.name,
+        symbol: args.symbol,
+        uri: args.uri.clone(),
+        seller_fee_basis_points: args.royalty_bps,
+        creators: Some(vec![Creator {
+            address: accounts.creator.key(),
+            verified: true,
+            share: 100,
+        }]),
+        collection: None,
+        uses: None,
+    };
+    create_metadata_accounts_v3(
+        CpiContext::new(
+            accounts.token_metadata_program.to_account_info(),
+            CreateMetadataAccountsV3 {
+                metadata: accounts.metadata.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                mint_authority: accounts.creator.to_account_info(),
+                payer: accounts.creator.to_account_info(),
+                update_authority: accounts.creator.to_account_info(),
+                system_program: accounts.system_program.to_account_info(),
+                rent: accounts.rent.to_account_info(),
+            },
+        ),
+        data,
+        true,
+        true,
+        None,
+    )?;
+    create_master_edition_v3(
+        CpiContext::new(
+            accounts.token_metadata_program.to_account_info(),
+            CreateMasterEditionV3 {
+                edition: accounts.master_edition.to_account_info(),
+                mint: accounts.mint.to_account_info(),
+                update_authority: accounts.creator.to_account_info(),
+                mint_authority: accounts.creator.to_account_info(),
+                payer: accounts.creator.to_account_info(),
+                metadata: accounts.metadata.to_account_info(),
+                token_program: accounts.token_program.to_account_info(),
+                system_program: accounts.system_program.to_account_info(),
+                rent: accounts.rent.to_account_info(),
+            },
+        ),
+        Some(args.max_supply),
+    )?;
+
+    let (subsidy, revenue_cut_bps) = boost(ctx.accounts, args.boost_bps)?;
+    if let Some(pool) = ctx.accounts.boost_pool.as_deref_mut() {
+        pool.paid += subsidy;
+    }
+    let song = &mut ctx.accounts.song;
+    song.set_inner(Song {
+        creator: ctx.accounts.creator.key(),
+        mint: ctx.accounts.mint.key(),
+        uri: args.uri,
+        content_hash: args.content_hash,
+        royalty_bps: args.royalty_bps,
+        max_supply: args.max_supply,
+        printed: 0,
+        created_at: Clock::get()?.unix_timestamp,
+        boost_bps: args.boost_bps,
+        revenue_cut_bps,
+        has_split: false,
+        bump: ctx.bumps.song,
+    });
+    emit!(SongMinted {
+        song: song.key(),
+        mint: song.mint,
+        creator: song.creator,
+        content_hash: song.content_hash,
+        subsidy,
+    });
+    Ok(())
+}
+
+/// Pay `ratio_bps` of the mint cost to the creator out of the boost pool,
+/// returning the subsidy and the revenue cut of the song
+fn boost(accounts: &MintSong, ratio_bps: u16) -> Result<(u64, u16)> {
+    if ratio_bps == 0 {
+        return Ok((0, 0));
+    }
+    let pool = accounts
+        .boost_pool
+        .as_deref()
+        .ok_or(Music3Error::InsufficientBoost)?;
+    require!(ratio_bps <= pool.max_ratio_bps, Music3Error::SubsidyTooHigh);
+    // rent of the new accounts by size, as their balances may have been topped up beforehand
+    let rent = Rent::get()?;
+    let cost = [
+        accounts.mint.to_account_info(),
+        accounts.token.to_account_info(),
+        accounts.song.to_account_info(),
+        accounts.metadata.to_account_info(),
+        accounts.master_edition.to_account_info(),
+    ]
+    .iter()
+    .map(|account| rent.minimum_balance(account.data_len()))
+    .sum();
+    let subsidy = share(cost, ratio_bps);
+
+    let pool_info = pool.to_account_info();
+    let reserved = rent.minimum_balance(pool_info.data_len());
+    require!(
+        pool_info.lamports().saturating_sub(reserved) >= subsidy,
+        Music3Error::InsufficientBoost
+    );
+    **pool_info.try_borrow_mut_lamports()? -= subsidy;
+    **accounts.creator.try_borrow_mut_lamports()? += subsidy;
+    Ok((subsidy, pool.revenue_cut(ratio_bps)))
+}
@@ -0,0 +1,18 @@
+//! # Instructions
+//!
+
+pub mod auction;
+pub mod boost;
+pub mod config;
+pub mod mint_song;
+pub mod primary;
+pub mod secondary;
+pub mod split;
+
+pub use auction::*;
+pub use boost::*;
+pub use config::*;
+pub use mint_song::*;
+pub use primary::*;
+pub use secondary::*;
+pub use split::*;
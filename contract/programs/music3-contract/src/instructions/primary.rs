@@ -0,0 +1,377 @@
+//! # 一级市场
+//!
+//! 创作者以固定价格出售歌曲的版本（edition）：
+//! - `list_primary`：设置价格、币种（SOL 或 SPL 代币）和出售的版本数量，主版本代币第一次挂单时转入挂单账户托管，
+//!   再次调用可以修改价格和数量，数量为 0 时停止出售；
+//! - `delist_primary`：停止出售，把主版本代币还给创作者并关闭挂单；
+//! - `buy_primary`：买家付款后由挂单账户签名，从主版本印制一个新的版本给买家。价格高于买家给出的 `max_price`
+//!   或币种不是买家给出的 `expected_currency` 时交易失败，防止创作者在买家下单前提高价格或更换币种。
+//!   扣除 [`Config::fee_bps`] 的平台手续费后，货款直接转给创作者。
+//!
+
+use crate::error::Music3Error;
+use crate::escrow;
+use crate::payment::{accrue, recipient, Funds, Revenue};
+use crate::state::{
+    BoostPool, Config, PrimaryListing, Song, Split, SplitBalance, BOOST_SEED, CONFIG_SEED,
+    PRIMARY_SEED, SONG_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::metadata::{
+    mint_new_edition_from_master_edition_via_token, Metadata,
+    MintNewEditionFromMasterEditionViaToken,
+};
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+/// Arguments of [`crate::music3_contract::list_primary`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ListPrimaryArgs {
+    /// Price of an edition, in lamports or the smallest unit of the currency
+    pub price: u64,
+    /// SPL token mint the price is paid in, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Editions for sale
+    pub supply: u64,
+}
+
+/// Accounts of [`crate::music3_contract::list_primary`]
+#[derive(Accounts)]
+pub struct ListPrimary<'info> {
+    /// Creator of the song
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    /// Song for sale
+    #[account(has_one = creator, seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Mint of the master edition
+    #[account(address = song.mint)]
+    pub mint: Box<Account<'info, Mint>>,
+    /// Token account of the creator holding the master edition before the first listing
+    #[account(mut, token::mint = mint, token::authority = creator)]
+    pub creator_token: Box<Account<'info, TokenAccount>>,
+    /// Listing of the song
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + PrimaryListing::INIT_SPACE,
+        seeds = [PRIMARY_SEED, song.key().as_ref()],
+        bump,
+    )]
+    pub listing: Box<Account<'info, PrimaryListing>>,
+    /// Token account of the listing holding the master edition
+    #[account(
+        init_if_needed,
+        payer = creator,
+        associated_token::mint = mint,
+        associated_token::authority = listing,
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::delist_primary`]
+#[derive(Accounts)]
+pub struct DelistPrimary<'info> {
+    /// Creator of the song
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    /// Song for sale
+    #[account(has_one = creator, seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Mint of the master edition
+    #[account(address = song.mint)]
+    pub mint: Box<Account<'info, Mint>>,
+    /// Listing of the song, closed to the creator
+    #[account(
+        mut,
+        has_one = song,
+        has_one = creator,
+        close = creator,
+        seeds = [PRIMARY_SEED, song.key().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Box<Account<'info, PrimaryListing>>,
+    /// Token account of the listing holding the master edition
+    #[account(mut, associated_token::mint = mint, associated_token::authority = listing)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// Token account of the creator receiving the master edition
+    #[account(
+        init_if_needed,
+        payer = creator,
+        associated_token::mint = mint,
+        associated_token::authority = creator,
+    )]
+    pub creator_token: Box<Account<'info, TokenAccount>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::buy_primary`]
+#[derive(Accounts)]
+pub struct BuyPrimary<'info> {
+    /// Buyer paying for the edition and its accounts
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    /// Platform settings
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+    /// Song for sale
+    #[account(mut, seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Listing of the song
+    #[account(
+        mut,
+        has_one = song,
+        has_one = creator,
+        seeds = [PRIMARY_SEED, song.key().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Box<Account<'info, PrimaryListing>>,
+    /// CHECK: wallet of the creator, checked against the listing
+    #[account(mut)]
+    pub creator: UncheckedAccount<'info>,
+    /// CHECK: wallet of the platform, checked against the settings
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    /// Mint of the master edition
+    #[account(address = song.mint)]
+    pub master_mint: Box<Account<'info, Mint>>,
+    /// CHECK: metadata of the master edition, checked by the token metadata program
+    pub master_metadata: UncheckedAccount<'info>,
+    /// CHECK: master edition, checked by the token metadata program
+    #[account(mut)]
+    pub master_edition: UncheckedAccount<'info>,
+    /// Token account of the listing holding the master edition
+    #[account(associated_token::mint = master_mint, associated_token::authority = listing)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// Mint of the new edition, a fresh keypair
+    #[account(
+        init,
+        payer = buyer,
+        mint::decimals = 0,
+        mint::authority = buyer,
+        mint::freeze_authority = buyer,
+    )]
+    pub edition_mint: Box<Account<'info, Mint>>,
+    /// Token account of the buyer receiving the new edition
+    #[account(
+        init,
+        payer = buyer,
+        associated_token::mint = edition_mint,
+        associated_token::authority = buyer,
+    )]
+    pub edition_token: Box<Account<'info, TokenAccount>>,
+    /// CHECK: created by the token metadata program
+    #[account(mut)]
+    pub edition_metadata: UncheckedAccount<'info>,
+    /// CHECK: created by the token metadata program
+    #[account(mut)]
+    pub edition: UncheckedAccount<'info>,
+    /// CHECK: edition marker of the edition number, checked by the token metadata program
+    #[account(mut)]
+    pub edition_marker: UncheckedAccount<'info>,
+    /// Token account of the buyer paying an SPL token price
+    #[account(mut)]
+    pub buyer_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the creator receiving an SPL token price
+    #[account(mut)]
+    pub creator_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the platform receiving an SPL token fee
+    #[account(mut)]
+    pub treasury_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Metaplex token metadata program
+    pub token_metadata_program: Program<'info, Metadata>,
+    /// Revenue split of the song, required if it has one
+    pub split: Option<Box<Account<'info, Split>>>,
+    /// Balance of the split in the currency of the sale
+    #[account(mut)]
+    pub split_balance: Option<Box<Account<'info, SplitBalance>>>,
+    /// Associated token account of the split balance receiving SPL token revenue
+    #[account(mut)]
+    pub split_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Boost pool receiving the revenue cut of a boosted song paid in SOL
+    #[account(mut, seeds = [BOOST_SEED], bump = boost_pool.bump)]
+    pub boost_pool: Option<Box<Account<'info, BoostPool>>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+    /// Rent sysvar, still required by the token metadata program
+    pub rent: Sysvar<'info, Rent>,
+}
+
+/// An edition was sold by its creator
+#[event]
+pub struct PrimarySale {
+    /// Song sold
+    pub song: Pubkey,
+    /// Mint of the new edition
+    pub edition_mint: Pubkey,
+    /// Edition number
+    pub edition: u64,
+    /// Buyer
+    pub buyer: Pubkey,
+    /// Price paid
+    pub price: u64,
+    /// Platform fee out of the price
+    pub fee: u64,
+}
+
+pub(crate) fn list(ctx: Context<ListPrimary>, args: ListPrimaryArgs) -> Result<()> {
+    let song = &ctx.accounts.song;
+    let listed = song
+        .printed
+        .checked_add(args.supply)
+        .ok_or(Music3Error::SupplyExceeded)?;
+    require!(listed <= song.max_supply, Music3Error::SupplyExceeded);
+
+    if ctx.accounts.vault.amount == 0 {
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                Transfer {
+                    from: ctx.accounts.creator_token.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.creator.to_account_info(),
+                },
+            ),
+            1,
+        )?;
+    }
+    let listing = &mut ctx.accounts.listing;
+    listing.song = song.key();
+    listing.creator = song.creator;
+    listing.price = args.price;
+    listing.currency = args.currency;
+    listing.supply = args.supply;
+    listing.bump = ctx.bumps.listing;
+    Ok(())
+}
+
+pub(crate) fn delist(ctx: Context<DelistPrimary>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let song = accounts.song.key();
+    let seeds: &[&[u8]] = &[PRIMARY_SEED, song.as_ref(), &[accounts.listing.bump]];
+    escrow::release(
+        &accounts.vault,
+        &accounts.creator_token.to_account_info(),
+        &accounts.creator.to_account_info(),
+        &accounts.listing.to_account_info(),
+        seeds,
+        &accounts.token_program,
+    )
+}
+
+pub(crate) fn buy(
+    ctx: Context<BuyPrimary>,
+    max_price: u64,
+    expected_currency: Option<Pubkey>,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let listing = &accounts.listing;
+    require!(listing.supply > 0, Music3Error::SoldOut);
+    require!(listing.price <= max_price, Music3Error::PriceTooHigh);
+    require!(
+        listing.currency == expected_currency,
+        Music3Error::CurrencyMismatch
+    );
+
+    let funds = Funds::new(
+        listing.currency,
+        &accounts.buyer,
+        accounts.buyer_payment.as_deref(),
+        &accounts.system_program,
+        &accounts.token_program,
+    )?;
+    let revenue = Revenue {
+        song: &accounts.song,
+        creator: (&accounts.creator, accounts.creator_payment.as_deref()),
+        treasury: (&accounts.treasury, accounts.treasury_payment.as_deref()),
+        split: accounts.split.as_deref(),
+        balance: accounts.split_balance.as_deref(),
+        balance_token: accounts.split_payment.as_deref(),
+        boost_pool: accounts.boost_pool.as_deref(),
+    };
+    let fee = accounts.config.fee(listing.price);
+    if fee > 0 {
+        let treasury = recipient(
+            listing.currency,
+            &accounts.treasury.to_account_info(),
+            accounts.treasury_payment.as_deref(),
+        )?;
+        funds.pay(&treasury, fee)?;
+    }
+    let credited = revenue.pay(&funds, listing.currency, listing.price - fee)?;
+
+    // Metaplex prints only onto a mint with exactly one token
+    token::mint_to(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token::MintTo {
+                mint: accounts.edition_mint.to_account_info(),
+                to: accounts.edition_token.to_account_info(),
+                authority: accounts.buyer.to_account_info(),
+            },
+        ),
+        1,
+    )?;
+    let edition = accounts.song.printed + 1;
+    let song = accounts.song.key();
+    let seeds: &[&[u8]] = &[PRIMARY_SEED, song.as_ref(), &[listing.bump]];
+    mint_new_edition_from_master_edition_via_token(
+        CpiContext::new_with_signer(
+            accounts.token_metadata_program.to_account_info(),
+            MintNewEditionFromMasterEditionViaToken {
+                new_metadata: accounts.edition_metadata.to_account_info(),
+                new_edition: accounts.edition.to_account_info(),
+                master_edition: accounts.master_edition.to_account_info(),
+                new_mint: accounts.edition_mint.to_account_info(),
+                edition_mark_pda: accounts.edition_marker.to_account_info(),
+                new_mint_authority: accounts.buyer.to_account_info(),
+                payer: accounts.buyer.to_account_info(),
+                token_account_owner: listing.to_account_info(),
+                token_account: accounts.vault.to_account_info(),
+                new_metadata_update_authority: accounts.creator.to_account_info(),
+                metadata: accounts.master_metadata.to_account_info(),
+                token_program: accounts.token_program.to_account_info(),
+                system_program: accounts.system_program.to_account_info(),
+                rent: accounts.rent.to_account_info(),
+                metadata_mint: accounts.master_mint.to_account_info(),
+            },
+            &[seeds],
+        ),
+        edition,
+    )?;
+    let price = listing.price;
+
+    let listing = &mut ctx.accounts.listing;
+    listing.supply -= 1;
+    listing.sold += 1;
+    ctx.accounts.song.printed = edition;
+    accrue(
+        ctx.accounts.split.as_deref(),
+        ctx.accounts.split_balance.as_deref_mut(),
+        credited,
+    );
+    emit!(PrimarySale {
+        song,
+        edition_mint: ctx.accounts.edition_mint.key(),
+        edition,
+        buyer: ctx.accounts.buyer.key(),
+        price,
+        fee,
+    });
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! # 二级市场
+//!
+//! 持有者以固定价格转售歌曲的主版本或印制的版本，代币在挂单账户中托管：
+//! - `list`：把一个代币转入托管并设置价格和币种，同一首歌可以陆续挂出多个版本，价格和币种对全部挂出的版本生效；
+//! - `set_price`：修改价格；
+//! - `delist`：取回一个代币，挂单清空时关闭；
+//! - `buy`：买下一个版本，其余版本继续出售。买家给出可接受的最高价格和币种，防止挂单在成交前被抬价或更换币种。
+//!
+//! 成交价按铸造时记录的 [`Song::royalty_bps`] 支付版税给创作者，按 [`Config::fee_bps`] 支付平台手续费，其余归卖家。
+//! 挂单时通过 Metaplex 的版本账户确认代币属于这首歌，因此无法换用版税更低的歌曲绕过版税。
+//!
+
+use crate::error::Music3Error;
+use crate::escrow;
+use crate::payment::{accrue, Funds, Proceeds, Revenue};
+use crate::state::{
+    BoostPool, Config, Listing, Song, Split, SplitBalance, BOOST_SEED, CONFIG_SEED, LISTING_SEED,
+    SONG_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+/// Arguments of [`crate::music3_contract::list`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ListArgs {
+    /// Price of a token, in lamports or the smallest unit of the currency
+    pub price: u64,
+    /// SPL token mint the price is paid in, SOL if not set
+    pub currency: Option<Pubkey>,
+}
+
+/// Accounts of [`crate::music3_contract::list`]
+#[derive(Accounts)]
+pub struct List<'info> {
+    /// Holder of the token
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    /// Song of the token
+    #[account(seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Mint of the token
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// CHECK: edition account of the token, checked against the song
+    pub token_edition: UncheckedAccount<'info>,
+    /// Token account of the seller holding the token
+    #[account(mut, token::mint = token_mint, token::authority = seller)]
+    pub seller_token: Box<Account<'info, TokenAccount>>,
+    /// Listing of the seller
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + Listing::INIT_SPACE,
+        seeds = [LISTING_SEED, song.key().as_ref(), seller.key().as_ref()],
+        bump,
+    )]
+    pub listing: Box<Account<'info, Listing>>,
+    /// Token account of the listing holding the token
+    #[account(
+        init,
+        payer = seller,
+        associated_token::mint = token_mint,
+        associated_token::authority = listing,
+    )]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::set_price`]
+#[derive(Accounts)]
+pub struct SetPrice<'info> {
+    /// Seller
+    pub seller: Signer<'info>,
+    /// Listing of the seller
+    #[account(
+        mut,
+        has_one = seller,
+        seeds = [LISTING_SEED, listing.song.as_ref(), seller.key().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Account<'info, Listing>,
+}
+
+/// Accounts of [`crate::music3_contract::delist`]
+#[derive(Accounts)]
+pub struct Delist<'info> {
+    /// Seller
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    /// Listing of the seller
+    #[account(
+        mut,
+        has_one = seller,
+        seeds = [LISTING_SEED, listing.song.as_ref(), seller.key().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Box<Account<'info, Listing>>,
+    /// Mint of the token
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// Token account of the listing holding the token
+    #[account(mut, associated_token::mint = token_mint, associated_token::authority = listing)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// Token account of the seller receiving the token
+    #[account(
+        init_if_needed,
+        payer = seller,
+        associated_token::mint = token_mint,
+        associated_token::authority = seller,
+    )]
+    pub seller_token: Box<Account<'info, TokenAccount>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::buy`]
+#[derive(Accounts)]
+pub struct Buy<'info> {
+    /// Buyer paying for the token
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    /// Platform settings
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+    /// Song of the token
+    #[account(seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Listing holding the token
+    #[account(
+        mut,
+        has_one = song,
+        has_one = seller,
+        seeds = [LISTING_SEED, song.key().as_ref(), seller.key().as_ref()],
+        bump = listing.bump,
+    )]
+    pub listing: Box<Account<'info, Listing>>,
+    /// CHECK: wallet of the seller, checked against the listing
+    #[account(mut)]
+    pub seller: UncheckedAccount<'info>,
+    /// CHECK: wallet of the creator, checked against the song
+    #[account(mut, address = song.creator)]
+    pub creator: UncheckedAccount<'info>,
+    /// CHECK: wallet of the platform, checked against the settings
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    /// Mint of the token
+    pub token_mint: Box<Account<'info, Mint>>,
+    /// Token account of the listing holding the token
+    #[account(mut, associated_token::mint = token_mint, associated_token::authority = listing)]
+    pub vault: Box<Account<'info, TokenAccount>>,
+    /// Token account of the buyer receiving the token
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = token_mint,
+        associated_token::authority = buyer,
+    )]
+    pub buyer_token: Box<Account<'info, TokenAccount>>,
+    /// Token account of the buyer paying an SPL token price
+    #[account(mut)]
+    pub buyer_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the seller receiving an SPL token price
+    #[account(mut)]
+    pub seller_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the creator receiving an SPL token royalty
+    #[account(mut)]
+    pub creator_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the platform receiving an SPL token fee
+    #[account(mut)]
+    pub treasury_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Revenue split of the song, required if it has one
+    pub split: Option<Box<Account<'info, Split>>>,
+    /// Balance of the split in the currency of the sale
+    #[account(mut)]
+    pub split_balance: Option<Box<Account<'info, SplitBalance>>>,
+    /// Associated token account of the split balance receiving SPL token revenue
+    #[account(mut)]
+    pub split_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Boost pool receiving the revenue cut of a boosted song paid in SOL
+    #[account(mut, seeds = [BOOST_SEED], bump = boost_pool.bump)]
+    pub boost_pool: Option<Box<Account<'info, BoostPool>>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// A token was resold
+#[event]
+pub struct SecondarySale {
+    /// Song of the token
+    pub song: Pubkey,
+    /// Mint of the token
+    pub token_mint: Pubkey,
+    /// Seller
+    pub seller: Pubkey,
+    /// Buyer
+    pub buyer: Pubkey,
+    /// Price paid
+    pub price: u64,
+    /// Royalty paid to the creator out of the price
+    pub royalty: u64,
+    /// Platform fee out of the price
+    pub fee: u64,
+}
+
+pub(crate) fn list(ctx: Context<List>, args: ListArgs) -> Result<()> {
+    let accounts = &ctx.accounts;
+    accounts
+        .song
+        .check_token(&accounts.token_mint.key(), &accounts.token_edition)?;
+    token::transfer(
+        CpiContext::new(
+            accounts.token_program.to_account_info(),
+            Transfer {
+                from: accounts.seller_token.to_account_info(),
+                to: accounts.vault.to_account_info(),
+                authority: accounts.seller.to_account_info(),
+            },
+        ),
+        1,
+    )?;
+    let listing = &mut ctx.accounts.listing;
+    listing.seller = ctx.accounts.seller.key();
+    listing.song = ctx.accounts.song.key();
+    listing.price = args.price;
+    listing.currency = args.currency;
+    listing.quantity += 1;
+    listing.bump = ctx.bumps.listing;
+    Ok(())
+}
+
+pub(crate) fn set_price(ctx: Context<SetPrice>, price: u64) -> Result<()> {
+    ctx.accounts.listing.price = price;
+    Ok(())
+}
+
+pub(crate) fn delist(ctx: Context<Delist>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    release(
+        &accounts.listing,
+        &accounts.vault,
+        &accounts.seller_token.to_account_info(),
+        &accounts.seller.to_account_info(),
+        &accounts.token_program,
+    )?;
+    close_if_empty(&mut ctx.accounts.listing, &ctx.accounts.seller)
+}
+
+pub(crate) fn buy(
+    ctx: Context<Buy>,
+    max_price: u64,
+    expected_currency: Option<Pubkey>,
+) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let listing = &accounts.listing;
+    require!(listing.price <= max_price, Music3Error::PriceTooHigh);
+    require!(
+        listing.currency == expected_currency,
+        Music3Error::CurrencyMismatch
+    );
+
+    let proceeds = Proceeds::of(
+        listing.price,
+        accounts.song.royalty_bps,
+        accounts.config.fee_bps,
+    )?;
+    let funds = Funds::new(
+        listing.currency,
+        &accounts.buyer,
+        accounts.buyer_payment.as_deref(),
+        &accounts.system_program,
+        &accounts.token_program,
+    )?;
+    let revenue = Revenue {
+        song: &accounts.song,
+        creator: (&accounts.creator, accounts.creator_payment.as_deref()),
+        treasury: (&accounts.treasury, accounts.treasury_payment.as_deref()),
+        split: accounts.split.as_deref(),
+        balance: accounts.split_balance.as_deref(),
+        balance_token: accounts.split_payment.as_deref(),
+        boost_pool: accounts.boost_pool.as_deref(),
+    };
+    let credited = proceeds.pay(
+        &funds,
+        listing.currency,
+        (&accounts.seller, accounts.seller_payment.as_deref()),
+        &revenue,
+    )?;
+    release(
+        listing,
+        &accounts.vault,
+        &accounts.buyer_token.to_account_info(),
+        &accounts.seller.to_account_info(),
+        &accounts.token_program,
+    )?;
+    emit!(SecondarySale {
+        song: accounts.song.key(),
+        token_mint: accounts.token_mint.key(),
+        seller: listing.seller,
+        buyer: accounts.buyer.key(),
+        price: listing.price,
+        royalty: proceeds.royalty,
+        fee: proceeds.fee,
+    });
+    accrue(
+        ctx.accounts.split.as_deref(),
+        ctx.accounts.split_balance.as_deref_mut(),
+        credited,
+    );
+    let seller = ctx.accounts.seller.to_account_info();
+    close_if_empty(&mut ctx.accounts.listing, &seller)
+}
+
+/// Move the token out of the vault to `to`, closing the vault to the seller
+fn release<'info>(
+    listing: &Account<'info, Listing>,
+    vault: &Account<'info, TokenAccount>,
+    to: &AccountInfo<'info>,
+    seller: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    let seeds: &[&[u8]] = &[
+        LISTING_SEED,
+        listing.song.as_ref(),
+        listing.seller.as_ref(),
+        &[listing.bump],
+    ];
+    escrow::release(
+        vault,
+        to,
+        seller,
+        &listing.to_account_info(),
+        seeds,
+        token_program,
+    )
+}
+
+/// Count the token out of the listing, closing it to the seller once empty
+fn close_if_empty<'info>(
+    listing: &mut Account<'info, Listing>,
+    seller: &AccountInfo<'info>,
+) -> Result<()> {
+    listing.quantity -= 1;
+    if listing.quantity == 0 {
+        listing.close(seller.clone())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,315 @@
+//! # 收益分成
+//!
+//! 歌曲往往由制作人、合唱歌手、作词人等多人完成，创作者可以为歌曲创建一次收益分成（[`Split`]），
+//! 最多 [`MAX_SPLIT_RECIPIENTS`] 个合作者按基点分享创作者的收入。分成创建后不能修改，创作者无法收回合作者的份额。
+//!
+//! 一级市场的售价、转售和拍卖的版税以及 `tip` 打赏都先扣除助力调整（见 [`crate::instructions::boost`]），
+//! 其余记入分成在该币种的余额（[`SplitBalance`]），合作者通过 `claim` 领取自己累计的部分：
+//! - 创建分成时同时开设 SOL 余额，SPL 代币的余额需要先通过 `open_split_balance` 开设；
+//! - SOL 存放在余额账户本身，SPL 代币存放在余额账户的关联代币账户。
+//!
+
+use crate::error::Music3Error;
+use crate::payment::{accrue, recipient, Funds, Revenue};
+use crate::state::{
+    BoostPool, Config, Recipient, Song, Split, SplitBalance, BOOST_SEED, BPS, CONFIG_SEED,
+    MAX_SPLIT_RECIPIENTS, SONG_SEED, SPLIT_BALANCE_SEED, SPLIT_SEED,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+/// Arguments of [`crate::music3_contract::create_split`]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CreateSplitArgs {
+    /// Collaborators, whose shares add up to 100%
+    pub recipients: Vec<Recipient>,
+}
+
+/// Accounts of [`crate::music3_contract::create_split`]
+#[derive(Accounts)]
+pub struct CreateSplit<'info> {
+    /// Creator of the song, paying for the accounts
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    /// Song to share
+    #[account(mut, has_one = creator, seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// Split of the song
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + Split::INIT_SPACE,
+        seeds = [SPLIT_SEED, song.key().as_ref()],
+        bump,
+    )]
+    pub split: Box<Account<'info, Split>>,
+    /// SOL balance of the split
+    #[account(
+        init,
+        payer = creator,
+        space = 8 + SplitBalance::INIT_SPACE,
+        seeds = [SPLIT_BALANCE_SEED, split.key().as_ref(), Pubkey::default().as_ref()],
+        bump,
+    )]
+    pub balance: Box<Account<'info, SplitBalance>>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::open_split_balance`]
+#[derive(Accounts)]
+pub struct OpenSplitBalance<'info> {
+    /// Anyone paying for the accounts
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    /// Split
+    pub split: Box<Account<'info, Split>>,
+    /// SPL token mint of the balance
+    pub currency: Box<Account<'info, Mint>>,
+    /// Balance of the split in the currency
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + SplitBalance::INIT_SPACE,
+        seeds = [SPLIT_BALANCE_SEED, split.key().as_ref(), currency.key().as_ref()],
+        bump,
+    )]
+    pub balance: Box<Account<'info, SplitBalance>>,
+    /// Associated token account of the balance holding the tokens
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = currency,
+        associated_token::authority = balance,
+    )]
+    pub balance_token: Box<Account<'info, TokenAccount>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// Associated token account program
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts of [`crate::music3_contract::claim`]
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    /// Recipient of the split
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    /// Split
+    pub split: Box<Account<'info, Split>>,
+    /// Balance of the split in the currency claimed
+    #[account(
+        mut,
+        has_one = split,
+        seeds = [
+            SPLIT_BALANCE_SEED,
+            split.key().as_ref(),
+            balance.currency.unwrap_or_default().as_ref(),
+        ],
+        bump = balance.bump,
+    )]
+    pub balance: Box<Account<'info, SplitBalance>>,
+    /// Associated token account of the balance holding an SPL token balance
+    #[account(mut)]
+    pub balance_token: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the recipient receiving an SPL token balance
+    #[account(mut)]
+    pub recipient_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+}
+
+/// Accounts of [`crate::music3_contract::tip`]
+#[derive(Accounts)]
+pub struct Tip<'info> {
+    /// Listener tipping
+    #[account(mut)]
+    pub tipper: Signer<'info>,
+    /// Platform settings
+    #[account(seeds = [CONFIG_SEED], bump = config.bump)]
+    pub config: Box<Account<'info, Config>>,
+    /// Song tipped for
+    #[account(seeds = [SONG_SEED, song.mint.as_ref()], bump = song.bump)]
+    pub song: Box<Account<'info, Song>>,
+    /// CHECK: wallet of the creator, checked against the song
+    #[account(mut, address = song.creator)]
+    pub creator: UncheckedAccount<'info>,
+    /// CHECK: wallet of the platform, checked against the settings
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    /// Token account of the tipper paying an SPL token tip
+    #[account(mut)]
+    pub tipper_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the creator receiving an SPL token tip
+    #[account(mut)]
+    pub creator_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Token account of the platform receiving the revenue cut of a boosted song in an SPL token
+    #[account(mut)]
+    pub treasury_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Revenue split of the song, required if it has one
+    pub split: Option<Box<Account<'info, Split>>>,
+    /// Balance of the split in the currency of the tip
+    #[account(mut)]
+    pub split_balance: Option<Box<Account<'info, SplitBalance>>>,
+    /// Associated token account of the split balance receiving SPL token revenue
+    #[account(mut)]
+    pub split_payment: Option<Box<Account<'info, TokenAccount>>>,
+    /// Boost pool receiving the revenue cut of a boosted song paid in SOL
+    #[account(mut, seeds = [BOOST_SEED], bump = boost_pool.bump)]
+    pub boost_pool: Option<Box<Account<'info, BoostPool>>>,
+    /// SPL token program
+    pub token_program: Program<'info, Token>,
+    /// System program
+    pub system_program: Program<'info, System>,
+}
+
+/// A recipient claimed its balance
+#[event]
+pub struct SplitClaimed {
+    /// Split
+    pub split: Pubkey,
+    /// Recipient
+    pub recipient: Pubkey,
+    /// SPL token mint of the balance, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Amount claimed
+    pub amount: u64,
+}
+
+/// A listener tipped the creator of a song
+#[event]
+pub struct Tipped {
+    /// Song tipped for
+    pub song: Pubkey,
+    /// Listener
+    pub tipper: Pubkey,
+    /// SPL token mint of the tip, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Amount tipped
+    pub amount: u64,
+}
+
+pub(crate) fn create(ctx: Context<CreateSplit>, args: CreateSplitArgs) -> Result<()> {
+    let recipients = args.recipients;
+    require!(
+        !recipients.is_empty() && recipients.len() <= MAX_SPLIT_RECIPIENTS,
+        Music3Error::InvalidSplit
+    );
+    let mut total = 0u32;
+    for (i, recipient) in recipients.iter().enumerate() {
+        require!(recipient.share_bps > 0, Music3Error::InvalidSplit);
+        require!(
+            recipients[..i]
+                .iter()
+                .all(|other| other.wallet != recipient.wallet),
+            Music3Error::InvalidSplit
+        );
+        total += u32::from(recipient.share_bps);
+    }
+    require!(total == u32::from(BPS), Music3Error::InvalidSplit);
+
+    let split_key = ctx.accounts.split.key();
+    ctx.accounts.balance.set_inner(SplitBalance {
+        split: split_key,
+        currency: None,
+        accrued: vec![0; recipients.len()],
+        bump: ctx.bumps.balance,
+    });
+    ctx.accounts.split.set_inner(Split {
+        song: ctx.accounts.song.key(),
+        recipients,
+        bump: ctx.bumps.split,
+    });
+    ctx.accounts.song.has_split = true;
+    Ok(())
+}
+
+pub(crate) fn open_balance(ctx: Context<OpenSplitBalance>) -> Result<()> {
+    let recipients = ctx.accounts.split.recipients.len();
+    ctx.accounts.balance.set_inner(SplitBalance {
+        split: ctx.accounts.split.key(),
+        currency: Some(ctx.accounts.currency.key()),
+        accrued: vec![0; recipients],
+        bump: ctx.bumps.balance,
+    });
+    Ok(())
+}
+
+pub(crate) fn claim(ctx: Context<Claim>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let index = accounts
+        .split
+        .recipients
+        .iter()
+        .position(|recipient| recipient.wallet == accounts.recipient.key())
+        .ok_or(Music3Error::NotRecipient)?;
+    let balance = &accounts.balance;
+    let amount = balance.accrued[index];
+    let currency = balance.currency;
+
+    let split = balance.split;
+    let currency_seed = currency.unwrap_or_default();
+    let funds = Funds::escrow(
+        currency,
+        balance.to_account_info(),
+        accounts.balance_token.as_deref(),
+        vec![
+            SPLIT_BALANCE_SEED.to_vec(),
+            split.to_bytes().to_vec(),
+            currency_seed.to_bytes().to_vec(),
+            vec![balance.bump],
+        ],
+        &accounts.token_program,
+    )?;
+    let to = recipient(
+        currency,
+        &accounts.recipient,
+        accounts.recipient_payment.as_deref(),
+    )?;
+    funds.pay(&to, amount)?;
+
+    ctx.accounts.balance.accrued[index] = 0;
+    emit!(SplitClaimed {
+        split,
+        recipient: ctx.accounts.recipient.key(),
+        currency,
+        amount,
+    });
+    Ok(())
+}
+
+pub(crate) fn tip(ctx: Context<Tip>, amount: u64, currency: Option<Pubkey>) -> Result<()> {
+    let accounts = &ctx.accounts;
+    let funds = Funds::new(
+        currency,
+        &accounts.tipper,
+        accounts.tipper_payment.as_deref(),
+        &accounts.system_program,
+        &accounts.token_program,
+    )?;
+    let revenue = Revenue {
+        song: &accounts.song,
+        creator: (&accounts.creator, accounts.creator_payment.as_deref()),
+        treasury: (&accounts.treasury, accounts.treasury_payment.as_deref()),
+        split: accounts.split.as_deref(),
+        balance: accounts.split_balance.as_deref(),
+        balance_token: accounts.split_payment.as_deref(),
+        boost_pool: accounts.boost_pool.as_deref(),
+    };
+    let credited = revenue.pay(&funds, currency, amount)?;
+    emit!(Tipped {
+        song: accounts.song.key(),
+        tipper: accounts.tipper.key(),
+        currency,
+        amount,
+    });
+    accrue(
+        ctx.accounts.split.as_deref(),
+        ctx.accounts.split_balance.as_deref_mut(),
+        credited,
+    );
+    Ok(())
+}
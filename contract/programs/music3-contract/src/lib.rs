@@ -1,16 +1,135 @@
+//! # Music3 合约
+//!
+//! 创作者把歌曲铸造为 Metaplex 兼容的 NFT（见 [`instructions::mint_song`]），可以由平台的助力池承担部分铸造费用
+//! （见 [`instructions::boost`]），链上的 [`state::Song`] 账户记录歌曲的存储 URI、内容哈希、版税和助力带来的收益分成调整。
+//! 创作者以固定价格出售印制的版本（见 [`instructions::primary`]），持有者可以在二级市场转售并向创作者支付版税
+//! （见 [`instructions::secondary`]），也可以通过英式或荷式拍卖出售（见 [`instructions::auction`]），
+//! 平台按 [`state::Config`] 收取手续费。创作者的收入可以按 [`state::Split`] 在多个合作者之间分成（见 [`instructions::split`]）。
+//!
+
 use anchor_lang::prelude::*;
 
+pub mod error;
+mod escrow;
+pub mod instructions;
+mod payment;
+pub mod state;
+
+use instructions::*;
+
 declare_id!("HmHG2JRTAVdsBZ6hibDaL9Px1q6afidMhL1E9QfJzUzd");
 
 #[program]
 pub mod music3_contract {
     use super::*;
 
-    pub fn create_candy_machine(ctx: Context<CreateCandyMachine>) -> Result<()> {
-        msg!("Greetings from: {:?}", ctx.program_id);
-        Ok(())
+    /// Create the platform settings, the caller pays for the account
+    pub fn initialize(ctx: Context<Initialize>, args: ConfigArgs) -> Result<()> {
+        instructions::config::initialize(ctx, args)
+    }
+
+    /// Change the platform settings
+    pub fn update_config(ctx: Context<UpdateConfig>, args: ConfigArgs) -> Result<()> {
+        instructions::config::update(ctx, args)
+    }
+
+    /// Create the boost pool or change its max ratio and revenue cut
+    pub fn set_boost(ctx: Context<SetBoost>, args: BoostArgs) -> Result<()> {
+        instructions::boost::set(ctx, args)
+    }
+
+    /// Deposit lamports into the boost pool
+    pub fn deposit_boost(ctx: Context<DepositBoost>, amount: u64) -> Result<()> {
+        instructions::boost::deposit(ctx, amount)
+    }
+
+    /// Mint a song as a master edition NFT of the creator
+    pub fn mint_song(ctx: Context<MintSong>, args: MintSongArgs) -> Result<()> {
+        instructions::mint_song::handler(ctx, args)
+    }
+
+    /// Sell editions of a song at a fixed price, or change the price and supply
+    pub fn list_primary(ctx: Context<ListPrimary>, args: ListPrimaryArgs) -> Result<()> {
+        instructions::primary::list(ctx, args)
+    }
+
+    /// Stop selling editions of a song, returning the master edition to the creator
+    pub fn delist_primary(ctx: Context<DelistPrimary>) -> Result<()> {
+        instructions::primary::delist(ctx)
+    }
+
+    /// Buy a freshly printed edition of a song from its creator, at no more than `max_price`
+    /// in `expected_currency`
+    pub fn buy_primary(
+        ctx: Context<BuyPrimary>,
+        max_price: u64,
+        expected_currency: Option<Pubkey>,
+    ) -> Result<()> {
+        instructions::primary::buy(ctx, max_price, expected_currency)
+    }
+
+    /// Escrow a token of a song for resale at a fixed price
+    pub fn list(ctx: Context<List>, args: ListArgs) -> Result<()> {
+        instructions::secondary::list(ctx, args)
+    }
+
+    /// Change the price of a resale
+    pub fn set_price(ctx: Context<SetPrice>, price: u64) -> Result<()> {
+        instructions::secondary::set_price(ctx, price)
+    }
+
+    /// Take a token back from a resale
+    pub fn delist(ctx: Context<Delist>) -> Result<()> {
+        instructions::secondary::delist(ctx)
+    }
+
+    /// Buy a resold token at no more than `max_price` in `expected_currency`,
+    /// paying the royalty and the platform fee out of the price
+    pub fn buy(
+        ctx: Context<Buy>,
+        max_price: u64,
+        expected_currency: Option<Pubkey>,
+    ) -> Result<()> {
+        instructions::secondary::buy(ctx, max_price, expected_currency)
+    }
+
+    /// Escrow a token of a song for sale by auction
+    pub fn create_auction(ctx: Context<CreateAuction>, args: CreateAuctionArgs) -> Result<()> {
+        instructions::auction::create(ctx, args)
+    }
+
+    /// Raise the highest bid of an English auction, or buy at the current price of a Dutch auction
+    pub fn place_bid(ctx: Context<PlaceBid>, amount: u64) -> Result<()> {
+        instructions::auction::place_bid(ctx, amount)
+    }
+
+    /// Return the funds of an outbid bidder
+    pub fn refund_bid(ctx: Context<RefundBid>) -> Result<()> {
+        instructions::auction::refund_bid(ctx)
     }
-}
 
-#[derive(Accounts)]
-pub struct CreateCandyMachine {}
+    /// Pay the seller, the creator and the platform out of the highest bid and hand over the token
+    pub fn settle_auction(ctx: Context<SettleAuction>) -> Result<()> {
+        instructions::auction::settle(ctx)
+    }
+
+    /// Share the revenue of a song between collaborators, once
+    pub fn create_split(ctx: Context<CreateSplit>, args: CreateSplitArgs) -> Result<()> {
+        instructions::split::create(ctx, args)
+    }
+
+    /// Open the balance of a split in an SPL token, before receiving revenue in it
+    pub fn open_split_balance(ctx: Context<OpenSplitBalance>) -> Result<()> {
+        instructions::split::open_balance(ctx)
+    }
+
+    /// Withdraw the revenue a recipient of a split accrued in one currency
+    pub fn claim(ctx: Context<Claim>) -> Result<()> {
+        instructions::split::claim(ctx)
+    }
+
+    /// Tip the creator of a song, or its collaborators through the split
+    pub fn tip(ctx: Context<Tip>, amount: u64, currency: Option<Pubkey>) -> Result<()> {
+        instructions::split::tip(ctx, amount, currency)
+    }
+}
@@ -0,0 +1,275 @@
+//! # 支付
+//!
+//! 价格以 SOL 或 SPL 代币计价：SOL 直接从签名者转到收款钱包，
+//! SPL 代币从签名者的代币账户转到收款钱包的同币种代币账户。
+//! 拍卖的出价先托管在合约账户中，成交时再从托管中支付。
+//!
+//! 创作者的收入（一级市场的售价、转售的版税和打赏）都通过 [`Revenue`] 支付：先按歌曲的助力调整扣除给助力池的部分，
+//! 歌曲设置了收益分成时，其余记入分成余额由各合作者领取，否则直接付给创作者。
+//!
+
+use crate::error::Music3Error;
+use crate::state::{share, BoostPool, Song, Split, SplitBalance};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, System, Transfer};
+use anchor_spl::token::{self, Token, TokenAccount};
+
+/// Shares of a resale
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Proceeds {
+    /// Paid to the seller
+    pub seller: u64,
+    /// Paid to the creator
+    pub royalty: u64,
+    /// Paid to the platform
+    pub fee: u64,
+}
+
+impl Proceeds {
+    /// Split `price` by the royalty of the song and the platform fee, the seller getting the rest
+    pub fn of(price: u64, royalty_bps: u16, fee_bps: u16) -> Result<Self> {
+        let royalty = share(price, royalty_bps);
+        let fee = share(price, fee_bps);
+        let seller = price
+            .checked_sub(royalty)
+            .and_then(|rest| rest.checked_sub(fee))
+            .ok_or(Music3Error::SharesExceedPrice)?;
+        Ok(Self {
+            seller,
+            royalty,
+            fee,
+        })
+    }
+
+    /// Pay the seller and the platform out of `funds`, and the royalty through `revenue`,
+    /// returning the amount to credit to the split with [`accrue`]
+    pub fn pay<'info>(
+        &self,
+        funds: &Funds<'info>,
+        currency: Option<Pubkey>,
+        seller: Payee<'_, 'info>,
+        revenue: &Revenue<'_, 'info>,
+    ) -> Result<u64> {
+        for ((wallet, token), amount) in [(seller, self.seller), (revenue.treasury, self.fee)] {
+            if amount > 0 {
+                funds.pay(&recipient(currency, wallet, token)?, amount)?;
+            }
+        }
+        revenue.pay(funds, currency, self.royalty)
+    }
+}
+
+/// Accounts receiving the revenue of the creator of a song
+///
+/// The boost pool only holds SOL, so the revenue cut of a boosted song paid in an SPL token goes to the platform.
+pub(crate) struct Revenue<'a, 'info> {
+    /// Song earning the revenue
+    pub song: &'a Account<'info, Song>,
+    /// Creator of the song
+    pub creator: Payee<'a, 'info>,
+    /// Platform
+    pub treasury: Payee<'a, 'info>,
+    /// Split of the song, required if it has one
+    pub split: Option<&'a Account<'info, Split>>,
+    /// Balance of the split in the currency
+    pub balance: Option<&'a Account<'info, SplitBalance>>,
+    /// Associated token account of the balance, for an SPL token
+    pub balance_token: Option<&'a Account<'info, TokenAccount>>,
+    /// Boost pool, required for the revenue cut of a boosted song in SOL
+    pub boost_pool: Option<&'a Account<'info, BoostPool>>,
+}
+
+impl<'info> Revenue<'_, 'info> {
+    /// Pay `amount` out of `funds`, returning the amount to credit to the split with [`accrue`]
+    pub fn pay(&self, funds: &Funds<'info>, currency: Option<Pubkey>, amount: u64) -> Result<u64> {
+        let cut = share(amount, self.song.revenue_cut_bps);
+        if cut > 0 {
+            let to = match currency {
+                None => self
+                    .boost_pool
+                    .ok_or(Music3Error::InvalidPayment)?
+                    .to_account_info(),
+                Some(_) => recipient(currency, self.treasury.0, self.treasury.1)?,
+            };
+            funds.pay(&to, cut)?;
+        }
+        let rest = amount - cut;
+        if !self.song.has_split {
+            let (wallet, token) = self.creator;
+            funds.pay(&recipient(currency, wallet, token)?, rest)?;
+            return Ok(0);
+        }
+        let split = self.split.ok_or(Music3Error::InvalidSplitAccounts)?;
+        let balance = self.balance.ok_or(Music3Error::InvalidSplitAccounts)?;
+        require_keys_eq!(
+            split.song,
+            self.song.key(),
+            Music3Error::InvalidSplitAccounts
+        );
+        require_keys_eq!(
+            balance.split,
+            split.key(),
+            Music3Error::InvalidSplitAccounts
+        );
+        require!(
+            balance.currency == currency,
+            Music3Error::InvalidSplitAccounts
+        );
+        let to = recipient(currency, &balance.to_account_info(), self.balance_token)?;
+        funds.pay(&to, rest)?;
+        Ok(rest)
+    }
+}
+
+/// Credit `amount` returned by [`Revenue::pay`] to the recipients of the split
+pub(crate) fn accrue(
+    split: Option<&Account<Split>>,
+    balance: Option<&mut Account<SplitBalance>>,
+    amount: u64,
+) {
+    if let (Some(split), Some(balance)) = (split, balance) {
+        balance.credit(&split.recipients, amount);
+    }
+}
+
+/// Wallet receiving a payment and its token account of the currency, if any
+pub(crate) type Payee<'a, 'info> = (
+    &'a AccountInfo<'info>,
+    Option<&'a Account<'info, TokenAccount>>,
+);
+
+/// Money of a buyer in the currency of a sale
+pub(crate) enum Funds<'info> {
+    /// Lamports of the buyer
+    Sol {
+        payer: AccountInfo<'info>,
+        system_program: AccountInfo<'info>,
+    },
+    /// Lamports held by an account of the program above its rent
+    Escrow { from: AccountInfo<'info> },
+    /// Tokens of the buyer, or of a PDA signing with `seeds`
+    Token {
+        from: AccountInfo<'info>,
+        authority: AccountInfo<'info>,
+        seeds: Vec<Vec<u8>>,
+        token_program: AccountInfo<'info>,
+    },
+}
+
+impl<'info> Funds<'info> {
+    /// Funds of `payer`, who must hold `from` if the currency is an SPL token
+    pub fn new(
+        currency: Option<Pubkey>,
+        payer: &Signer<'info>,
+        from: Option<&Account<'info, TokenAccount>>,
+        system_program: &Program<'info, System>,
+        token_program: &Program<'info, Token>,
+    ) -> Result<Self> {
+        match currency {
+            None => Ok(Self::Sol {
+                payer: payer.to_account_info(),
+                system_program: system_program.to_account_info(),
+            }),
+            Some(mint) => {
+                let from = from.ok_or(Music3Error::InvalidPayment)?;
+                require_keys_eq!(from.mint, mint, Music3Error::InvalidPayment);
+                require_keys_eq!(from.owner, payer.key(), Music3Error::InvalidPayment);
+                Ok(Self::Token {
+                    from: from.to_account_info(),
+                    authority: payer.to_account_info(),
+                    seeds: Vec::new(),
+                    token_program: token_program.to_account_info(),
+                })
+            }
+        }
+    }
+
+    /// Funds held in escrow by `escrow`, a PDA of the program signing with `seeds`,
+    /// in its lamports or in `from` if the currency is an SPL token
+    pub fn escrow(
+        currency: Option<Pubkey>,
+        escrow: AccountInfo<'info>,
+        from: Option<&Account<'info, TokenAccount>>,
+        seeds: Vec<Vec<u8>>,
+        token_program: &Program<'info, Token>,
+    ) -> Result<Self> {
+        match currency {
+            None => Ok(Self::Escrow { from: escrow }),
+            Some(mint) => {
+                let from = from.ok_or(Music3Error::InvalidPayment)?;
+                require_keys_eq!(from.mint, mint, Music3Error::InvalidPayment);
+                require_keys_eq!(from.owner, escrow.key(), Music3Error::InvalidPayment);
+                Ok(Self::Token {
+                    from: from.to_account_info(),
+                    authority: escrow,
+                    seeds,
+                    token_program: token_program.to_account_info(),
+                })
+            }
+        }
+    }
+
+    /// Pay `amount` to an account returned by [`recipient`]
+    pub fn pay(&self, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        if amount == 0 {
+            return Ok(());
+        }
+        match self {
+            Self::Sol {
+                payer,
+                system_program,
+            } => system_program::transfer(
+                CpiContext::new(
+                    system_program.clone(),
+                    Transfer {
+                        from: payer.clone(),
+                        to: to.clone(),
+                    },
+                ),
+                amount,
+            ),
+            Self::Escrow { from } => {
+                **from.try_borrow_mut_lamports()? -= amount;
+                **to.try_borrow_mut_lamports()? += amount;
+                Ok(())
+            }
+            Self::Token {
+                from,
+                authority,
+                seeds,
+                token_program,
+            } => {
+                let seeds: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        token_program.clone(),
+                        token::Transfer {
+                            from: from.clone(),
+                            to: to.clone(),
+                            authority: authority.clone(),
+                        },
+                        &[&seeds],
+                    ),
+                    amount,
+                )
+            }
+        }
+    }
+}
+
+/// Account receiving the payments of `wallet`: the wallet for SOL, its token account of the currency otherwise
+pub(crate) fn recipient<'info>(
+    currency: Option<Pubkey>,
+    wallet: &AccountInfo<'info>,
+    token: Option<&Account<'info, TokenAccount>>,
+) -> Result<AccountInfo<'info>> {
+    match currency {
+        None => Ok(wallet.clone()),
+        Some(mint) => {
+            let token = token.ok_or(Music3Error::InvalidPayment)?;
+            require_keys_eq!(token.mint, mint, Music3Error::InvalidPayment);
+            require_keys_eq!(token.owner, wallet.key(), Music3Error::InvalidPayment);
+            Ok(token.to_account_info())
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! # Accounts
+//!
+
+use crate::error::Music3Error;
+use anchor_lang::prelude::*;
+use anchor_spl::metadata::mpl_token_metadata;
+use anchor_spl::metadata::mpl_token_metadata::accounts::{Edition, MasterEdition};
+use anchor_spl::metadata::mpl_token_metadata::types::Key;
+
+/// Seed of a [`Song`] account, followed by the mint
+pub const SONG_SEED: &[u8] = b"song";
+
+/// Max bytes of a URI, the limit of Metaplex
+pub const MAX_URI_LEN: usize = 200;
+
+/// Max bytes of a name, the limit of Metaplex
+pub const MAX_NAME_LEN: usize = 32;
+
+/// Max bytes of a symbol, the limit of Metaplex
+pub const MAX_SYMBOL_LEN: usize = 10;
+
+/// Basis points of 100%
+pub const BPS: u16 = 10_000;
+
+/// Seed of the [`Config`] account
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Seed of a [`PrimaryListing`] account, followed by the song
+pub const PRIMARY_SEED: &[u8] = b"primary";
+
+/// Seed of a [`Listing`] account, followed by the song and the seller
+pub const LISTING_SEED: &[u8] = b"listing";
+
+/// Seed of an [`Auction`] account, followed by the mint of the token sold
+pub const AUCTION_SEED: &[u8] = b"auction";
+
+/// Seed of a [`Bid`] account, followed by the auction and the bidder
+pub const BID_SEED: &[u8] = b"bid";
+
+/// Seed of a [`Split`] account, followed by the song
+pub const SPLIT_SEED: &[u8] = b"split";
+
+/// Seed of a [`SplitBalance`] account, followed by the split and the currency, [`Pubkey::default`] for SOL
+pub const SPLIT_BALANCE_SEED: &[u8] = b"split_balance";
+
+/// Most recipients of a [`Split`]
+pub const MAX_SPLIT_RECIPIENTS: usize = 10;
+
+/// Seed of the [`BoostPool`] account
+pub const BOOST_SEED: &[u8] = b"boost";
+
+/// Platform settings, a singleton PDA
+#[account]
+#[derive(InitSpace)]
+pub struct Config {
+    /// Wallet that may change the settings
+    pub admin: Pubkey,
+    /// Wallet receiving the platform fees
+    pub treasury: Pubkey,
+    /// Share of every sale paid to the platform, in basis points
+    pub fee_bps: u16,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+impl Config {
+    /// Platform fee of a sale at `price`
+    pub fn fee(&self, price: u64) -> u64 {
+        share(price, self.fee_bps)
+    }
+}
+
+/// `bps` basis points of `amount`, rounded down
+pub fn share(amount: u64, bps: u16) -> u64 {
+    (u128::from(amount) * u128::from(bps) / u128::from(BPS)) as u64
+}
+
+/// Song minted as an NFT, a PDA of the mint
+#[account]
+#[derive(InitSpace)]
+pub struct Song {
+    /// Wallet that minted the song, the verified creator of the NFT
+    pub creator: Pubkey,
+    /// Mint of the master edition
+    pub mint: Pubkey,
+    /// Storage URI of the track, also the URI of the Metaplex metadata
+    #[max_len(MAX_URI_LEN)]
+    pub uri: String,
+    /// SHA-256 of the track content
+    pub content_hash: [u8; 32],
+    /// Share of every resale paid to the creator, in basis points
+    pub royalty_bps: u16,
+    /// Editions that may be printed, 0 for a one of one
+    pub max_supply: u64,
+    /// Editions printed so far
+    pub printed: u64,
+    /// Unix timestamp of the mint
+    pub created_at: i64,
+    /// Share of the mint cost paid by the boost pool, in basis points
+    pub boost_bps: u16,
+    /// Share of the revenue of the creator from the song given up for the boost, in basis points
+    pub revenue_cut_bps: u16,
+    /// Whether the revenue of the creator is shared through a [`Split`]
+    pub has_split: bool,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+impl Song {
+    /// Check that `mint` is the master edition of the song or an edition printed from it,
+    /// `edition` being the edition account of `mint`
+    pub fn check_token(&self, mint: &Pubkey, edition: &AccountInfo) -> Result<()> {
+        if *mint == self.mint {
+            return Ok(());
+        }
+        // masters and editions share the PDA
+        let (address, _) = MasterEdition::find_pda(mint);
+        require_keys_eq!(*edition.key, address, Music3Error::NotSongToken);
+        require_keys_eq!(
+            *edition.owner,
+            mpl_token_metadata::ID,
+            Music3Error::NotSongToken
+        );
+        let edition = Edition::try_from(edition).map_err(|_| Music3Error::NotSongToken)?;
+        require!(edition.key == Key::EditionV1, Music3Error::NotSongToken);
+        let (master, _) = MasterEdition::find_pda(&self.mint);
+        require_keys_eq!(edition.parent, master, Music3Error::NotSongToken);
+        Ok(())
+    }
+}
+
+/// Editions of a song sold by its creator at a fixed price, a PDA of the song
+///
+/// The listing holds the master edition token to print the editions it sells.
+#[account]
+#[derive(InitSpace)]
+pub struct PrimaryListing {
+    /// Song for sale
+    pub song: Pubkey,
+    /// Creator receiving the proceeds
+    pub creator: Pubkey,
+    /// Price of an edition, in lamports or the smallest unit of the currency
+    pub price: u64,
+    /// SPL token mint the price is paid in, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Editions left for sale
+    pub supply: u64,
+    /// Editions sold
+    pub sold: u64,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+/// Tokens of a song resold by a holder at a fixed price, a PDA of the song and the seller
+///
+/// Every token listed, the master edition or a printed edition, is held by an associated token account of the listing.
+#[account]
+#[derive(InitSpace)]
+pub struct Listing {
+    /// Holder selling the tokens
+    pub seller: Pubkey,
+    /// Song of the tokens
+    pub song: Pubkey,
+    /// Price of a token, in lamports or the smallest unit of the currency
+    pub price: u64,
+    /// SPL token mint the price is paid in, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Tokens held by the listing
+    pub quantity: u64,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+/// How the price of an [`Auction`] is found
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum AuctionKind {
+    /// Ascending bids, the highest wins at the end
+    English,
+    /// Price declining from the start price to the reserve price, the first bid wins
+    Dutch,
+}
+
+/// Sale of a token of a song by auction, a PDA of the mint of the token
+///
+/// The token is held by an associated token account of the auction until settled.
+#[account]
+#[derive(InitSpace)]
+pub struct Auction {
+    /// Holder selling the token
+    pub seller: Pubkey,
+    /// Song of the token
+    pub song: Pubkey,
+    /// Mint of the token
+    pub token_mint: Pubkey,
+    /// SPL token mint the bids are paid in, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// English or Dutch
+    pub kind: AuctionKind,
+    /// Price at the start of a Dutch auction
+    pub start_price: u64,
+    /// Lowest price the token sells for
+    pub reserve_price: u64,
+    /// Least amount a bid must raise the highest bid by
+    pub min_increment: u64,
+    /// Unix timestamp bids open at
+    pub start_time: i64,
+    /// Unix timestamp bids close at
+    pub end_time: i64,
+    /// Seconds a bid close to the end keeps the auction open for, to prevent sniping
+    pub extension: i64,
+    /// Highest bid so far
+    pub highest_bid: u64,
+    /// Bidder of the highest bid
+    pub highest_bidder: Option<Pubkey>,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+impl Auction {
+    /// Least amount of the next bid of an English auction
+    pub fn min_bid(&self) -> u64 {
+        match self.highest_bidder {
+            Some(_) => self.highest_bid.saturating_add(self.min_increment),
+            None => self.reserve_price,
+        }
+    }
+
+    /// Price of a Dutch auction at `now`, declining linearly from the start price to the reserve price
+    pub fn price_at(&self, now: i64) -> u64 {
+        if now <= self.start_time {
+            return self.start_price;
+        }
+        if now >= self.end_time {
+            return self.reserve_price;
+        }
+        let drop = (self.start_price - self.reserve_price) as u128;
+        let elapsed = (now - self.start_time) as u128;
+        let duration = (self.end_time - self.start_time) as u128;
+        self.start_price - (drop * elapsed / duration) as u64
+    }
+}
+
+/// Funds a bidder has put up in an auction, a PDA of the auction and the bidder
+///
+/// SOL is held in the lamports of the account, SPL tokens by its associated token account.
+#[account]
+#[derive(InitSpace)]
+pub struct Bid {
+    /// Auction bid in
+    pub auction: Pubkey,
+    /// Bidder
+    pub bidder: Pubkey,
+    /// SPL token mint of the bid, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Amount held
+    pub amount: u64,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+/// Platform funded pool paying part of the mint cost of creators, a singleton PDA holding SOL
+#[account]
+#[derive(InitSpace)]
+pub struct BoostPool {
+    /// Highest share of a mint cost the pool pays, in basis points, below 100%
+    pub max_ratio_bps: u16,
+    /// Share of the revenue of the creator given up for a mint fully paid by the pool, in basis points,
+    /// scaled down by the share actually paid
+    pub revenue_cut_bps: u16,
+    /// Lamports deposited so far
+    pub deposited: u64,
+    /// Lamports paid to creators so far
+    pub paid: u64,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+impl BoostPool {
+    /// Revenue cut of a song whose mint cost the pool paid `ratio_bps` of
+    pub fn revenue_cut(&self, ratio_bps: u16) -> u16 {
+        share(u64::from(self.revenue_cut_bps), ratio_bps) as u16
+    }
+}
+
+/// Wallet sharing the revenue of a song
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct Recipient {
+    /// Wallet of the collaborator
+    pub wallet: Pubkey,
+    /// Share of the revenue, in basis points
+    pub share_bps: u16,
+}
+
+/// Collaborators sharing the revenue of the creator from a song, a PDA of the song
+///
+/// Fixed once created, so that the creator cannot take the share of a collaborator back.
+#[account]
+#[derive(InitSpace)]
+pub struct Split {
+    /// Song shared
+    pub song: Pubkey,
+    /// Collaborators, whose shares add up to 100%
+    #[max_len(MAX_SPLIT_RECIPIENTS)]
+    pub recipients: Vec<Recipient>,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+/// Revenue of a [`Split`] in one currency not claimed yet, a PDA of the split and the currency
+///
+/// SOL is held in the lamports of the account, SPL tokens by its associated token account.
+#[account]
+#[derive(InitSpace)]
+pub struct SplitBalance {
+    /// Split of the balance
+    pub split: Pubkey,
+    /// SPL token mint of the balance, SOL if not set
+    pub currency: Option<Pubkey>,
+    /// Amount owed to each recipient, in the order of the split
+    #[max_len(MAX_SPLIT_RECIPIENTS)]
+    pub accrued: Vec<u64>,
+    /// Bump of the PDA
+    pub bump: u8,
+}
+
+impl SplitBalance {
+    /// Share `amount` between the recipients, the first one getting what rounding leaves
+    pub fn credit(&mut self, recipients: &[Recipient], amount: u64) {
+        let mut left = amount;
+        for (accrued, recipient) in self.accrued.iter_mut().zip(recipients) {
+            let part = share(amount, recipient.share_bps);
+            *accrued += part;
+            left -= part;
+        }
+        if let Some(first) = self.accrued.first_mut() {
+            *first += left;
+        }
+    }
+}
@@ -1,16 +1,1424 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
+import {
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  sendAndConfirmTransaction,
+} from "@solana/web3.js";
+import { assert } from "chai";
+import { createHash } from "crypto";
 import { Music3Contract } from "../target/types/music3_contract";
+import {
+  balance,
+  createMint,
+  createTokenAccount,
+  mintTo,
+  transfer,
+} from "./token";
+
+const TOKEN_METADATA_PROGRAM_ID = new PublicKey(
+  "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"
+);
+const { associatedAddress } = anchor.utils.token;
 
 describe("music3-contract", () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.Music3Contract as Program<Music3Contract>;
+  const creator = provider.wallet.publicKey;
+  const treasury = Keypair.generate().publicKey;
+  const FEE_BPS = 250;
+
+  const pda = (seeds: Buffer[], programId = program.programId) =>
+    PublicKey.findProgramAddressSync(seeds, programId)[0];
+  const metadataAddress = (mint: PublicKey) =>
+    pda(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        mint.toBuffer(),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+  const editionAddress = (mint: PublicKey) =>
+    pda(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        mint.toBuffer(),
+        Buffer.from("edition"),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+  const editionMarkerAddress = (mint: PublicKey, edition: number) =>
+    pda(
+      [
+        Buffer.from("metadata"),
+        TOKEN_METADATA_PROGRAM_ID.toBuffer(),
+        mint.toBuffer(),
+        Buffer.from("edition"),
+        Buffer.from(Math.floor(edition / 248).toString()),
+      ],
+      TOKEN_METADATA_PROGRAM_ID
+    );
+  const songAddress = (mint: PublicKey) =>
+    pda([Buffer.from("song"), mint.toBuffer()]);
+  const configAddress = pda([Buffer.from("config")]);
+  const boostAddress = pda([Buffer.from("boost")]);
+  const splitAddress = (song: PublicKey) =>
+    pda([Buffer.from("split"), song.toBuffer()]);
+  const splitBalanceAddress = (split: PublicKey, currency: PublicKey | null) =>
+    pda([
+      Buffer.from("split_balance"),
+      split.toBuffer(),
+      (currency ?? PublicKey.default).toBuffer(),
+    ]);
+  const primaryAddress = (song: PublicKey) =>
+    pda([Buffer.from("primary"), song.toBuffer()]);
+  const listingAddress = (song: PublicKey, seller: PublicKey) =>
+    pda([Buffer.from("listing"), song.toBuffer(), seller.toBuffer()]);
+  const auctionAddress = (tokenMint: PublicKey) =>
+    pda([Buffer.from("auction"), tokenMint.toBuffer()]);
+  const bidAddress = (auction: PublicKey, bidder: PublicKey) =>
+    pda([Buffer.from("bid"), auction.toBuffer(), bidder.toBuffer()]);
+
+  const fund = async (sol = 10) => {
+    const wallet = Keypair.generate();
+    const signature = await provider.connection.requestAirdrop(
+      wallet.publicKey,
+      sol * LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(signature);
+    return wallet;
+  };
+  const lamports = (address: PublicKey) =>
+    provider.connection.getBalance(address);
+  const share = (amount: number, bps: number) =>
+    Math.floor((amount * bps) / 10_000);
+
+  // Unix timestamp of the cluster
+  const now = async () =>
+    provider.connection.getBlockTime(await provider.connection.getSlot());
+  const waitUntil = async (time: number) => {
+    while ((await now()) <= time) {
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+  };
+
+  // Sends a transaction paid by `payer`, so that the provider wallet, the
+  // creator of the songs, only sees what the program pays it
+  const send = async (
+    payer: Keypair,
+    transaction: Promise<Transaction>,
+    signers: Keypair[] = []
+  ) =>
+    sendAndConfirmTransaction(provider.connection, await transaction, [
+      payer,
+      ...signers,
+    ]);
+
+  // Mints a song of the provider wallet, returning its mint
+  const mintSong = async (
+    args: Partial<{
+      name: string;
+      uri: string;
+      royaltyBps: number;
+      maxSupply: number;
+      boostBps: number;
+      mint: Keypair;
+    }> = {}
+  ) => {
+    const mint = args.mint ?? Keypair.generate();
+    const uri =
+      args.uri ??
+      "ipfs://bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+    await program.methods
+      .mintSong({
+        name: args.name ?? "Song",
+        symbol: "M3",
+        uri,
+        contentHash: [...createHash("sha256").update(uri).digest()],
+        royaltyBps: args.royaltyBps ?? 1000,
+        maxSupply: new anchor.BN(args.maxSupply ?? 100),
+        boostBps: args.boostBps ?? 0,
+      })
+      .accountsPartial({
+        creator,
+        mint: mint.publicKey,
+        token: associatedAddress({ mint: mint.publicKey, owner: creator }),
+        song: songAddress(mint.publicKey),
+        metadata: metadataAddress(mint.publicKey),
+        masterEdition: editionAddress(mint.publicKey),
+        boostPool: args.boostBps ? boostAddress : null,
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+      })
+      .signers([mint])
+      .rpc();
+    return mint.publicKey;
+  };
+
+  // Rent of the accounts created by minting a song, by their size
+  const mintCost = async (mint: PublicKey) => {
+    const rents = await Promise.all(
+      [
+        mint,
+        associatedAddress({ mint, owner: creator }),
+        songAddress(mint),
+        metadataAddress(mint),
+        editionAddress(mint),
+      ].map(async (address) => {
+        const { data } = await provider.connection.getAccountInfo(address);
+        return provider.connection.getMinimumBalanceForRentExemption(
+          data.length
+        );
+      })
+    );
+    return rents.reduce((sum, rent) => sum + rent, 0);
+  };
+
+  const listPrimary = (
+    mint: PublicKey,
+    price: number,
+    supply: number,
+    currency: PublicKey | null = null
+  ) => {
+    const song = songAddress(mint);
+    const listing = primaryAddress(song);
+    return program.methods
+      .listPrimary({
+        price: new anchor.BN(price),
+        currency,
+        supply: new anchor.BN(supply),
+      })
+      .accountsPartial({
+        creator,
+        song,
+        mint,
+        creatorToken: associatedAddress({ mint, owner: creator }),
+        listing,
+        vault: associatedAddress({ mint, owner: listing }),
+      })
+      .rpc();
+  };
+
+  // Buys the next edition of a song, returning the mint of the edition
+  // Buys an edition, at the listed price and currency unless `maxPrice` or
+  // `expectedCurrency` is given
+  const buyPrimary = async (
+    buyer: Keypair,
+    mint: PublicKey,
+    currency: PublicKey | null = null,
+    maxPrice?: number,
+    expectedCurrency: PublicKey | null = currency
+  ) => {
+    const song = songAddress(mint);
+    const listing = primaryAddress(song);
+    const { price } = await program.account.primaryListing.fetch(listing);
+    const { printed } = await program.account.song.fetch(song);
+    const edition = printed.toNumber() + 1;
+    const editionMint = Keypair.generate();
+    const payment = (owner: PublicKey) =>
+      currency ? associatedAddress({ mint: currency, owner }) : null;
+    const transaction = program.methods
+      .buyPrimary(
+        maxPrice === undefined ? price : new anchor.BN(maxPrice),
+        expectedCurrency
+      )
+      .accountsPartial({
+        buyer: buyer.publicKey,
+        config: configAddress,
+        song,
+        listing,
+        creator,
+        treasury,
+        masterMint: mint,
+        masterMetadata: metadataAddress(mint),
+        masterEdition: editionAddress(mint),
+        vault: associatedAddress({ mint, owner: listing }),
+        editionMint: editionMint.publicKey,
+        editionToken: associatedAddress({
+          mint: editionMint.publicKey,
+          owner: buyer.publicKey,
+        }),
+        editionMetadata: metadataAddress(editionMint.publicKey),
+        edition: editionAddress(editionMint.publicKey),
+        editionMarker: editionMarkerAddress(mint, edition),
+        buyerPayment: payment(buyer.publicKey),
+        creatorPayment: payment(creator),
+        treasuryPayment: payment(treasury),
+        ...(await revenueAccounts(mint, currency ?? null)),
+        tokenMetadataProgram: TOKEN_METADATA_PROGRAM_ID,
+      })
+      .transaction();
+    await send(buyer, transaction, [editionMint]);
+    return editionMint.publicKey;
+  };
+
+  // Accounts receiving the revenue of a song: its split and the boost pool, when they apply
+  const revenueAccounts = async (
+    mint: PublicKey,
+    currency: PublicKey | null
+  ) => {
+    const song = songAddress(mint);
+    const { hasSplit, revenueCutBps } = await program.account.song.fetch(song);
+    const split = splitAddress(song);
+    const balance = splitBalanceAddress(split, currency);
+    return {
+      split: hasSplit ? split : null,
+      splitBalance: hasSplit ? balance : null,
+      splitPayment:
+        hasSplit && currency
+          ? associatedAddress({ mint: currency, owner: balance })
+          : null,
+      boostPool: revenueCutBps ? boostAddress : null,
+    };
+  };
+
+  // Escrows a token of a song for resale
+  const list = (
+    seller: Keypair,
+    mint: PublicKey,
+    tokenMint: PublicKey,
+    price: number,
+    currency: PublicKey | null = null,
+    song = songAddress(mint)
+  ) => {
+    const listing = listingAddress(song, seller.publicKey);
+    const transaction = program.methods
+      .list({ price: new anchor.BN(price), currency })
+      .accountsPartial({
+        seller: seller.publicKey,
+        song,
+        tokenMint,
+        tokenEdition: editionAddress(tokenMint),
+        sellerToken: associatedAddress({
+          mint: tokenMint,
+          owner: seller.publicKey,
+        }),
+        listing,
+        vault: associatedAddress({ mint: tokenMint, owner: listing }),
+      })
+      .transaction();
+    return send(seller, transaction);
+  };
+
+  // Buys a resold token
+  const buy = async (
+    buyer: Keypair,
+    mint: PublicKey,
+    seller: PublicKey,
+    tokenMint: PublicKey,
+    maxPrice: number,
+    accounts: Partial<{
+      creator: PublicKey;
+      currency: PublicKey;
+      expectedCurrency: PublicKey | null;
+    }> = {}
+  ) => {
+    const song = songAddress(mint);
+    const listing = listingAddress(song, seller);
+    const { currency } = accounts;
+    const expectedCurrency =
+      accounts.expectedCurrency === undefined
+        ? currency ?? null
+        : accounts.expectedCurrency;
+    const payment = (owner: PublicKey) =>
+      currency ? associatedAddress({ mint: currency, owner }) : null;
+    const transaction = program.methods
+      .buy(new anchor.BN(maxPrice), expectedCurrency)
+      .accountsPartial({
+        buyer: buyer.publicKey,
+        config: configAddress,
+        song,
+        listing,
+        seller,
+        creator: accounts.creator ?? creator,
+        treasury,
+        tokenMint,
+        vault: associatedAddress({ mint: tokenMint, owner: listing }),
+        buyerToken: associatedAddress({
+          mint: tokenMint,
+          owner: buyer.publicKey,
+        }),
+        buyerPayment: payment(buyer.publicKey),
+        sellerPayment: payment(seller),
+        creatorPayment: payment(creator),
+        treasuryPayment: payment(treasury),
+        ...(await revenueAccounts(mint, currency ?? null)),
+      })
+      .transaction();
+    return send(buyer, transaction);
+  };
+
+  const ROYALTY_BPS = 1000;
+
+  // Song whose editions are held by a collector
+  const collect = async (editions: number) => {
+    const mint = await mintSong({ royaltyBps: ROYALTY_BPS });
+    await listPrimary(mint, LAMPORTS_PER_SOL / 10, editions);
+    const collector = await fund();
+    const tokens: PublicKey[] = [];
+    for (let i = 0; i < editions; i++) {
+      tokens.push(await buyPrimary(collector, mint));
+    }
+    return { mint, collector, tokens };
+  };
+
+  // Escrows a token of a song for sale by auction, starting now
+  const createAuction = async (
+    seller: Keypair,
+    mint: PublicKey,
+    tokenMint: PublicKey,
+    args: Partial<{
+      dutch: boolean;
+      currency: PublicKey;
+      startPrice: number;
+      reservePrice: number;
+      minIncrement: number;
+      duration: number;
+      extension: number;
+    }>
+  ) => {
+    const start = await now();
+    const auction = auctionAddress(tokenMint);
+    const transaction = program.methods
+      .createAuction({
+        kind: args.dutch ? { dutch: {} } : { english: {} },
+        currency: args.currency ?? null,
+        startPrice: new anchor.BN(args.startPrice ?? 0),
+        reservePrice: new anchor.BN(args.reservePrice ?? LAMPORTS_PER_SOL),
+        minIncrement: new anchor.BN(
+          args.minIncrement ?? LAMPORTS_PER_SOL / 10
+        ),
+        startTime: new anchor.BN(start - 1),
+        endTime: new anchor.BN(start + (args.duration ?? 5)),
+        extension: new anchor.BN(args.extension ?? 0),
+      })
+      .accountsPartial({
+        seller: seller.publicKey,
+        song: songAddress(mint),
+        tokenMint,
+        tokenEdition: editionAddress(tokenMint),
+        sellerToken: associatedAddress({
+          mint: tokenMint,
+          owner: seller.publicKey,
+        }),
+        auction,
+        vault: associatedAddress({ mint: tokenMint, owner: auction }),
+      })
+      .transaction();
+    await send(seller, transaction);
+    return auction;
+  };
+
+  const placeBid = async (
+    bidder: Keypair,
+    auction: PublicKey,
+    amount: number,
+    currency: PublicKey | null = null
+  ) => {
+    const bid = bidAddress(auction, bidder.publicKey);
+    if (currency) {
+      await createTokenAccount(provider, currency, bid);
+    }
+    const transaction = program.methods
+      .placeBid(new anchor.BN(amount))
+      .accountsPartial({
+        bidder: bidder.publicKey,
+        auction,
+        bid,
+        bidderPayment: currency
+          ? associatedAddress({ mint: currency, owner: bidder.publicKey })
+          : null,
+        bidPayment: currency
+          ? associatedAddress({ mint: currency, owner: bid })
+          : null,
+      })
+      .transaction();
+    return send(bidder, transaction);
+  };
+
+  const refundBid = (auction: PublicKey, bidder: PublicKey) =>
+    program.methods
+      .refundBid()
+      .accountsPartial({
+        bidder,
+        auction,
+        bid: bidAddress(auction, bidder),
+        bidPayment: null,
+        bidderPayment: null,
+      })
+      .rpc();
+
+  // Settles an auction, paid by a third party
+  const settleAuction = async (
+    mint: PublicKey,
+    tokenMint: PublicKey,
+    currency: PublicKey | null = null
+  ) => {
+    const auction = auctionAddress(tokenMint);
+    const state = await program.account.auction.fetch(auction);
+    const receiver = state.highestBidder ?? state.seller;
+    const bid = state.highestBidder && bidAddress(auction, receiver);
+    const payment = (owner: PublicKey) =>
+      currency && owner ? associatedAddress({ mint: currency, owner }) : null;
+    const transaction = program.methods
+      .settleAuction()
+      .accountsPartial({
+        payer: cranker.publicKey,
+        config: configAddress,
+        song: songAddress(mint),
+        auction,
+        seller: state.seller,
+        creator,
+        treasury,
+        tokenMint,
+        vault: associatedAddress({ mint: tokenMint, owner: auction }),
+        receiver,
+        receiverToken: associatedAddress({ mint: tokenMint, owner: receiver }),
+        winningBid: bid,
+        bidPayment: payment(bid),
+        sellerPayment: payment(state.seller),
+        creatorPayment: payment(creator),
+        treasuryPayment: payment(treasury),
+        ...(await revenueAccounts(mint, currency ?? null)),
+      })
+      .transaction();
+    return send(cranker, transaction);
+  };
+
+  const errorCode = async (promise: Promise<unknown>) => {
+    try {
+      await promise;
+    } catch (e) {
+      const error =
+        e instanceof anchor.AnchorError
+          ? e
+          : anchor.AnchorError.parse(e.logs ?? null);
+      return error?.error.errorCode.code ?? e.toString();
+    }
+    assert.fail("succeeded");
+  };
+
+  let cranker: Keypair;
+
+  before(async () => {
+    cranker = await fund();
+    await program.methods
+      .initialize({ admin: creator, treasury, feeBps: FEE_BPS })
+      .accountsPartial({ payer: creator, config: configAddress })
+      .rpc();
+  });
+
+  describe("mint_song", () => {
+    it("mints a song with a verified creator and royalty", async () => {
+      const uri = "ar://track";
+      const mint = await mintSong({ uri, royaltyBps: 1000, maxSupply: 10 });
+
+      const song = await program.account.song.fetch(songAddress(mint));
+      assert.ok(song.creator.equals(creator));
+      assert.ok(song.mint.equals(mint));
+      assert.equal(song.uri, uri);
+      assert.deepEqual(
+        Buffer.from(song.contentHash),
+        createHash("sha256").update(uri).digest()
+      );
+      assert.equal(song.royaltyBps, 1000);
+      assert.equal(song.maxSupply.toNumber(), 10);
+
+      const token = associatedAddress({ mint, owner: creator });
+      assert.equal(await balance(provider, token), 1);
+
+      // key, update authority, mint, then the name, symbol and URI padded by Metaplex
+      const metadata = await provider.connection.getAccountInfo(
+        metadataAddress(mint)
+      );
+      assert.ok(metadata.owner.equals(TOKEN_METADATA_PROGRAM_ID));
+      const data = metadata.data;
+      let offset = 1 + 32 + 32;
+      const string = () => {
+        const len = data.readUInt32LE(offset);
+        const value = data
+          .subarray(offset + 4, offset + 4 + len)
+          .toString()
+          .replace(/\0+$/, "");
+        offset += 4 + len;
+        return value;
+      };
+      assert.equal(string(), "Song");
+      assert.equal(string(), "M3");
+      assert.equal(string(), uri);
+      assert.equal(data.readUInt16LE(offset), 1000);
+      offset += 2;
+      assert.equal(data[offset], 1, "creators are set");
+      assert.equal(data.readUInt32LE(offset + 1), 1);
+      const address = new PublicKey(data.subarray(offset + 5, offset + 37));
+      assert.ok(address.equals(creator));
+      assert.equal(data[offset + 37], 1, "the creator is verified");
+      assert.equal(data[offset + 38], 100);
+
+      const edition = await provider.connection.getAccountInfo(
+        editionAddress(mint)
+      );
+      assert.ok(edition.owner.equals(TOKEN_METADATA_PROGRAM_ID));
+    });
+
+    it("rejects a royalty above 100%", async () => {
+      assert.equal(
+        await errorCode(mintSong({ royaltyBps: 10_001 })),
+        "InvalidRoyalty"
+      );
+    });
+  });
+
+  describe("boost pool", () => {
+    const setBoost = (maxRatioBps: number, revenueCutBps: number) =>
+      program.methods
+        .setBoost({ maxRatioBps, revenueCutBps })
+        .accountsPartial({
+          admin: creator,
+          config: configAddress,
+          boostPool: boostAddress,
+        })
+        .rpc();
+
+    it("pays part of the mint cost for a revenue cut", async () => {
+      assert.equal(
+        await errorCode(setBoost(10_000, 2000)),
+        "InvalidBoostRatio",
+        "paying the whole cost"
+      );
+      await setBoost(5000, 2000);
+      const stranger = await fund();
+      assert.equal(
+        await errorCode(
+          send(
+            stranger,
+            program.methods
+              .setBoost({ maxRatioBps: 9000, revenueCutBps: 0 })
+              .accountsPartial({
+                admin: stranger.publicKey,
+                config: configAddress,
+                boostPool: boostAddress,
+              })
+              .transaction()
+          )
+        ),
+        "ConstraintHasOne"
+      );
+      assert.equal(
+        await errorCode(mintSong({ boostBps: 5000 })),
+        "InsufficientBoost",
+        "an empty pool"
+      );
+      await program.methods
+        .depositBoost(new anchor.BN(5 * LAMPORTS_PER_SOL))
+        .accountsPartial({ depositor: creator, boostPool: boostAddress })
+        .rpc();
+      assert.equal(
+        await errorCode(mintSong({ boostBps: 6000 })),
+        "SubsidyTooHigh"
+      );
+
+      const poolBefore = await lamports(boostAddress);
+      const mint = await mintSong({ boostBps: 5000 });
+      const subsidy = share(await mintCost(mint), 5000);
+      assert.equal(poolBefore - (await lamports(boostAddress)), subsidy);
+      const song = await program.account.song.fetch(songAddress(mint));
+      assert.equal(song.boostBps, 5000);
+      assert.equal(song.revenueCutBps, 1000);
+      const pool = await program.account.boostPool.fetch(boostAddress);
+      assert.equal(pool.deposited.toNumber(), 5 * LAMPORTS_PER_SOL);
+      assert.equal(pool.paid.toNumber(), subsidy);
+
+      const unboosted = await program.account.song.fetch(
+        songAddress(await mintSong())
+      );
+      assert.equal(unboosted.revenueCutBps, 0);
+    });
+
+    it("ignores SOL sent to the new accounts beforehand", async () => {
+      const first = await lamports(boostAddress);
+      const cost = await mintCost(await mintSong({ boostBps: 5000 }));
+      assert.equal(first - (await lamports(boostAddress)), share(cost, 5000));
+
+      const mint = Keypair.generate();
+      const token = associatedAddress({ mint: mint.publicKey, owner: creator });
+      await provider.sendAndConfirm(
+        new Transaction().add(
+          SystemProgram.transfer({
+            fromPubkey: creator,
+            toPubkey: token,
+            lamports: 2 * LAMPORTS_PER_SOL,
+          })
+        )
+      );
+      const before = await lamports(boostAddress);
+      await mintSong({ boostBps: 5000, mint });
+      assert.equal(before - (await lamports(boostAddress)), share(cost, 5000));
+      assert.equal(await mintCost(mint.publicKey), cost);
+    });
+  });
+
+  describe("primary sale", () => {
+    it("sells editions for SOL minus the platform fee", async () => {
+      const mint = await mintSong({ maxSupply: 3 });
+      assert.equal(
+        await errorCode(listPrimary(mint, LAMPORTS_PER_SOL, 4)),
+        "SupplyExceeded"
+      );
+      await listPrimary(mint, LAMPORTS_PER_SOL, 2);
+      const listing = primaryAddress(songAddress(mint));
+      const vault = associatedAddress({ mint, owner: listing });
+      assert.equal(await balance(provider, vault), 1);
+
+      const buyer = await fund();
+      const creatorBefore = await lamports(creator);
+      const treasuryBefore = await lamports(treasury);
+      const editionMint = await buyPrimary(buyer, mint);
+      const fee = (LAMPORTS_PER_SOL * FEE_BPS) / 10_000;
+      assert.equal(
+        (await lamports(creator)) - creatorBefore,
+        LAMPORTS_PER_SOL - fee
+      );
+      assert.equal((await lamports(treasury)) - treasuryBefore, fee);
+      const token = associatedAddress({
+        mint: editionMint,
+        owner: buyer.publicKey,
+      });
+      assert.equal(await balance(provider, token), 1);
+      const edition = await provider.connection.getAccountInfo(
+        editionAddress(editionMint)
+      );
+      assert.ok(edition.owner.equals(TOKEN_METADATA_PROGRAM_ID));
+
+      // the price changes for the next edition, then the sale stops
+      await listPrimary(mint, 2 * LAMPORTS_PER_SOL, 1);
+      assert.equal(
+        await errorCode(buyPrimary(buyer, mint, null, LAMPORTS_PER_SOL)),
+        "PriceTooHigh",
+        "raised after the buyer looked"
+      );
+      const before = await lamports(creator);
+      await buyPrimary(buyer, mint);
+      assert.equal(
+        (await lamports(creator)) - before,
+        2 * LAMPORTS_PER_SOL - (2 * LAMPORTS_PER_SOL * FEE_BPS) / 10_000
+      );
+      assert.equal(await errorCode(buyPrimary(buyer, mint)), "SoldOut");
+      const state = await program.account.primaryListing.fetch(listing);
+      assert.equal(state.sold.toNumber(), 2);
+      const song = await program.account.song.fetch(songAddress(mint));
+      assert.equal(song.printed.toNumber(), 2);
+    });
+
+    it("sells editions for an SPL token", async () => {
+      const currency = await createMint(provider);
+      const buyer = await fund();
+      await mintTo(provider, currency, buyer.publicKey, 1_000_000);
+      const creatorToken = await createTokenAccount(provider, currency, creator);
+      const treasuryToken = await createTokenAccount(
+        provider,
+        currency,
+        treasury
+      );
+
+      const mint = await mintSong({ maxSupply: 5 });
+      await listPrimary(mint, 400_000, 5, currency);
+      assert.equal(
+        await errorCode(buyPrimary(buyer, mint)),
+        "CurrencyMismatch",
+        "expecting SOL"
+      );
+      assert.equal(
+        await errorCode(buyPrimary(buyer, mint, null, undefined, currency)),
+        "InvalidPayment",
+        "paying in SOL"
+      );
+      await buyPrimary(buyer, mint, currency);
+      assert.equal(await balance(provider, creatorToken), 390_000);
+      assert.equal(await balance(provider, treasuryToken), 10_000);
+      const buyerToken = associatedAddress({
+        mint: currency,
+        owner: buyer.publicKey,
+      });
+      assert.equal(await balance(provider, buyerToken), 600_000);
+    });
+
+    it("returns the master edition when delisted", async () => {
+      const mint = await mintSong({ maxSupply: 5 });
+      await listPrimary(mint, LAMPORTS_PER_SOL, 3);
+      const buyer = await fund();
+      await buyPrimary(buyer, mint);
+
+      const song = songAddress(mint);
+      const listing = primaryAddress(song);
+      const vault = associatedAddress({ mint, owner: listing });
+      const creatorToken = associatedAddress({ mint, owner: creator });
+      const stranger = await fund();
+      assert.equal(
+        await errorCode(
+          send(
+            stranger,
+            program.methods
+              .delistPrimary()
+              .accountsPartial({
+                creator: stranger.publicKey,
+                song,
+                mint,
+                listing,
+                vault,
+                creatorToken: associatedAddress({
+                  mint,
+                  owner: stranger.publicKey,
+                }),
+              })
+              .transaction()
+          )
+        ),
+        "ConstraintHasOne"
+      );
+      await program.methods
+        .delistPrimary()
+        .accountsPartial({ creator, song, mint, listing, vault, creatorToken })
+        .rpc();
+      assert.equal(await balance(provider, creatorToken), 1);
+      assert.isNull(await provider.connection.getAccountInfo(vault));
+      assert.isNull(
+        await program.account.primaryListing.fetchNullable(listing)
+      );
+
+      // the sale can start again
+      await listPrimary(mint, LAMPORTS_PER_SOL, 1);
+      assert.equal(await balance(provider, vault), 1);
+      await buyPrimary(buyer, mint);
+      const { printed } = await program.account.song.fetch(song);
+      assert.equal(printed.toNumber(), 2);
+    });
+  });
+
+  describe("secondary market", () => {
+    it("resells editions paying the creator royalty", async () => {
+      const { mint, collector, tokens } = await collect(2);
+      await list(collector, mint, tokens[0], LAMPORTS_PER_SOL);
+      await list(collector, mint, tokens[1], LAMPORTS_PER_SOL);
+      const song = songAddress(mint);
+      const listing = listingAddress(song, collector.publicKey);
+      let state = await program.account.listing.fetch(listing);
+      assert.equal(state.quantity.toNumber(), 2);
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: tokens[0], owner: listing })
+        ),
+        1
+      );
+
+      await send(
+        collector,
+        program.methods
+          .setPrice(new anchor.BN(2 * LAMPORTS_PER_SOL))
+          .accountsPartial({ seller: collector.publicKey, listing })
+          .transaction()
+      );
+      const buyer = await fund();
+      assert.equal(
+        await errorCode(
+          buy(buyer, mint, collector.publicKey, tokens[0], LAMPORTS_PER_SOL)
+        ),
+        "PriceTooHigh"
+      );
+
+      const price = 2 * LAMPORTS_PER_SOL;
+      const royalty = share(price, ROYALTY_BPS);
+      const fee = share(price, FEE_BPS);
+      const vaultRent = await lamports(
+        associatedAddress({ mint: tokens[0], owner: listing })
+      );
+      const creatorBefore = await lamports(creator);
+      const treasuryBefore = await lamports(treasury);
+      const sellerBefore = await lamports(collector.publicKey);
+      await buy(buyer, mint, collector.publicKey, tokens[0], price);
+      assert.equal((await lamports(creator)) - creatorBefore, royalty);
+      assert.equal((await lamports(treasury)) - treasuryBefore, fee);
+      assert.equal(
+        (await lamports(collector.publicKey)) - sellerBefore,
+        price - royalty - fee + vaultRent
+      );
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: tokens[0], owner: buyer.publicKey })
+        ),
+        1
+      );
+
+      // the other edition stays listed until taken back
+      state = await program.account.listing.fetch(listing);
+      assert.equal(state.quantity.toNumber(), 1);
+      await send(
+        collector,
+        program.methods
+          .delist()
+          .accountsPartial({
+            seller: collector.publicKey,
+            listing,
+            tokenMint: tokens[1],
+            vault: associatedAddress({ mint: tokens[1], owner: listing }),
+            sellerToken: associatedAddress({
+              mint: tokens[1],
+              owner: collector.publicKey,
+            }),
+          })
+          .transaction()
+      );
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: tokens[1], owner: collector.publicKey })
+        ),
+        1
+      );
+      assert.isNull(await program.account.listing.fetchNullable(listing));
+    });
+
+    it("resells the master edition for an SPL token", async () => {
+      const currency = await createMint(provider);
+      const buyer = await fund();
+      await mintTo(provider, currency, buyer.publicKey, 1_000_000);
+      const seller = await fund();
+      for (const owner of [creator, treasury, seller.publicKey]) {
+        await createTokenAccount(provider, currency, owner);
+      }
+
+      // the creator hands the master edition to a seller
+      const mint = await mintSong({ royaltyBps: ROYALTY_BPS });
+      await transfer(provider, mint, seller.publicKey, 1);
+
+      await list(seller, mint, mint, 500_000, currency);
+      assert.equal(
+        await errorCode(
+          buy(buyer, mint, seller.publicKey, mint, 500_000, {
+            currency,
+            expectedCurrency: null,
+          })
+        ),
+        "CurrencyMismatch",
+        "expecting SOL"
+      );
+      await buy(buyer, mint, seller.publicKey, mint, 500_000, { currency });
+      const tokenOf = (owner: PublicKey) =>
+        associatedAddress({ mint: currency, owner });
+      assert.equal(await balance(provider, tokenOf(creator)), 50_000);
+      assert.equal(await balance(provider, tokenOf(treasury)), 12_500);
+      assert.equal(await balance(provider, tokenOf(seller.publicKey)), 437_500);
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint, owner: buyer.publicKey })
+        ),
+        1
+      );
+    });
+
+    it("rejects attempts to skip the royalty", async () => {
+      const { mint, collector, tokens } = await collect(1);
+
+      // a song of the same creator with no royalty
+      const free = await mintSong({ royaltyBps: 0 });
+      assert.equal(
+        await errorCode(
+          list(
+            collector,
+            free,
+            tokens[0],
+            LAMPORTS_PER_SOL,
+            null,
+            songAddress(free)
+          )
+        ),
+        "NotSongToken"
+      );
+
+      await list(collector, mint, tokens[0], LAMPORTS_PER_SOL);
+      const buyer = await fund();
+      assert.equal(
+        await errorCode(
+          buy(buyer, mint, collector.publicKey, tokens[0], LAMPORTS_PER_SOL, {
+            creator: buyer.publicKey,
+          })
+        ),
+        "ConstraintAddress"
+      );
+    });
+  });
+
+  describe("auction", () => {
+    it("sells to the highest bid of an English auction", async () => {
+      const { mint, collector, tokens } = await collect(1);
+      const [alice, bob] = [await fund(), await fund()];
+      const auction = await createAuction(collector, mint, tokens[0], {
+        reservePrice: LAMPORTS_PER_SOL,
+        minIncrement: LAMPORTS_PER_SOL / 10,
+        duration: 8,
+      });
+      assert.equal(
+        await errorCode(placeBid(alice, auction, LAMPORTS_PER_SOL / 2)),
+        "BidTooLow",
+        "under the reserve price"
+      );
+      await placeBid(alice, auction, LAMPORTS_PER_SOL);
+      assert.equal(
+        await errorCode(placeBid(bob, auction, (LAMPORTS_PER_SOL * 105) / 100)),
+        "BidTooLow",
+        "under the minimum increment"
+      );
+      const price = (LAMPORTS_PER_SOL * 12) / 10;
+      await placeBid(bob, auction, price);
+      assert.equal(
+        await errorCode(refundBid(auction, bob.publicKey)),
+        "WinningBid"
+      );
+      assert.equal(
+        await errorCode(settleAuction(mint, tokens[0])),
+        "AuctionNotEnded"
+      );
+
+      const { endTime } = await program.account.auction.fetch(auction);
+      await waitUntil(endTime.toNumber());
+      assert.equal(
+        await errorCode(placeBid(alice, auction, 2 * LAMPORTS_PER_SOL)),
+        "AuctionEnded"
+      );
+      const creatorBefore = await lamports(creator);
+      const treasuryBefore = await lamports(treasury);
+      await settleAuction(mint, tokens[0]);
+      assert.equal(
+        (await lamports(creator)) - creatorBefore,
+        share(price, ROYALTY_BPS)
+      );
+      assert.equal(
+        (await lamports(treasury)) - treasuryBefore,
+        share(price, FEE_BPS)
+      );
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: tokens[0], owner: bob.publicKey })
+        ),
+        1
+      );
+      assert.isNull(await program.account.auction.fetchNullable(auction));
+
+      // the outbid bidder gets the bid back after settlement
+      const bid = bidAddress(auction, alice.publicKey);
+      const held = await lamports(bid);
+      const before = await lamports(alice.publicKey);
+      await refundBid(auction, alice.publicKey);
+      assert.equal((await lamports(alice.publicKey)) - before, held);
+    });
+
+    it("extends an English auction on a late bid", async () => {
+      const { mint, collector, tokens } = await collect(1);
+      const bidder = await fund();
+      const auction = await createAuction(collector, mint, tokens[0], {
+        duration: 3,
+        extension: 120,
+      });
+      const { endTime } = await program.account.auction.fetch(auction);
+      await placeBid(bidder, auction, LAMPORTS_PER_SOL);
+      const state = await program.account.auction.fetch(auction);
+      assert.isAbove(state.endTime.toNumber(), endTime.toNumber() + 100);
+      await waitUntil(endTime.toNumber());
+      assert.equal(
+        await errorCode(settleAuction(mint, tokens[0])),
+        "AuctionNotEnded"
+      );
+    });
+
+    it("returns the token to the seller without bids", async () => {
+      const { mint, collector, tokens } = await collect(1);
+      const auction = await createAuction(collector, mint, tokens[0], {
+        duration: 2,
+      });
+      const { endTime } = await program.account.auction.fetch(auction);
+      await waitUntil(endTime.toNumber());
+      await settleAuction(mint, tokens[0]);
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: tokens[0], owner: collector.publicKey })
+        ),
+        1
+      );
+    });
+
+    it("sells at the declining price of a Dutch auction", async () => {
+      const currency = await createMint(provider);
+      const [alice, bob] = [await fund(), await fund()];
+      await mintTo(provider, currency, alice.publicKey, 1_000_000);
+      await mintTo(provider, currency, bob.publicKey, 1_000_000);
+      const { mint, collector, tokens } = await collect(1);
+      for (const owner of [creator, treasury, collector.publicKey]) {
+        await createTokenAccount(provider, currency, owner);
+      }
+
+      const auction = await createAuction(collector, mint, tokens[0], {
+        dutch: true,
+        currency,
+        startPrice: 1_000_000,
+        reservePrice: 500_000,
+        duration: 1000,
+      });
+      assert.equal(
+        await errorCode(placeBid(alice, auction, 100_000, currency)),
+        "PriceTooHigh"
+      );
+      await placeBid(alice, auction, 1_000_000, currency);
+      const { highestBid } = await program.account.auction.fetch(auction);
+      const price = highestBid.toNumber();
+      assert.isAtLeast(price, 500_000);
+      assert.isAtMost(price, 1_000_000);
+      assert.equal(
+        await errorCode(placeBid(bob, auction, 1_000_000, currency)),
+        "AuctionEnded"
+      );
+
+      await settleAuction(mint, tokens[0], currency);
+      const tokenOf = (owner: PublicKey) =>
+        associatedAddress({ mint: currency, owner });
+      const royalty = share(price, ROYALTY_BPS);
+      const fee = share(price, FEE_BPS);
+      assert.equal(await balance(provider, tokenOf(creator)), royalty);
+      assert.equal(await balance(provider, tokenOf(treasury)), fee);
+      assert.equal(
+        await balance(provider, tokenOf(collector.publicKey)),
+        price - royalty - fee
+      );
+      assert.equal(
+        await balance(provider, tokenOf(alice.publicKey)),
+        1_000_000 - price
+      );
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: tokens[0], owner: alice.publicKey })
+        ),
+        1
+      );
+    });
+
+    it("re-auctions a token under a larger stale bid", async () => {
+      const { mint, collector, tokens } = await collect(1);
+      const [alice, bob] = [await fund(), await fund()];
+      let auction = await createAuction(collector, mint, tokens[0], {
+        duration: 4,
+      });
+      await placeBid(alice, auction, LAMPORTS_PER_SOL);
+      await placeBid(bob, auction, 2 * LAMPORTS_PER_SOL);
+      const { endTime } = await program.account.auction.fetch(auction);
+      await waitUntil(endTime.toNumber());
+      await settleAuction(mint, tokens[0]);
+
+      // the same token again, with the 1 SOL bid of alice still held
+      auction = await createAuction(bob, mint, tokens[0], {
+        reservePrice: LAMPORTS_PER_SOL / 2,
+        duration: 4,
+      });
+      assert.equal(
+        await errorCode(placeBid(alice, auction, LAMPORTS_PER_SOL / 2)),
+        "StaleBid"
+      );
+      await refundBid(auction, alice.publicKey);
+      await placeBid(alice, auction, LAMPORTS_PER_SOL / 2);
+      const state = await program.account.auction.fetch(auction);
+      assert.equal(state.highestBid.toNumber(), LAMPORTS_PER_SOL / 2);
+    });
+
+    it("re-auctions a token in another currency", async () => {
+      const { mint, collector, tokens } = await collect(1);
+      const [alice, bob] = [await fund(), await fund()];
+      let auction = await createAuction(collector, mint, tokens[0], {
+        duration: 4,
+      });
+      await placeBid(alice, auction, LAMPORTS_PER_SOL);
+      await placeBid(bob, auction, 2 * LAMPORTS_PER_SOL);
+      const { endTime } = await program.account.auction.fetch(auction);
+      await waitUntil(endTime.toNumber());
+      await settleAuction(mint, tokens[0]);
+
+      // the same token again, in a token, with the SOL bid of alice still held
+      const currency = await createMint(provider);
+      await mintTo(provider, currency, alice.publicKey, 1_000_000);
+      for (const owner of [creator, treasury, bob.publicKey]) {
+        await createTokenAccount(provider, currency, owner);
+      }
+      auction = await createAuction(bob, mint, tokens[0], {
+        currency,
+        reservePrice: 500_000,
+        minIncrement: 1,
+        duration: 4,
+      });
+      assert.equal(
+        await errorCode(placeBid(alice, auction, 500_000, currency)),
+        "StaleBid"
+      );
+      const before = await lamports(alice.publicKey);
+      const held = await lamports(bidAddress(auction, alice.publicKey));
+      await refundBid(auction, alice.publicKey);
+      assert.equal((await lamports(alice.publicKey)) - before, held);
+
+      await placeBid(alice, auction, 500_000, currency);
+      const state = await program.account.auction.fetch(auction);
+      await waitUntil(state.endTime.toNumber());
+      await settleAuction(mint, tokens[0], currency);
+      const tokenOf = (owner: PublicKey) =>
+        associatedAddress({ mint: currency, owner });
+      const royalty = share(500_000, ROYALTY_BPS);
+      const fee = share(500_000, FEE_BPS);
+      assert.equal(
+        await balance(provider, tokenOf(bob.publicKey)),
+        500_000 - royalty - fee
+      );
+      assert.equal(await balance(provider, tokenOf(alice.publicKey)), 500_000);
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: tokens[0], owner: alice.publicKey })
+        ),
+        1
+      );
+    });
+  });
+
+  describe("revenue split", () => {
+    const createSplit = (
+      mint: PublicKey,
+      recipients: { wallet: PublicKey; shareBps: number }[]
+    ) => {
+      const song = songAddress(mint);
+      const split = splitAddress(song);
+      return program.methods
+        .createSplit({ recipients })
+        .accountsPartial({
+          creator,
+          song,
+          split,
+          balance: splitBalanceAddress(split, null),
+        })
+        .rpc();
+    };
+
+    const tip = async (
+      tipper: Keypair,
+      mint: PublicKey,
+      amount: number,
+      currency: PublicKey | null = null
+    ) => {
+      const transaction = program.methods
+        .tip(new anchor.BN(amount), currency)
+        .accountsPartial({
+          tipper: tipper.publicKey,
+          config: configAddress,
+          song: songAddress(mint),
+          creator,
+          treasury,
+          tipperPayment: currency
+            ? associatedAddress({ mint: currency, owner: tipper.publicKey })
+            : null,
+          creatorPayment: null,
+          treasuryPayment: currency
+            ? associatedAddress({ mint: currency, owner: treasury })
+            : null,
+          ...(await revenueAccounts(mint, currency)),
+        })
+        .transaction();
+      return send(tipper, transaction);
+    };
+
+    // Claims with the provider paying the fee, so that the recipient gets exactly its balance
+    const claim = (
+      recipient: Keypair,
+      mint: PublicKey,
+      currency: PublicKey | null = null
+    ) => {
+      const split = splitAddress(songAddress(mint));
+      const balance = splitBalanceAddress(split, currency);
+      return program.methods
+        .claim()
+        .accountsPartial({
+          recipient: recipient.publicKey,
+          split,
+          balance,
+          balanceToken: currency
+            ? associatedAddress({ mint: currency, owner: balance })
+            : null,
+          recipientPayment: currency
+            ? associatedAddress({ mint: currency, owner: recipient.publicKey })
+            : null,
+        })
+        .signers([recipient])
+        .rpc();
+    };
+
+    const accrued = async (
+      mint: PublicKey,
+      currency: PublicKey | null = null
+    ) =>
+      (
+        await program.account.splitBalance.fetch(
+          splitBalanceAddress(splitAddress(songAddress(mint)), currency)
+        )
+      ).accrued.map((amount) => amount.toNumber());
+
+    it("shares sales, royalties and tips between collaborators", async () => {
+      const [producer, lyricist] = [await fund(), await fund()];
+      const mint = await mintSong({ royaltyBps: ROYALTY_BPS });
+      assert.equal(
+        await errorCode(
+          createSplit(mint, [
+            { wallet: creator, shareBps: 5000 },
+            { wallet: producer.publicKey, shareBps: 3000 },
+          ])
+        ),
+        "InvalidSplit",
+        "shares not adding up"
+      );
+      assert.equal(
+        await errorCode(
+          createSplit(mint, [
+            { wallet: creator, shareBps: 5000 },
+            { wallet: creator, shareBps: 5000 },
+          ])
+        ),
+        "InvalidSplit",
+        "a duplicated recipient"
+      );
+      await createSplit(mint, [
+        { wallet: creator, shareBps: 5000 },
+        { wallet: producer.publicKey, shareBps: 3000 },
+        { wallet: lyricist.publicKey, shareBps: 2000 },
+      ]);
+
+      // a primary sale, the platform fee taken first
+      await listPrimary(mint, LAMPORTS_PER_SOL, 2);
+      const collector = await fund();
+      const creatorBefore = await lamports(creator);
+      const edition = await buyPrimary(collector, mint);
+      assert.equal(await lamports(creator), creatorBefore);
+      const sale = LAMPORTS_PER_SOL - share(LAMPORTS_PER_SOL, FEE_BPS);
+      assert.deepEqual(await accrued(mint), [
+        share(sale, 5000),
+        share(sale, 3000),
+        share(sale, 2000),
+      ]);
+
+      // a tip and the royalty of a resale
+      await tip(await fund(), mint, LAMPORTS_PER_SOL / 10);
+      await list(collector, mint, edition, LAMPORTS_PER_SOL);
+      await buy(
+        await fund(),
+        mint,
+        collector.publicKey,
+        edition,
+        LAMPORTS_PER_SOL
+      );
+      const revenue =
+        sale + LAMPORTS_PER_SOL / 10 + share(LAMPORTS_PER_SOL, ROYALTY_BPS);
+      const [, producerShare] = await accrued(mint);
+      assert.equal(
+        producerShare,
+        share(sale, 3000) +
+          share(LAMPORTS_PER_SOL / 10, 3000) +
+          share(share(LAMPORTS_PER_SOL, ROYALTY_BPS), 3000)
+      );
+      assert.equal(
+        (await accrued(mint)).reduce((sum, amount) => sum + amount, 0),
+        revenue
+      );
+
+      const before = await lamports(producer.publicKey);
+      await claim(producer, mint);
+      assert.equal((await lamports(producer.publicKey)) - before, producerShare);
+      assert.equal((await accrued(mint))[1], 0);
+      assert.equal(await errorCode(claim(collector, mint)), "NotRecipient");
+    });
+
+    it("takes the boost cut before the split", async () => {
+      const producer = await fund();
+      const mint = await mintSong({ boostBps: 5000 });
+      const { revenueCutBps } = await program.account.song.fetch(
+        songAddress(mint)
+      );
+      await createSplit(mint, [
+        { wallet: creator, shareBps: 5000 },
+        { wallet: producer.publicKey, shareBps: 5000 },
+      ]);
+      const poolBefore = await lamports(boostAddress);
+      await tip(await fund(), mint, LAMPORTS_PER_SOL);
+      const cut = share(LAMPORTS_PER_SOL, revenueCutBps);
+      assert.equal((await lamports(boostAddress)) - poolBefore, cut);
+      const rest = LAMPORTS_PER_SOL - cut;
+      assert.deepEqual(await accrued(mint), [
+        share(rest, 5000),
+        share(rest, 5000),
+      ]);
+    });
+
+    it("accrues and claims an SPL token", async () => {
+      const currency = await createMint(provider);
+      const [producer, tipper] = [await fund(), await fund()];
+      await mintTo(provider, currency, tipper.publicKey, 1_000_000);
+      await createTokenAccount(provider, currency, producer.publicKey);
+      const mint = await mintSong();
+      await createSplit(mint, [
+        { wallet: creator, shareBps: 4000 },
+        { wallet: producer.publicKey, shareBps: 6000 },
+      ]);
+      assert.equal(
+        await errorCode(tip(tipper, mint, 1_000_000, currency)),
+        "AccountNotInitialized",
+        "no balance in the currency yet"
+      );
+
+      const split = splitAddress(songAddress(mint));
+      const splitBalance = splitBalanceAddress(split, currency);
+      await program.methods
+        .openSplitBalance()
+        .accountsPartial({
+          payer: creator,
+          split,
+          currency,
+          balance: splitBalance,
+          balanceToken: associatedAddress({
+            mint: currency,
+            owner: splitBalance,
+          }),
+        })
+        .rpc();
+      await tip(tipper, mint, 1_000_000, currency);
+      assert.deepEqual(await accrued(mint, currency), [400_000, 600_000]);
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+      await claim(producer, mint, currency);
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: currency, owner: producer.publicKey })
+        ),
+        600_000
+      );
+      assert.equal(
+        await balance(
+          provider,
+          associatedAddress({ mint: currency, owner: splitBalance })
+        ),
+        400_000
+      );
+    });
   });
 });
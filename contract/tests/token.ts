@@ -0,0 +1,134 @@
+// Minimal SPL token instructions, enough to pay in a token
+import * as anchor from "@coral-xyz/anchor";
+import {
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+} from "@solana/web3.js";
+
+const { TOKEN_PROGRAM_ID, ASSOCIATED_PROGRAM_ID, associatedAddress } =
+  anchor.utils.token;
+
+const MINT_SIZE = 82;
+
+// Creates a mint with no decimals whose authority is the provider wallet
+export const createMint = async (provider: anchor.AnchorProvider) => {
+  const mint = Keypair.generate();
+  const lamports =
+    await provider.connection.getMinimumBalanceForRentExemption(MINT_SIZE);
+  // InitializeMint2: decimals, mint authority, no freeze authority
+  const data = Buffer.alloc(67);
+  data.writeUInt8(20, 0);
+  data.writeUInt8(0, 1);
+  provider.wallet.publicKey.toBuffer().copy(data, 2);
+  const tx = new Transaction().add(
+    SystemProgram.createAccount({
+      fromPubkey: provider.wallet.publicKey,
+      newAccountPubkey: mint.publicKey,
+      lamports,
+      space: MINT_SIZE,
+      programId: TOKEN_PROGRAM_ID,
+    }),
+    new TransactionInstruction({
+      programId: TOKEN_PROGRAM_ID,
+      keys: [{ pubkey: mint.publicKey, isSigner: false, isWritable: true }],
+      data,
+    })
+  );
+  await provider.sendAndConfirm(tx, [mint]);
+  return mint.publicKey;
+};
+
+// Creates the associated token account of `owner` if missing
+export const createTokenAccount = async (
+  provider: anchor.AnchorProvider,
+  mint: PublicKey,
+  owner: PublicKey
+) => {
+  const address = associatedAddress({ mint, owner });
+  const tx = new Transaction().add(
+    new TransactionInstruction({
+      programId: ASSOCIATED_PROGRAM_ID,
+      keys: [
+        { pubkey: provider.wallet.publicKey, isSigner: true, isWritable: true },
+        { pubkey: address, isSigner: false, isWritable: true },
+        { pubkey: owner, isSigner: false, isWritable: false },
+        { pubkey: mint, isSigner: false, isWritable: false },
+        { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+        { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+      ],
+      // CreateIdempotent
+      data: Buffer.from([1]),
+    })
+  );
+  await provider.sendAndConfirm(tx);
+  return address;
+};
+
+// Mints `amount` tokens to the associated token account of `owner`
+export const mintTo = async (
+  provider: anchor.AnchorProvider,
+  mint: PublicKey,
+  owner: PublicKey,
+  amount: number
+) => {
+  const address = await createTokenAccount(provider, mint, owner);
+  const data = Buffer.concat([
+    Buffer.from([7]),
+    new anchor.BN(amount).toArrayLike(Buffer, "le", 8),
+  ]);
+  const tx = new Transaction().add(
+    new TransactionInstruction({
+      programId: TOKEN_PROGRAM_ID,
+      keys: [
+        { pubkey: mint, isSigner: false, isWritable: true },
+        { pubkey: address, isSigner: false, isWritable: true },
+        { pubkey: provider.wallet.publicKey, isSigner: true, isWritable: false },
+      ],
+      data,
+    })
+  );
+  await provider.sendAndConfirm(tx);
+  return address;
+};
+
+// Transfers `amount` tokens of the provider wallet to the associated token account of `owner`
+export const transfer = async (
+  provider: anchor.AnchorProvider,
+  mint: PublicKey,
+  owner: PublicKey,
+  amount: number
+) => {
+  const address = await createTokenAccount(provider, mint, owner);
+  const data = Buffer.concat([
+    Buffer.from([3]),
+    new anchor.BN(amount).toArrayLike(Buffer, "le", 8),
+  ]);
+  const tx = new Transaction().add(
+    new TransactionInstruction({
+      programId: TOKEN_PROGRAM_ID,
+      keys: [
+        {
+          pubkey: associatedAddress({ mint, owner: provider.wallet.publicKey }),
+          isSigner: false,
+          isWritable: true,
+        },
+        { pubkey: address, isSigner: false, isWritable: true },
+        { pubkey: provider.wallet.publicKey, isSigner: true, isWritable: false },
+      ],
+      data,
+    })
+  );
+  await provider.sendAndConfirm(tx);
+  return address;
+};
+
+export const balance = async (
+  provider: anchor.AnchorProvider,
+  address: PublicKey
+) =>
+  Number(
+    (await provider.connection.getTokenAccountBalance(address)).value.amount
+  );